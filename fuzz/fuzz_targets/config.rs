@@ -0,0 +1,10 @@
+// cargo +nightly fuzz run config
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pulseWM::config::Config;
+
+// Errors are fine, panics are not
+fuzz_target!(|contents: &str| {
+    let _ = Config::parse(contents);
+});
@@ -0,0 +1,10 @@
+// cargo +nightly fuzz run ipc
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pulseWM::ipc;
+
+// Errors are fine, panics are not
+fuzz_target!(|line: &str| {
+    let _ = ipc::parse_request(line);
+});
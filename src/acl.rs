@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use smithay::reexports::wayland_server::{backend::Credentials, Client};
+
+use crate::{config::SecurityConfig, state::State};
+
+// Globals that can read the screen or the clipboard
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivilegedProtocol {
+    Screencopy,
+    DataControl,
+}
+
+impl PrivilegedProtocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            PrivilegedProtocol::Screencopy => "screencopy",
+            PrivilegedProtocol::DataControl => "data_control",
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PermissionRule {
+    // Absolute path of the client binary, e.g. "/usr/bin/grim"
+    pub executable: Option<PathBuf>,
+    pub uid: Option<u32>,
+    pub protocols: Vec<PrivilegedProtocol>,
+}
+
+impl PermissionRule {
+    // Every field that is set has to match, a rule without any never does
+    fn matches(&self, credentials: &Credentials, executable: Option<&PathBuf>) -> bool {
+        if self.executable.is_none() && self.uid.is_none() {
+            return false;
+        }
+
+        self.uid.map_or(true, |uid: u32| uid == credentials.uid)
+            && self
+                .executable
+                .as_ref()
+                .map_or(true, |wanted: &PathBuf| executable == Some(wanted))
+    }
+}
+
+// The binary behind the socket, not argv[0] which the client controls
+pub fn executable(pid: i32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{pid}/exe")).ok()
+}
+
+pub fn is_allowed(
+    config: &SecurityConfig,
+    protocol: PrivilegedProtocol,
+    credentials: &Credentials,
+) -> bool {
+    if !config.restrict_privileged {
+        return true;
+    }
+
+    let executable: Option<PathBuf> = executable(credentials.pid);
+    config.allow.iter().any(|rule: &PermissionRule| {
+        rule.protocols.contains(&protocol) && rule.matches(credentials, executable.as_ref())
+    })
+}
+
+impl State {
+    // Checked when a client binds one of the privileged globals, a denied
+    // client gets a protocol error instead of the global
+    pub fn may_bind(&self, client: &Client, protocol: PrivilegedProtocol) -> bool {
+        let Ok(credentials) = client.get_credentials(&self.display_handle) else {
+            return false;
+        };
+
+        let allowed: bool = is_allowed(&self.config.security, protocol, &credentials);
+        if allowed {
+            self.audit_bind(client, protocol.name());
+        } else {
+            eprintln!(
+                "Denied {protocol:?} to pid {} (uid {}, {})",
+                credentials.pid,
+                credentials.uid,
+                executable(credentials.pid).map_or("unknown executable".into(), |path: PathBuf| {
+                    path.display().to_string()
+                })
+            );
+        }
+        allowed
+    }
+}
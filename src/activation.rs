@@ -0,0 +1,231 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use smithay::{
+    delegate_xdg_activation,
+    desktop::Window,
+    input::keyboard::KeyboardHandle,
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    utils::{IsAlive, Serial},
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        shell::xdg::XdgToplevelSurfaceData,
+        xdg_activation::{
+            XdgActivationHandler, XdgActivationState, XdgActivationToken, XdgActivationTokenData,
+        },
+    },
+};
+
+use crate::{ipc::IpcEvent, spawn, state::State, tree};
+
+// Older tokens don't focus anything anymore, and neither do processes
+// started that long ago
+const TOKEN_LIFETIME: Duration = Duration::from_secs(10);
+// How far up from a client's process to look for one we started
+const MAX_ANCESTORS: usize = 8;
+
+// A window that wanted focus and didn't get it, until it's focused
+struct Urgent(AtomicBool);
+
+#[derive(Default)]
+pub struct Activation {
+    // The last key press that went to a client
+    typed_at: Option<Instant>,
+    // Activated with a valid token before their initial commit, they take
+    // focus once they're mapped
+    granted: Vec<WlSurface>,
+    // Shells running commands the user started through pulseWM, by pid
+    launched: Vec<(u32, Instant)>,
+}
+
+pub fn is_urgent(window: &Window) -> bool {
+    window
+        .user_data()
+        .get::<Urgent>()
+        .is_some_and(|urgent: &Urgent| urgent.0.load(Ordering::Relaxed))
+}
+
+fn set_urgent(window: &Window, urgent: bool) {
+    window
+        .user_data()
+        .insert_if_missing(|| Urgent(AtomicBool::new(false)));
+    window
+        .user_data()
+        .get::<Urgent>()
+        .unwrap()
+        .0
+        .store(urgent, Ordering::Relaxed);
+}
+
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat: String = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name before it may have spaces and parentheses of its own
+    let fields: &str = &stat[stat.rfind(')')? + 1..];
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn is_mapped(surface: &WlSurface) -> bool {
+    with_states(surface, |states: &SurfaceData| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .is_some_and(|data| data.lock().unwrap().initial_configure_sent)
+    })
+}
+
+impl State {
+    // For commands the user started with a binding. Their windows take focus
+    // as if they came with an activation token, the user asked for them
+    // even if they're typing elsewhere while it starts.
+    pub fn launch(&mut self, command: &str) {
+        let Some(pid) = spawn::spawn_detached(command) else {
+            return;
+        };
+        self.activation
+            .launched
+            .retain(|(_, at): &(u32, Instant)| at.elapsed() < TOKEN_LIFETIME);
+        self.activation.launched.push((pid, Instant::now()));
+    }
+
+    // Whether the client is one of the launched commands, or was started by
+    // one
+    fn was_launched(&self, surface: &WlSurface) -> bool {
+        let launched: Vec<u32> = self
+            .activation
+            .launched
+            .iter()
+            .filter(|(_, at): &&(u32, Instant)| at.elapsed() < TOKEN_LIFETIME)
+            .map(|(pid, _): &(u32, Instant)| *pid)
+            .collect();
+        if launched.is_empty() {
+            return false;
+        }
+        let Some(client) = surface.client() else {
+            return false;
+        };
+        let Ok(credentials) = client.get_credentials(&self.display_handle) else {
+            return false;
+        };
+
+        let mut pid: Option<u32> = u32::try_from(credentials.pid).ok();
+        for _ in 0..MAX_ANCESTORS {
+            let Some(current) = pid.filter(|pid: &u32| *pid > 1) else {
+                return false;
+            };
+            if launched.contains(&current) {
+                return true;
+            }
+            pid = parent_pid(current);
+        }
+        false
+    }
+
+    pub fn key_typed(&mut self) {
+        self.activation.typed_at = Some(Instant::now());
+    }
+
+    fn is_typing(&self) -> bool {
+        let Some(typed_at) = self.activation.typed_at else {
+            return false;
+        };
+        let timeout: Duration = Duration::from_millis(self.config.focus.typing_timeout_ms);
+        self.focused_window().is_some() && typed_at.elapsed() < timeout
+    }
+
+    // With focus.steal_prevention a window mapped while another one is
+    // typed in only gets focus if it was activated with a valid token
+    pub fn focus_new_window(&mut self, window: &Window) {
+        let surface: &WlSurface = window.toplevel().wl_surface();
+        let granted: bool = self.activation.granted.contains(surface) || self.was_launched(surface);
+        self.activation
+            .granted
+            .retain(|other: &WlSurface| other != surface && other.alive());
+
+        if granted || !self.config.focus.steal_prevention || !self.is_typing() {
+            self.focus_window(window);
+        } else {
+            self.mark_urgent(window);
+        }
+    }
+
+    pub fn mark_urgent(&mut self, window: &Window) {
+        if is_urgent(window) || self.focused_window().as_ref() == Some(window) {
+            return;
+        }
+        set_urgent(window, true);
+        self.emit_ipc_event(IpcEvent::WindowUrgent {
+            id: tree::window_id(window),
+        });
+        self.request_redraw();
+    }
+
+    pub fn clear_urgent(&mut self, window: &Window) {
+        if is_urgent(window) {
+            set_urgent(window, false);
+            self.request_redraw();
+        }
+    }
+
+    // Only tokens asked for in reply to input, while the client asking had
+    // the keyboard
+    fn token_is_valid(&self, data: &XdgActivationTokenData) -> bool {
+        if data.timestamp.elapsed() >= TOKEN_LIFETIME {
+            return false;
+        }
+        let Some((serial, _)) = &data.serial else {
+            return false;
+        };
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        keyboard
+            .last_enter()
+            .is_some_and(|last: Serial| serial.is_no_older_than(&last))
+    }
+}
+
+impl XdgActivationHandler for State {
+    fn activation_state(&mut self) -> &mut XdgActivationState {
+        &mut self.xdg_activation_state
+    }
+
+    fn request_activation(
+        &mut self,
+        token: XdgActivationToken,
+        token_data: XdgActivationTokenData,
+        surface: WlSurface,
+    ) {
+        let valid: bool = self.token_is_valid(&token_data);
+        self.xdg_activation_state.remove_request(&token);
+        let Some(window) = self.windows.get(&surface).cloned() else {
+            return;
+        };
+        if !valid {
+            self.mark_urgent(&window);
+            return;
+        }
+        if !is_mapped(&surface) {
+            self.activation.granted.push(surface);
+            return;
+        }
+
+        // Hidden on another workspace, minimized ones stay where they are
+        if self.space.element_location(&window).is_none() {
+            let Some(workspace) = self.workspace_of(&window) else {
+                self.mark_urgent(&window);
+                return;
+            };
+            self.switch_workspace(workspace);
+        }
+        self.focus_window(&window);
+    }
+
+    fn destroy_activation(
+        &mut self,
+        _token: XdgActivationToken,
+        _token_data: XdgActivationTokenData,
+        _surface: WlSurface,
+    ) {
+    }
+}
+delegate_xdg_activation!(State);
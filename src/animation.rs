@@ -0,0 +1,173 @@
+use std::time::{Duration, Instant};
+
+use smithay::{
+    desktop::Window,
+    utils::{IsAlive, Logical, Point, Rectangle, Serial},
+};
+
+use crate::{configure, state::State};
+
+pub fn ease_out_cubic(t: f64) -> f64 {
+    1.0 - (1.0 - t.clamp(0.0, 1.0)).powi(3)
+}
+
+fn lerp(from: i32, to: i32, t: f64) -> i32 {
+    from + ((to - from) as f64 * t).round() as i32
+}
+
+pub fn lerp_rect(
+    from: Rectangle<i32, Logical>,
+    to: Rectangle<i32, Logical>,
+    t: f64,
+) -> Rectangle<i32, Logical> {
+    Rectangle::from_loc_and_size(
+        (lerp(from.loc.x, to.loc.x, t), lerp(from.loc.y, to.loc.y, t)),
+        (
+            lerp(from.size.w, to.size.w, t),
+            lerp(from.size.h, to.size.h, t),
+        ),
+    )
+}
+
+#[derive(Clone, Copy)]
+pub struct Animation {
+    start: Instant,
+    duration: Duration,
+}
+
+impl Animation {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            duration,
+        }
+    }
+
+    // Eased, 0.0 at the start and 1.0 once done
+    pub fn progress(&self, now: Instant) -> f64 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+
+        let elapsed: Duration = now.saturating_duration_since(self.start);
+        ease_out_cubic(elapsed.as_secs_f64() / self.duration.as_secs_f64())
+    }
+
+    pub fn is_done(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.start) >= self.duration
+    }
+}
+
+struct WindowAnimation {
+    window: Window,
+    from: Rectangle<i32, Logical>,
+    to: Rectangle<i32, Logical>,
+    animation: Animation,
+    // The last size sent, while the client hasn't caught up with it the
+    // window only moves
+    configure: Option<Serial>,
+}
+
+#[derive(Default)]
+pub struct Animations {
+    windows: Vec<WindowAnimation>,
+}
+
+impl Animations {
+    pub fn is_animating(&self) -> bool {
+        !self.windows.is_empty()
+    }
+}
+
+impl State {
+    // Moves `window` to `to` over the configured duration, starting from
+    // wherever it is right now, even halfway through another animation.
+    // Returns the configure's serial when there's nothing to animate and
+    // the window was resized right away.
+    pub fn animate_window(
+        &mut self,
+        window: &Window,
+        to: Rectangle<i32, Logical>,
+    ) -> Option<Serial> {
+        let now: Instant = Instant::now();
+        let from: Option<Rectangle<i32, Logical>> = self
+            .animations
+            .windows
+            .iter()
+            .find(|animation: &&WindowAnimation| animation.window == *window)
+            .map(|animation: &WindowAnimation| {
+                lerp_rect(
+                    animation.from,
+                    animation.to,
+                    animation.animation.progress(now),
+                )
+            })
+            .or_else(|| {
+                let loc = self.space.element_location(window)?;
+                Some(Rectangle::from_loc_and_size(
+                    loc + window.geometry().loc,
+                    window.geometry().size,
+                ))
+            });
+
+        let duration: Duration = Duration::from_millis(self.config.animations.duration_ms);
+        let Some(from) = from.filter(|_| self.config.animations.enabled && !duration.is_zero())
+        else {
+            return self.set_window_geometry(window, to);
+        };
+
+        self.animations
+            .windows
+            .retain(|animation: &WindowAnimation| animation.window != *window);
+        self.animations.windows.push(WindowAnimation {
+            window: window.clone(),
+            from,
+            to,
+            animation: Animation::new(duration),
+            configure: None,
+        });
+        None
+    }
+
+    // Called once per frame, before rendering
+    pub fn advance_animations(&mut self) {
+        if !self.animations.is_animating() {
+            return;
+        }
+
+        let now: Instant = Instant::now();
+        // Taken out so set_window_geometry can borrow self, and put back
+        // to keep the allocation
+        let mut windows: Vec<WindowAnimation> = std::mem::take(&mut self.animations.windows);
+        windows.retain_mut(|animation: &mut WindowAnimation| {
+            // Closed, or moved to another workspace, while animating
+            if !animation.window.alive() || self.space.element_location(&animation.window).is_none()
+            {
+                return false;
+            }
+
+            let geometry: Rectangle<i32, Logical> = lerp_rect(
+                animation.from,
+                animation.to,
+                animation.animation.progress(now),
+            );
+            // The final size is always sent
+            let done: bool = animation.animation.is_done(now);
+            let caught_up: bool = animation.configure.map_or(true, |serial: Serial| {
+                configure::has_caught_up(animation.window.toplevel(), serial)
+            });
+            if done || caught_up {
+                if let Some(serial) = self.set_window_geometry(&animation.window, geometry) {
+                    animation.configure = Some(serial);
+                }
+            } else {
+                let location: Point<i32, Logical> = geometry.loc - animation.window.geometry().loc;
+                self.space
+                    .map_element(animation.window.clone(), location, true);
+            }
+
+            !done
+        });
+        self.animations.windows = windows;
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use smithay::reexports::wayland_server::{
+    backend::{ClientId, Credentials, DisconnectReason},
+    Client,
+};
+
+use crate::{acl, state::State};
+
+// Disconnected clients are forgotten once there are more than this
+const MAX_RECORDS: usize = 256;
+
+// Shared with every ClientData, disconnects are reported there
+pub type SharedAuditLog = Arc<Mutex<AuditLog>>;
+
+#[derive(Clone, Serialize)]
+pub struct ClientRecord {
+    #[serde(skip)]
+    id: ClientId,
+    pub pid: Option<i32>,
+    pub uid: Option<u32>,
+    pub executable: Option<PathBuf>,
+    // Seconds since the epoch
+    pub connected_at: u64,
+    pub disconnected_at: Option<u64>,
+    pub disconnect_reason: Option<String>,
+    pub globals: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct AuditLog {
+    records: VecDeque<ClientRecord>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+impl AuditLog {
+    pub fn connected(&mut self, id: ClientId, credentials: Option<Credentials>) {
+        let executable: Option<PathBuf> =
+            credentials.and_then(|credentials: Credentials| acl::executable(credentials.pid));
+        eprintln!(
+            "Client connected: pid {}, {}",
+            credentials.map_or(-1, |credentials: Credentials| credentials.pid),
+            executable
+                .as_ref()
+                .map_or("unknown executable".into(), |path: &PathBuf| path
+                    .display()
+                    .to_string())
+        );
+
+        if self.records.len() >= MAX_RECORDS {
+            // Prefer dropping the oldest disconnected client over a live one
+            let index: usize = self
+                .records
+                .iter()
+                .position(|record: &ClientRecord| record.disconnected_at.is_some())
+                .unwrap_or(0);
+            self.records.remove(index);
+        }
+
+        self.records.push_back(ClientRecord {
+            id,
+            pid: credentials.map(|credentials: Credentials| credentials.pid),
+            uid: credentials.map(|credentials: Credentials| credentials.uid),
+            executable,
+            connected_at: now(),
+            disconnected_at: None,
+            disconnect_reason: None,
+            globals: Vec::new(),
+        });
+    }
+
+    pub fn disconnected(&mut self, id: &ClientId, reason: &DisconnectReason) {
+        let Some(record) = self.record_mut(id) else {
+            return;
+        };
+
+        let reason: String = match reason {
+            DisconnectReason::ConnectionClosed => "connection closed".into(),
+            DisconnectReason::ProtocolError(err) => err.to_string(),
+        };
+        eprintln!(
+            "Client disconnected: pid {}, {reason}",
+            record.pid.unwrap_or(-1)
+        );
+
+        record.disconnected_at = Some(now());
+        record.disconnect_reason = Some(reason);
+    }
+
+    pub fn bound(&mut self, id: &ClientId, global: &str) {
+        if let Some(record) = self.record_mut(id) {
+            if !record.globals.iter().any(|bound: &String| bound == global) {
+                record.globals.push(global.to_string());
+            }
+        }
+    }
+
+    pub fn has_connected_clients(&self) -> bool {
+        self.records
+            .iter()
+            .any(|record: &ClientRecord| record.disconnected_at.is_none())
+    }
+
+    pub fn records(&self) -> Vec<ClientRecord> {
+        self.records.iter().cloned().collect()
+    }
+
+    // The latest one, ids can be reused after a disconnect
+    fn record_mut(&mut self, id: &ClientId) -> Option<&mut ClientRecord> {
+        self.records
+            .iter_mut()
+            .rev()
+            .find(|record: &&mut ClientRecord| record.id == *id && record.disconnected_at.is_none())
+    }
+}
+
+impl State {
+    pub fn audit_connected(&self, client: &Client) {
+        let credentials: Option<Credentials> = client.get_credentials(&self.display_handle).ok();
+        self.audit
+            .lock()
+            .unwrap()
+            .connected(client.id(), credentials);
+    }
+
+    // Globals handled by smithay's own dispatch can't be observed, only the
+    // ones pulseWM implements or gates itself get recorded
+    pub fn audit_bind(&self, client: &Client, global: &str) {
+        self.audit.lock().unwrap().bound(&client.id(), global);
+    }
+}
@@ -0,0 +1,802 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use smithay::{
+    backend::{
+        allocator::{
+            gbm::{GbmAllocator, GbmBufferFlags, GbmDevice},
+            Format, Fourcc,
+        },
+        drm::{
+            compositor::{DrmCompositor, RenderFrameResult},
+            DrmDevice, DrmDeviceFd, DrmEvent, DrmNode, DrmSurface, Planes,
+        },
+        egl::{EGLContext, EGLDisplay},
+        input::InputEvent,
+        libinput::{LibinputInputBackend, LibinputSessionInterface},
+        renderer::{damage::OutputModeSource, gles::GlesRenderer, ImportDma, ImportMemWl},
+        session::{libseat::LibSeatSession, Event as SessionEvent, Session},
+        udev::{UdevBackend, UdevEvent},
+    },
+    output::{Mode, Output, PhysicalProperties, Subpixel},
+    reexports::{
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            LoopHandle, RegistrationToken,
+        },
+        drm::control::{self, connector, crtc, Device as ControlDevice, ModeTypeFlags},
+        input::Libinput,
+        nix::{fcntl::OFlag, libc::dev_t},
+        wayland_server::backend::GlobalId,
+    },
+    utils::DeviceFd,
+};
+
+use crate::{
+    backend::Backend, data::Data, edid::EdidInfo, render::CustomRenderElements, state::State,
+};
+
+// What we ask GBM for, in order of preference
+const COLOR_FORMATS: [Fourcc; 2] = [Fourcc::Argb8888, Fourcc::Xrgb8888];
+// Tried before those with [render] ten_bit
+const TEN_BIT_FORMATS: [Fourcc; 2] = [Fourcc::Argb2101010, Fourcc::Xrgb2101010];
+
+// Commits atomically where the driver can, so planes get used and modes
+// change without a blank. Buffers with modifiers the display engine can't
+// scan out are replaced by ones without.
+type Compositor = DrmCompositor<GbmAllocator<DrmDeviceFd>, GbmDevice<DrmDeviceFd>, (), DrmDeviceFd>;
+
+struct Surface {
+    output: Output,
+    global: GlobalId,
+    connector: connector::Handle,
+    // Powered down with DPMS, see State::dpms
+    off: bool,
+    compositor: Compositor,
+    // Every mode of the connector, what the output advertises. `mode` is
+    // the one set, the output's current mode is switched to on the next
+    // frame.
+    modes: Vec<control::Mode>,
+    mode: control::Mode,
+    // Reused every frame
+    elements: Vec<CustomRenderElements>,
+    // Nothing was queued, so no vblank will come to render the next frame
+    idle: bool,
+    last_render: Instant,
+    // A timer stands in for the vblank, see DrmBackend::schedule_render
+    scheduled: bool,
+}
+
+struct Device {
+    drm: DrmDevice,
+    gbm: GbmDevice<DrmDeviceFd>,
+    renderer: GlesRenderer,
+    surfaces: HashMap<crtc::Handle, Surface>,
+    token: RegistrationToken,
+}
+
+pub struct DrmBackend {
+    session: LibSeatSession,
+    libinput: Libinput,
+    handle: LoopHandle<'static, Data>,
+    devices: HashMap<DrmNode, Device>,
+    start_time: Instant,
+}
+
+pub fn init(handle: &LoopHandle<'static, Data>, state: &mut State) -> Result<DrmBackend, String> {
+    let (session, notifier) =
+        LibSeatSession::new().map_err(|err| format!("Failed to open a session: {err}"))?;
+    state.session = Some(session.clone());
+
+    let mut libinput: Libinput =
+        Libinput::new_with_udev::<LibinputSessionInterface<LibSeatSession>>(session.clone().into());
+    libinput
+        .udev_assign_seat(&session.seat())
+        .map_err(|_| "Failed to assign the libinput seat".to_string())?;
+
+    handle
+        .insert_source(
+            LibinputInputBackend::new(libinput.clone()),
+            |event: InputEvent<LibinputInputBackend>, _, data: &mut Data| {
+                match &event {
+                    InputEvent::DeviceAdded { device } => {
+                        data.state.input_devices.add(device.clone())
+                    }
+                    InputEvent::DeviceRemoved { device } => data.state.input_devices.remove(device),
+                    _ => {}
+                }
+                data.state.process_input_event(event)
+            },
+        )
+        .unwrap();
+
+    handle
+        .insert_source(notifier, |event: SessionEvent, _, data: &mut Data| {
+            let Backend::Drm(drm) = &mut data.backend else {
+                return;
+            };
+            match event {
+                SessionEvent::PauseSession => drm.pause(),
+                SessionEvent::ActivateSession => drm.activate(&mut data.state),
+            }
+        })
+        .unwrap();
+
+    let udev: UdevBackend = UdevBackend::new(session.seat())
+        .map_err(|err| format!("Failed to initialize udev: {err}"))?;
+
+    let mut backend: DrmBackend = DrmBackend {
+        session,
+        libinput,
+        handle: handle.clone(),
+        devices: HashMap::new(),
+        start_time: Instant::now(),
+    };
+
+    for (device_id, path) in udev.device_list() {
+        backend.device_added(state, device_id, path);
+    }
+    if backend.devices.is_empty() {
+        return Err("No usable GPU found".into());
+    }
+
+    handle
+        .insert_source(udev, |event: UdevEvent, _, data: &mut Data| {
+            let Backend::Drm(drm) = &mut data.backend else {
+                return;
+            };
+            match event {
+                UdevEvent::Added { device_id, path } => {
+                    drm.device_added(&mut data.state, device_id, &path);
+                    data.state.apply_output_config();
+                }
+                // A monitor was plugged in or out
+                UdevEvent::Changed { device_id } => {
+                    drm.device_changed(&mut data.state, device_id);
+                    data.state.apply_output_config();
+                }
+                UdevEvent::Removed { device_id } => {
+                    drm.device_removed(&mut data.state, device_id);
+                    data.state.apply_output_config();
+                }
+            }
+        })
+        .unwrap();
+
+    Ok(backend)
+}
+
+// The raw EDID blob the kernel read from the monitor
+fn read_edid(drm: &DrmDevice, connector: connector::Handle) -> Option<Vec<u8>> {
+    let properties = drm.get_properties(connector).ok()?;
+    let (handles, values) = properties.as_props_and_values();
+
+    let blob: u64 = handles.iter().zip(values).find_map(|(handle, value)| {
+        let info = drm.get_property(*handle).ok()?;
+        (info.name().to_str() == Ok("EDID")).then_some(*value)
+    })?;
+    if blob == 0 {
+        return None;
+    }
+
+    drm.get_property_blob(blob).ok()
+}
+
+// We never change the gamma ramp, but firmware and other sessions may
+// have. A linear one is what we render for.
+fn reset_gamma(drm: &DrmDevice, crtc: crtc::Handle) -> Result<(), String> {
+    let length: usize = drm
+        .get_crtc(crtc)
+        .map_err(|err| err.to_string())?
+        .gamma_length() as usize;
+    if length < 2 {
+        return Ok(());
+    }
+    let ramp: Vec<u16> = (0..length)
+        .map(|index: usize| (index * u16::MAX as usize / (length - 1)) as u16)
+        .collect();
+    drm.set_gamma(crtc, &ramp, &ramp, &ramp)
+        .map_err(|err| err.to_string())
+}
+
+// The legacy DPMS property, atomic drivers turn the CRTC off for it
+fn set_dpms(drm: &DrmDevice, connector: connector::Handle, on: bool) -> Result<(), String> {
+    let properties = drm
+        .get_properties(connector)
+        .map_err(|err| err.to_string())?;
+    let (handles, _) = properties.as_props_and_values();
+    let property = handles
+        .iter()
+        .find(|handle| {
+            drm.get_property(**handle)
+                .map_or(false, |info| info.name().to_str() == Ok("DPMS"))
+        })
+        .ok_or("no DPMS property")?;
+
+    // DRM_MODE_DPMS_ON and DRM_MODE_DPMS_OFF
+    let value: u64 = if on { 0 } else { 3 };
+    drm.set_property(connector, *property, value)
+        .map_err(|err| err.to_string())
+}
+
+impl DrmBackend {
+    fn device_added(&mut self, state: &mut State, device_id: dev_t, path: &Path) {
+        let Ok(node) = DrmNode::from_dev_id(device_id) else {
+            return;
+        };
+
+        match self.open_device(state, node, path) {
+            Ok(device) => {
+                // Clients' buffers end up on the first GPU's renderer, see
+                // what it can import beyond ARGB8888 and XRGB8888
+                if self.devices.is_empty() {
+                    state
+                        .shm_state
+                        .update_formats(device.renderer.shm_formats());
+                    let dmabuf_formats: Vec<Format> =
+                        device.renderer.dmabuf_formats().cloned().collect();
+                    state.create_dmabuf_global(dmabuf_formats);
+                }
+                self.devices.insert(node, device);
+                for crtc in self.devices[&node]
+                    .surfaces
+                    .keys()
+                    .copied()
+                    .collect::<Vec<_>>()
+                {
+                    self.render(state, node, crtc);
+                }
+            }
+            Err(err) => eprintln!("Failed to open {}: {err}", path.display()),
+        }
+    }
+
+    fn open_device(
+        &mut self,
+        state: &mut State,
+        node: DrmNode,
+        path: &Path,
+    ) -> Result<Device, String> {
+        let fd = self
+            .session
+            .open(
+                path,
+                OFlag::O_RDWR | OFlag::O_CLOEXEC | OFlag::O_NOCTTY | OFlag::O_NONBLOCK,
+            )
+            .map_err(|err| err.to_string())?;
+        let fd: DrmDeviceFd = DrmDeviceFd::new(DeviceFd::from(fd));
+
+        let (drm, notifier) = DrmDevice::new(fd.clone(), true).map_err(|err| err.to_string())?;
+        if !drm.is_atomic() {
+            eprintln!("{node} has no atomic modesetting, overlay planes won't be used");
+        }
+        let gbm: GbmDevice<DrmDeviceFd> = GbmDevice::new(fd).map_err(|err| err.to_string())?;
+
+        let egl_display: EGLDisplay =
+            unsafe { EGLDisplay::new(gbm.clone()) }.map_err(|err| err.to_string())?;
+        let egl_context: EGLContext =
+            EGLContext::new(&egl_display).map_err(|err| err.to_string())?;
+        let renderer: GlesRenderer =
+            unsafe { GlesRenderer::new(egl_context) }.map_err(|err| err.to_string())?;
+
+        let token: RegistrationToken = self
+            .handle
+            .insert_source(
+                notifier,
+                move |event: DrmEvent, _, data: &mut Data| match event {
+                    DrmEvent::VBlank(crtc) => on_vblank(data, node, crtc),
+                    DrmEvent::Error(err) => eprintln!("DRM error on {node}: {err}"),
+                },
+            )
+            .unwrap();
+
+        let mut device: Device = Device {
+            drm,
+            gbm,
+            renderer,
+            surfaces: HashMap::new(),
+            token,
+        };
+        device.scan_connectors(state);
+
+        Ok(device)
+    }
+
+    fn device_removed(&mut self, state: &mut State, device_id: dev_t) {
+        let Ok(node) = DrmNode::from_dev_id(device_id) else {
+            return;
+        };
+        let Some(device) = self.devices.remove(&node) else {
+            return;
+        };
+
+        for surface in device.surfaces.into_values() {
+            state.remove_output(&surface.output, surface.global);
+        }
+        self.handle.remove(device.token);
+    }
+
+    fn device_changed(&mut self, state: &mut State, device_id: dev_t) {
+        let Ok(node) = DrmNode::from_dev_id(device_id) else {
+            return;
+        };
+        let Some(device) = self.devices.get_mut(&node) else {
+            return;
+        };
+
+        device.remove_disconnected(state);
+        let before: Vec<crtc::Handle> = device.surfaces.keys().copied().collect();
+        device.scan_connectors(state);
+        let added: Vec<crtc::Handle> = device
+            .surfaces
+            .keys()
+            .filter(|crtc| !before.contains(crtc))
+            .copied()
+            .collect();
+
+        for crtc in added {
+            self.render(state, node, crtc);
+        }
+    }
+
+    fn pause(&mut self) {
+        self.libinput.suspend();
+        for device in self.devices.values_mut() {
+            device.drm.pause();
+        }
+    }
+
+    // Back on our VT, nothing on screen can be trusted anymore
+    fn activate(&mut self, state: &mut State) {
+        if let Err(err) = self.libinput.resume() {
+            eprintln!("Failed to resume libinput: {err:?}");
+        }
+
+        let mut surfaces: Vec<(DrmNode, crtc::Handle)> = Vec::new();
+        for (node, device) in &mut self.devices {
+            if let Err(err) = device.drm.activate() {
+                eprintln!("Failed to activate {node}: {err}");
+            }
+            for (crtc, surface) in &mut device.surfaces {
+                surface.compositor.reset_buffers();
+                surfaces.push((*node, *crtc));
+            }
+        }
+
+        // Nothing is waiting for a vblank anymore, so kick rendering off
+        // again
+        for (node, crtc) in surfaces {
+            self.render(state, node, crtc);
+        }
+    }
+
+    pub fn wake(&mut self, state: &mut State) {
+        // activate() renders everything once we're back
+        if !self.session.is_active() {
+            return;
+        }
+        let reapply: bool = state.take_output_reapply();
+        self.apply_dpms(state, reapply);
+
+        let idle: Vec<(DrmNode, crtc::Handle)> = self
+            .devices
+            .iter()
+            .flat_map(|(node, device)| {
+                device
+                    .surfaces
+                    .iter()
+                    .filter(|(_, surface)| surface.idle)
+                    .map(move |(crtc, _)| (*node, *crtc))
+            })
+            .collect();
+
+        for (node, crtc) in idle {
+            self.schedule_render(state, node, crtc);
+        }
+    }
+
+    // Idle surfaces render right away, unless the last frame was less than
+    // a refresh ago. Then a timer waits out the rest, so clients committing
+    // as fast as they get frame callbacks don't get more than the refresh
+    // rate.
+    fn schedule_render(&mut self, state: &mut State, node: DrmNode, crtc: crtc::Handle) {
+        let Some(surface) = self
+            .devices
+            .get_mut(&node)
+            .and_then(|device: &mut Device| device.surfaces.get_mut(&crtc))
+        else {
+            return;
+        };
+        if surface.scheduled {
+            return;
+        }
+
+        let remaining: Duration = surface
+            .frame_interval()
+            .saturating_sub(surface.last_render.elapsed());
+        if remaining.is_zero() {
+            self.render(state, node, crtc);
+            return;
+        }
+
+        surface.scheduled = true;
+        self.handle
+            .insert_source(
+                Timer::from_duration(remaining),
+                move |_, _, data: &mut Data| {
+                    let Data {
+                        display,
+                        state,
+                        backend,
+                    } = data;
+                    let Backend::Drm(drm) = backend else {
+                        return TimeoutAction::Drop;
+                    };
+                    if let Some(surface) = drm
+                        .devices
+                        .get_mut(&node)
+                        .and_then(|device: &mut Device| device.surfaces.get_mut(&crtc))
+                    {
+                        surface.scheduled = false;
+                        // Switched away in the meantime, activate() renders
+                        // everything once we're back
+                        if surface.idle && drm.session.is_active() {
+                            drm.render(state, node, crtc);
+                            display.flush_clients().unwrap();
+                        }
+                    }
+                    TimeoutAction::Drop
+                },
+            )
+            .unwrap();
+    }
+
+    // Turning an output on leaves its surface idle, wake() renders it
+    // right after. With `reapply` every output is set again, whatever we
+    // think it's in.
+    fn apply_dpms(&mut self, state: &State, reapply: bool) {
+        for device in self.devices.values_mut() {
+            for (crtc, surface) in device.surfaces.iter_mut() {
+                let off: bool = state.dpms.is_output_off(&surface.output);
+                if off == surface.off && !reapply {
+                    continue;
+                }
+
+                if let Err(err) = set_dpms(&device.drm, surface.connector, !off) {
+                    eprintln!("Failed to set DPMS on {}: {err}", surface.output.name());
+                }
+                if reapply {
+                    if let Err(err) = reset_gamma(&device.drm, *crtc) {
+                        eprintln!("Failed to reset gamma on {}: {err}", surface.output.name());
+                    }
+                }
+                surface.off = off;
+                surface.compositor.reset_buffers();
+                surface.idle = true;
+            }
+        }
+    }
+
+    fn render(&mut self, state: &mut State, node: DrmNode, crtc: crtc::Handle) {
+        let Some(device) = self.devices.get_mut(&node) else {
+            return;
+        };
+        let Some(surface) = device.surfaces.get_mut(&crtc) else {
+            return;
+        };
+        // Page flips fail on a CRTC that's off
+        if surface.off {
+            surface.idle = true;
+            return;
+        }
+        // The previous frame stays up while clients catch up with a new
+        // layout, their commits wake us again
+        if !state.transaction_ready(&surface.output) {
+            surface.idle = true;
+            return;
+        }
+
+        state.prepare_frame();
+        let failed: bool =
+            match surface.render(state, &mut device.renderer, self.start_time.elapsed()) {
+                Ok(()) => false,
+                Err(err) => {
+                    eprintln!("Failed to render {}: {err}", surface.output.name());
+                    // No vblank is coming for a frame that wasn't queued
+                    surface.idle = true;
+                    true
+                }
+            };
+        state.process_screencopies(&mut device.renderer, &surface.output);
+        state.process_screenshots(&mut device.renderer, &surface.output);
+        state.frame_finished();
+
+        // An empty frame can't be committed, so no vblank comes for what's
+        // still animating without having changed anything yet. A failed
+        // frame is tried again a refresh later.
+        if surface.idle && (failed || !state.can_idle()) {
+            self.schedule_render(state, node, crtc);
+        }
+    }
+}
+
+impl Device {
+    // One surface per connected connector, each on a CRTC nobody else uses.
+    // Connectors that already have one are left alone.
+    fn scan_connectors(&mut self, state: &mut State) {
+        let Ok(resources) = self.drm.resource_handles() else {
+            return;
+        };
+
+        for handle in resources.connectors() {
+            let Ok(connector) = self.drm.get_connector(*handle, false) else {
+                continue;
+            };
+            if connector.state() != connector::State::Connected
+                || self
+                    .surfaces
+                    .values()
+                    .any(|surface: &Surface| surface.connector == *handle)
+            {
+                continue;
+            }
+
+            let Some(mode) = connector
+                .modes()
+                .iter()
+                .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+                .or_else(|| connector.modes().first())
+                .copied()
+            else {
+                continue;
+            };
+
+            let crtc: Option<crtc::Handle> = connector
+                .encoders()
+                .iter()
+                .filter_map(|encoder| self.drm.get_encoder(*encoder).ok())
+                .flat_map(|encoder| resources.filter_crtcs(encoder.possible_crtcs()))
+                .find(|crtc| !self.surfaces.contains_key(crtc));
+            let Some(crtc) = crtc else {
+                continue;
+            };
+
+            let name: String = format!(
+                "{}-{}",
+                connector.interface().as_str(),
+                connector.interface_id()
+            );
+            match self.create_surface(state, &connector, crtc, mode, name.clone()) {
+                Ok(surface) => {
+                    self.surfaces.insert(crtc, surface);
+                }
+                Err(err) => eprintln!("Failed to set up {name}: {err}"),
+            }
+        }
+    }
+
+    // Drops the surfaces of monitors that were unplugged, their windows are
+    // moved to the remaining outputs by apply_output_config()
+    fn remove_disconnected(&mut self, state: &mut State) {
+        let disconnected: Vec<crtc::Handle> = self
+            .surfaces
+            .iter()
+            .filter(|(_, surface)| {
+                self.drm
+                    .get_connector(surface.connector, false)
+                    .map_or(true, |connector: connector::Info| {
+                        connector.state() != connector::State::Connected
+                    })
+            })
+            .map(|(crtc, _)| *crtc)
+            .collect();
+
+        for crtc in disconnected {
+            let surface: Surface = self.surfaces.remove(&crtc).unwrap();
+            eprintln!("{} was disconnected", surface.output.name());
+            state.remove_output(&surface.output, surface.global);
+        }
+    }
+
+    fn create_surface(
+        &mut self,
+        state: &mut State,
+        connector: &connector::Info,
+        crtc: crtc::Handle,
+        mode: control::Mode,
+        name: String,
+    ) -> Result<Surface, String> {
+        let drm_surface: DrmSurface = self
+            .drm
+            .create_surface(crtc, mode, &[connector.handle()])
+            .map_err(|err| err.to_string())?;
+        let mut planes: Planes = drm_surface.planes().clone();
+        if !state.config.render.overlay_planes {
+            planes.overlay.clear();
+        }
+        let formats: Vec<Fourcc> = if state.config.render.ten_bit {
+            TEN_BIT_FORMATS
+                .iter()
+                .chain(&COLOR_FORMATS)
+                .copied()
+                .collect()
+        } else {
+            COLOR_FORMATS.to_vec()
+        };
+        let allocator: GbmAllocator<DrmDeviceFd> = GbmAllocator::new(
+            self.gbm.clone(),
+            GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT,
+        );
+
+        let edid: Option<EdidInfo> =
+            read_edid(&self.drm, connector.handle()).and_then(|edid| EdidInfo::parse(&edid));
+        let properties: PhysicalProperties = match &edid {
+            Some(edid) => edid.physical_properties(),
+            None => {
+                let (width, height) = connector.size().unwrap_or((0, 0));
+                PhysicalProperties {
+                    size: (width as i32, height as i32).into(),
+                    subpixel: Subpixel::Unknown,
+                    make: "Unknown".into(),
+                    model: "Unknown".into(),
+                }
+            }
+        };
+
+        let output: Output = Output::new(name.clone(), properties);
+        for other in connector.modes() {
+            output.add_mode(Mode::from(*other));
+        }
+        output.change_current_state(Some(Mode::from(mode)), None, None, None);
+        output.set_preferred(Mode::from(mode));
+
+        let compositor: Compositor = DrmCompositor::new(
+            OutputModeSource::Auto(output.clone()),
+            drm_surface,
+            Some(planes),
+            allocator,
+            self.gbm.clone(),
+            &formats,
+            self.renderer.egl_context().dmabuf_render_formats().clone(),
+            self.drm.cursor_size(),
+            Some(self.gbm.clone()),
+        )
+        .map_err(|err| err.to_string())?;
+        if state.config.render.ten_bit && !TEN_BIT_FORMATS.contains(&compositor.format()) {
+            eprintln!("{name} can't do 10 bits per channel, using 8");
+        }
+
+        let global: GlobalId = output.create_global::<State>(&state.display_handle);
+        if let Some(edid) = edid {
+            output.user_data().insert_if_missing(|| edid);
+        }
+        state.outputs.push(output.clone());
+
+        Ok(Surface {
+            elements: Vec::new(),
+            idle: false,
+            last_render: Instant::now(),
+            scheduled: false,
+            output,
+            global,
+            connector: connector.handle(),
+            off: false,
+            compositor,
+            modes: connector.modes().to_vec(),
+            mode,
+        })
+    }
+}
+
+impl Surface {
+    // From the mode's refresh rate, in mHz
+    fn frame_interval(&self) -> Duration {
+        let refresh: i32 = self
+            .output
+            .current_mode()
+            .map_or(60_000, |mode: Mode| mode.refresh);
+        Duration::from_secs_f64(1000.0 / refresh.max(1) as f64)
+    }
+
+    // The output's mode is what [[output]] mode asked for, see
+    // apply_output_config. Switched in the same commit as the next frame.
+    fn apply_mode(&mut self) -> Result<(), String> {
+        let Some(wanted) = self.output.current_mode() else {
+            return Ok(());
+        };
+        if Mode::from(self.mode) == wanted {
+            return Ok(());
+        }
+        let Some(mode) = self
+            .modes
+            .iter()
+            .find(|mode: &&control::Mode| Mode::from(**mode) == wanted)
+            .copied()
+        else {
+            return Ok(());
+        };
+
+        self.compositor
+            .use_mode(mode)
+            .map_err(|err| err.to_string())?;
+        self.mode = mode;
+        Ok(())
+    }
+
+    fn render(
+        &mut self,
+        state: &mut State,
+        renderer: &mut GlesRenderer,
+        time: Duration,
+    ) -> Result<(), String> {
+        self.last_render = Instant::now();
+        self.apply_mode()?;
+
+        state.output_render_elements(renderer, &self.output, &mut self.elements);
+        let RenderFrameResult { damage, states, .. } = self
+            .compositor
+            .render_frame(
+                renderer,
+                &self.elements,
+                state.background_color(&self.output),
+            )
+            .map_err(|err| format!("{err:?}"))?;
+
+        self.idle = damage.is_none();
+        if self.idle {
+            // Nothing changed, but clients waiting on a frame callback
+            // still get it. Whatever they commit next wakes us up again.
+            state.send_frames(&self.output, &states, time);
+            state.lock_frame_presented(&self.output);
+            return Ok(());
+        }
+
+        self.compositor
+            .queue_frame(())
+            .map_err(|err| err.to_string())?;
+        state.latency.frame_submitted(Instant::now());
+
+        state.send_frames(&self.output, &states, time);
+        Ok(())
+    }
+}
+
+// The previous frame is on screen, render the next one
+fn on_vblank(data: &mut Data, node: DrmNode, crtc: crtc::Handle) {
+    let Data {
+        display,
+        state,
+        backend,
+    } = data;
+    let Backend::Drm(drm) = backend else {
+        return;
+    };
+
+    if let Some(surface) = drm
+        .devices
+        .get_mut(&node)
+        .and_then(|device: &mut Device| device.surfaces.get_mut(&crtc))
+    {
+        if let Err(err) = surface.compositor.frame_submitted() {
+            eprintln!("Failed to submit frame: {err}");
+        }
+        state.lock_frame_presented(&surface.output);
+    }
+
+    drm.render(state, node, crtc);
+    display.flush_clients().unwrap();
+}
+
+impl State {
+    pub fn switch_vt(&mut self, vt: i32) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        if let Err(err) = session.change_vt(vt) {
+            eprintln!("Failed to switch to VT {vt}: {err}");
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use smithay::reexports::calloop::LoopHandle;
+
+use crate::{data::Data, state::State};
+
+pub mod drm;
+pub mod winit;
+
+pub enum Backend {
+    // Nested in another compositor or an X server
+    Winit(winit::WinitBackend),
+    // Directly on a TTY
+    Drm(drm::DrmBackend),
+}
+
+impl Backend {
+    // Something happened that may need a new frame, restarts rendering on
+    // outputs that went idle
+    pub fn wake(&mut self, state: &mut State) {
+        match self {
+            Backend::Winit(winit) => winit.wake(),
+            Backend::Drm(drm) => drm.wake(state),
+        }
+    }
+}
+
+// PULSEWM_BACKEND=winit|drm picks one explicitly, otherwise we run nested
+// when started from inside another graphical session
+pub fn init(handle: &LoopHandle<'static, Data>, state: &mut State) -> Result<Backend, String> {
+    let nested: bool = match std::env::var("PULSEWM_BACKEND").as_deref() {
+        Ok("winit") => true,
+        Ok("drm") => false,
+        Ok(other) => return Err(format!("Unknown backend \"{other}\"")),
+        Err(_) => {
+            std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_some()
+        }
+    };
+
+    if nested {
+        winit::init(handle, state).map(Backend::Winit)
+    } else {
+        drm::init(handle, state).map(Backend::Drm)
+    }
+}
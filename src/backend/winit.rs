@@ -0,0 +1,203 @@
+use std::time::{Duration, Instant};
+
+use smithay::{
+    backend::{
+        allocator::Format,
+        renderer::{
+            damage::OutputDamageTracker, element::RenderElementStates, gles::GlesRenderer,
+            ImportDma, ImportMemWl,
+        },
+        winit::{self, WinitEvent, WinitEventLoop, WinitGraphicsBackend},
+    },
+    output::{Mode, Output, PhysicalProperties, Subpixel},
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        LoopHandle, RegistrationToken,
+    },
+    utils::{Physical, Size, Transform},
+};
+
+use crate::{backend::Backend, data::Data, render::CustomRenderElements, state::State};
+
+// winit has no fd to wait on, so while idle the host window is still
+// polled for input, just rarely
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct WinitBackend {
+    backend: WinitGraphicsBackend<GlesRenderer>,
+    winit: WinitEventLoop,
+    output: Output,
+    damage_tracker: OutputDamageTracker,
+    // Reused every frame
+    elements: Vec<CustomRenderElements>,
+    start_time: Instant,
+    frame_interval: Duration,
+    handle: LoopHandle<'static, Data>,
+    timer: RegistrationToken,
+    idle: bool,
+    last_render: Instant,
+}
+
+pub fn init(handle: &LoopHandle<'static, Data>, state: &mut State) -> Result<WinitBackend, String> {
+    let (backend, winit) = winit::init::<GlesRenderer>()
+        .map_err(|err| format!("Failed to initialize winit: {err}"))?;
+
+    let size: Size<i32, Physical> = backend.window_size().physical_size;
+    // We draw our own
+    backend.window().set_cursor_visible(false);
+    state
+        .shm_state
+        .update_formats(backend.renderer().shm_formats());
+    let dmabuf_formats: Vec<Format> = backend.renderer().dmabuf_formats().cloned().collect();
+    state.create_dmabuf_global(dmabuf_formats);
+
+    let mut refresh_rate: u32 = state.config.winit.refresh_rate.max(1);
+    // In mHz, which has to fit an i32
+    let refresh: i32 = match i32::try_from(refresh_rate)
+        .ok()
+        .and_then(|rate: i32| rate.checked_mul(1000))
+    {
+        Some(refresh) => refresh,
+        None => {
+            eprintln!("Refresh rate {refresh_rate} Hz is too high, using 60");
+            refresh_rate = 60;
+            60_000
+        }
+    };
+    let mode: Mode = Mode { size, refresh };
+
+    // Doesn't matter, winit takes care of it
+    let psychical_properties: PhysicalProperties = PhysicalProperties {
+        size: (0, 0).into(),
+        subpixel: Subpixel::Unknown,
+        make: "pulseWM".into(),
+        model: "pulseWM-Winit".into(),
+    };
+
+    let output: Output = Output::new("pulseWM-winit".to_string(), psychical_properties);
+    output.create_global::<State>(&state.display_handle);
+    output.change_current_state(
+        Some(mode),
+        Some(Transform::Flipped180),
+        None,
+        Some((0, 0).into()),
+    );
+    output.set_preferred(mode);
+    state.outputs.push(output.clone());
+
+    Ok(WinitBackend {
+        damage_tracker: OutputDamageTracker::from_output(&output),
+        elements: Vec::new(),
+        backend,
+        winit,
+        output,
+        start_time: Instant::now(),
+        frame_interval: Duration::from_secs(1) / refresh_rate,
+        timer: schedule(handle, Duration::ZERO),
+        handle: handle.clone(),
+        idle: false,
+        last_render: Instant::now(),
+    })
+}
+
+fn schedule(handle: &LoopHandle<'static, Data>, delay: Duration) -> RegistrationToken {
+    handle
+        .insert_source(Timer::from_duration(delay), |_, _, data: &mut Data| {
+            let Data {
+                display,
+                state,
+                backend,
+            } = data;
+            let Backend::Winit(winit) = backend else {
+                return TimeoutAction::Drop;
+            };
+
+            let next: Duration = winit.tick(state);
+            display.flush_clients().unwrap();
+
+            TimeoutAction::ToDuration(next)
+        })
+        .unwrap()
+}
+
+impl WinitBackend {
+    // Renders once a frame interval has passed since the last frame, so
+    // clients committing right after every frame callback don't get more
+    // than the refresh rate
+    pub fn wake(&mut self) {
+        if !self.idle {
+            return;
+        }
+
+        self.idle = false;
+        self.handle.remove(self.timer);
+        let delay: Duration = self
+            .frame_interval
+            .saturating_sub(self.last_render.elapsed());
+        self.timer = schedule(&self.handle, delay);
+    }
+
+    // Returns when to run again
+    fn tick(&mut self, state: &mut State) -> Duration {
+        self.winit
+            .dispatch_new_events(|event: WinitEvent| match event {
+                WinitEvent::Input(event) => state.process_input_event(event),
+                // Resizes and exposes need a frame too
+                _ => state.request_redraw(),
+            })
+            .unwrap();
+
+        if self.idle {
+            return IDLE_POLL_INTERVAL;
+        }
+
+        if !self.render(state) && state.can_idle() {
+            self.idle = true;
+            return IDLE_POLL_INTERVAL;
+        }
+
+        self.frame_interval
+    }
+
+    // Returns whether anything was damaged. Frame callbacks go out either
+    // way, clients waiting on one may have nothing new to show yet.
+    fn render(&mut self, state: &mut State) -> bool {
+        // The previous frame stays up while clients catch up with a new
+        // layout, their commits wake us again
+        if !state.transaction_ready(&self.output) {
+            return false;
+        }
+        self.last_render = Instant::now();
+        state.prepare_frame();
+
+        // Locking renders the frozen desktop into textures of its own, so
+        // the window is bound after
+        state.output_render_elements(self.backend.renderer(), &self.output, &mut self.elements);
+        self.backend.bind().unwrap();
+
+        // Without the buffer's age everything counts as damaged every frame
+        let age: usize = self.backend.buffer_age().unwrap_or(0);
+        let (damage, render_states): (_, RenderElementStates) = self
+            .damage_tracker
+            .render_output(
+                self.backend.renderer(),
+                age,
+                &self.elements,
+                state.background_color(&self.output),
+            )
+            .unwrap();
+
+        if damage.is_some() {
+            self.backend.submit(None).unwrap();
+            state.latency.frame_submitted(Instant::now());
+        }
+        state.lock_frame_presented(&self.output);
+
+        state.send_frames(&self.output, &render_states, self.start_time.elapsed());
+        state.process_screencopies(self.backend.renderer(), &self.output);
+        state.process_screenshots(self.backend.renderer(), &self.output);
+        state.frame_finished();
+
+        damage.is_some()
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                texture::{TextureBuffer, TextureRenderElement},
+                Kind,
+            },
+            gles::{GlesRenderer, GlesTexture},
+        },
+    },
+    output::Output,
+    utils::{Logical, Rectangle, Size, Transform},
+};
+
+use crate::{
+    config::{self, OutputRule},
+    outputs, profile,
+    render::CustomRenderElements,
+    state::State,
+};
+
+const DEFAULT_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+// Decoded wallpapers by path, None if loading failed so it isn't retried
+// every frame
+#[derive(Default)]
+pub struct Wallpapers {
+    textures: HashMap<PathBuf, Option<Wallpaper>>,
+}
+
+struct Wallpaper {
+    texture: TextureBuffer<GlesTexture>,
+    size: Size<i32, Logical>,
+}
+
+fn load(renderer: &mut GlesRenderer, path: &Path) -> Option<Wallpaper> {
+    let image: image::RgbaImage = match image::open(config::expand_home(path)) {
+        Ok(image) => image.into_rgba8(),
+        Err(err) => {
+            eprintln!("Failed to load wallpaper {}: {err}", path.display());
+            return None;
+        }
+    };
+    let (width, height) = image.dimensions();
+
+    // RGBA bytes in memory are ABGR8888 in DRM fourcc's little endian naming
+    let texture: TextureBuffer<GlesTexture> = TextureBuffer::from_memory(
+        renderer,
+        image.as_raw(),
+        Fourcc::Abgr8888,
+        (width as i32, height as i32),
+        false,
+        1,
+        Transform::Normal,
+        None,
+    )
+    .map_err(|err| eprintln!("Failed to upload wallpaper {}: {err}", path.display()))
+    .ok()?;
+
+    Some(Wallpaper {
+        texture,
+        size: (width as i32, height as i32).into(),
+    })
+}
+
+// The part of an image of `image` size that covers `target` without
+// stretching, centered
+fn cover(image: Size<i32, Logical>, target: Size<i32, Logical>) -> Rectangle<f64, Logical> {
+    let image: Size<f64, Logical> = image.to_f64();
+    let target: Size<f64, Logical> = target.to_f64();
+    let scale: f64 = (target.w / image.w).max(target.h / image.h);
+    let size: Size<f64, Logical> = (target.w / scale, target.h / scale).into();
+
+    Rectangle::from_loc_and_size(((image.w - size.w) / 2.0, (image.h - size.h) / 2.0), size)
+}
+
+impl State {
+    // Later rules override earlier ones field by field
+    pub fn output_rules(&self, output: &Output) -> OutputRule {
+        self.config
+            .output_rules
+            .iter()
+            .filter(|rule: &&OutputRule| profile::matches(&rule.criteria, output))
+            .fold(
+                OutputRule::default(),
+                |merged: OutputRule, rule: &OutputRule| merged.merge(rule),
+            )
+    }
+
+    // Used as the clear color, so it costs nothing to draw
+    pub fn background_color(&self, output: &Output) -> [f32; 4] {
+        self.output_rules(output)
+            .background_color
+            .or(self.config.appearance.background_color)
+            .map_or(DEFAULT_COLOR, |color| color.0)
+    }
+
+    pub fn background_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        let Some(path) = self.output_rules(output).wallpaper else {
+            return;
+        };
+
+        let wallpaper: &Option<Wallpaper> = self
+            .wallpapers
+            .textures
+            .entry(path.clone())
+            .or_insert_with(|| load(renderer, &path));
+        let Some(wallpaper) = wallpaper else {
+            return;
+        };
+
+        let output_size: Size<i32, Logical> = outputs::logical_size(output);
+        elements.push(
+            TextureRenderElement::from_texture_buffer(
+                (0.0, 0.0),
+                &wallpaper.texture,
+                None,
+                Some(cover(wallpaper.size, output_size)),
+                Some(output_size),
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+use zbus::{blocking::Connection, dbus_proxy};
+
+use crate::state::State;
+
+const BACKLIGHT_DIR: &str = "/sys/class/backlight";
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+trait Session {
+    fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()>;
+}
+
+pub struct Backlight {
+    name: String,
+    path: PathBuf,
+    max: u32,
+    // logind lets the session owner change brightness without write access
+    // to sysfs, writing the file directly is only a fallback
+    session: Option<SessionProxyBlocking<'static>>,
+}
+
+impl Backlight {
+    // Uses `device` if given, otherwise the first backlight the kernel lists
+    pub fn open(device: Option<&str>) -> Option<Self> {
+        let name: String = match device {
+            Some(device) => device.to_string(),
+            None => {
+                let mut names: Vec<String> = std::fs::read_dir(BACKLIGHT_DIR)
+                    .ok()?
+                    .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .collect();
+                names.sort();
+                names.into_iter().next()?
+            }
+        };
+
+        let path: PathBuf = Path::new(BACKLIGHT_DIR).join(&name);
+        let max: u32 = read_value(&path.join("max_brightness"))?;
+
+        let session: Option<SessionProxyBlocking<'static>> = Connection::system()
+            .and_then(|connection: Connection| SessionProxyBlocking::new(&connection))
+            .ok();
+
+        Some(Self {
+            name,
+            path,
+            max,
+            session,
+        })
+    }
+
+    pub fn percent(&self) -> Option<u32> {
+        let current: u32 = read_value(&self.path.join("brightness"))?;
+        Some((current * 100 + self.max / 2) / self.max.max(1))
+    }
+
+    // Returns the new brightness in percent
+    pub fn adjust(&self, delta: i32) -> Option<u32> {
+        let percent: u32 = (self.percent()? as i32 + delta).clamp(0, 100) as u32;
+        // Never turn the panel off completely, that's what DPMS is for
+        let value: u32 = (percent * self.max / 100).max(1);
+
+        let via_logind: bool =
+            self.session
+                .as_ref()
+                .map_or(false, |session: &SessionProxyBlocking| {
+                    session
+                        .set_brightness("backlight", &self.name, value)
+                        .is_ok()
+                });
+
+        if !via_logind {
+            if let Err(err) = std::fs::write(self.path.join("brightness"), value.to_string()) {
+                eprintln!("Failed to set brightness of {}: {err}", self.name);
+                return None;
+            }
+        }
+
+        Some(percent)
+    }
+}
+
+fn read_value(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+impl State {
+    pub fn adjust_brightness(&mut self, delta: i32) {
+        let Some(percent) = self
+            .backlight
+            .as_ref()
+            .and_then(|b: &Backlight| b.adjust(delta))
+        else {
+            return;
+        };
+
+        self.osd.show(
+            format!("Brightness {percent}%"),
+            Some(percent as f64 / 100.0),
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::{collections::HashMap, fmt::Write};
+
+use smithay::{
+    backend::renderer::{
+        element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            texture::{TextureBuffer, TextureRenderElement},
+            Kind,
+        },
+        gles::{GlesRenderer, GlesTexture},
+    },
+    desktop::{layer_map_for_output, Window},
+    output::Output,
+    utils::{Logical, Point, Rectangle},
+};
+
+use crate::{
+    config::{BarPosition, OutputRule},
+    render::CustomRenderElements,
+    state::State,
+    theme::Palette,
+};
+
+pub const HEIGHT: i32 = 24;
+const PADDING: i32 = 8;
+const FONT_SIZE: f32 = 13.0;
+
+// Workspaces and the focused window's title along one edge of an output
+#[derive(Default)]
+pub struct Bar {
+    // By output name
+    backgrounds: HashMap<String, SolidColorBuffer>,
+}
+
+impl State {
+    pub fn bar_position(&self, output: &Output) -> Option<BarPosition> {
+        let rule: OutputRule = self.output_rules(output);
+        let enabled: bool = rule.bar.unwrap_or(self.config.bar.enabled);
+        enabled.then(|| rule.bar_position.unwrap_or(self.config.bar.position))
+    }
+
+    // The output minus the bar and the exclusive zones of layer surfaces,
+    // where windows should be placed
+    pub fn usable_area(&self, output: &Output) -> Option<Rectangle<i32, Logical>> {
+        let output_location: Point<i32, Logical> = self.space.output_geometry(output)?.loc;
+        let zone: Rectangle<i32, Logical> = layer_map_for_output(output).non_exclusive_zone();
+        let mut area: Rectangle<i32, Logical> =
+            Rectangle::from_loc_and_size(output_location + zone.loc, zone.size);
+
+        match self.bar_position(output) {
+            Some(BarPosition::Top) => {
+                area.loc.y += HEIGHT;
+                area.size.h -= HEIGHT;
+            }
+            Some(BarPosition::Bottom) => area.size.h -= HEIGHT,
+            None => {}
+        }
+
+        Some(area)
+    }
+
+    // "1 [2] 5  Firefox", occupied workspaces with the active one marked
+    fn bar_label(&self) -> String {
+        let mut label: String = String::new();
+        for index in 0..self.workspaces.count() {
+            let active: bool = self.workspaces.is_shown(index);
+            if !active && self.workspace_is_empty(index) {
+                continue;
+            }
+
+            if !label.is_empty() {
+                label.push(' ');
+            }
+            if active {
+                let _ = write!(label, "[{}]", index + 1);
+            } else {
+                let _ = write!(label, "{}", index + 1);
+            }
+        }
+
+        if let Some(title) = self
+            .focused_window()
+            .and_then(|window: Window| self.window_name(&window))
+        {
+            label.push_str("   ");
+            label.push_str(&title);
+        }
+
+        label
+    }
+
+    pub fn bar_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        let Some(position) = self.bar_position(output) else {
+            return;
+        };
+        let Some(output_geometry) = self.space.output_geometry(output) else {
+            return;
+        };
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let y: i32 = match position {
+            BarPosition::Top => 0,
+            BarPosition::Bottom => output_geometry.size.h - HEIGHT,
+        };
+        let at =
+            |offset: (i32, i32)| -> Point<i32, Logical> { Point::from((offset.0, y + offset.1)) };
+
+        let palette: &Palette = self.theme.palette();
+        let label: String = self.bar_label();
+
+        if let Some(text) = self.text.as_mut() {
+            let (_, text_height) = text.measure(&label, FONT_SIZE);
+            let buffer: Option<TextureBuffer<GlesTexture>> = text.render(
+                renderer,
+                &label,
+                FONT_SIZE,
+                palette.bar_text,
+                scale.ceil() as i32,
+            );
+            if let Some(buffer) = buffer {
+                elements.push(
+                    TextureRenderElement::from_texture_buffer(
+                        at((PADDING, (HEIGHT - text_height) / 2))
+                            .to_physical_precise_round(scale)
+                            .to_f64(),
+                        &buffer,
+                        None,
+                        None,
+                        None,
+                        Kind::Unspecified,
+                    )
+                    .into(),
+                );
+            }
+        }
+
+        let background: &mut SolidColorBuffer = self
+            .bar
+            .backgrounds
+            .entry(output.name())
+            .or_insert_with(|| SolidColorBuffer::new((0, 0), palette.bar_background));
+        background.update((output_geometry.size.w, HEIGHT), palette.bar_background);
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                background,
+                at((0, 0)).to_physical_precise_round(scale),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+}
@@ -0,0 +1,69 @@
+// Sends one command to the running pulseWM over its IPC socket and prints
+// the JSON it answers with. Arguments become the request's fields, values
+// are parsed as JSON and taken as strings otherwise:
+//
+//   pulsectl tree
+//   pulsectl focus id=4
+//   pulsectl move-to-workspace workspace=2
+//   pulsectl switch-workspace workspace=2
+//   pulsectl fullscreen app_id=mpv enabled=true
+//   pulsectl move app_id=foot x=0 y=0
+//   pulsectl resize width=-100 height=0 relative=true
+//   pulsectl set-gaps inner=8 outer=4
+//   pulsectl set-theme theme=light
+//   pulsectl screenshot output=DP-1
+//   pulsectl subscribe events='["window", "workspace"]'
+//
+// Bindings can run the same commands, see pulseWM::ipc::CommandChain
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    process::ExitCode,
+};
+
+use pulseWM::ipc::command_request;
+use serde_json::Value;
+
+const USAGE: &str = "Usage: pulsectl <command> [field=value]...";
+
+fn run(args: &[String]) -> Result<bool, String> {
+    let request: Value = command_request(args).map_err(|err: String| format!("{err}\n{USAGE}"))?;
+    let path: String = std::env::var("PULSEWM_SOCK")
+        .map_err(|_| "PULSEWM_SOCK is not set, is pulseWM running?".to_string())?;
+
+    let mut stream: UnixStream =
+        UnixStream::connect(&path).map_err(|err| format!("Failed to connect to {path}: {err}"))?;
+    let mut line: Vec<u8> = serde_json::to_vec(&request).map_err(|err| err.to_string())?;
+    line.push(b'\n');
+    stream
+        .write_all(&line)
+        .map_err(|err| format!("Failed to send the request: {err}"))?;
+
+    // Subscriptions answer once and then send events until pulseWM quits
+    let mut success: bool = true;
+    let mut first: bool = true;
+    for line in BufReader::new(&stream).lines() {
+        let line: String = line.map_err(|err| format!("Failed to read the response: {err}"))?;
+        if first {
+            let response: Value = serde_json::from_str(&line).map_err(|err| err.to_string())?;
+            success = response["success"] == Value::Bool(true);
+            first = false;
+        }
+        println!("{line}");
+    }
+
+    Ok(success)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
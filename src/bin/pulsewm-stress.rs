@@ -0,0 +1,687 @@
+// Opens a bunch of windows that commit new content at a fixed rate and
+// resize themselves randomly, to load the layout and render paths without
+// any real clients. Run it inside a pulseWM session:
+//
+//   pulsewm-stress --windows 16 --rate 120 --resize-ms 250 --duration 30
+//
+// With --dmabuf the buffers come from the first render node through gbm
+// instead of shared memory, like those of GPU clients.
+
+use std::{
+    ffi::OsStr,
+    fs::{DirEntry, File},
+    num::NonZeroUsize,
+    os::{
+        fd::OwnedFd,
+        unix::{fs::FileExt, io::AsRawFd},
+    },
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use smithay::{
+    backend::{
+        allocator::{
+            dmabuf::{AsDmabuf, Dmabuf},
+            gbm::{GbmAllocator, GbmBuffer, GbmBufferFlags, GbmDevice},
+            Allocator, Buffer, Fourcc, Modifier,
+        },
+        drm::DrmDeviceFd,
+    },
+    reexports::nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags},
+    utils::DeviceFd,
+};
+use wayland_client::{
+    delegate_noop,
+    globals::{registry_queue_init, GlobalList, GlobalListContents},
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_compositor::WlCompositor,
+        wl_registry::WlRegistry,
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, EventQueue, QueueHandle,
+};
+use wayland_protocols::{
+    wp::linux_dmabuf::zv1::client::{
+        zwp_linux_buffer_params_v1::{self, ZwpLinuxBufferParamsV1},
+        zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1,
+    },
+    xdg::shell::client::{
+        xdg_surface::{self, XdgSurface},
+        xdg_toplevel::{self, XdgToplevel},
+        xdg_wm_base::{self, XdgWmBase},
+    },
+};
+
+const MIN_SIZE: (i32, i32) = (100, 100);
+const MAX_SIZE: (i32, i32) = (1200, 800);
+// Two buffers per window, one on screen and one being drawn
+const SLOT_BYTES: usize = (MAX_SIZE.0 * MAX_SIZE.1 * 4) as usize;
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+struct Options {
+    windows: usize,
+    rate: u32,
+    // 0 disables resizing
+    resize_ms: u64,
+    duration: Option<Duration>,
+    dmabuf: bool,
+}
+
+impl Options {
+    fn parse() -> Result<Self, String> {
+        let mut options: Options = Options {
+            windows: 8,
+            rate: 60,
+            resize_ms: 500,
+            duration: None,
+            dmabuf: false,
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || -> Result<u64, String> {
+                args.next()
+                    .ok_or_else(|| format!("{arg} needs a value"))?
+                    .parse()
+                    .map_err(|err| format!("Invalid value for {arg}: {err}"))
+            };
+
+            match arg.as_str() {
+                "--windows" => options.windows = value()? as usize,
+                "--rate" => options.rate = value()?.max(1) as u32,
+                "--resize-ms" => options.resize_ms = value()?,
+                "--duration" => options.duration = Some(Duration::from_secs(value()?)),
+                "--dmabuf" => options.dmabuf = true,
+                _ => {
+                    return Err(format!(
+                        "Unknown argument {arg}\n\
+                         Usage: pulsewm-stress [--windows N] [--rate HZ] [--resize-ms MS] \
+                         [--duration SECS] [--dmabuf]"
+                    ))
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+// xorshift, good enough for picking window sizes
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let seed: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |time: Duration| time.as_nanos() as u64);
+        Self(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn range(&mut self, min: i32, max: i32) -> i32 {
+        min + (self.next() % (max - min + 1) as u64) as i32
+    }
+}
+
+// Where a window's two buffers live
+enum Backing {
+    Shm { pool: WlShmPool, file: File },
+    // Allocated again whenever the window's size changed since
+    Dmabuf([Option<Dmabuf>; 2]),
+}
+
+// The render node buffers are allocated on and the compositor's global to
+// share them with
+struct Gpu {
+    allocator: GbmAllocator<DrmDeviceFd>,
+    dmabuf: ZwpLinuxDmabufV1,
+}
+
+impl Gpu {
+    fn open(dmabuf: ZwpLinuxDmabufV1) -> Result<Self, String> {
+        let mut nodes: Vec<PathBuf> = std::fs::read_dir("/dev/dri")
+            .map_err(|err| format!("/dev/dri: {err}"))?
+            .filter_map(|entry: std::io::Result<DirEntry>| entry.ok())
+            .map(|entry: DirEntry| entry.path())
+            .filter(|path: &PathBuf| {
+                path.file_name()
+                    .is_some_and(|name: &OsStr| name.to_string_lossy().starts_with("renderD"))
+            })
+            .collect();
+        nodes.sort();
+        let node: &PathBuf = nodes.first().ok_or("no render node in /dev/dri")?;
+
+        let file: File = File::options()
+            .read(true)
+            .write(true)
+            .open(node)
+            .map_err(|err| format!("{}: {err}", node.display()))?;
+        let fd: DrmDeviceFd = DrmDeviceFd::new(DeviceFd::from(OwnedFd::from(file)));
+        let gbm: GbmDevice<DrmDeviceFd> = GbmDevice::new(fd).map_err(|err| err.to_string())?;
+        // Linear, so they can be drawn into through a plain mapping
+        let allocator: GbmAllocator<DrmDeviceFd> =
+            GbmAllocator::new(gbm, GbmBufferFlags::LINEAR | GbmBufferFlags::RENDERING);
+
+        Ok(Self { allocator, dmabuf })
+    }
+
+    fn allocate(&mut self, (width, height): (i32, i32)) -> Result<Dmabuf, String> {
+        let buffer: GbmBuffer = self
+            .allocator
+            .create_buffer(
+                width as u32,
+                height as u32,
+                Fourcc::Argb8888,
+                &[Modifier::Linear],
+            )
+            .map_err(|err| err.to_string())?;
+        buffer.export().map_err(|err| err.to_string())
+    }
+}
+
+// Copies `pixels`, rows of `row_bytes`, into the first plane of a linear
+// dmabuf, whose stride may be larger
+fn write_dmabuf(dmabuf: &Dmabuf, pixels: &[u8], row_bytes: usize) -> Result<(), String> {
+    let fd = dmabuf.handles().next().ok_or("dmabuf without planes")?;
+    let offset: usize = dmabuf.offsets().next().unwrap_or(0) as usize;
+    let stride: usize = dmabuf.strides().next().unwrap_or(0) as usize;
+    let rows: usize = pixels.len() / row_bytes;
+    let length: NonZeroUsize = NonZeroUsize::new(offset + stride * rows).ok_or("empty dmabuf")?;
+
+    let map: *mut u8 = unsafe {
+        mmap(
+            None,
+            length,
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            MapFlags::MAP_SHARED,
+            fd.as_raw_fd(),
+            0,
+        )
+    }
+    .map_err(|err| err.to_string())?
+    .cast();
+    for (y, row) in pixels.chunks_exact(row_bytes).enumerate() {
+        unsafe {
+            std::ptr::copy_nonoverlapping(row.as_ptr(), map.add(offset + y * stride), row_bytes)
+        };
+    }
+    unsafe { munmap(map.cast(), length.get()) }.map_err(|err| err.to_string())
+}
+
+struct StressWindow {
+    surface: WlSurface,
+    xdg_surface: XdgSurface,
+    toplevel: XdgToplevel,
+    backing: Backing,
+    size: (i32, i32),
+    configured: bool,
+    closed: bool,
+    busy: [bool; 2],
+    frame: u32,
+    pixels: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Stats {
+    commits: u64,
+    // Both buffers were still held by the compositor
+    skipped: u64,
+}
+
+struct Stress {
+    windows: Vec<StressWindow>,
+    stats: Stats,
+}
+
+// Unlinked right away, the compositor only needs the fd
+fn create_shm_file(size: usize) -> std::io::Result<File> {
+    let dir: PathBuf =
+        std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from);
+    let path: PathBuf = dir.join(format!(
+        "pulsewm-stress-{}-{}",
+        std::process::id(),
+        Rng::new().next()
+    ));
+
+    let file: File = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    std::fs::remove_file(&path)?;
+    file.set_len(size as u64)?;
+    Ok(file)
+}
+
+impl StressWindow {
+    fn new(
+        index: usize,
+        globals: &Globals,
+        dmabuf: bool,
+        size: (i32, i32),
+        queue_handle: &QueueHandle<Stress>,
+    ) -> std::io::Result<Self> {
+        let backing: Backing = if dmabuf {
+            Backing::Dmabuf([None, None])
+        } else {
+            let file: File = create_shm_file(SLOT_BYTES * 2)?;
+            let pool: WlShmPool = globals.shm.create_pool(
+                file.as_raw_fd(),
+                (SLOT_BYTES * 2) as i32,
+                queue_handle,
+                (),
+            );
+            Backing::Shm { pool, file }
+        };
+
+        let surface: WlSurface = globals.compositor.create_surface(queue_handle, ());
+        let xdg_surface: XdgSurface =
+            globals
+                .wm_base
+                .get_xdg_surface(&surface, queue_handle, index);
+        let toplevel: XdgToplevel = xdg_surface.get_toplevel(queue_handle, index);
+        toplevel.set_title(format!("pulsewm-stress {index}"));
+        toplevel.set_app_id("pulsewm-stress".into());
+        surface.commit();
+
+        Ok(Self {
+            surface,
+            xdg_surface,
+            toplevel,
+            backing,
+            size,
+            configured: false,
+            closed: false,
+            busy: [false; 2],
+            frame: 0,
+            pixels: Vec::new(),
+        })
+    }
+
+    // Returns false if there was no free buffer to draw into
+    fn draw(
+        &mut self,
+        index: usize,
+        gpu: Option<&mut Gpu>,
+        queue_handle: &QueueHandle<Stress>,
+    ) -> bool {
+        let Some(slot) = self.busy.iter().position(|busy: &bool| !busy) else {
+            return false;
+        };
+
+        let (width, height) = self.size;
+        let stride: i32 = width * 4;
+
+        // A gradient that scrolls every frame, so every commit damages the
+        // whole window
+        self.pixels.resize((stride * height) as usize, 0);
+        for (y, row) in self.pixels.chunks_exact_mut(stride as usize).enumerate() {
+            let shade: u8 = (y as u32 + self.frame) as u8;
+            for pixel in row.chunks_exact_mut(4) {
+                pixel.copy_from_slice(&[shade, (index * 40) as u8, 255 - shade, 255]);
+            }
+        }
+
+        let buffer: WlBuffer = match (&mut self.backing, gpu) {
+            (Backing::Shm { pool, file }, _) => {
+                if let Err(err) = file.write_all_at(&self.pixels, (slot * SLOT_BYTES) as u64) {
+                    eprintln!("Failed to write buffer: {err}");
+                    return false;
+                }
+                pool.create_buffer(
+                    (slot * SLOT_BYTES) as i32,
+                    width,
+                    height,
+                    stride,
+                    wl_shm::Format::Argb8888,
+                    queue_handle,
+                    (index, slot),
+                )
+            }
+            (Backing::Dmabuf(slots), Some(gpu)) => {
+                let stale: bool = slots[slot].as_ref().map_or(true, |dmabuf: &Dmabuf| {
+                    (dmabuf.width() as i32, dmabuf.height() as i32) != self.size
+                });
+                if stale {
+                    match gpu.allocate(self.size) {
+                        Ok(dmabuf) => slots[slot] = Some(dmabuf),
+                        Err(err) => {
+                            eprintln!("Failed to allocate dmabuf: {err}");
+                            return false;
+                        }
+                    }
+                }
+                let dmabuf: &Dmabuf = slots[slot].as_ref().unwrap();
+                if let Err(err) = write_dmabuf(dmabuf, &self.pixels, stride as usize) {
+                    eprintln!("Failed to write dmabuf: {err}");
+                    return false;
+                }
+                dmabuf_buffer(gpu, dmabuf, queue_handle, (index, slot))
+            }
+            (Backing::Dmabuf(_), None) => return false,
+        };
+        self.busy[slot] = true;
+        self.frame = self.frame.wrapping_add(1);
+
+        self.surface.attach(Some(&buffer), 0, 0);
+        self.surface.damage_buffer(0, 0, width, height);
+        self.surface.commit();
+        true
+    }
+
+    fn destroy(&self) {
+        self.toplevel.destroy();
+        self.xdg_surface.destroy();
+        self.surface.destroy();
+        if let Backing::Shm { pool, .. } = &self.backing {
+            pool.destroy();
+        }
+    }
+}
+
+fn dmabuf_buffer(
+    gpu: &Gpu,
+    dmabuf: &Dmabuf,
+    queue_handle: &QueueHandle<Stress>,
+    slot: (usize, usize),
+) -> WlBuffer {
+    let params: ZwpLinuxBufferParamsV1 = gpu.dmabuf.create_params(queue_handle, ());
+    let modifier: u64 = dmabuf.format().modifier.into();
+    for (plane, ((fd, offset), stride)) in dmabuf
+        .handles()
+        .zip(dmabuf.offsets())
+        .zip(dmabuf.strides())
+        .enumerate()
+    {
+        params.add(
+            fd.as_raw_fd(),
+            plane as u32,
+            offset,
+            stride,
+            (modifier >> 32) as u32,
+            modifier as u32,
+        );
+    }
+    let buffer: WlBuffer = params.create_immed(
+        dmabuf.width() as i32,
+        dmabuf.height() as i32,
+        Fourcc::Argb8888 as u32,
+        zwp_linux_buffer_params_v1::Flags::empty(),
+        queue_handle,
+        slot,
+    );
+    params.destroy();
+    buffer
+}
+
+struct Globals {
+    compositor: WlCompositor,
+    shm: WlShm,
+    wm_base: XdgWmBase,
+}
+
+impl Globals {
+    fn bind(globals: &GlobalList, queue_handle: &QueueHandle<Stress>) -> Result<Self, String> {
+        Ok(Self {
+            compositor: globals
+                .bind(queue_handle, 1..=5, ())
+                .map_err(|err| format!("wl_compositor: {err}"))?,
+            shm: globals
+                .bind(queue_handle, 1..=1, ())
+                .map_err(|err| format!("wl_shm: {err}"))?,
+            wm_base: globals
+                .bind(queue_handle, 1..=5, ())
+                .map_err(|err| format!("xdg_wm_base: {err}"))?,
+        })
+    }
+}
+
+fn main() {
+    let options: Options = match Options::parse() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+
+    let connection: Connection = match Connection::connect_to_env() {
+        Ok(connection) => connection,
+        Err(err) => {
+            eprintln!("Failed to connect to the compositor: {err}");
+            std::process::exit(1);
+        }
+    };
+    let (globals_list, mut event_queue): (GlobalList, EventQueue<Stress>) =
+        registry_queue_init(&connection).unwrap();
+    let queue_handle: QueueHandle<Stress> = event_queue.handle();
+
+    let globals: Globals = match Globals::bind(&globals_list, &queue_handle) {
+        Ok(globals) => globals,
+        Err(err) => {
+            eprintln!("Missing global {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut gpu: Option<Gpu> = if options.dmabuf {
+        let dmabuf: ZwpLinuxDmabufV1 = match globals_list.bind(&queue_handle, 3..=3, ()) {
+            Ok(dmabuf) => dmabuf,
+            Err(err) => {
+                eprintln!("Missing global zwp_linux_dmabuf_v1: {err}");
+                std::process::exit(1);
+            }
+        };
+        match Gpu::open(dmabuf) {
+            Ok(gpu) => Some(gpu),
+            Err(err) => {
+                eprintln!("Failed to open a render node: {err}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut rng: Rng = Rng::new();
+    let mut stress: Stress = Stress {
+        windows: Vec::new(),
+        stats: Stats::default(),
+    };
+    for index in 0..options.windows {
+        let size: (i32, i32) = (
+            rng.range(MIN_SIZE.0, MAX_SIZE.0),
+            rng.range(MIN_SIZE.1, MAX_SIZE.1),
+        );
+        match StressWindow::new(index, &globals, options.dmabuf, size, &queue_handle) {
+            Ok(window) => stress.windows.push(window),
+            Err(err) => {
+                eprintln!("Failed to create window {index}: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    event_queue.roundtrip(&mut stress).unwrap();
+
+    let frame_interval: Duration = Duration::from_secs(1) / options.rate;
+    let resize_interval: Duration = Duration::from_millis(options.resize_ms);
+    let start: Instant = Instant::now();
+    let mut next_frame: Instant = start;
+    let mut next_resize: Instant = start + resize_interval;
+    let mut next_report: Instant = start + REPORT_INTERVAL;
+    let mut reported: u64 = 0;
+
+    loop {
+        let now: Instant = Instant::now();
+        if options.duration.map_or(false, |duration: Duration| {
+            now.duration_since(start) >= duration
+        }) {
+            break;
+        }
+
+        // Buffer releases, configures and pings
+        if let Some(guard) = connection.prepare_read() {
+            let _ = guard.read();
+        }
+        if let Err(err) = event_queue.dispatch_pending(&mut stress) {
+            eprintln!("Connection to the compositor lost: {err}");
+            break;
+        }
+        if stress
+            .windows
+            .iter()
+            .all(|window: &StressWindow| window.closed)
+        {
+            break;
+        }
+
+        if !resize_interval.is_zero() && now >= next_resize {
+            for window in &mut stress.windows {
+                window.size = (
+                    rng.range(MIN_SIZE.0, MAX_SIZE.0),
+                    rng.range(MIN_SIZE.1, MAX_SIZE.1),
+                );
+            }
+            next_resize += resize_interval;
+        }
+
+        if now >= next_frame {
+            for (index, window) in stress.windows.iter_mut().enumerate() {
+                if !window.configured || window.closed {
+                    continue;
+                }
+                if window.draw(index, gpu.as_mut(), &queue_handle) {
+                    stress.stats.commits += 1;
+                } else {
+                    stress.stats.skipped += 1;
+                }
+            }
+            // Don't try to catch up after a stall
+            next_frame = (next_frame + frame_interval).max(now);
+        }
+
+        if now >= next_report {
+            let commits: u64 = stress.stats.commits - reported;
+            reported = stress.stats.commits;
+            println!(
+                "{:.1} commits/s, {} skipped so far",
+                commits as f64 / REPORT_INTERVAL.as_secs_f64(),
+                stress.stats.skipped
+            );
+            next_report += REPORT_INTERVAL;
+        }
+
+        if let Err(err) = connection.flush() {
+            eprintln!("Failed to flush: {err}");
+            break;
+        }
+
+        let wake: Instant = next_frame.min(next_report);
+        std::thread::sleep(wake.saturating_duration_since(Instant::now()));
+    }
+
+    let elapsed: f64 = start.elapsed().as_secs_f64();
+    println!(
+        "{} commits in {elapsed:.1}s ({:.1}/s), {} skipped",
+        stress.stats.commits,
+        stress.stats.commits as f64 / elapsed,
+        stress.stats.skipped
+    );
+
+    stress.windows.iter().for_each(StressWindow::destroy);
+    let _ = connection.flush();
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for Stress {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlBuffer, (usize, usize)> for Stress {
+    fn event(
+        state: &mut Self,
+        buffer: &WlBuffer,
+        event: wl_buffer::Event,
+        (index, slot): &(usize, usize),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            state.windows[*index].busy[*slot] = false;
+            buffer.destroy();
+        }
+    }
+}
+
+impl Dispatch<XdgWmBase, ()> for Stress {
+    fn event(
+        _: &mut Self,
+        wm_base: &XdgWmBase,
+        event: xdg_wm_base::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<XdgSurface, usize> for Stress {
+    fn event(
+        state: &mut Self,
+        xdg_surface: &XdgSurface,
+        event: xdg_surface::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_surface::Event::Configure { serial } = event {
+            xdg_surface.ack_configure(serial);
+            state.windows[*index].configured = true;
+        }
+    }
+}
+
+// The sizes the compositor suggests are ignored on purpose, the point is
+// to resize whenever the stress test wants to
+impl Dispatch<XdgToplevel, usize> for Stress {
+    fn event(
+        state: &mut Self,
+        _: &XdgToplevel,
+        event: xdg_toplevel::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_toplevel::Event::Close = event {
+            state.windows[*index].closed = true;
+        }
+    }
+}
+
+delegate_noop!(Stress: ignore WlCompositor);
+delegate_noop!(Stress: ignore WlSurface);
+delegate_noop!(Stress: ignore WlShm);
+delegate_noop!(Stress: ignore WlShmPool);
+delegate_noop!(Stress: ignore ZwpLinuxDmabufV1);
+// Buffers are created with create_immed, a failure is a protocol error
+delegate_noop!(Stress: ignore ZwpLinuxBufferParamsV1);
@@ -0,0 +1,143 @@
+use smithay::{
+    backend::renderer::element::{
+        solid::{SolidColorBuffer, SolidColorRenderElement},
+        Kind,
+    },
+    desktop::Window,
+    output::Output,
+    utils::{IsAlive, Logical, Rectangle},
+};
+
+use crate::{
+    activation,
+    config::LayoutConfig,
+    layout::{self, Rect},
+    render::CustomRenderElements,
+    rules::{self, WindowRule},
+    state::State,
+};
+
+struct Border {
+    window: Window,
+    // Top, bottom, left and right
+    edges: [SolidColorBuffer; 4],
+}
+
+// Drawn around windows without a server-side decoration, which has a
+// border of its own. Kept so their damage is tracked between frames.
+#[derive(Default)]
+pub struct Borders {
+    borders: Vec<Border>,
+}
+
+impl Borders {
+    fn edges(&mut self, window: &Window) -> &mut [SolidColorBuffer; 4] {
+        self.borders.retain(|border: &Border| border.window.alive());
+        let index: usize = match self
+            .borders
+            .iter()
+            .position(|border: &Border| border.window == *window)
+        {
+            Some(index) => index,
+            None => {
+                self.borders.push(Border {
+                    window: window.clone(),
+                    edges: Default::default(),
+                });
+                self.borders.len() - 1
+            }
+        };
+        &mut self.borders[index].edges
+    }
+}
+
+fn edge_rects(geometry: Rectangle<i32, Logical>, width: i32) -> [Rectangle<i32, Logical>; 4] {
+    let rect: Rect = Rect::new(
+        geometry.loc.x,
+        geometry.loc.y,
+        geometry.size.w,
+        geometry.size.h,
+    );
+    layout::border_rects(rect, width)
+        .map(|edge: Rect| Rectangle::from_loc_and_size((edge.x, edge.y), (edge.w, edge.h)))
+}
+
+impl State {
+    // How far the border reaches out of the window geometry
+    pub fn border_width(&self, window: &Window) -> i32 {
+        if self.is_server_side(window) || self.is_fullscreen(window) {
+            return 0;
+        }
+        if rules::window_rule(window).and_then(|rule: &WindowRule| rule.border) == Some(false) {
+            return 0;
+        }
+        self.config.layout.border_width.max(0)
+    }
+
+    // Drawn below the window, in the focused color while it has the
+    // keyboard and the urgent one while it waits for it. `borders` is taken
+    // out of the state by the caller, which is iterating the space.
+    pub fn border_elements(
+        &self,
+        borders: &mut Borders,
+        output: &Output,
+        window: &Window,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        let width: i32 = self.border_width(window);
+        if width == 0 {
+            return;
+        }
+        let (Some(output_geometry), Some(location)) = (
+            self.space.output_geometry(output),
+            self.space.element_location(window),
+        ) else {
+            return;
+        };
+
+        let mut geometry: Rectangle<i32, Logical> = window.geometry();
+        geometry.loc += location - output_geometry.loc;
+        let config: &LayoutConfig = &self.config.layout;
+        let color: [f32; 4] = if self.focused_window().as_ref() == Some(window) {
+            config.focused_border_color.0
+        } else if activation::is_urgent(window) {
+            config.urgent_border_color.0
+        } else {
+            config.border_color.0
+        };
+        let scale: f64 = output.current_scale().fractional_scale();
+
+        edge_elements(
+            borders.edges(window),
+            geometry,
+            width,
+            color,
+            scale,
+            elements,
+        );
+    }
+}
+
+// `geometry` is relative to the output
+pub(crate) fn edge_elements(
+    edges: &mut [SolidColorBuffer; 4],
+    geometry: Rectangle<i32, Logical>,
+    width: i32,
+    color: [f32; 4],
+    scale: f64,
+    elements: &mut Vec<CustomRenderElements>,
+) {
+    for (buffer, rect) in edges.iter_mut().zip(edge_rects(geometry, width)) {
+        buffer.update(rect.size, color);
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                buffer,
+                rect.loc.to_physical_precise_round(scale),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use smithay::{
+    backend::renderer::{
+        element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            Kind,
+        },
+        gles::GlesRenderer,
+    },
+    desktop::Window,
+    output::Output,
+    utils::{Logical, Point, Rectangle},
+};
+
+use crate::{
+    render::CustomRenderElements,
+    rules::{self, WindowRule},
+    state::State,
+    switcher,
+};
+
+const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+impl State {
+    // Password managers and the like, never shown in screenshots or casts.
+    // A window rule saying either way wins over the app_id list.
+    pub fn is_protected(&self, window: &Window) -> bool {
+        if let Some(protected) =
+            rules::window_rule(window).and_then(|rule: &WindowRule| rule.protected)
+        {
+            return protected;
+        }
+        let Some(app_id) = switcher::app_id(window) else {
+            return false;
+        };
+
+        self.config
+            .capture
+            .protected_app_ids
+            .iter()
+            .any(|protected: &String| *protected == app_id)
+    }
+
+    // A black rectangle the size of the window, `location` is where the
+    // window is relative to the output
+    pub fn protected_element(
+        &self,
+        window: &Window,
+        location: Point<i32, Logical>,
+        scale: f64,
+    ) -> CustomRenderElements {
+        let geometry: Rectangle<i32, Logical> = window.geometry();
+        let buffer: SolidColorBuffer = SolidColorBuffer::new(geometry.size, BLACK);
+        SolidColorRenderElement::from_buffer(
+            &buffer,
+            (location + geometry.loc).to_physical_precise_round(scale),
+            scale,
+            1.0,
+            Kind::Unspecified,
+        )
+        .into()
+    }
+
+    // What screencopy and screenshots see of `output`, topmost first. Same
+    // as on screen, except that protected windows are replaced by a black
+    // rectangle of their size.
+    pub fn capture_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+    ) -> Vec<CustomRenderElements> {
+        let mut elements: Vec<CustomRenderElements> = Vec::new();
+        self.scene_elements(renderer, output, true, &mut elements);
+        elements
+    }
+}
@@ -0,0 +1,268 @@
+use smithay::{
+    backend::renderer::{
+        element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            texture::{TextureBuffer, TextureRenderElement},
+            Kind,
+        },
+        gles::{GlesRenderer, GlesTexture},
+    },
+    output::Output,
+    utils::{Logical, Physical, Point, Size},
+};
+
+use crate::{config, keybindings::Binding, render::CustomRenderElements, state::State};
+
+const FONT_SIZE: f32 = 14.0;
+const LINE_HEIGHT: i32 = 22;
+const PADDING: i32 = 24;
+// Between the key and what it does, and between columns
+const GAP: i32 = 16;
+const COLUMN_GAP: i32 = 40;
+// Kept free around the panel
+const MARGIN: i32 = 48;
+
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.85];
+const HEADING: [u8; 4] = [255, 255, 255, 255];
+const KEY: [u8; 4] = [140, 190, 255, 255];
+const TEXT: [u8; 4] = [220, 220, 220, 255];
+
+enum Line {
+    Heading(String),
+    // Spans the columns, only the welcome has any
+    Text(String),
+    Binding { keys: String, description: String },
+    Blank,
+}
+
+// Every binding by category, shown until its key is pressed again
+pub struct Cheatsheet {
+    visible: bool,
+    // Shown on the first run, with a few words on top, until any key is
+    // pressed
+    welcome: bool,
+    // Of the background, only updated when it changes so it isn't damaged
+    // every frame
+    size: (i32, i32),
+    background: SolidColorBuffer,
+}
+
+impl Default for Cheatsheet {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            welcome: false,
+            size: (0, 0),
+            background: SolidColorBuffer::new((0, 0), BACKGROUND),
+        }
+    }
+}
+
+// Categories are split into columns so they fit `max_height`, a category
+// isn't broken up unless it's taller than that on its own
+fn columns(categories: Vec<(String, Vec<&Binding>)>, max_height: i32) -> Vec<Vec<Line>> {
+    let max_rows: usize = (max_height / LINE_HEIGHT).max(1) as usize;
+    let mut columns: Vec<Vec<Line>> = vec![Vec::new()];
+
+    for (category, bindings) in categories {
+        let column: &mut Vec<Line> = columns.last_mut().unwrap();
+        if !column.is_empty() && column.len() + 2 + bindings.len() > max_rows {
+            columns.push(Vec::new());
+        } else if !column.is_empty() {
+            column.push(Line::Blank);
+        }
+
+        columns.last_mut().unwrap().push(Line::Heading(category));
+        for binding in bindings {
+            if columns.last().unwrap().len() >= max_rows {
+                columns.push(Vec::new());
+            }
+            columns.last_mut().unwrap().push(Line::Binding {
+                keys: binding.name.clone(),
+                description: binding.description(),
+            });
+        }
+    }
+
+    columns
+}
+
+// Above the bindings on the first run
+fn welcome_lines() -> Vec<Line> {
+    vec![
+        Line::Heading("Welcome to pulseWM".to_string()),
+        Line::Text("No config was found, these are the default keybindings.".to_string()),
+        Line::Text(format!(
+            "To change them create {}, anything left out keeps its default.",
+            config::config_path().display()
+        )),
+        Line::Text("Press any key to start.".to_string()),
+        Line::Blank,
+    ]
+}
+
+impl Cheatsheet {
+    // With no config file, the first thing shown is how to get going
+    pub fn welcome() -> Self {
+        Self {
+            visible: true,
+            welcome: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_welcome(&self) -> bool {
+        self.welcome
+    }
+}
+
+impl State {
+    pub fn toggle_cheatsheet(&mut self) {
+        self.cheatsheet.visible = !self.cheatsheet.visible;
+        self.cheatsheet.welcome = false;
+        self.request_redraw();
+    }
+
+    pub fn dismiss_welcome(&mut self) {
+        if self.cheatsheet.welcome {
+            self.cheatsheet.welcome = false;
+            self.cheatsheet.visible = false;
+            self.request_redraw();
+        }
+    }
+
+    // Centered on the output the pointer is on
+    pub fn cheatsheet_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        if !self.cheatsheet.visible || self.active_output().as_ref() != Some(output) {
+            return;
+        }
+        let Some(text) = self.text.as_mut() else {
+            return;
+        };
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let output_size: Size<i32, Logical> = output
+            .current_mode()
+            .map(|mode| mode.size.to_f64().to_logical(scale).to_i32_round())
+            .unwrap_or_default();
+
+        let intro: Vec<Line> = if self.cheatsheet.welcome {
+            welcome_lines()
+        } else {
+            Vec::new()
+        };
+        let intro_height: i32 = intro.len() as i32 * LINE_HEIGHT;
+        let columns: Vec<Vec<Line>> = columns(
+            self.config.keybindings.by_category(),
+            output_size.h - 2 * (MARGIN + PADDING) - intro_height,
+        );
+
+        // Width of the keys and of the whole column, for each column
+        let widths: Vec<(i32, i32)> = columns
+            .iter()
+            .map(|column: &Vec<Line>| {
+                let (mut keys_width, mut descriptions_width, mut headings_width) = (0, 0, 0);
+                for line in column {
+                    match line {
+                        Line::Heading(heading) => {
+                            headings_width = headings_width.max(text.measure(heading, FONT_SIZE).0)
+                        }
+                        Line::Binding { keys, description } => {
+                            keys_width = keys_width.max(text.measure(keys, FONT_SIZE).0);
+                            descriptions_width =
+                                descriptions_width.max(text.measure(description, FONT_SIZE).0);
+                        }
+                        Line::Text(_) | Line::Blank => {}
+                    }
+                }
+                let width: i32 = headings_width.max(keys_width + GAP + descriptions_width);
+                (keys_width, width)
+            })
+            .collect();
+
+        let intro_width: i32 = intro
+            .iter()
+            .map(|line: &Line| match line {
+                Line::Heading(line) | Line::Text(line) => text.measure(line, FONT_SIZE).0,
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0);
+        let rows: i32 = columns.iter().map(Vec::len).max().unwrap_or(0) as i32;
+        let columns_width: i32 = widths.iter().map(|(_, width)| width).sum::<i32>()
+            + COLUMN_GAP * (widths.len() as i32 - 1).max(0);
+        let width: i32 = columns_width.max(intro_width) + 2 * PADDING;
+        let height: i32 = intro_height + rows * LINE_HEIGHT + 2 * PADDING;
+        let origin: Point<i32, Logical> =
+            Point::from(((output_size.w - width) / 2, (output_size.h - height) / 2));
+        let at = |offset: (i32, i32)| -> Point<i32, Physical> {
+            (origin + Point::from(offset)).to_physical_precise_round(scale)
+        };
+
+        let mut push =
+            |renderer: &mut GlesRenderer, line: &str, color: [u8; 4], offset: (i32, i32)| {
+                let buffer: Option<TextureBuffer<GlesTexture>> =
+                    text.render(renderer, line, FONT_SIZE, color, scale.ceil() as i32);
+                if let Some(buffer) = buffer {
+                    elements.push(
+                        TextureRenderElement::from_texture_buffer(
+                            at(offset).to_f64(),
+                            &buffer,
+                            None,
+                            None,
+                            None,
+                            Kind::Unspecified,
+                        )
+                        .into(),
+                    );
+                }
+            };
+
+        for (row, line) in intro.iter().enumerate() {
+            let y: i32 = PADDING + row as i32 * LINE_HEIGHT;
+            match line {
+                Line::Heading(heading) => push(renderer, heading, HEADING, (PADDING, y)),
+                Line::Text(line) => push(renderer, line, TEXT, (PADDING, y)),
+                _ => {}
+            }
+        }
+
+        let mut x: i32 = PADDING;
+        for (column, (keys_width, column_width)) in columns.iter().zip(&widths) {
+            for (row, line) in column.iter().enumerate() {
+                let y: i32 = PADDING + intro_height + row as i32 * LINE_HEIGHT;
+                match line {
+                    Line::Heading(heading) => push(renderer, heading, HEADING, (x, y)),
+                    Line::Binding { keys, description } => {
+                        push(renderer, keys, KEY, (x, y));
+                        push(renderer, description, TEXT, (x + keys_width + GAP, y));
+                    }
+                    Line::Text(_) | Line::Blank => {}
+                }
+            }
+            x += column_width + COLUMN_GAP;
+        }
+
+        if self.cheatsheet.size != (width, height) {
+            self.cheatsheet.size = (width, height);
+            self.cheatsheet
+                .background
+                .update((width, height), BACKGROUND);
+        }
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                &self.cheatsheet.background,
+                at((0, 0)),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+}
@@ -0,0 +1,1485 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use smithay::{
+    input::keyboard::{xkb, ModifiersState},
+    utils::Transform,
+};
+
+use crate::{
+    acl::PermissionRule,
+    dpms::WakeTrigger,
+    globals::OptionalGlobal,
+    keybindings::{Action, Keybindings},
+    layout::LayoutParams,
+    root_scroll::RootAction,
+    rules::WindowRule,
+    theme::Theme,
+};
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(rename = "output")]
+    pub outputs: Vec<OutputConfig>,
+    pub output_layout: OutputLayoutConfig,
+    #[serde(rename = "profile")]
+    pub profiles: Vec<OutputProfile>,
+    pub lid: LidConfig,
+    pub tablet_mode: TabletModeConfig,
+    pub session: SessionConfig,
+    pub brightness: BrightnessConfig,
+    pub appearance: AppearanceConfig,
+    #[serde(rename = "service")]
+    pub services: Vec<ServiceConfig>,
+    pub snap: SnapConfig,
+    pub animations: AnimationConfig,
+    pub kiosk: KioskConfig,
+    pub security: SecurityConfig,
+    pub globals: GlobalsConfig,
+    pub capture: CaptureConfig,
+    pub workspaces: WorkspaceConfig,
+    pub focus: FocusConfig,
+    pub root_scroll: RootScrollConfig,
+    pub root_menu: RootMenuConfig,
+    pub bar: BarConfig,
+    #[serde(rename = "output_rule")]
+    pub output_rules: Vec<OutputRule>,
+    #[serde(rename = "window_rule")]
+    pub window_rules: Vec<WindowRule>,
+    pub debug: DebugConfig,
+    pub programs: ProgramsConfig,
+    pub layout: LayoutConfig,
+    pub winit: WinitConfig,
+    pub render: RenderConfig,
+    pub keybindings: Keybindings,
+    pub window_drag: WindowDragConfig,
+    pub cursor: CursorConfig,
+    pub decorations: DecorationConfig,
+    pub dpms: DpmsConfig,
+    pub presentation: PresentationConfig,
+    pub window_title: WindowTitleConfig,
+    // Exported to everything pulseWM starts, e.g. MOZ_ENABLE_WAYLAND = "1"
+    pub env: HashMap<String, String>,
+}
+
+impl Config {
+    // What spawned processes get on top of our own environment. Entries in
+    // [env] win over the ones derived from other settings.
+    pub fn child_env(&self) -> Vec<(String, String)> {
+        let mut env: Vec<(String, String)> = vec![
+            ("XCURSOR_THEME".into(), self.cursor.theme.clone()),
+            ("XCURSOR_SIZE".into(), self.cursor.size.to_string()),
+        ];
+        env.retain(|(name, _)| !self.env.contains_key(name));
+        env.extend(self.env.clone());
+        env
+    }
+
+    // A missing file is not an error, everything has a default. Fragments
+    // from `include` are merged on top in path order, a broken fragment is
+    // reported and skipped without taking the rest of the config with it.
+    pub fn load() -> Self {
+        Self::try_load().unwrap_or_else(|err: String| {
+            eprintln!("Failed to parse {err}");
+            Self::default()
+        })
+    }
+
+    // Like load, but a broken file is an error instead of the defaults, so
+    // reloading doesn't throw away a working config over a typo
+    pub fn try_load() -> Result<Self, String> {
+        let path: PathBuf = config_path();
+        let Some(table) = load_with_includes(&path)? else {
+            return Ok(Self::default());
+        };
+
+        Self::from_table(table).map_err(|err: toml::de::Error| format!("{}: {err}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        Self::from_table(toml::from_str(contents)?)
+    }
+
+    fn from_table(mut table: toml::Table) -> Result<Self, toml::de::Error> {
+        // Only load() follows includes
+        table.remove("include");
+        let variables: HashMap<String, String> = take_strings(&mut table, "variables")?;
+        let aliases: HashMap<String, String> = take_strings(&mut table, "aliases")?;
+
+        for (_, value) in table.iter_mut() {
+            expand_value(value, &variables);
+        }
+
+        // A binding to an alias runs its command
+        if let Some(toml::Value::Table(keybindings)) = table.get_mut("keybindings") {
+            for (_, entry) in keybindings.iter_mut() {
+                // Either the whole entry or its `action`, when it has a
+                // description
+                let action: &mut toml::Value = match entry {
+                    toml::Value::Table(described) if described.contains_key("action") => {
+                        described.get_mut("action").unwrap()
+                    }
+                    entry => entry,
+                };
+                let command: Option<&String> = match action {
+                    toml::Value::String(name) => aliases.get(name),
+                    _ => None,
+                };
+                if let Some(command) = command {
+                    let mut spawn: toml::Table = toml::Table::new();
+                    spawn.insert(
+                        "spawn".into(),
+                        toml::Value::String(expand(command, &variables)),
+                    );
+                    *action = toml::Value::Table(spawn);
+                }
+            }
+        }
+
+        toml::Value::Table(table).try_into()
+    }
+}
+
+// The config at `path` with everything it includes merged in, None if
+// there is no such file
+fn load_with_includes(path: &Path) -> Result<Option<toml::Table>, String> {
+    let contents: String = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(None),
+    };
+    let mut table: toml::Table = toml::from_str(&contents)
+        .map_err(|err: toml::de::Error| format!("{}: {err}", path.display()))?;
+
+    let base: &Path = path.parent().unwrap_or(Path::new("/"));
+    let paths: Vec<PathBuf> = includes(&mut table, base);
+    merge_includes(&mut table, paths, &mut vec![canonical(path)]);
+    Ok(Some(table))
+}
+
+// Symlinked and relative paths to the same file compare equal
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Merges each fragment into `table`, followed by the fragments it includes
+// itself. `including` are the files on the way there from the main config,
+// one of them included again would be a cycle and is skipped.
+fn merge_includes(table: &mut toml::Table, paths: Vec<PathBuf>, including: &mut Vec<PathBuf>) {
+    for path in paths {
+        let file: PathBuf = canonical(&path);
+        if including.contains(&file) {
+            eprintln!("Failed to include {}: it includes itself", path.display());
+            continue;
+        }
+        let mut fragment: toml::Table = match load_fragment(&path, table) {
+            Ok(fragment) => fragment,
+            Err(err) => {
+                eprintln!("Failed to parse {}: {err}", path.display());
+                continue;
+            }
+        };
+
+        let base: &Path = path.parent().unwrap_or(Path::new("/"));
+        let nested: Vec<PathBuf> = includes(&mut fragment, base);
+        merge(table, fragment);
+
+        including.push(file);
+        merge_includes(table, nested, including);
+        including.pop();
+    }
+}
+
+// include = "~/.config/pulsewm/conf.d/*.toml", or a list of those. Relative
+// paths start at the directory of the file including them, `*` and `?`
+// only work in the file name. Fragments can include further files.
+fn includes(table: &mut toml::Table, base: &Path) -> Vec<PathBuf> {
+    let patterns: Vec<String> = match table.remove("include") {
+        Some(toml::Value::String(pattern)) => vec![pattern],
+        Some(toml::Value::Array(patterns)) => patterns
+            .into_iter()
+            .filter_map(|pattern: toml::Value| match pattern {
+                toml::Value::String(pattern) => Some(pattern),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for pattern in patterns {
+        let pattern: PathBuf = base.join(expand_home(Path::new(&pattern)));
+        let name: String = pattern
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !name.contains(['*', '?']) {
+            paths.push(pattern);
+            continue;
+        }
+
+        let dir: &Path = pattern.parent().unwrap_or(Path::new("/"));
+        let mut matches: Vec<PathBuf> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| wildcard_matches(&name, &entry.file_name().to_string_lossy()))
+                .map(|entry| entry.path())
+                .collect(),
+            Err(err) => {
+                eprintln!("Failed to read {}: {err}", dir.display());
+                Vec::new()
+            }
+        };
+        // read_dir has no order of its own
+        matches.sort();
+        paths.extend(matches);
+    }
+
+    paths
+}
+
+// `*` matches any run of characters, `?` exactly one
+fn wildcard_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was, and how much of the name it has eaten
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c: &char| *c == '*')
+}
+
+// Checked on its own, with the main file's aliases, so an error names the
+// fragment it's in. Its [variables] only apply to the fragment itself, on
+// top of the main file's, and are expanded here so they never reach the
+// rest of the config.
+fn load_fragment(path: &Path, main: &toml::Table) -> Result<toml::Table, String> {
+    let contents: String = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut fragment: toml::Table = toml::from_str(&contents).map_err(|err| err.to_string())?;
+
+    let mut variables: HashMap<String, String> = match main.get("variables") {
+        Some(toml::Value::Table(values)) => values
+            .iter()
+            .filter_map(|(key, value): (&String, &toml::Value)| {
+                Some((key.clone(), value.as_str()?.to_string()))
+            })
+            .collect(),
+        _ => HashMap::new(),
+    };
+    variables.extend(take_strings(&mut fragment, "variables").map_err(|err| err.to_string())?);
+    for (_, value) in fragment.iter_mut() {
+        expand_value(value, &variables);
+    }
+
+    let mut check: toml::Table = fragment.clone();
+    if let Some(toml::Value::Table(aliases)) = main.get("aliases") {
+        let mut aliases: toml::Table = aliases.clone();
+        if let Some(toml::Value::Table(own)) = check.remove("aliases") {
+            merge(&mut aliases, own);
+        }
+        check.insert("aliases".into(), toml::Value::Table(aliases));
+    }
+    Config::from_table(check).map_err(|err| err.to_string())?;
+
+    Ok(fragment)
+}
+
+// Tables merge key by key and lists like [[output]] are appended to,
+// anything else is replaced by the later file
+fn merge(into: &mut toml::Table, from: toml::Table) {
+    for (key, value) in from {
+        match (into.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(table)) => {
+                merge(existing, table)
+            }
+            (Some(toml::Value::Array(existing)), toml::Value::Array(array)) => {
+                existing.extend(array)
+            }
+            (_, value) => {
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+// [variables] and [aliases] hold plain strings and are gone once parsed
+fn take_strings(
+    table: &mut toml::Table,
+    name: &str,
+) -> Result<HashMap<String, String>, toml::de::Error> {
+    let Some(section) = table.remove(name) else {
+        return Ok(HashMap::new());
+    };
+    let toml::Value::Table(section) = section else {
+        return Err(serde::de::Error::custom(format!(
+            "[{name}] must be a table"
+        )));
+    };
+
+    section
+        .into_iter()
+        .map(|(key, value)| match value {
+            toml::Value::String(value) => Ok((key, value)),
+            _ => Err(serde::de::Error::custom(format!(
+                "{name}.{key} must be a string"
+            ))),
+        })
+        .collect()
+}
+
+// [variables] mod = "super" turns "$mod+Return" into "super+Return", in
+// every string and key of the config. Names that aren't defined, like $HOME
+// in a command, are left alone for the shell.
+fn expand(text: &str, variables: &HashMap<String, String>) -> String {
+    let mut result: String = String::with_capacity(text.len());
+    let mut rest: &str = text;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after: &str = &rest[start + 1..];
+        let length: usize = after
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(after.len());
+        let name: &str = &after[..length];
+
+        match variables.get(name) {
+            Some(value) => result.push_str(value),
+            None => {
+                result.push('$');
+                result.push_str(name);
+            }
+        }
+        rest = &after[length..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn expand_value(value: &mut toml::Value, variables: &HashMap<String, String>) {
+    match value {
+        toml::Value::String(text) => *text = expand(text, variables),
+        toml::Value::Array(items) => {
+            for item in items {
+                expand_value(item, variables);
+            }
+        }
+        toml::Value::Table(table) => {
+            *table = std::mem::take(table)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    expand_value(&mut value, variables);
+                    (expand(&key, variables), value)
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+// "~/Pictures/wall.png" relative to $HOME, anything else as is
+pub fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+pub fn config_path() -> PathBuf {
+    let config_home: PathBuf = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => dir.into(),
+        None => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".config"),
+    };
+
+    config_home.join("pulsewm").join("config.toml")
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct LidConfig {
+    // Run when the lid closes and no external output is left on,
+    // e.g. "swaylock -f && systemctl suspend"
+    pub close_command: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct TabletModeConfig {
+    // Started when the device is folded into a tablet, killed when it's back
+    // in laptop mode
+    pub osk_command: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    // Run before the system suspends, e.g. "swaylock -f"
+    pub lock_command: Option<String>,
+    // How long the frozen desktop takes to blur and fade out when locking.
+    // The locker hears that the session is locked once that's done.
+    pub lock_fade_ms: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            lock_command: None,
+            lock_fade_ms: 200,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BrightnessConfig {
+    // Handle the XF86MonBrightness keys instead of leaving them to clients
+    pub enabled: bool,
+    // Backlight device in /sys/class/backlight, the first one if unset
+    pub device: Option<String>,
+    // In percent
+    pub step: u32,
+}
+
+impl Default for BrightnessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            device: None,
+            step: 5,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AppearanceConfig {
+    // Any fontconfig pattern, e.g. "Noto Sans:bold"
+    pub font: String,
+    // Behind everything, [[output_rule]] can override it per output
+    pub background_color: Option<Color>,
+    // Can be switched at runtime over IPC
+    pub theme: Theme,
+}
+
+impl Default for AppearanceConfig {
+    fn default() -> Self {
+        Self {
+            font: "sans-serif".into(),
+            background_color: None,
+            theme: Theme::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ProgramsConfig {
+    // Run through sh -c, so arguments work
+    pub terminal: String,
+}
+
+impl Default for ProgramsConfig {
+    fn default() -> Self {
+        Self {
+            terminal: "alacritty".into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    // Share of the width the master column gets, 0.05 to 0.95
+    pub master_ratio: f64,
+    pub master_count: usize,
+    // Between tiled windows and between them and the output edges
+    pub inner_gap: i32,
+    pub outer_gap: i32,
+    // Around windows that draw their own title bar, server-side decorated
+    // ones use [decorations] border_width
+    pub border_width: i32,
+    pub border_color: Color,
+    pub focused_border_color: Color,
+    pub urgent_border_color: Color,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        let params: LayoutParams = LayoutParams::default();
+        Self {
+            master_ratio: params.master_ratio,
+            master_count: params.master_count,
+            inner_gap: params.inner_gap,
+            outer_gap: params.outer_gap,
+            border_width: 2,
+            border_color: Color([0.27, 0.27, 0.27, 1.0]),
+            focused_border_color: Color([0.37, 0.51, 0.67, 1.0]),
+            urgent_border_color: Color([0.80, 0.36, 0.27, 1.0]),
+        }
+    }
+}
+
+impl LayoutConfig {
+    pub fn params(&self) -> LayoutParams {
+        LayoutParams {
+            master_ratio: self.master_ratio,
+            master_count: self.master_count,
+            inner_gap: self.inner_gap,
+            outer_gap: self.outer_gap,
+        }
+    }
+}
+
+// Only used when running nested
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct WinitConfig {
+    // In Hz, the host compositor doesn't tell us
+    pub refresh_rate: u32,
+}
+
+impl Default for WinitConfig {
+    fn default() -> Self {
+        Self { refresh_rate: 60 }
+    }
+}
+
+// Only used on the DRM backend, the host compositor picks the format when
+// running nested
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RenderConfig {
+    // 10 bits per channel framebuffers for less banding in gradients, on
+    // GPUs and outputs that can't do them outputs stay at 8. Applies to
+    // outputs as they get connected.
+    pub ten_bit: bool,
+    // Put fullscreen and other client buffers on overlay planes instead of
+    // compositing them, where the driver can do it with atomic
+    // modesetting. Some drivers get it wrong. Applies like ten_bit.
+    pub overlay_planes: bool,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            ten_bit: false,
+            overlay_planes: true,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct CursorConfig {
+    // An XCursor theme name, e.g. "Adwaita"
+    pub theme: String,
+    // In logical pixels, multiplied by each output's scale
+    pub size: u32,
+}
+
+// Whatever the session was started with, so an unset config keeps the
+// user's existing cursor
+impl Default for CursorConfig {
+    fn default() -> Self {
+        Self {
+            theme: std::env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".into()),
+            size: std::env::var("XCURSOR_SIZE")
+                .ok()
+                .and_then(|size: String| size.parse().ok())
+                .unwrap_or(24),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DpmsConfig {
+    // Seconds without input before the displays are turned off, 0 never
+    // turns them off
+    pub timeout: u64,
+    // Leave "motion" out so a bumped desk doesn't wake the monitors
+    pub wake_on: Vec<WakeTrigger>,
+}
+
+impl Default for DpmsConfig {
+    fn default() -> Self {
+        Self {
+            timeout: 0,
+            wake_on: vec![
+                WakeTrigger::Key,
+                WakeTrigger::Button,
+                WakeTrigger::Motion,
+                WakeTrigger::Scroll,
+                WakeTrigger::Lid,
+            ],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PresentationConfig {
+    // Layer surfaces hidden in presentation mode, mako and dunst use
+    // "notifications"
+    pub hidden_namespaces: Vec<String>,
+}
+
+impl Default for PresentationConfig {
+    fn default() -> Self {
+        Self {
+            hidden_namespaces: vec!["notifications".into()],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DecorationConfig {
+    // Asks clients to leave their title bars out and draws ours instead,
+    // those that don't support xdg-decoration keep their own
+    pub server_side: bool,
+    pub title_height: i32,
+    pub border_width: i32,
+}
+
+impl Default for DecorationConfig {
+    fn default() -> Self {
+        Self {
+            server_side: true,
+            title_height: 24,
+            border_width: 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BarPosition {
+    #[default]
+    Top,
+    Bottom,
+}
+
+// How windows are named in the bar and over IPC
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct WindowTitleConfig {
+    // "{app_id}" and "{title}" are replaced, e.g. "{app_id} — {title}"
+    pub format: String,
+    // In characters, longer names are cut off with an ellipsis. Zero
+    // means no limit.
+    pub max_length: usize,
+}
+
+impl Default for WindowTitleConfig {
+    fn default() -> Self {
+        Self {
+            format: "{title}".into(),
+            max_length: 80,
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct BarConfig {
+    pub enabled: bool,
+    pub position: BarPosition,
+}
+
+// Per output appearance, matched like [[output]] entries but applied
+// whatever profile is active
+#[derive(Clone, Default, Deserialize)]
+pub struct OutputRule {
+    #[serde(rename = "match")]
+    pub criteria: String,
+    pub background_color: Option<Color>,
+    pub wallpaper: Option<PathBuf>,
+    // Override [bar] for this output
+    pub bar: Option<bool>,
+    pub bar_position: Option<BarPosition>,
+    // Switched to when the output gets enabled, counting from 1
+    pub workspace: Option<usize>,
+}
+
+impl OutputRule {
+    pub fn merge(self, other: &OutputRule) -> Self {
+        Self {
+            criteria: other.criteria.clone(),
+            background_color: other.background_color.or(self.background_color),
+            wallpaper: other.wallpaper.clone().or(self.wallpaper),
+            bar: other.bar.or(self.bar),
+            bar_position: other.bar_position.or(self.bar_position),
+            workspace: other.workspace.or(self.workspace),
+        }
+    }
+}
+
+// "#rrggbb" or "#rrggbbaa"
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(pub [f32; 4]);
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(color: String) -> Result<Self, Self::Error> {
+        let error = || format!("invalid color \"{color}\", expected #RRGGBB or #RRGGBBAA");
+
+        let hex: &str = color.strip_prefix('#').ok_or_else(error)?;
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return Err(error());
+        }
+
+        let mut channels: [f32; 4] = [1.0; 4];
+        for (index, channel) in channels.iter_mut().enumerate().take(hex.len() / 2) {
+            let byte: u8 =
+                u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| error())?;
+            *channel = byte as f32 / 255.0;
+        }
+
+        // Premultiplied, like everything the renderer takes
+        let alpha: f32 = channels[3];
+        Ok(Self([
+            channels[0] * alpha,
+            channels[1] * alpha,
+            channels[2] * alpha,
+            alpha,
+        ]))
+    }
+}
+
+// Scrolling over the desktop background, where no window is
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct RootScrollConfig {
+    pub up: Option<RootAction>,
+    pub down: Option<RootAction>,
+    pub left: Option<RootAction>,
+    pub right: Option<RootAction>,
+}
+
+// Clicking the bare desktop, for setups without a bar or launcher
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RootMenuConfig {
+    pub enabled: bool,
+    pub button: MouseButton,
+    // Top to bottom, e.g. { label = "Browser", action = { spawn = "firefox" } }
+    pub entries: Vec<RootMenuEntry>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct RootMenuEntry {
+    pub label: String,
+    pub action: Action,
+}
+
+impl Default for RootMenuConfig {
+    fn default() -> Self {
+        let entry = |label: &str, action: Action| RootMenuEntry {
+            label: label.to_string(),
+            action,
+        };
+        Self {
+            enabled: true,
+            button: MouseButton::Right,
+            entries: vec![
+                entry("Terminal", Action::SpawnTerminal),
+                entry("Reload config", Action::ReloadConfig),
+                entry("Exit", Action::Quit),
+            ],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct FocusConfig {
+    // Focus whatever window the pointer is over instead of waiting for a
+    // click
+    pub follows_mouse: bool,
+    // Raise windows focused by hovering them, not just focus them
+    pub hover_raise: bool,
+    // Only raise once the pointer stayed on the window this long
+    pub raise_delay_ms: u64,
+    // New windows don't take focus from a window that's being typed in,
+    // they're marked urgent instead. Unless they were activated with a
+    // token from the focused client, like a link opened in a browser.
+    pub steal_prevention: bool,
+    // How long after the last key press the focused window still counts
+    // as typed in
+    pub typing_timeout_ms: u64,
+}
+
+impl Default for FocusConfig {
+    fn default() -> Self {
+        Self {
+            follows_mouse: false,
+            hover_raise: true,
+            raise_delay_ms: 0,
+            steal_prevention: false,
+            typing_timeout_ms: 1000,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct WorkspaceConfig {
+    pub count: usize,
+    pub mode: WorkspaceMode,
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        Self {
+            count: 9,
+            mode: WorkspaceMode::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceMode {
+    // Every window is on exactly one workspace, one is shown at a time
+    #[default]
+    Workspaces,
+    // Like dwm, windows carry any number of tags and everything with one
+    // of the selected tags is shown. At most 32.
+    Tags,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    // Windows of these apps show up black in screenshots and screencasts,
+    // e.g. ["org.keepassxc.KeePassXC"]
+    pub protected_app_ids: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct GlobalsConfig {
+    // Never advertised unless turned on over IPC, e.g. ["screencopy"]
+    pub disabled: Vec<OptionalGlobal>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct SecurityConfig {
+    // Only clients matching an [[security.allow]] rule may bind screencopy
+    // and data-control
+    pub restrict_privileged: bool,
+    pub allow: Vec<PermissionRule>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct KioskConfig {
+    // Only a single fullscreen client at a time, no compositor keybindings
+    pub enabled: bool,
+    // The one binding left working, e.g. to get a shell on the device
+    pub admin_chord: Option<KeyChord>,
+    pub admin_command: Option<String>,
+    pub new_windows: KioskNewWindows,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KioskNewWindows {
+    // Close the current window, the new one takes over
+    Replace,
+    // Keep the current window on top, the new one waits behind it
+    #[default]
+    StackBehind,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AnimationConfig {
+    pub enabled: bool,
+    pub duration_ms: u64,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            duration_ms: 150,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SnapConfig {
+    // Snap windows dragged to a screen edge or corner
+    pub edge_drag: bool,
+    // How close to the left or right edge the pointer has to get, in
+    // logical pixels
+    pub edge_margin: i32,
+    // How far from the top or bottom an edge still counts as a corner
+    pub corner_margin: i32,
+}
+
+impl Default for SnapConfig {
+    fn default() -> Self {
+        Self {
+            edge_drag: true,
+            edge_margin: 8,
+            corner_margin: 64,
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct DebugConfig {
+    // Measure input to frame submit latency from startup, can also be
+    // toggled over IPC
+    pub latency: bool,
+    // Tint damaged regions for a moment after every frame, can also be
+    // toggled over IPC
+    pub damage: bool,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ServiceConfig {
+    pub name: String,
+    pub command: String,
+    // Restart the service when it exits unsuccessfully
+    #[serde(default = "default_true")]
+    pub restart: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct OutputLayoutConfig {
+    pub reject_overlaps: bool,
+    // Require every output to share an edge with another one
+    pub reject_gaps: bool,
+}
+
+impl Default for OutputLayoutConfig {
+    fn default() -> Self {
+        Self {
+            reject_overlaps: true,
+            reject_gaps: false,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct OutputProfile {
+    pub name: String,
+    #[serde(rename = "output")]
+    pub outputs: Vec<OutputConfig>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct OutputConfig {
+    // Connector name ("eDP-1") or monitor description ("Dell Inc. DELL U2720Q")
+    #[serde(rename = "match")]
+    pub criteria: String,
+    #[serde(default = "default_true")]
+    pub enable: bool,
+    pub position: Option<(i32, i32)>,
+    pub mode: Option<OutputMode>,
+    // 2 for an integer scale, 1.5 and the like for a fractional one
+    pub scale: Option<f64>,
+    pub transform: Option<OutputTransform>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// "1920x1080" or "1920x1080@143.98"
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct OutputMode {
+    pub width: i32,
+    pub height: i32,
+    // mHz, like wl_output
+    pub refresh: Option<i32>,
+}
+
+impl TryFrom<String> for OutputMode {
+    type Error = String;
+
+    fn try_from(mode: String) -> Result<Self, Self::Error> {
+        let error = || format!("invalid mode \"{mode}\", expected WIDTHxHEIGHT[@HZ]");
+
+        let (size, refresh) = match mode.split_once('@') {
+            Some((size, refresh)) => (size, Some(refresh)),
+            None => (mode.as_str(), None),
+        };
+        let (width, height) = size.split_once('x').ok_or_else(error)?;
+
+        Ok(Self {
+            width: width.trim().parse().map_err(|_| error())?,
+            height: height.trim().parse().map_err(|_| error())?,
+            refresh: match refresh {
+                Some(refresh) => {
+                    let hz: f64 = refresh.trim().parse().map_err(|_| error())?;
+                    Some((hz * 1000.0).round() as i32)
+                }
+                None => None,
+            },
+        })
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Modifier {
+    Super,
+    Alt,
+    Ctrl,
+    Shift,
+}
+
+impl Modifier {
+    pub fn is_held(self, modifiers: &ModifiersState) -> bool {
+        match self {
+            Modifier::Super => modifiers.logo,
+            Modifier::Alt => modifiers.alt,
+            Modifier::Ctrl => modifiers.ctrl,
+            Modifier::Shift => modifiers.shift,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+impl MouseButton {
+    // As in linux/input-event-codes.h
+    pub fn code(self) -> u32 {
+        match self {
+            MouseButton::Left => 0x110,
+            MouseButton::Right => 0x111,
+            MouseButton::Middle => 0x112,
+        }
+    }
+}
+
+// Dragging a window from anywhere inside it while the modifier is held
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct WindowDragConfig {
+    pub modifier: Modifier,
+    pub move_button: MouseButton,
+    // Resizes from the corner closest to the pointer
+    pub resize_button: MouseButton,
+}
+
+impl Default for WindowDragConfig {
+    fn default() -> Self {
+        Self {
+            modifier: Modifier::Super,
+            move_button: MouseButton::Left,
+            resize_button: MouseButton::Right,
+        }
+    }
+}
+
+// "ctrl+alt+shift+BackSpace", modifiers first, then an xkb keysym name
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeyChord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub logo: bool,
+    pub keysym: u32,
+}
+
+impl KeyChord {
+    pub fn matches(&self, modifiers: &ModifiersState, keysym: u32) -> bool {
+        modifiers.ctrl == self.ctrl
+            && modifiers.alt == self.alt
+            && modifiers.shift == self.shift
+            && modifiers.logo == self.logo
+            && to_lower(keysym) == to_lower(self.keysym)
+    }
+}
+
+// Shift turns "a" into "A", the chord should match either way
+fn to_lower(keysym: u32) -> u32 {
+    match keysym {
+        0x41..=0x5a => keysym + 0x20,
+        _ => keysym,
+    }
+}
+
+impl TryFrom<String> for KeyChord {
+    type Error = String;
+
+    fn try_from(chord: String) -> Result<Self, Self::Error> {
+        let mut parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+        let key: &str = parts
+            .pop()
+            .filter(|key: &&str| !key.is_empty())
+            .ok_or_else(|| format!("invalid key chord \"{chord}\", no key"))?;
+
+        // xkbcommon panics on names it can't turn into a C string
+        let keysym: u32 = if key.contains('\0') {
+            xkb::KEY_NoSymbol
+        } else {
+            xkb::keysym_from_name(key, xkb::KEYSYM_CASE_INSENSITIVE)
+        };
+        if keysym == xkb::KEY_NoSymbol {
+            return Err(format!("unknown key \"{key}\" in \"{chord}\""));
+        }
+
+        let mut result: Self = Self {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+            // So "super+Q" and "super+q" are the same chord
+            keysym: to_lower(keysym),
+        };
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => result.ctrl = true,
+                "alt" | "mod1" => result.alt = true,
+                "shift" => result.shift = true,
+                "super" | "logo" | "mod4" => result.logo = true,
+                _ => return Err(format!("unknown modifier \"{modifier}\" in \"{chord}\"")),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub enum OutputTransform {
+    #[serde(rename = "normal")]
+    Normal,
+    #[serde(rename = "90")]
+    Rotate90,
+    #[serde(rename = "180")]
+    Rotate180,
+    #[serde(rename = "270")]
+    Rotate270,
+    #[serde(rename = "flipped")]
+    Flipped,
+    #[serde(rename = "flipped-90")]
+    Flipped90,
+    #[serde(rename = "flipped-180")]
+    Flipped180,
+    #[serde(rename = "flipped-270")]
+    Flipped270,
+}
+
+impl From<OutputTransform> for Transform {
+    fn from(transform: OutputTransform) -> Self {
+        match transform {
+            OutputTransform::Normal => Transform::Normal,
+            OutputTransform::Rotate90 => Transform::_90,
+            OutputTransform::Rotate180 => Transform::_180,
+            OutputTransform::Rotate270 => Transform::_270,
+            OutputTransform::Flipped => Transform::Flipped,
+            OutputTransform::Flipped90 => Transform::Flipped90,
+            OutputTransform::Flipped180 => Transform::Flipped180,
+            OutputTransform::Flipped270 => Transform::Flipped270,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keybindings::Binding;
+
+    // A fresh directory per test, so they can run in parallel
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("pulsewm-config-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn table(contents: &str) -> toml::Table {
+        toml::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn includes_globs_in_order_then_plain_paths() {
+        let dir: PathBuf = scratch_dir("includes");
+        for name in ["20-b.toml", "10-a.toml", "notes.txt", "30-c.toml"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let mut main: toml::Table = table(r#"include = ["conf.d.toml", "*.toml"]"#);
+
+        assert_eq!(
+            includes(&mut main, &dir),
+            vec![
+                dir.join("conf.d.toml"),
+                dir.join("10-a.toml"),
+                dir.join("20-b.toml"),
+                dir.join("30-c.toml"),
+            ]
+        );
+        assert!(!main.contains_key("include"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_matches("*.toml", "output.toml"));
+        assert!(wildcard_matches("*.toml", ".toml"));
+        assert!(!wildcard_matches("*.toml", "output.toml.bak"));
+        assert!(wildcard_matches("?0-*.toml", "10-keys.toml"));
+        assert!(!wildcard_matches("?0-*.toml", "0-keys.toml"));
+        assert!(wildcard_matches("a*b*c", "aXbYbZc"));
+        assert!(wildcard_matches("*", ""));
+        assert!(!wildcard_matches("?", ""));
+    }
+
+    #[test]
+    fn merge_appends_lists_and_merges_tables() {
+        let mut into: toml::Table = table(
+            r#"
+            [[output]]
+            name = "eDP-1"
+            [layout]
+            gap = 4
+            border = 2
+            "#,
+        );
+        merge(
+            &mut into,
+            table(
+                r#"
+                [[output]]
+                name = "HDMI-A-1"
+                [layout]
+                gap = 8
+                "#,
+            ),
+        );
+
+        assert_eq!(
+            into,
+            table(
+                r#"
+                [[output]]
+                name = "eDP-1"
+                [[output]]
+                name = "HDMI-A-1"
+                [layout]
+                gap = 8
+                border = 2
+                "#,
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_variables_are_left_alone() {
+        let variables: HashMap<String, String> =
+            HashMap::from([("mod".to_string(), "super".to_string())]);
+
+        assert_eq!(expand("$mod+Return", &variables), "super+Return");
+        assert_eq!(expand("cd $HOME && ls", &variables), "cd $HOME && ls");
+        assert_eq!(expand("costs $5", &variables), "costs $5");
+        assert_eq!(expand("trailing $", &variables), "trailing $");
+    }
+
+    #[test]
+    fn fragment_variables_stay_in_the_fragment() {
+        let dir: PathBuf = scratch_dir("fragment");
+        let path: PathBuf = dir.join("editor.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [variables]
+            editor = "nvim"
+            [env]
+            EDITOR = "$editor"
+            TERMINAL = "$terminal"
+            "#,
+        )
+        .unwrap();
+        let main: toml::Table = table(
+            r#"
+            [variables]
+            terminal = "foot"
+            "#,
+        );
+
+        let fragment: toml::Table = load_fragment(&path, &main).unwrap();
+        assert_eq!(
+            fragment,
+            table(
+                r#"
+                [env]
+                EDITOR = "nvim"
+                TERMINAL = "foot"
+                "#,
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn alias_bindings_spawn_their_command() {
+        let config: Config = Config::parse(
+            r#"
+            [variables]
+            mod = "super"
+            term = "foot"
+            [aliases]
+            browser = "firefox --new-window"
+            editor = "$term nvim"
+            [keybindings]
+            "$mod+w" = "browser"
+            "$mod+e" = { action = "editor", description = "Edit" }
+            "$mod+q" = "close_window"
+            "#,
+        )
+        .unwrap();
+
+        let action = |name: &str| -> Action {
+            config
+                .keybindings
+                .by_category()
+                .into_iter()
+                .flat_map(|(_, bindings)| bindings)
+                .find(|binding: &&Binding| binding.name == name)
+                .unwrap()
+                .action
+                .clone()
+        };
+        assert_eq!(
+            action("super+w"),
+            Action::Spawn("firefox --new-window".into())
+        );
+        assert_eq!(action("super+e"), Action::Spawn("foot nvim".into()));
+        assert_eq!(action("super+q"), Action::CloseWindow);
+    }
+
+    #[test]
+    fn fragments_include_further_fragments() {
+        let dir: PathBuf = scratch_dir("nested");
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        std::fs::write(dir.join("config.toml"), r#"include = "conf.d/*.toml""#).unwrap();
+        std::fs::write(
+            dir.join("conf.d/10-env.toml"),
+            r#"
+            include = "local/editor.toml"
+            [env]
+            EDITOR = "vi"
+            PAGER = "less"
+            "#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.join("conf.d/local")).unwrap();
+        std::fs::write(
+            dir.join("conf.d/local/editor.toml"),
+            r#"
+            [env]
+            EDITOR = "nvim"
+            "#,
+        )
+        .unwrap();
+
+        let loaded: toml::Table = load_with_includes(&dir.join("config.toml"))
+            .unwrap()
+            .unwrap();
+        // Relative to the fragment and on top of it
+        assert_eq!(
+            loaded.get("env"),
+            Some(&toml::Value::Table(table(
+                r#"
+                EDITOR = "nvim"
+                PAGER = "less"
+                "#
+            )))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_are_cut() {
+        let dir: PathBuf = scratch_dir("cycle");
+        std::fs::write(
+            dir.join("config.toml"),
+            r#"
+            include = ["a.toml", "config.toml"]
+            [env]
+            MAIN = "1"
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("a.toml"),
+            r#"
+            include = ["b.toml", "a.toml"]
+            [env]
+            A = "1"
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("b.toml"),
+            r#"
+            include = "./a.toml"
+            [env]
+            B = "1"
+            "#,
+        )
+        .unwrap();
+
+        let loaded: toml::Table = load_with_includes(&dir.join("config.toml"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            loaded.get("env"),
+            Some(&toml::Value::Table(table(
+                r#"
+                MAIN = "1"
+                A = "1"
+                B = "1"
+                "#
+            )))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,26 @@
+use smithay::{
+    utils::Serial,
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        shell::xdg::{ToplevelSurface, XdgToplevelSurfaceData},
+    },
+};
+
+// Whether the client acked and committed the configure with `serial`, or a
+// later one. Until it did, sending it more sizes only piles up configures
+// it will render one after the other, and interactive resizes and
+// animations fall further behind with every frame.
+pub fn has_caught_up(toplevel: &ToplevelSurface, serial: Serial) -> bool {
+    with_states(toplevel.wl_surface(), |states: &SurfaceData| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .configure_serial
+            .map_or(false, |committed: Serial| {
+                committed.is_no_older_than(&serial)
+            })
+    })
+}
@@ -0,0 +1,218 @@
+use std::{path::PathBuf, sync::Mutex, time::Duration};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+                texture::{TextureBuffer, TextureRenderElement},
+                Kind,
+            },
+            gles::{GlesRenderer, GlesTexture},
+        },
+    },
+    desktop::utils::send_frames_surface_tree,
+    input::pointer::{CursorImageAttributes, CursorImageStatus},
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{IsAlive, Logical, Physical, Point, Rectangle, Scale, Transform},
+    wayland::compositor::{with_states, SurfaceData},
+};
+use xcursor::{
+    parser::{parse_xcursor, Image},
+    CursorTheme,
+};
+
+use crate::{render::CustomRenderElements, state::State};
+
+// Tried in this order, some themes only have the X11 name
+const DEFAULT_ICONS: [&str; 2] = ["default", "left_ptr"];
+// When the configured theme has no cursor at all
+const FALLBACK_THEME: &str = "default";
+
+// The themed cursor shown unless a client sets its own. Only the first
+// frame of animated cursors is drawn.
+pub struct Cursor {
+    theme: String,
+    // Every size the theme has, loaded once
+    images: Vec<Image>,
+    // Uploaded as they're needed, by nominal size
+    textures: Vec<(u32, TextureBuffer<GlesTexture>, Point<i32, Physical>)>,
+}
+
+fn load_images(theme: &str) -> Option<Vec<Image>> {
+    let theme: CursorTheme = CursorTheme::load(theme);
+    let path: PathBuf = DEFAULT_ICONS
+        .iter()
+        .find_map(|icon: &&str| theme.load_icon(icon))?;
+    let bytes: Vec<u8> = std::fs::read(&path)
+        .map_err(|err| eprintln!("Failed to read cursor {}: {err}", path.display()))
+        .ok()?;
+    parse_xcursor(&bytes).filter(|images: &Vec<Image>| !images.is_empty())
+}
+
+impl Cursor {
+    pub fn load(theme: &str) -> Self {
+        let images: Vec<Image> = load_images(theme)
+            .or_else(|| {
+                eprintln!("Failed to load cursor theme \"{theme}\", using \"{FALLBACK_THEME}\"");
+                load_images(FALLBACK_THEME)
+            })
+            .unwrap_or_else(|| {
+                eprintln!("No cursor theme found, only client cursors are drawn");
+                Vec::new()
+            });
+
+        Self {
+            theme: theme.to_string(),
+            images,
+            textures: Vec::new(),
+        }
+    }
+
+    pub fn theme(&self) -> &str {
+        &self.theme
+    }
+
+    // The image closest to `size` physical pixels, as a texture and its
+    // hotspot
+    fn texture(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        size: u32,
+    ) -> Option<(TextureBuffer<GlesTexture>, Point<i32, Physical>)> {
+        let image: &Image = self
+            .images
+            .iter()
+            .min_by_key(|image: &&Image| image.size.abs_diff(size))?;
+        let nominal: u32 = image.size;
+
+        if let Some((_, buffer, hotspot)) =
+            self.textures.iter().find(|(other, _, _)| *other == nominal)
+        {
+            return Some((buffer.clone(), *hotspot));
+        }
+
+        let buffer: TextureBuffer<GlesTexture> = TextureBuffer::from_memory(
+            renderer,
+            &image.pixels_rgba,
+            Fourcc::Abgr8888,
+            (image.width as i32, image.height as i32),
+            false,
+            1,
+            Transform::Normal,
+            None,
+        )
+        .map_err(|err| eprintln!("Failed to upload cursor: {err}"))
+        .ok()?;
+        let hotspot: Point<i32, Physical> = Point::from((image.xhot as i32, image.yhot as i32));
+        self.textures.push((nominal, buffer.clone(), hotspot));
+        Some((buffer, hotspot))
+    }
+}
+
+impl State {
+    // Cursor images are picked at the output's scale instead of scaling up
+    // the logical size, which looks tiny or blurry on HiDPI outputs
+    pub fn cursor_size(&self, output: &Output) -> u32 {
+        let scale: f64 = output.current_scale().fractional_scale();
+        (self.config.cursor.size as f64 * scale).round() as u32
+    }
+
+    // Relative to `output`, if the pointer is on it
+    pub fn pointer_on(&self, output: &Output) -> Option<Point<f64, Logical>> {
+        let output_geometry: Rectangle<f64, Logical> = self.space.output_geometry(output)?.to_f64();
+        output_geometry
+            .contains(self.pointer_location)
+            .then(|| self.pointer_location - output_geometry.loc)
+    }
+
+    // Topmost, on the output the pointer is on
+    pub fn cursor_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        let Some(location) = self.pointer_on(output) else {
+            return;
+        };
+        let scale: f64 = output.current_scale().fractional_scale();
+
+        // The client's surface goes away with the client
+        if let CursorImageStatus::Surface(surface) = &self.cursor_status {
+            if !surface.alive() {
+                self.cursor_status = CursorImageStatus::Default;
+            }
+        }
+
+        match &self.cursor_status {
+            CursorImageStatus::Hidden => {}
+            CursorImageStatus::Surface(surface) => {
+                let hotspot: Point<i32, Logical> = with_states(surface, |states: &SurfaceData| {
+                    states
+                        .data_map
+                        .get::<Mutex<CursorImageAttributes>>()
+                        .map(|attributes| attributes.lock().unwrap().hotspot)
+                        .unwrap_or_default()
+                });
+                let position: Point<i32, Physical> =
+                    (location - hotspot.to_f64()).to_physical_precise_round(scale);
+                let surface_elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> =
+                    render_elements_from_surface_tree(
+                        renderer,
+                        surface,
+                        position,
+                        Scale::from(scale),
+                        1.0,
+                        Kind::Cursor,
+                    );
+                elements.extend(surface_elements.into_iter().map(Into::into));
+            }
+            CursorImageStatus::Default => {
+                let size: u32 = self.cursor_size(output);
+                let Some((buffer, hotspot)) = self.cursor.texture(renderer, size) else {
+                    return;
+                };
+                let position: Point<i32, Physical> =
+                    location.to_physical_precise_round(scale) - hotspot;
+                elements.push(
+                    TextureRenderElement::from_texture_buffer(
+                        position.to_f64(),
+                        &buffer,
+                        None,
+                        None,
+                        None,
+                        Kind::Cursor,
+                    )
+                    .into(),
+                );
+            }
+        }
+    }
+
+    // Client cursors animate through frame callbacks like any surface. The
+    // cursor is only ever on the output the pointer is on, so that's its
+    // primary output.
+    pub fn send_cursor_frames(&self, output: &Output, time: Duration) {
+        let CursorImageStatus::Surface(surface) = &self.cursor_status else {
+            return;
+        };
+        if self.pointer_on(output).is_some() {
+            send_frames_surface_tree(
+                surface,
+                output,
+                time,
+                Some(Duration::ZERO),
+                |_: &WlSurface, _: &SurfaceData| Some(output.clone()),
+            );
+        }
+    }
+
+    pub fn reload_cursor_theme(&mut self) {
+        if self.cursor.theme() != self.config.cursor.theme {
+            self.cursor = Cursor::load(&self.config.cursor.theme);
+        }
+    }
+}
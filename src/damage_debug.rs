@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use smithay::{
+    backend::renderer::{
+        element::{solid::SolidColorRenderElement, Element, Id, Kind},
+        utils::CommitCounter,
+    },
+    output::Output,
+    utils::{Physical, Rectangle, Scale},
+};
+
+use crate::{render::CustomRenderElements, state::State};
+
+// Long enough to see damage from a single frame
+const FLASH_DURATION: Duration = Duration::from_millis(250);
+const TINT: [f32; 3] = [1.0, 0.0, 0.4];
+const TINT_ALPHA: f32 = 0.4;
+
+// One tinted rect. The id stays the same for as long as it's shown and the
+// commit only moves while it fades, so the damage tracker redraws it no more
+// than the fade needs.
+struct Flash {
+    id: Id,
+    commit: CommitCounter,
+    rect: Rectangle<i32, Physical>,
+    at: Instant,
+}
+
+impl Flash {
+    fn new(rect: Rectangle<i32, Physical>, now: Instant) -> Self {
+        Self {
+            id: Id::new(),
+            commit: CommitCounter::default(),
+            rect,
+            at: now,
+        }
+    }
+
+    fn alpha(&self, now: Instant) -> f32 {
+        let fade: f32 = 1.0
+            - (now.saturating_duration_since(self.at).as_secs_f32() / FLASH_DURATION.as_secs_f32());
+        TINT_ALPHA * fade.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Default)]
+struct OutputDamage {
+    // What each element looked like last frame
+    elements: HashMap<Id, (CommitCounter, Rectangle<i32, Physical>)>,
+    flashes: Vec<Flash>,
+}
+
+impl OutputDamage {
+    // Works out the damage the same way the damage tracker does, but on its
+    // own copy of the element state, so the tint drawn on top never counts
+    // as damage itself
+    fn update(&mut self, elements: &[CustomRenderElements], scale: Scale<f64>, now: Instant) {
+        let mut damage: Vec<Rectangle<i32, Physical>> = Vec::new();
+        let mut seen: HashMap<Id, (CommitCounter, Rectangle<i32, Physical>)> = HashMap::new();
+
+        for element in elements {
+            let geometry: Rectangle<i32, Physical> = element.geometry(scale);
+            let commit: CommitCounter = element.current_commit();
+
+            match self.elements.get(element.id()) {
+                Some((_, old_geometry)) if *old_geometry != geometry => {
+                    damage.push(*old_geometry);
+                    damage.push(geometry);
+                }
+                Some((old_commit, _)) => damage.extend(
+                    element
+                        .damage_since(scale, Some(*old_commit))
+                        .into_iter()
+                        .map(|mut rect: Rectangle<i32, Physical>| {
+                            rect.loc += geometry.loc;
+                            rect
+                        }),
+                ),
+                None => damage.push(geometry),
+            }
+
+            seen.insert(element.id().clone(), (commit, geometry));
+        }
+
+        // Whatever disappeared uncovers what was below it
+        damage.extend(
+            self.elements
+                .iter()
+                .filter(|(id, _)| !seen.contains_key(*id))
+                .map(|(_, (_, geometry))| *geometry),
+        );
+        self.elements = seen;
+
+        self.flashes
+            .retain(|flash: &Flash| now.saturating_duration_since(flash.at) < FLASH_DURATION);
+        for flash in &mut self.flashes {
+            flash.commit.increment();
+        }
+        // A surface damaged every frame keeps a single flash lit instead of
+        // piling up new ones
+        for rect in damage {
+            if rect.is_empty() {
+                continue;
+            }
+            match self
+                .flashes
+                .iter_mut()
+                .find(|flash: &&mut Flash| flash.rect == rect)
+            {
+                Some(flash) => flash.at = now,
+                None => self.flashes.push(Flash::new(rect, now)),
+            }
+        }
+    }
+}
+
+// Tints everything that was damaged in the last few frames
+#[derive(Default)]
+pub struct DamageDebug {
+    enabled: bool,
+    outputs: HashMap<String, OutputDamage>,
+}
+
+impl DamageDebug {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            outputs: HashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl State {
+    pub fn set_damage_debug(&mut self, enabled: bool) {
+        self.damage_debug = DamageDebug::new(enabled);
+        eprintln!(
+            "Damage visualization {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+    }
+
+    // `elements` are the ones about to be drawn on `output`, topmost first.
+    // The tint goes in front of them.
+    pub fn damage_debug_elements(
+        &mut self,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        if !self.damage_debug.enabled {
+            return;
+        }
+
+        let now: Instant = Instant::now();
+        let scale: Scale<f64> = output.current_scale().fractional_scale().into();
+        let damage: &mut OutputDamage = self.damage_debug.outputs.entry(output.name()).or_default();
+        damage.update(elements, scale, now);
+
+        let scene: usize = elements.len();
+        for flash in &damage.flashes {
+            let alpha: f32 = flash.alpha(now);
+            let color: [f32; 4] = [TINT[0] * alpha, TINT[1] * alpha, TINT[2] * alpha, alpha];
+            elements.push(
+                SolidColorRenderElement::new(
+                    flash.id.clone(),
+                    flash.rect,
+                    flash.commit,
+                    color,
+                    Kind::Unspecified,
+                )
+                .into(),
+            );
+        }
+        // Moved to the front in place, the scene stays in its order behind
+        let tint: usize = elements.len() - scene;
+        elements.rotate_right(tint);
+    }
+}
@@ -1,17 +1,34 @@
-use crate::state::State;
+use crate::{audit::SharedAuditLog, backend::Backend, state::State};
 use smithay::{
-    reexports::wayland_server::{backend, Display},
+    reexports::wayland_server::{
+        backend::{self, ClientId, DisconnectReason},
+        Display,
+    },
     wayland::compositor::CompositorClientState,
 };
 
 pub struct Data {
     pub display: Display<State>,
     pub state: State,
+    pub backend: Backend,
 }
 
-#[derive(Default)]
 pub struct ClientData {
     pub compositor_state: CompositorClientState,
+    pub audit: SharedAuditLog,
 }
 
-impl backend::ClientData for ClientData {}
+impl ClientData {
+    pub fn new(audit: SharedAuditLog) -> Self {
+        Self {
+            compositor_state: CompositorClientState::default(),
+            audit,
+        }
+    }
+}
+
+impl backend::ClientData for ClientData {
+    fn disconnected(&self, client_id: ClientId, reason: DisconnectReason) {
+        self.audit.lock().unwrap().disconnected(&client_id, &reason);
+    }
+}
@@ -0,0 +1,381 @@
+use std::{os::unix::io::RawFd, sync::Mutex};
+
+use smithay::{
+    reexports::{
+        nix::unistd::close,
+        wayland_protocols::wp::primary_selection::zv1::server::zwp_primary_selection_source_v1,
+        wayland_protocols_wlr::data_control::v1::server::{
+            zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+            zwlr_data_control_manager_v1::{self, ZwlrDataControlManagerV1},
+            zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+            zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId, ObjectId},
+            protocol::wl_data_source::WlDataSource,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    wayland::{
+        data_device::{
+            self, clear_data_device_selection, request_data_device_client_selection,
+            set_data_device_selection,
+        },
+        primary_selection::{
+            self, clear_primary_selection, request_primary_client_selection, set_primary_selection,
+        },
+    },
+};
+
+use crate::{acl::PrivilegedProtocol, state::State};
+
+const VERSION: u32 = 2;
+// zwlr_data_control_device_v1.primary_selection and its setter
+const PRIMARY_SINCE: u32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKind {
+    Clipboard,
+    Primary,
+}
+
+// What's selected right now, so devices bound later can be told
+#[derive(Clone)]
+struct Selection {
+    mime_types: Vec<String>,
+    // Set through data-control, it's asked for the data directly. Otherwise
+    // a client's wl_data_source or primary selection source has it.
+    source: Option<ZwlrDataControlSourceV1>,
+}
+
+#[derive(Default)]
+pub struct SourceData {
+    mime_types: Mutex<Vec<String>>,
+}
+
+pub struct OfferData {
+    kind: SelectionKind,
+    source: Option<ZwlrDataControlSourceV1>,
+}
+
+// Clipboard managers see every selection without having focus, and can set
+// one that's offered to clients like any other
+#[derive(Default)]
+pub struct DataControl {
+    devices: Vec<ZwlrDataControlDeviceV1>,
+    clipboard: Option<Selection>,
+    primary: Option<Selection>,
+}
+
+impl DataControl {
+    fn selection(&mut self, kind: SelectionKind) -> &mut Option<Selection> {
+        match kind {
+            SelectionKind::Clipboard => &mut self.clipboard,
+            SelectionKind::Primary => &mut self.primary,
+        }
+    }
+}
+
+pub fn create_global(state: &mut State) -> GlobalId {
+    state
+        .display_handle
+        .create_global::<State, ZwlrDataControlManagerV1, _>(VERSION, ())
+}
+
+// The fd is ours to close once the source has it
+fn send_to_source(source: &ZwlrDataControlSourceV1, mime_type: String, fd: RawFd) {
+    if source.is_alive() {
+        source.send(mime_type, fd);
+    }
+    if let Err(err) = close(fd) {
+        eprintln!("Failed to close selection fd: {err}");
+    }
+}
+
+impl State {
+    // A client set the clipboard through wl_data_device
+    pub fn client_selection(&mut self, source: Option<WlDataSource>) {
+        let selection: Option<Selection> = source.map(|source: WlDataSource| Selection {
+            mime_types: data_device::with_source_metadata(&source, |metadata| {
+                metadata.mime_types.clone()
+            })
+            .unwrap_or_default(),
+            source: None,
+        });
+        self.selection_changed(SelectionKind::Clipboard, selection);
+    }
+
+    // A client set the primary selection through zwp_primary_selection_device_v1
+    pub fn client_primary_selection(
+        &mut self,
+        source: Option<zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1>,
+    ) {
+        let selection: Option<Selection> = source.map(
+            |source: zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1| Selection {
+                mime_types: primary_selection::with_source_metadata(&source, |metadata| {
+                    metadata.mime_types.clone()
+                })
+                .unwrap_or_default(),
+                source: None,
+            },
+        );
+        self.selection_changed(SelectionKind::Primary, selection);
+    }
+
+    // A client pasted from a selection a clipboard manager set
+    pub fn send_control_selection(
+        &mut self,
+        mime_type: String,
+        fd: RawFd,
+        source: &ZwlrDataControlSourceV1,
+    ) {
+        send_to_source(source, mime_type, fd);
+    }
+
+    fn selection_changed(&mut self, kind: SelectionKind, selection: Option<Selection>) {
+        let previous: Option<Selection> =
+            std::mem::replace(self.data_control.selection(kind), selection.clone());
+        // Replaced sources are told, unless the same one was set again
+        if let Some(source) = previous.and_then(|previous: Selection| previous.source) {
+            let current: Option<&ZwlrDataControlSourceV1> = selection
+                .as_ref()
+                .and_then(|selection: &Selection| selection.source.as_ref());
+            if current != Some(&source) && source.is_alive() {
+                source.cancelled();
+            }
+        }
+
+        for device in self.data_control.devices.clone() {
+            self.offer_selection(&device, kind, selection.as_ref());
+        }
+    }
+
+    fn offer_selection(
+        &self,
+        device: &ZwlrDataControlDeviceV1,
+        kind: SelectionKind,
+        selection: Option<&Selection>,
+    ) {
+        if kind == SelectionKind::Primary && device.version() < PRIMARY_SINCE {
+            return;
+        }
+        let Some(client) = device.client() else {
+            return;
+        };
+
+        let offer: Option<ZwlrDataControlOfferV1> = selection.and_then(|selection: &Selection| {
+            let data: OfferData = OfferData {
+                kind,
+                source: selection.source.clone(),
+            };
+            let offer: ZwlrDataControlOfferV1 = client
+                .create_resource::<ZwlrDataControlOfferV1, OfferData, State>(
+                    &self.display_handle,
+                    device.version(),
+                    data,
+                )
+                .map_err(|err| eprintln!("Failed to create data-control offer: {err}"))
+                .ok()?;
+            device.data_offer(&offer);
+            for mime_type in &selection.mime_types {
+                offer.offer(mime_type.clone());
+            }
+            Some(offer)
+        });
+
+        match kind {
+            SelectionKind::Clipboard => device.selection(offer.as_ref()),
+            SelectionKind::Primary => device.primary_selection(offer.as_ref()),
+        }
+    }
+
+    fn set_control_selection(
+        &mut self,
+        kind: SelectionKind,
+        source: Option<ZwlrDataControlSourceV1>,
+    ) {
+        let selection: Option<Selection> = source.map(|source: ZwlrDataControlSourceV1| {
+            let mime_types: Vec<String> = source
+                .data::<SourceData>()
+                .map(|data: &SourceData| data.mime_types.lock().unwrap().clone())
+                .unwrap_or_default();
+            Selection {
+                mime_types,
+                source: Some(source),
+            }
+        });
+
+        // Clients with focus get it through the regular protocols
+        let display_handle: DisplayHandle = self.display_handle.clone();
+        match (kind, &selection) {
+            (SelectionKind::Clipboard, Some(selection)) => set_data_device_selection(
+                &display_handle,
+                &self.seat,
+                selection.mime_types.clone(),
+                selection.source.clone().unwrap(),
+            ),
+            (SelectionKind::Clipboard, None) => {
+                clear_data_device_selection(&display_handle, &self.seat)
+            }
+            (SelectionKind::Primary, Some(selection)) => set_primary_selection(
+                &display_handle,
+                &self.seat,
+                selection.mime_types.clone(),
+                selection.source.clone().unwrap(),
+            ),
+            (SelectionKind::Primary, None) => clear_primary_selection(&display_handle, &self.seat),
+        }
+
+        self.selection_changed(kind, selection);
+    }
+
+    // The source is gone, a selection it held is cleared
+    fn control_source_destroyed(&mut self, source: &ObjectId) {
+        for kind in [SelectionKind::Clipboard, SelectionKind::Primary] {
+            let held: bool = self
+                .data_control
+                .selection(kind)
+                .as_ref()
+                .and_then(|selection: &Selection| selection.source.as_ref())
+                .is_some_and(|other: &ZwlrDataControlSourceV1| other.id() == *source);
+            if held {
+                self.set_control_selection(kind, None);
+            }
+        }
+    }
+
+    fn receive_offer(&mut self, data: &OfferData, mime_type: String, fd: RawFd) {
+        if let Some(source) = &data.source {
+            send_to_source(source, mime_type, fd);
+            return;
+        }
+
+        let result: Result<(), String> = match data.kind {
+            SelectionKind::Clipboard => {
+                request_data_device_client_selection(&self.seat, mime_type, fd)
+                    .map_err(|err| err.to_string())
+            }
+            SelectionKind::Primary => request_primary_client_selection(&self.seat, mime_type, fd)
+                .map_err(|err| err.to_string()),
+        };
+        // Otherwise the client's source closes it
+        if let Err(err) = result {
+            eprintln!("Failed to request selection: {err}");
+            let _ = close(fd);
+        }
+    }
+}
+
+impl GlobalDispatch<ZwlrDataControlManagerV1, ()> for State {
+    fn bind(
+        state: &mut Self,
+        _handle: &DisplayHandle,
+        client: &Client,
+        resource: New<ZwlrDataControlManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if !state.may_bind(client, PrivilegedProtocol::DataControl) {
+            data_init.post_error(
+                resource,
+                0u32,
+                "data-control is not allowed for this client",
+            );
+            return;
+        }
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, ()> for State {
+    // There's only the one seat, the one asked for doesn't matter
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrDataControlManagerV1,
+        request: zwlr_data_control_manager_v1::Request,
+        _data: &(),
+        _handle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_data_control_manager_v1::Request::CreateDataSource { id } => {
+                data_init.init(id, SourceData::default());
+            }
+            zwlr_data_control_manager_v1::Request::GetDataDevice { id, .. } => {
+                let device: ZwlrDataControlDeviceV1 = data_init.init(id, ());
+                let clipboard: Option<Selection> = state.data_control.clipboard.clone();
+                let primary: Option<Selection> = state.data_control.primary.clone();
+                state.offer_selection(&device, SelectionKind::Clipboard, clipboard.as_ref());
+                state.offer_selection(&device, SelectionKind::Primary, primary.as_ref());
+                state.data_control.devices.push(device);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrDataControlDeviceV1,
+        request: zwlr_data_control_device_v1::Request,
+        _data: &(),
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_data_control_device_v1::Request::SetSelection { source } => {
+                state.set_control_selection(SelectionKind::Clipboard, source);
+            }
+            zwlr_data_control_device_v1::Request::SetPrimarySelection { source } => {
+                state.set_control_selection(SelectionKind::Primary, source);
+            }
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, resource: ObjectId, _data: &()) {
+        state
+            .data_control
+            .devices
+            .retain(|device: &ZwlrDataControlDeviceV1| device.id() != resource);
+    }
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, SourceData> for State {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrDataControlSourceV1,
+        request: zwlr_data_control_source_v1::Request,
+        data: &SourceData,
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwlr_data_control_source_v1::Request::Offer { mime_type } = request {
+            data.mime_types.lock().unwrap().push(mime_type);
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, resource: ObjectId, _data: &SourceData) {
+        state.control_source_destroyed(&resource);
+    }
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, OfferData> for State {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrDataControlOfferV1,
+        request: zwlr_data_control_offer_v1::Request,
+        data: &OfferData,
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwlr_data_control_offer_v1::Request::Receive { mime_type, fd } = request {
+            state.receive_offer(data, mime_type, fd);
+        }
+    }
+}
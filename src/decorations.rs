@@ -0,0 +1,439 @@
+use smithay::{
+    backend::renderer::{
+        element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            texture::{TextureBuffer, TextureRenderElement},
+            Kind,
+        },
+        gles::{GlesRenderer, GlesTexture},
+    },
+    delegate_kde_decoration, delegate_xdg_decoration,
+    desktop::Window,
+    output::Output,
+    reexports::{
+        wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode,
+        wayland_protocols_misc::server_decoration::server::org_kde_kwin_server_decoration::{
+            Mode as KdeMode, OrgKdeKwinServerDecoration,
+        },
+        wayland_server::{protocol::wl_surface::WlSurface, WEnum},
+    },
+    utils::{IsAlive, Logical, Point, Rectangle},
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        shell::{
+            kde::decoration::{KdeDecorationHandler, KdeDecorationState},
+            xdg::{decoration::XdgDecorationHandler, ToplevelSurface, XdgToplevelSurfaceData},
+        },
+    },
+};
+
+use crate::{
+    config::DecorationConfig, render::CustomRenderElements, state::State, switcher,
+    text::TextRenderer, theme::Palette,
+};
+
+const PADDING: i32 = 8;
+const FONT_SIZE: f32 = 13.0;
+// How far past their edges the title bar and close button take taps in
+// tablet mode, a finger is a lot less precise than a pointer
+const TOUCH_SLOP: i32 = 8;
+
+// Where the parts of a server-side decoration go around a window's
+// geometry. The title bar sits right above the window, the border goes
+// around both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameRects {
+    pub outer: Rectangle<i32, Logical>,
+    pub title: Rectangle<i32, Logical>,
+    pub close: Rectangle<i32, Logical>,
+}
+
+pub fn frame_rects(
+    geometry: Rectangle<i32, Logical>,
+    title_height: i32,
+    border: i32,
+) -> FrameRects {
+    let title: Rectangle<i32, Logical> = Rectangle::from_loc_and_size(
+        (geometry.loc.x, geometry.loc.y - title_height),
+        (geometry.size.w, title_height),
+    );
+    let close: Rectangle<i32, Logical> = Rectangle::from_loc_and_size(
+        (title.loc.x + title.size.w - title_height, title.loc.y),
+        (title_height.min(title.size.w), title_height),
+    );
+    let outer: Rectangle<i32, Logical> = Rectangle::from_loc_and_size(
+        (geometry.loc.x - border, title.loc.y - border),
+        (
+            geometry.size.w + 2 * border,
+            geometry.size.h + title_height + 2 * border,
+        ),
+    );
+
+    FrameRects {
+        outer,
+        title,
+        close,
+    }
+}
+
+// The title bar and close button grown by `slop` on every side but the
+// bottom, which would reach into the window itself
+fn touch_targets(rects: FrameRects, slop: i32) -> FrameRects {
+    let grow = |rect: Rectangle<i32, Logical>| -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size(
+            (rect.loc.x - slop, rect.loc.y - slop),
+            (rect.size.w + 2 * slop, rect.size.h + slop),
+        )
+    };
+    FrameRects {
+        outer: rects.outer,
+        title: grow(rects.title),
+        close: grow(rects.close),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FramePart {
+    Title,
+    Close,
+}
+
+struct Frame {
+    window: Window,
+    outer: SolidColorBuffer,
+    title: SolidColorBuffer,
+    close: SolidColorBuffer,
+}
+
+// Buffers of the title bars and borders, kept so their damage is tracked
+// between frames
+#[derive(Default)]
+pub struct Decorations {
+    frames: Vec<Frame>,
+}
+
+impl Decorations {
+    fn frame(&mut self, window: &Window) -> &mut Frame {
+        self.frames.retain(|frame: &Frame| frame.window.alive());
+        let index: usize = match self
+            .frames
+            .iter()
+            .position(|frame: &Frame| frame.window == *window)
+        {
+            Some(index) => index,
+            None => {
+                self.frames.push(Frame {
+                    window: window.clone(),
+                    outer: SolidColorBuffer::default(),
+                    title: SolidColorBuffer::default(),
+                    close: SolidColorBuffer::default(),
+                });
+                self.frames.len() - 1
+            }
+        };
+        &mut self.frames[index]
+    }
+}
+
+// What the client ends up with when it doesn't ask for anything
+fn preferred_mode(config: &DecorationConfig) -> Mode {
+    if config.server_side {
+        Mode::ServerSide
+    } else {
+        Mode::ClientSide
+    }
+}
+
+pub fn kde_default_mode(config: &DecorationConfig) -> KdeMode {
+    match preferred_mode(config) {
+        Mode::ServerSide => KdeMode::Server,
+        _ => KdeMode::Client,
+    }
+}
+
+fn set_mode(toplevel: &ToplevelSurface, mode: Mode) {
+    toplevel.with_pending_state(|state| {
+        state.decoration_mode = Some(mode);
+    });
+    let initial_configure_sent: bool =
+        with_states(toplevel.wl_surface(), |states: &SurfaceData| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .initial_configure_sent
+        });
+    // Otherwise it goes out with the initial configure
+    if initial_configure_sent {
+        toplevel.send_pending_configure();
+    }
+}
+
+impl State {
+    pub fn is_server_side(&self, window: &Window) -> bool {
+        window.toplevel().current_state().decoration_mode == Some(Mode::ServerSide)
+            && !self.is_fullscreen(window)
+    }
+
+    // The part of `area` left for the window itself once its decoration
+    // is drawn around it
+    pub fn inside_decoration(
+        &self,
+        window: &Window,
+        area: Rectangle<i32, Logical>,
+    ) -> Rectangle<i32, Logical> {
+        let pending_server_side: bool = window
+            .toplevel()
+            .with_pending_state(|state| state.decoration_mode == Some(Mode::ServerSide));
+        // Room for the border drawn around windows without one
+        if !pending_server_side {
+            let border: i32 = self.config.layout.border_width.max(0);
+            return Rectangle::from_loc_and_size(
+                (area.loc.x + border, area.loc.y + border),
+                (
+                    (area.size.w - 2 * border).max(1),
+                    (area.size.h - 2 * border).max(1),
+                ),
+            );
+        }
+
+        let config: &DecorationConfig = &self.config.decorations;
+        let border: i32 = config.border_width;
+        Rectangle::from_loc_and_size(
+            (
+                area.loc.x + border,
+                area.loc.y + config.title_height + border,
+            ),
+            (
+                (area.size.w - 2 * border).max(1),
+                (area.size.h - config.title_height - 2 * border).max(1),
+            ),
+        )
+    }
+
+    fn window_frame_rects(&self, window: &Window) -> Option<FrameRects> {
+        let mut geometry: Rectangle<i32, Logical> = window.geometry();
+        geometry.loc += self.space.element_location(window)?;
+        let config: &DecorationConfig = &self.config.decorations;
+        Some(frame_rects(
+            geometry,
+            config.title_height,
+            config.border_width,
+        ))
+    }
+
+    // The decorated window whose title bar is at `location`, topmost first
+    pub fn decoration_under(&self, location: Point<f64, Logical>) -> Option<(Window, FramePart)> {
+        for window in self.space.elements().rev() {
+            let Some(mut rects) = self.window_frame_rects(window) else {
+                continue;
+            };
+            if self.tablet_mode {
+                rects = touch_targets(rects, TOUCH_SLOP);
+            }
+
+            if self.is_server_side(window) {
+                if rects.close.to_f64().contains(location) {
+                    return Some((window.clone(), FramePart::Close));
+                }
+                if rects.title.to_f64().contains(location) {
+                    return Some((window.clone(), FramePart::Title));
+                }
+            }
+            // Windows on top hide the title bars below them
+            let mut geometry: Rectangle<i32, Logical> = window.geometry();
+            geometry.loc += self.space.element_location(window)?;
+            if geometry.to_f64().contains(location) {
+                return None;
+            }
+        }
+        None
+    }
+
+    // The title bar with its text and close button, drawn above the window.
+    // `decorations` and `text` are taken out of the state by the caller,
+    // which is iterating the space.
+    pub fn decoration_elements(
+        &self,
+        decorations: &mut Decorations,
+        text: Option<&mut TextRenderer>,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        window: &Window,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        if !self.is_server_side(window) {
+            return;
+        }
+        let Some(output_geometry) = self.space.output_geometry(output) else {
+            return;
+        };
+        let Some(rects) = self.window_frame_rects(window) else {
+            return;
+        };
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let at = |location: Point<i32, Logical>| -> Point<i32, Logical> {
+            location - output_geometry.loc
+        };
+        let focused: bool = self.focused_window().as_ref() == Some(window);
+        let palette: &Palette = self.theme.palette();
+        let title_color: [f32; 4] = if focused {
+            palette.title_focused
+        } else {
+            palette.title_unfocused
+        };
+
+        if let Some(text) = text {
+            let title: String = switcher::title(window).unwrap_or_default();
+            let (text_width, text_height) = text.measure(&title, FONT_SIZE);
+            let available: i32 = rects.title.size.w - rects.close.size.w - 2 * PADDING;
+            let buffer: Option<TextureBuffer<GlesTexture>> = text.render(
+                renderer,
+                &title,
+                FONT_SIZE,
+                palette.title_text,
+                scale.ceil() as i32,
+            );
+            if let Some(buffer) = buffer.filter(|_| available > 0 && !title.is_empty()) {
+                // Long titles are cut off before the close button
+                let (src, size) = if text_width > available {
+                    (
+                        Some(Rectangle::from_loc_and_size(
+                            (0.0, 0.0),
+                            (available as f64, text_height as f64),
+                        )),
+                        Some((available, text_height).into()),
+                    )
+                } else {
+                    (None, None)
+                };
+                let location: Point<i32, Logical> = at(rects.title.loc)
+                    + Point::from((PADDING, (rects.title.size.h - text_height) / 2));
+                elements.push(
+                    TextureRenderElement::from_texture_buffer(
+                        location.to_physical_precise_round(scale).to_f64(),
+                        &buffer,
+                        None,
+                        src,
+                        size,
+                        Kind::Unspecified,
+                    )
+                    .into(),
+                );
+            }
+        }
+
+        let frame: &mut Frame = decorations.frame(window);
+        frame.close.update(rects.close.size, palette.close);
+        frame.title.update(rects.title.size, title_color);
+        for (buffer, rect) in [(&frame.close, rects.close), (&frame.title, rects.title)] {
+            elements.push(
+                SolidColorRenderElement::from_buffer(
+                    buffer,
+                    at(rect.loc).to_physical_precise_round(scale),
+                    scale,
+                    1.0,
+                    Kind::Unspecified,
+                )
+                .into(),
+            );
+        }
+    }
+
+    // The border around the window and its title bar, drawn below the
+    // window
+    pub fn decoration_border_element(
+        &self,
+        decorations: &mut Decorations,
+        output: &Output,
+        window: &Window,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        if !self.is_server_side(window) {
+            return;
+        }
+        let Some(output_geometry) = self.space.output_geometry(output) else {
+            return;
+        };
+        let Some(rects) = self.window_frame_rects(window) else {
+            return;
+        };
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let frame: &mut Frame = decorations.frame(window);
+        frame
+            .outer
+            .update(rects.outer.size, self.theme.palette().border);
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                &frame.outer,
+                (rects.outer.loc - output_geometry.loc).to_physical_precise_round(scale),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+}
+
+impl XdgDecorationHandler for State {
+    fn new_decoration(&mut self, toplevel: ToplevelSurface) {
+        set_mode(&toplevel, preferred_mode(&self.config.decorations));
+    }
+
+    // With server_side set everyone gets server-side decorations, clients
+    // that insist on drawing their own are told no
+    fn request_mode(&mut self, toplevel: ToplevelSurface, mode: Mode) {
+        let mode: Mode = if self.config.decorations.server_side {
+            Mode::ServerSide
+        } else {
+            mode
+        };
+        set_mode(&toplevel, mode);
+        self.arrange();
+    }
+
+    fn unset_mode(&mut self, toplevel: ToplevelSurface) {
+        set_mode(&toplevel, preferred_mode(&self.config.decorations));
+        self.arrange();
+    }
+}
+delegate_xdg_decoration!(State);
+
+impl KdeDecorationHandler for State {
+    fn kde_decoration_state(&self) -> &KdeDecorationState {
+        &self.kde_decoration_state
+    }
+
+    // Qt apps, the mode is tracked on the toplevel like with xdg-decoration
+    fn request_mode(
+        &mut self,
+        surface: &WlSurface,
+        decoration: &OrgKdeKwinServerDecoration,
+        mode: WEnum<KdeMode>,
+    ) {
+        let WEnum::Value(mode) = mode else {
+            return;
+        };
+        let mode: KdeMode = if self.config.decorations.server_side {
+            KdeMode::Server
+        } else {
+            mode
+        };
+
+        if let Some(window) = self.windows.get(surface).cloned() {
+            let xdg_mode: Mode = match mode {
+                KdeMode::Server => Mode::ServerSide,
+                _ => Mode::ClientSide,
+            };
+            set_mode(window.toplevel(), xdg_mode);
+            self.arrange();
+        }
+        decoration.mode(mode);
+    }
+}
+delegate_kde_decoration!(State);
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use smithay::reexports::input::{self, DeviceCapability};
+
+use crate::state::State;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccelProfile {
+    Flat,
+    Adaptive,
+}
+
+impl From<AccelProfile> for input::AccelProfile {
+    fn from(profile: AccelProfile) -> Self {
+        match profile {
+            AccelProfile::Flat => input::AccelProfile::Flat,
+            AccelProfile::Adaptive => input::AccelProfile::Adaptive,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PointerSettings {
+    // -1 to 1, 0 is libinput's default
+    speed: f64,
+    profile: Option<AccelProfile>,
+}
+
+// libinput devices, only the DRM backend has any. Settings made over IPC
+// are kept so they apply again when the device is plugged back in.
+#[derive(Default)]
+pub struct InputDevices {
+    devices: Vec<input::Device>,
+    // By device name, `None` for every pointer without its own entry
+    pointer_settings: HashMap<Option<String>, PointerSettings>,
+}
+
+#[derive(Serialize)]
+pub struct PointerDeviceStatus {
+    pub name: String,
+    pub sysname: String,
+    pub speed: f64,
+    pub profile: Option<AccelProfile>,
+}
+
+fn is_pointer(device: &input::Device) -> bool {
+    device.has_capability(DeviceCapability::Pointer) && device.config_accel_is_available()
+}
+
+fn apply(device: &mut input::Device, settings: PointerSettings) -> Result<(), String> {
+    device
+        .config_accel_set_speed(settings.speed)
+        .map_err(|err| format!("{}: {err:?}", device.name()))?;
+    if let Some(profile) = settings.profile {
+        device
+            .config_accel_set_profile(profile.into())
+            .map_err(|err| format!("{}: {err:?}", device.name()))?;
+    }
+    Ok(())
+}
+
+impl InputDevices {
+    pub fn add(&mut self, mut device: input::Device) {
+        if is_pointer(&device) {
+            let settings: Option<PointerSettings> = self
+                .pointer_settings
+                .get(&Some(device.name().to_string()))
+                .or_else(|| self.pointer_settings.get(&None))
+                .copied();
+            if let Some(settings) = settings {
+                if let Err(err) = apply(&mut device, settings) {
+                    eprintln!("Failed to set pointer speed: {err}");
+                }
+            }
+        }
+        self.devices.push(device);
+    }
+
+    pub fn remove(&mut self, device: &input::Device) {
+        self.devices.retain(|other: &input::Device| other != device);
+    }
+}
+
+impl State {
+    pub fn pointer_devices(&self) -> Vec<PointerDeviceStatus> {
+        self.input_devices
+            .devices
+            .iter()
+            .filter(|device: &&input::Device| is_pointer(device))
+            .map(|device: &input::Device| PointerDeviceStatus {
+                name: device.name().to_string(),
+                sysname: device.sysname().to_string(),
+                speed: device.config_accel_speed(),
+                profile: match device.config_accel_profile() {
+                    Some(input::AccelProfile::Flat) => Some(AccelProfile::Flat),
+                    Some(input::AccelProfile::Adaptive) => Some(AccelProfile::Adaptive),
+                    _ => None,
+                },
+            })
+            .collect()
+    }
+
+    // `device` is a name or sysname like "event4", all pointers if unset
+    pub fn set_pointer_speed(
+        &mut self,
+        device: Option<String>,
+        speed: f64,
+        profile: Option<AccelProfile>,
+    ) -> Result<(), String> {
+        if !(-1.0..=1.0).contains(&speed) {
+            return Err(format!("speed {speed} is outside of -1 to 1"));
+        }
+        let settings: PointerSettings = PointerSettings { speed, profile };
+
+        let mut matched: Vec<&mut input::Device> = self
+            .input_devices
+            .devices
+            .iter_mut()
+            .filter(|candidate: &&mut input::Device| is_pointer(candidate))
+            .filter(|candidate: &&mut input::Device| {
+                device.as_ref().map_or(true, |wanted: &String| {
+                    candidate.name() == wanted || candidate.sysname() == wanted
+                })
+            })
+            .collect();
+        if matched.is_empty() {
+            return Err(match device {
+                Some(device) => format!("no pointer device named \"{device}\""),
+                None => "no pointer devices".into(),
+            });
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        for device in matched.iter_mut() {
+            apply(device, settings)?;
+            names.push(device.name().to_string());
+        }
+
+        match device {
+            Some(_) => {
+                for name in names {
+                    self.input_devices
+                        .pointer_settings
+                        .insert(Some(name), settings);
+                }
+            }
+            // Per device settings made before are overridden as well
+            None => {
+                self.input_devices.pointer_settings.clear();
+                self.input_devices.pointer_settings.insert(None, settings);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use smithay::{
+    backend::allocator::{dmabuf::Dmabuf, Buffer, Format},
+    delegate_dmabuf,
+    wayland::dmabuf::{DmabufGlobal, DmabufHandler, DmabufState, ImportError},
+};
+
+use crate::state::State;
+
+impl State {
+    // Called by the backend once it has a renderer, with what that
+    // renderer can import. GL clients fall back to wl_shm until then.
+    pub fn create_dmabuf_global(&mut self, formats: Vec<Format>) {
+        if formats.is_empty() {
+            eprintln!("Renderer can't import any dmabuf format, only wl_shm is available");
+            return;
+        }
+        self.dmabuf_state
+            .create_global::<State>(&self.display_handle, formats.clone());
+        self.dmabuf_formats = formats;
+    }
+}
+
+impl DmabufHandler for State {
+    fn dmabuf_state(&mut self) -> &mut DmabufState {
+        &mut self.dmabuf_state
+    }
+
+    // The renderer imports the buffer when it first draws it. Until then
+    // all that can be checked is that it's in a format we advertised.
+    fn dmabuf_imported(
+        &mut self,
+        _global: &DmabufGlobal,
+        dmabuf: Dmabuf,
+    ) -> Result<(), ImportError> {
+        if self.dmabuf_formats.contains(&dmabuf.format()) {
+            Ok(())
+        } else {
+            Err(ImportError::InvalidFormat)
+        }
+    }
+}
+delegate_dmabuf!(State);
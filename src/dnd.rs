@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use smithay::{
+    backend::renderer::{
+        element::{
+            surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+            Kind,
+        },
+        gles::GlesRenderer,
+    },
+    desktop::utils::send_frames_surface_tree,
+    input::Seat,
+    output::Output,
+    reexports::wayland_server::protocol::{wl_data_source::WlDataSource, wl_surface::WlSurface},
+    utils::{IsAlive, Logical, Physical, Point, Scale},
+    wayland::{
+        compositor::{with_states, SurfaceAttributes, SurfaceData},
+        data_device::{ClientDndGrabHandler, ServerDndGrabHandler},
+    },
+};
+
+use crate::{render::CustomRenderElements, state::State};
+
+// What a client drags around, drawn under the cursor until the drop
+pub struct DndIcon {
+    surface: WlSurface,
+    // From the pointer, moved by the offsets the client attaches buffers at
+    offset: Point<i32, Logical>,
+}
+
+impl State {
+    pub fn dnd_icon_commit(&mut self, surface: &WlSurface) {
+        let Some(icon) = &mut self.dnd_icon else {
+            return;
+        };
+        if icon.surface != *surface {
+            return;
+        }
+
+        let delta: Option<Point<i32, Logical>> = with_states(surface, |states: &SurfaceData| {
+            states
+                .cached_state
+                .current::<SurfaceAttributes>()
+                .buffer_delta
+                .take()
+        });
+        if let Some(delta) = delta {
+            icon.offset += delta;
+        }
+    }
+
+    pub fn dnd_icon_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        if self
+            .dnd_icon
+            .as_ref()
+            .is_some_and(|icon: &DndIcon| !icon.surface.alive())
+        {
+            self.dnd_icon = None;
+        }
+        let (Some(icon), Some(location)) = (&self.dnd_icon, self.pointer_on(output)) else {
+            return;
+        };
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let position: Point<i32, Physical> =
+            (location + icon.offset.to_f64()).to_physical_precise_round(scale);
+        let surface_elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> =
+            render_elements_from_surface_tree(
+                renderer,
+                &icon.surface,
+                position,
+                Scale::from(scale),
+                1.0,
+                Kind::Unspecified,
+            );
+        elements.extend(surface_elements.into_iter().map(Into::into));
+    }
+
+    // Like the cursor, the icon is only on the output the pointer is on
+    pub fn send_dnd_icon_frames(&self, output: &Output, time: Duration) {
+        let Some(icon) = &self.dnd_icon else {
+            return;
+        };
+        if self.pointer_on(output).is_some() {
+            send_frames_surface_tree(
+                &icon.surface,
+                output,
+                time,
+                Some(Duration::ZERO),
+                |_: &WlSurface, _: &SurfaceData| Some(output.clone()),
+            );
+        }
+    }
+}
+
+// smithay's DnD grab does the rest: it sends enter, motion and leave to
+// whatever surface is under the pointer, and on drop hands the offer to
+// the target and tells the source whether it was accepted
+impl ClientDndGrabHandler for State {
+    fn started(
+        &mut self,
+        _source: Option<WlDataSource>,
+        icon: Option<WlSurface>,
+        _seat: Seat<Self>,
+    ) {
+        self.dnd_icon = icon.map(|surface: WlSurface| DndIcon {
+            surface,
+            offset: Point::default(),
+        });
+        self.request_redraw();
+    }
+
+    fn dropped(&mut self, _seat: Seat<Self>) {
+        self.dnd_icon = None;
+        self.request_redraw();
+    }
+}
+
+// Drags started by the compositor itself, there are none
+impl ServerDndGrabHandler for State {}
@@ -0,0 +1,196 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use smithay::{
+    backend::input::{
+        ButtonState, InputBackend, InputEvent, KeyState, KeyboardKeyEvent, PointerButtonEvent,
+        Switch, SwitchState, SwitchToggleEvent,
+    },
+    output::Output,
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        LoopHandle,
+    },
+};
+
+use crate::{data::Data, state::State};
+
+// With the timeout at zero, how often to look whether a config reload
+// set one
+const DISABLED_RECHECK: Duration = Duration::from_secs(60);
+
+// Input that may turn the displays back on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WakeTrigger {
+    Key,
+    Button,
+    Motion,
+    Scroll,
+    // Opening the lid
+    Lid,
+}
+
+pub struct Dpms {
+    off: bool,
+    last_input: Instant,
+    // Names of outputs turned off by hand, they stay off until turned on
+    // again whatever the inactivity timeout does
+    blanked: Vec<String>,
+    // After a resume the hardware may have lost the power state and gamma
+    // we left it in, the backend sets both again on its next wake()
+    reapply: bool,
+}
+
+impl Default for Dpms {
+    fn default() -> Self {
+        Self {
+            off: false,
+            last_input: Instant::now(),
+            blanked: Vec::new(),
+            reapply: false,
+        }
+    }
+}
+
+impl Dpms {
+    // Checks for inactivity in the background
+    pub fn start(handle: &LoopHandle<'static, Data>, timeout: Duration) -> Self {
+        let first: Duration = if timeout.is_zero() {
+            DISABLED_RECHECK
+        } else {
+            timeout
+        };
+        handle
+            .insert_source(Timer::from_duration(first), |_, _, data: &mut Data| {
+                data.state.dpms_timeout()
+            })
+            .unwrap();
+        Self::default()
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.off
+    }
+
+    // Whether `output` should be powered down right now
+    pub fn is_output_off(&self, output: &Output) -> bool {
+        self.off || self.blanked.contains(&output.name())
+    }
+
+    pub fn blanked(&self) -> &[String] {
+        &self.blanked
+    }
+}
+
+// Only presses count, releasing a key that was pressed before the displays
+// went off shouldn't turn them on again
+fn trigger<I: InputBackend>(event: &InputEvent<I>) -> Option<WakeTrigger> {
+    match event {
+        InputEvent::Keyboard { event } => {
+            (event.state() == KeyState::Pressed).then_some(WakeTrigger::Key)
+        }
+        InputEvent::PointerButton { event } => {
+            (event.state() == ButtonState::Pressed).then_some(WakeTrigger::Button)
+        }
+        InputEvent::PointerMotion { .. } | InputEvent::PointerMotionAbsolute { .. } => {
+            Some(WakeTrigger::Motion)
+        }
+        InputEvent::PointerAxis { .. } => Some(WakeTrigger::Scroll),
+        InputEvent::SwitchToggle { event } => (event.switch() == Some(Switch::Lid)
+            && event.state() == SwitchState::Off)
+            .then_some(WakeTrigger::Lid),
+        _ => None,
+    }
+}
+
+impl State {
+    // Returns whether `event` should be dropped. While the displays are
+    // off nothing reaches clients, not even the input that woke them, so
+    // typing blind can't end up in a window nobody can see.
+    pub fn dpms_input<I: InputBackend>(&mut self, event: &InputEvent<I>) -> bool {
+        let trigger: Option<WakeTrigger> = trigger(event);
+        if !self.dpms.off {
+            if trigger.is_some() {
+                self.dpms.last_input = Instant::now();
+            }
+            return false;
+        }
+
+        if let Some(trigger) = trigger {
+            if self.config.dpms.wake_on.contains(&trigger) {
+                self.set_displays_off(false);
+            }
+        }
+        // The lid still has to turn the internal panel on or off
+        !matches!(event, InputEvent::SwitchToggle { .. })
+    }
+
+    pub fn set_output_blanked(&mut self, name: &str, blanked: bool) -> Result<(), String> {
+        if !self
+            .outputs
+            .iter()
+            .any(|output: &Output| output.name() == name)
+        {
+            return Err(format!("no output named \"{name}\""));
+        }
+
+        self.dpms.blanked.retain(|other: &String| other != name);
+        if blanked {
+            self.dpms.blanked.push(name.to_string());
+        }
+        self.request_redraw();
+        Ok(())
+    }
+
+    // The output the pointer is on
+    pub fn toggle_blank_output(&mut self) {
+        let Some(output) = self
+            .space
+            .output_under(self.pointer_location)
+            .next()
+            .cloned()
+        else {
+            return;
+        };
+        let blanked: bool = self.dpms.blanked.contains(&output.name());
+        let _ = self.set_output_blanked(&output.name(), !blanked);
+    }
+
+    pub fn reapply_output_power(&mut self) {
+        self.dpms.reapply = true;
+        self.request_redraw();
+    }
+
+    pub fn take_output_reapply(&mut self) -> bool {
+        std::mem::take(&mut self.dpms.reapply)
+    }
+
+    // The backend picks the change up on its next wake()
+    pub fn set_displays_off(&mut self, off: bool) {
+        self.dpms.off = off;
+        self.dpms.last_input = Instant::now();
+        self.request_redraw();
+    }
+
+    fn dpms_timeout(&mut self) -> TimeoutAction {
+        let timeout: Duration = Duration::from_secs(self.config.dpms.timeout);
+        if timeout.is_zero() {
+            return TimeoutAction::ToDuration(DISABLED_RECHECK);
+        }
+        // Presenting or an app asking to keep the screen on counts as
+        // activity
+        if self.presentation_mode || self.is_idle_inhibited() {
+            self.dpms.last_input = Instant::now();
+        }
+        let idle: Duration = self.dpms.last_input.elapsed();
+        if idle < timeout {
+            return TimeoutAction::ToDuration(timeout - idle);
+        }
+
+        if !self.dpms.off {
+            self.set_displays_off(true);
+        }
+        TimeoutAction::ToDuration(timeout)
+    }
+}
@@ -0,0 +1,198 @@
+use smithay::output::{Output, PhysicalProperties, Subpixel};
+
+const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+const PNP_IDS: &str = "/usr/share/hwdata/pnp.ids";
+
+const DESCRIPTOR_SERIAL: u8 = 0xff;
+const DESCRIPTOR_NAME: u8 = 0xfc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EdidInfo {
+    pub make: String,
+    pub model: String,
+    pub serial: Option<String>,
+    // In millimeters, (0, 0) for projectors and other displays without a fixed size
+    pub physical_size: (i32, i32),
+}
+
+impl EdidInfo {
+    pub fn parse(edid: &[u8]) -> Option<Self> {
+        if edid.len() < 128 || edid[..8] != HEADER {
+            return None;
+        }
+
+        // Three 5 bit letters, 'A' is 1
+        let vendor: u16 = u16::from_be_bytes([edid[8], edid[9]]);
+        let pnp_id: String = [10, 5, 0]
+            .iter()
+            .map(|shift| (b'A' - 1 + ((vendor >> shift) & 0x1f) as u8) as char)
+            .collect();
+        let product: u16 = u16::from_le_bytes([edid[10], edid[11]]);
+        let serial_number: u32 = u32::from_le_bytes([edid[12], edid[13], edid[14], edid[15]]);
+
+        let mut name: Option<String> = None;
+        let mut serial: Option<String> = None;
+        let mut physical_size: (i32, i32) = (edid[21] as i32 * 10, edid[22] as i32 * 10);
+
+        for (index, descriptor) in edid[54..126].chunks_exact(18).enumerate() {
+            if descriptor[0] != 0 || descriptor[1] != 0 {
+                // The preferred timing has the size with millimeter precision
+                if index == 0 {
+                    let width: i32 = descriptor[12] as i32 | (descriptor[14] as i32 >> 4) << 8;
+                    let height: i32 = descriptor[13] as i32 | (descriptor[14] as i32 & 0xf) << 8;
+                    if width > 0 && height > 0 {
+                        physical_size = (width, height);
+                    }
+                }
+                continue;
+            }
+
+            match descriptor[3] {
+                DESCRIPTOR_NAME => name = descriptor_text(descriptor),
+                DESCRIPTOR_SERIAL => serial = descriptor_text(descriptor),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            make: vendor_name(&pnp_id).unwrap_or(pnp_id),
+            model: name.unwrap_or_else(|| format!("0x{product:04X}")),
+            serial: serial.or_else(|| (serial_number != 0).then(|| serial_number.to_string())),
+            physical_size,
+        })
+    }
+
+    // Identifies the monitor independently of the connector it is plugged into
+    pub fn description(&self) -> String {
+        match &self.serial {
+            Some(serial) => format!("{} {} {serial}", self.make, self.model),
+            None => format!("{} {}", self.make, self.model),
+        }
+    }
+
+    pub fn physical_properties(&self) -> PhysicalProperties {
+        PhysicalProperties {
+            size: self.physical_size.into(),
+            subpixel: Subpixel::Unknown,
+            make: self.make.clone(),
+            model: self.model.clone(),
+        }
+    }
+}
+
+fn descriptor_text(descriptor: &[u8]) -> Option<String> {
+    let text: String = descriptor[5..]
+        .iter()
+        .take_while(|byte| **byte != b'\n')
+        .map(|byte| *byte as char)
+        .collect::<String>()
+        .trim()
+        .to_string();
+
+    (!text.is_empty()).then_some(text)
+}
+
+// hwdata ships the full PNP ID registry, "DEL" -> "Dell Inc."
+fn vendor_name(pnp_id: &str) -> Option<String> {
+    let ids: String = std::fs::read_to_string(PNP_IDS).ok()?;
+
+    ids.lines().find_map(|line| {
+        let (id, name) = line.split_once('\t')?;
+        (id == pnp_id).then(|| name.trim().to_string())
+    })
+}
+
+// Outputs created from a real EDID keep it in their user data, everything
+// else falls back to what the backend put into PhysicalProperties
+pub fn output_description(output: &Output) -> String {
+    match output.user_data().get::<EdidInfo>() {
+        Some(edid) => edid.description(),
+        None => {
+            let properties: PhysicalProperties = output.physical_properties();
+            format!("{} {}", properties.make, properties.model)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Dell U2715H, with a serial descriptor and a 597x336 mm preferred timing
+    const U2715H: [u8; 128] = [
+        0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x10, 0xac, 0x6e, 0xd0, 0x53, 0x31, 0x4a,
+        0x4c, 0x05, 0x19, 0x01, 0x04, 0xb5, 0x3c, 0x22, 0x78, 0x3a, 0xee, 0x95, 0xa3, 0x54, 0x4c,
+        0x99, 0x26, 0x0f, 0x50, 0x54, 0xa5, 0x4b, 0x00, 0x71, 0x4f, 0x81, 0x80, 0xa9, 0x40, 0xd1,
+        0xc0, 0xd1, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x56, 0x5e, 0x00, 0xa0, 0xa0, 0xa0,
+        0x29, 0x50, 0x30, 0x20, 0x35, 0x00, 0x55, 0x50, 0x21, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00,
+        0xff, 0x00, 0x47, 0x48, 0x38, 0x35, 0x44, 0x35, 0x36, 0x44, 0x30, 0x33, 0x4d, 0x4c, 0x0a,
+        0x00, 0x00, 0x00, 0xfc, 0x00, 0x44, 0x45, 0x4c, 0x4c, 0x20, 0x55, 0x32, 0x37, 0x31, 0x35,
+        0x48, 0x0a, 0x20, 0x00, 0x00, 0x00, 0xfd, 0x00, 0x31, 0x56, 0x1d, 0x71, 0x1c, 0x00, 0x0a,
+        0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x9f,
+    ];
+
+    fn make() -> String {
+        vendor_name("DEL").unwrap_or_else(|| "DEL".to_string())
+    }
+
+    #[test]
+    fn parses_monitor() {
+        let info: EdidInfo = EdidInfo::parse(&U2715H).unwrap();
+
+        assert_eq!(info.make, make());
+        assert_eq!(info.model, "DELL U2715H");
+        assert_eq!(info.serial.as_deref(), Some("GH85D56D03ML"));
+        assert_eq!(info.physical_size, (597, 336));
+        assert_eq!(
+            info.description(),
+            format!("{} DELL U2715H GH85D56D03ML", make())
+        );
+    }
+
+    #[test]
+    fn serial_falls_back_to_serial_number() {
+        let mut edid: [u8; 128] = U2715H;
+        // Turn the serial descriptor into a dummy one
+        edid[75] = 0x10;
+
+        let info: EdidInfo = EdidInfo::parse(&edid).unwrap();
+        assert_eq!(info.serial, Some(0x4c4a3153.to_string()));
+    }
+
+    #[test]
+    fn missing_serial() {
+        let mut edid: [u8; 128] = U2715H;
+        edid[75] = 0x10;
+        edid[12..16].fill(0);
+
+        let info: EdidInfo = EdidInfo::parse(&edid).unwrap();
+        assert_eq!(info.serial, None);
+        assert_eq!(info.description(), format!("{} DELL U2715H", make()));
+    }
+
+    #[test]
+    fn zero_size() {
+        let mut edid: [u8; 128] = U2715H;
+        edid[21..23].fill(0);
+        edid[66..69].fill(0);
+
+        assert_eq!(EdidInfo::parse(&edid).unwrap().physical_size, (0, 0));
+    }
+
+    #[test]
+    fn size_falls_back_to_centimeters() {
+        let mut edid: [u8; 128] = U2715H;
+        edid[66..69].fill(0);
+
+        assert_eq!(EdidInfo::parse(&edid).unwrap().physical_size, (600, 340));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert_eq!(EdidInfo::parse(&U2715H[..127]), None);
+
+        let mut edid: [u8; 128] = U2715H;
+        edid[0] = 0xff;
+        assert_eq!(EdidInfo::parse(&edid), None);
+    }
+}
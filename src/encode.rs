@@ -0,0 +1,126 @@
+use std::{path::PathBuf, sync::mpsc};
+
+use smithay::reexports::calloop::{
+    channel::{self, Channel, Event},
+    LoopHandle,
+};
+
+use crate::{data::Data, state::State};
+
+// A captured frame read back from the GPU, RGBA with the first row at the
+// top
+pub struct Frame {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Where the capture was written, or why it couldn't be
+type Encoded = Result<PathBuf, String>;
+
+struct Job {
+    frame: Frame,
+    // The extension picks the format, PNG or JPEG
+    path: PathBuf,
+}
+
+// Compressing a 4K frame takes long enough to drop frames, so captures are
+// encoded on their own thread and reported back through the event loop
+pub struct Encoder {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl Encoder {
+    pub fn new(handle: &LoopHandle<'_, Data>) -> Self {
+        let (sender, channel): (channel::Sender<Encoded>, Channel<Encoded>) = channel::channel();
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+
+        handle
+            .insert_source(channel, |event: Event<Encoded>, _, data: &mut Data| {
+                if let Event::Msg(result) = event {
+                    data.state.on_encoded(result);
+                }
+            })
+            .unwrap();
+
+        std::thread::Builder::new()
+            .name("pulseWM-encode".into())
+            .spawn(move || {
+                for job in job_receiver {
+                    if sender.send(encode(job)).is_err() {
+                        break;
+                    }
+                }
+            })
+            .unwrap();
+
+        Self { jobs }
+    }
+
+    pub fn encode(&self, frame: Frame, path: PathBuf) {
+        if self.jobs.send(Job { frame, path }).is_err() {
+            eprintln!("Failed to encode capture: encoder thread is gone");
+        }
+    }
+}
+
+fn encode(job: Job) -> Encoded {
+    let Frame {
+        pixels,
+        width,
+        height,
+    } = job.frame;
+
+    let jpeg: bool = job.path.extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("jpg") || extension.eq_ignore_ascii_case("jpeg")
+    });
+    let result: image::ImageResult<()> = if jpeg {
+        // JPEG has no alpha channel, the encoder refuses RGBA
+        let rgb: Vec<u8> = pixels
+            .chunks_exact(4)
+            .flat_map(|pixel: &[u8]| [pixel[0], pixel[1], pixel[2]])
+            .collect();
+        image::save_buffer(&job.path, &rgb, width, height, image::ColorType::Rgb8)
+    } else {
+        image::save_buffer(&job.path, &pixels, width, height, image::ColorType::Rgba8)
+    };
+
+    result.map_err(|err| format!("{}: {err}", job.path.display()))?;
+    Ok(job.path)
+}
+
+impl State {
+    fn on_encoded(&mut self, result: Encoded) {
+        match result {
+            Ok(path) => eprintln!("Saved capture to {}", path.display()),
+            Err(err) => eprintln!("Failed to encode capture: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(name: &str) -> Job {
+        Job {
+            frame: Frame {
+                pixels: [0xff, 0x80, 0x00, 0xff].repeat(4 * 3),
+                width: 4,
+                height: 3,
+            },
+            path: std::env::temp_dir()
+                .join(format!("pulsewm-encode-{}-{name}", std::process::id())),
+        }
+    }
+
+    #[test]
+    fn encodes_every_format() {
+        for name in ["frame.png", "frame.jpg", "frame.JPEG"] {
+            let path: PathBuf = encode(job(name)).unwrap();
+            let decoded = image::open(&path).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (4, 3), "{name}");
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::time::{Duration, Instant};
+
+use smithay::{
+    desktop::Window,
+    input::keyboard::KeyboardHandle,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{IsAlive, SERIAL_COUNTER},
+};
+
+use crate::state::State;
+
+// A window focused by hovering it, raised once the pointer stayed long
+// enough
+pub struct PendingRaise {
+    window: Window,
+    at: Instant,
+}
+
+// Windows in the order they had keyboard focus, most recent last, so
+// closing one can hand focus back to whatever had it before
+#[derive(Default)]
+pub struct FocusStack {
+    windows: Vec<Window>,
+}
+
+impl FocusStack {
+    pub fn push(&mut self, window: &Window) {
+        self.remove(window);
+        self.windows.push(window.clone());
+    }
+
+    pub fn remove(&mut self, window: &Window) {
+        self.windows.retain(|other: &Window| other != window);
+    }
+}
+
+impl State {
+    pub fn focused_window(&self) -> Option<Window> {
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        let focus: WlSurface = keyboard.current_focus()?;
+        self.windows.get(&focus).cloned()
+    }
+
+    // Raises and focuses, for clicks, keybindings and newly mapped windows
+    pub fn focus_window(&mut self, window: &Window) {
+        self.pending_raise = None;
+        self.space.raise_element(window, true);
+        self.set_keyboard_focus(window);
+    }
+
+    pub fn set_keyboard_focus(&mut self, window: &Window) {
+        // Windows can't take the keyboard away from the locker
+        if self.is_locked() {
+            return;
+        }
+        self.clear_urgent(window);
+
+        for other in self.space.elements() {
+            other.set_activated(other == window);
+            other.toplevel().send_pending_configure();
+        }
+
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        keyboard.set_focus(
+            self,
+            Some(window.toplevel().wl_surface().clone()),
+            SERIAL_COUNTER.next_serial(),
+        );
+    }
+
+    // The most recently focused window still on screen, for when the
+    // focused one went away
+    pub fn focus_previous(&mut self) {
+        let previous: Option<Window> = self
+            .focus_stack
+            .windows
+            .iter()
+            .rev()
+            .find(|window: &&Window| {
+                window.alive() && self.space.element_location(window).is_some()
+            })
+            .cloned();
+
+        match previous {
+            Some(window) => self.focus_window(&window),
+            None => self.focus_topmost(),
+        }
+    }
+
+    pub fn focus_topmost(&mut self) {
+        match self.space.elements().last().cloned() {
+            Some(window) => self.focus_window(&window),
+            None => {
+                let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+                keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+            }
+        }
+    }
+
+    // Focus follows mouse. Raising waits for focus.raise_delay_ms so
+    // skimming across windows doesn't reshuffle the stack.
+    pub fn hover_focus(&mut self, window: &Window) {
+        if self.focused_window().as_ref() == Some(window) {
+            return;
+        }
+        self.set_keyboard_focus(window);
+
+        self.pending_raise = None;
+        if !self.config.focus.hover_raise {
+            return;
+        }
+
+        let delay: Duration = Duration::from_millis(self.config.focus.raise_delay_ms);
+        if delay.is_zero() {
+            self.space.raise_element(window, true);
+        } else {
+            self.pending_raise = Some(PendingRaise {
+                window: window.clone(),
+                at: Instant::now() + delay,
+            });
+        }
+    }
+
+    // Called once per frame
+    pub fn process_pending_raise(&mut self) {
+        let due: bool = self
+            .pending_raise
+            .as_ref()
+            .map_or(false, |raise: &PendingRaise| Instant::now() >= raise.at);
+        if !due {
+            return;
+        }
+
+        let Some(raise) = self.pending_raise.take() else {
+            return;
+        };
+        // Only if it still has focus, the pointer may have moved on
+        if raise.window.alive() && self.focused_window().as_ref() == Some(&raise.window) {
+            self.space.raise_element(&raise.window, true);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use smithay::{
+    delegate_fractional_scale,
+    desktop::{utils::surface_primary_scanout_output, Window},
+    output::{Output, Scale},
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::{
+        compositor::{with_states, with_surface_tree_downward, SurfaceData, TraversalAction},
+        fractional_scale::{with_fractional_scale, FractionalScaleHandler},
+    },
+};
+
+use crate::{state::State, subsurfaces};
+
+// The scale an [[output]] entry asks for. Whole numbers stay integer
+// scales, anything else is rounded to the 1/120 steps fractional-scale
+// clients can be told about. wl_output advertises the next integer up, so
+// clients without fractional-scale render sharp and get scaled down.
+pub fn output_scale(scale: f64) -> Option<Scale> {
+    if !(0.25..=8.0).contains(&scale) {
+        return None;
+    }
+    if scale.fract() == 0.0 {
+        return Some(Scale::Integer(scale as i32));
+    }
+    Some(Scale::Fractional((scale * 120.0).round() / 120.0))
+}
+
+// Only sent when it changed, so this is cheap enough to call every frame
+pub fn send_preferred_scale(output: &Output, data: &SurfaceData) {
+    with_fractional_scale(data, |fractional_scale| {
+        fractional_scale.set_preferred_scale(output.current_scale().fractional_scale());
+    });
+}
+
+// For surfaces without a primary scanout output, like lock surfaces that
+// only ever show on one
+pub fn send_preferred_scale_tree(surface: &WlSurface, output: &Output) {
+    with_surface_tree_downward(
+        surface,
+        (),
+        |_, _, _| TraversalAction::DoChildren(()),
+        |_, data: &SurfaceData, _| send_preferred_scale(output, data),
+        |_, _, _| true,
+    );
+}
+
+impl FractionalScaleHandler for State {
+    // Told right away, so the first buffer already has the right size.
+    // Afterwards send_frames keeps it up to date with whichever output
+    // shows the surface most.
+    fn new_fractional_scale(&mut self, surface: WlSurface) {
+        let root: WlSurface = subsurfaces::root_surface(&surface);
+        let output: Option<Output> = with_states(&root, |states: &SurfaceData| {
+            surface_primary_scanout_output(&root, states)
+        })
+        .or_else(|| {
+            self.windows
+                .get(&root)
+                .and_then(|window: &Window| self.window_output(window))
+        })
+        .or_else(|| self.active_output());
+
+        if let Some(output) = output {
+            with_states(&surface, |states: &SurfaceData| {
+                send_preferred_scale(&output, states)
+            });
+        }
+    }
+}
+delegate_fractional_scale!(State);
@@ -0,0 +1,108 @@
+use smithay::{
+    desktop::Window,
+    output::Output,
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel,
+        wayland_server::protocol::wl_output::WlOutput,
+    },
+    utils::{Logical, Rectangle},
+};
+
+use crate::state::State;
+
+pub struct FullscreenWindow {
+    window: Window,
+    output: Output,
+    // Where the window goes back to, in global coordinates
+    restore: Rectangle<i32, Logical>,
+    tiled: bool,
+}
+
+impl State {
+    pub fn is_fullscreen(&self, window: &Window) -> bool {
+        self.fullscreen
+            .iter()
+            .any(|fullscreen: &FullscreenWindow| fullscreen.window == *window)
+    }
+
+    // The window covering `output`, if any
+    pub fn fullscreen_on(&self, output: &Output) -> Option<&Window> {
+        self.fullscreen
+            .iter()
+            .rev()
+            .find(|fullscreen: &&FullscreenWindow| {
+                fullscreen.output == *output
+                    && self.space.element_location(&fullscreen.window).is_some()
+            })
+            .map(|fullscreen: &FullscreenWindow| &fullscreen.window)
+    }
+
+    // Covers the requested output, or the one the window is on. Leaves the
+    // tiling, the others close the gap.
+    pub fn fullscreen_window(&mut self, window: &Window, output: Option<WlOutput>) {
+        if self.is_fullscreen(window) {
+            window.toplevel().send_pending_configure();
+            return;
+        }
+
+        let output: Option<Output> = output
+            .as_ref()
+            .and_then(Output::from_resource)
+            .or_else(|| self.space.outputs_for_element(window).into_iter().next())
+            .or_else(|| self.space.outputs().next().cloned());
+        let Some(output) = output else {
+            window.toplevel().send_pending_configure();
+            return;
+        };
+        let Some(output_geometry) = self.space.output_geometry(&output) else {
+            window.toplevel().send_pending_configure();
+            return;
+        };
+
+        let mut restore: Rectangle<i32, Logical> = window.geometry();
+        restore.loc += self.space.element_location(window).unwrap_or_default();
+        let tiled: bool = self.tiling.is_tiled(window);
+
+        self.fullscreen.push(FullscreenWindow {
+            window: window.clone(),
+            output,
+            restore,
+            tiled,
+        });
+        if tiled {
+            self.untile_window(window);
+        }
+
+        window.toplevel().with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Fullscreen);
+        });
+        // Also raises it above everything else on the output
+        self.set_window_geometry(window, output_geometry);
+    }
+
+    pub fn unfullscreen_window(&mut self, window: &Window) {
+        let Some(index) = self
+            .fullscreen
+            .iter()
+            .position(|fullscreen: &FullscreenWindow| fullscreen.window == *window)
+        else {
+            window.toplevel().send_pending_configure();
+            return;
+        };
+        let fullscreen: FullscreenWindow = self.fullscreen.remove(index);
+
+        window.toplevel().with_pending_state(|state| {
+            state.states.unset(xdg_toplevel::State::Fullscreen);
+        });
+        self.set_window_geometry(window, fullscreen.restore);
+        if fullscreen.tiled {
+            self.tile_window(window);
+        }
+    }
+
+    // Destroyed windows don't come back
+    pub fn forget_fullscreen(&mut self, window: &Window) {
+        self.fullscreen
+            .retain(|fullscreen: &FullscreenWindow| fullscreen.window != *window);
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use smithay::reexports::{
+    calloop::{
+        timer::{TimeoutAction, Timer},
+        LoopHandle,
+    },
+    wayland_server::backend::GlobalId,
+};
+
+use crate::{data::Data, state::State};
+
+// Clients that saw the global right before it got disabled may still try
+// to bind it, destroying it right away would kill them with an error
+const REMOVE_AFTER: Duration = Duration::from_secs(5);
+
+// Globals that can be turned off at runtime, core ones like wl_compositor
+// are always there
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptionalGlobal {
+    Screencopy,
+    DataControl,
+}
+
+// Creates the protocol state, stores it in `State` and returns its global
+pub type CreateGlobal = fn(&mut State) -> GlobalId;
+
+struct Entry {
+    global: OptionalGlobal,
+    create: CreateGlobal,
+    id: Option<GlobalId>,
+}
+
+#[derive(Serialize)]
+pub struct GlobalStatus {
+    pub name: OptionalGlobal,
+    pub enabled: bool,
+}
+
+pub struct OptionalGlobals {
+    entries: Vec<Entry>,
+    handle: LoopHandle<'static, Data>,
+}
+
+impl OptionalGlobals {
+    pub fn new(handle: LoopHandle<'static, Data>) -> Self {
+        Self {
+            entries: Vec::new(),
+            handle,
+        }
+    }
+}
+
+impl State {
+    // Registers a global that can be toggled, creating it unless the
+    // config disables it
+    pub fn register_optional_global(&mut self, global: OptionalGlobal, create: CreateGlobal) {
+        let enabled: bool = !self.config.globals.disabled.contains(&global);
+        let id: Option<GlobalId> = enabled.then(|| create(self));
+
+        self.globals.entries.push(Entry { global, create, id });
+    }
+
+    pub fn set_global_enabled(
+        &mut self,
+        global: OptionalGlobal,
+        enabled: bool,
+    ) -> Result<(), String> {
+        let index: usize = self
+            .globals
+            .entries
+            .iter()
+            .position(|entry: &Entry| entry.global == global)
+            .ok_or_else(|| format!("{global:?} is not supported"))?;
+
+        let entry: &mut Entry = &mut self.globals.entries[index];
+        match (enabled, entry.id.take()) {
+            (true, None) => {
+                let create: CreateGlobal = entry.create;
+                let id: GlobalId = create(self);
+                self.globals.entries[index].id = Some(id);
+            }
+            (false, Some(id)) => {
+                self.display_handle.disable_global::<State>(id.clone());
+                self.schedule_global_removal(id);
+            }
+            // Already in the requested state
+            (_, id) => entry.id = id,
+        }
+
+        Ok(())
+    }
+
+    // Destroys a disabled global once REMOVE_AFTER has passed, whether or
+    // not anything is drawn in the meantime
+    fn schedule_global_removal(&mut self, id: GlobalId) {
+        let timer: Timer = Timer::from_duration(REMOVE_AFTER);
+        if let Err(err) = self
+            .globals
+            .handle
+            .insert_source(timer, move |_, _, data: &mut Data| {
+                data.state.display_handle.remove_global::<State>(id.clone());
+                TimeoutAction::Drop
+            })
+        {
+            eprintln!("Failed to schedule the removal of a global: {err}");
+        }
+    }
+
+    pub fn global_status(&self) -> Vec<GlobalStatus> {
+        self.globals
+            .entries
+            .iter()
+            .map(|entry: &Entry| GlobalStatus {
+                name: entry.global,
+                enabled: entry.id.is_some(),
+            })
+            .collect()
+    }
+}
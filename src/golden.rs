@@ -0,0 +1,261 @@
+// Headless render tests. Known scenes are laid out with the same tiling,
+// border and stacking code the compositor uses, turned into the render
+// elements the backends draw, rendered offscreen by a GlesRenderer on an
+// EGL device and compared against the images in tests/golden.
+// `UPDATE_GOLDEN=1 cargo test` writes them anew after an intended change.
+// Machines without an EGL device, not even Mesa's software one, skip them.
+
+use std::{env, fs, path::PathBuf};
+
+use smithay::{
+    backend::{
+        egl::{EGLContext, EGLDevice, EGLDisplay},
+        renderer::{
+            element::{
+                solid::{SolidColorBuffer, SolidColorRenderElement},
+                Kind,
+            },
+            gles::GlesRenderer,
+        },
+    },
+    utils::{Logical, Rectangle},
+};
+
+use crate::{
+    borders,
+    layout::{self, LayoutParams, LayoutWindow, Rect},
+    render::CustomRenderElements,
+    screenshot,
+    stacking::{self, KeyboardInteractivity, StackEntry, StackLayer},
+};
+
+type Color = [u8; 3];
+
+const WIDTH: i32 = 64;
+const HEIGHT: i32 = 40;
+
+const BACKGROUND: Color = [0x20, 0x20, 0x20];
+const BORDER: Color = [0x55, 0x55, 0x55];
+const FOCUSED_BORDER: Color = [0x52, 0x94, 0xe2];
+const WINDOWS: [Color; 3] = [[0xd0, 0x50, 0x50], [0x50, 0xd0, 0x50], [0xd0, 0xd0, 0x50]];
+
+fn rgba(color: Color) -> [f32; 4] {
+    let [r, g, b] = color.map(|channel: u8| channel as f32 / 255.0);
+    [r, g, b, 1.0]
+}
+
+fn rectangle(rect: Rect) -> Rectangle<i32, Logical> {
+    Rectangle::from_loc_and_size((rect.x, rect.y), (rect.w, rect.h))
+}
+
+// The first EGL device that takes a context, a GPU or llvmpipe
+fn renderer() -> Option<GlesRenderer> {
+    let devices = match EGLDevice::enumerate() {
+        Ok(devices) => devices,
+        Err(err) => {
+            eprintln!("Skipping golden image test, no EGL devices: {err}");
+            return None;
+        }
+    };
+
+    for device in devices {
+        let Ok(display) = (unsafe { EGLDisplay::new(device) }) else {
+            continue;
+        };
+        let Ok(context) = EGLContext::new(&display) else {
+            continue;
+        };
+        if let Ok(renderer) = unsafe { GlesRenderer::new(context) } {
+            return Some(renderer);
+        }
+    }
+    eprintln!("Skipping golden image test, no EGL device can render");
+    None
+}
+
+// Stands in for a client surface, the tests have no clients
+fn solid(rect: Rect, color: Color) -> CustomRenderElements {
+    let buffer: SolidColorBuffer = SolidColorBuffer::new((rect.w, rect.h), rgba(color));
+    SolidColorRenderElement::from_buffer(&buffer, (rect.x, rect.y), 1.0, 1.0, Kind::Unspecified)
+        .into()
+}
+
+// A window drawn the way render.rs draws it, its border below it
+fn window(rect: Rect, color: Color, border: Option<(i32, Color)>) -> Vec<CustomRenderElements> {
+    let mut elements: Vec<CustomRenderElements> = vec![solid(rect, color)];
+    if let Some((width, border)) = border {
+        let mut edges: [SolidColorBuffer; 4] = Default::default();
+        borders::edge_elements(
+            &mut edges,
+            rectangle(rect),
+            width,
+            rgba(border),
+            1.0,
+            &mut elements,
+        );
+    }
+    elements
+}
+
+// Binary PPM, readable by about every image viewer
+fn to_ppm(rgba: &[u8]) -> Vec<u8> {
+    let mut ppm: Vec<u8> = format!("P6\n{WIDTH} {HEIGHT}\n255\n").into_bytes();
+    for pixel in rgba.chunks_exact(4) {
+        ppm.extend_from_slice(&pixel[..3]);
+    }
+    ppm
+}
+
+// `elements` topmost first, like the backends hand them to the damage
+// tracker
+fn assert_golden(name: &str, elements: &[CustomRenderElements]) {
+    let Some(mut renderer) = renderer() else {
+        return;
+    };
+    let pixels: Vec<u8> = screenshot::render_pixels(
+        &mut renderer,
+        (WIDTH, HEIGHT).into(),
+        1.0,
+        elements,
+        rgba(BACKGROUND),
+    )
+    .unwrap_or_else(|err: String| panic!("{name}: failed to render: {err}"));
+
+    let path: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.ppm"));
+    let actual: Vec<u8> = to_ppm(&pixels);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected: Vec<u8> = fs::read(&path).unwrap_or_else(|err: std::io::Error| {
+        panic!("{}: {err}, UPDATE_GOLDEN=1 writes it", path.display())
+    });
+    if expected != actual {
+        let differing: usize = expected
+            .chunks(3)
+            .zip(actual.chunks(3))
+            .filter(|(expected, actual)| expected != actual)
+            .count();
+        let rendered: PathBuf = env::temp_dir().join(format!("{name}.ppm"));
+        fs::write(&rendered, &actual).unwrap();
+        panic!(
+            "{name}: {differing} pixels differ from {}, the frame drawn is in {}",
+            path.display(),
+            rendered.display()
+        );
+    }
+}
+
+fn tiles(count: usize, params: &LayoutParams) -> Vec<Rect> {
+    layout::master_stack(
+        &vec![LayoutWindow::default(); count],
+        Rect::new(0, 0, WIDTH, HEIGHT),
+        params,
+    )
+}
+
+// Sorted like the compositor sorts its stack, then handed out topmost first
+fn draw(mut stack: Vec<StackEntry<(Rect, Color)>>) -> Vec<CustomRenderElements> {
+    stacking::sort(&mut stack);
+    stack
+        .iter()
+        .rev()
+        .map(|entry: &StackEntry<(Rect, Color)>| solid(entry.target.0, entry.target.1))
+        .collect()
+}
+
+fn entry(rect: Rect, color: Color, layer: StackLayer) -> StackEntry<(Rect, Color)> {
+    StackEntry::new((rect, color), layer, KeyboardInteractivity::None)
+}
+
+#[test]
+fn gaps() {
+    let params: LayoutParams = LayoutParams {
+        master_ratio: 0.5,
+        master_count: 1,
+        inner_gap: 4,
+        outer_gap: 2,
+    };
+    let elements: Vec<CustomRenderElements> = tiles(3, &params)
+        .into_iter()
+        .zip(WINDOWS)
+        .flat_map(|(rect, color): (Rect, Color)| window(rect, color, None))
+        .collect();
+    assert_golden("gaps", &elements);
+}
+
+#[test]
+fn borders() {
+    let width: i32 = 2;
+    // The layout leaves room for the border on every side
+    let params: LayoutParams = LayoutParams {
+        master_ratio: 0.6,
+        master_count: 1,
+        inner_gap: 4 + 2 * width,
+        outer_gap: 3 + width,
+    };
+    let mut elements: Vec<CustomRenderElements> = Vec::new();
+    for (index, (rect, color)) in tiles(3, &params).into_iter().zip(WINDOWS).enumerate() {
+        // The first window has the keyboard
+        let border: Color = if index == 0 { FOCUSED_BORDER } else { BORDER };
+        elements.extend(window(rect, color, Some((width, border))));
+    }
+    assert_golden("borders", &elements);
+}
+
+#[test]
+fn stacking() {
+    let bar: Rect = Rect::new(0, 0, WIDTH, 6);
+    // Inserted out of order, the sort has to put each where it belongs:
+    // the bar and the notification over the windows, the windows in the
+    // order they were raised and the cursor above everything
+    let stack: Vec<StackEntry<(Rect, Color)>> = vec![
+        entry(
+            Rect::new(40, 20, 4, 6),
+            [0xff, 0xff, 0xff],
+            StackLayer::Cursor,
+        ),
+        entry(
+            Rect::new(44, 2, 18, 10),
+            [0x80, 0x40, 0xc0],
+            StackLayer::Overlay,
+        ),
+        entry(bar, [0x10, 0x10, 0x60], StackLayer::Top),
+        entry(Rect::new(4, 2, 30, 24), WINDOWS[0], StackLayer::Windows),
+        entry(Rect::new(20, 14, 30, 22), WINDOWS[1], StackLayer::Windows),
+        entry(
+            Rect::new(0, 0, WIDTH, HEIGHT),
+            [0x30, 0x50, 0x60],
+            StackLayer::Background,
+        ),
+    ];
+    assert_golden("stacking", &draw(stack));
+}
+
+#[test]
+fn fullscreen_covers_bars_but_not_notifications() {
+    let stack: Vec<StackEntry<(Rect, Color)>> = vec![
+        entry(
+            Rect::new(44, 2, 18, 10),
+            [0x80, 0x40, 0xc0],
+            StackLayer::Overlay,
+        ),
+        entry(
+            Rect::new(0, 0, WIDTH, HEIGHT),
+            WINDOWS[2],
+            StackLayer::Fullscreen,
+        ),
+        entry(
+            Rect::new(0, 0, WIDTH, 6),
+            [0x10, 0x10, 0x60],
+            StackLayer::Top,
+        ),
+        entry(Rect::new(4, 2, 30, 24), WINDOWS[0], StackLayer::Windows),
+    ];
+    assert_golden("fullscreen", &draw(stack));
+}
@@ -0,0 +1,400 @@
+use smithay::{
+    desktop::Window,
+    input::pointer::{
+        AxisFrame, ButtonEvent, Focus, GrabStartData, MotionEvent, PointerGrab, PointerHandle,
+        PointerInnerHandle, RelativeMotionEvent,
+    },
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel,
+        wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    },
+    utils::{Logical, Point, Rectangle, Serial, Size},
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        shell::xdg::{SurfaceCachedState, ToplevelSurface},
+    },
+};
+
+use crate::{configure, state::State};
+
+// xdg_toplevel.resize_edge is a bitfield in all but name
+const TOP: u32 = 1;
+const BOTTOM: u32 = 2;
+const LEFT: u32 = 4;
+const RIGHT: u32 = 8;
+
+// Drags a window until the button that started it is released. Floating
+// windows follow the pointer and can be snapped, tiled ones stay put and
+// are dropped onto another tile.
+pub struct MoveGrab {
+    start_data: GrabStartData<State>,
+    window: Window,
+    initial_location: Point<i32, Logical>,
+    tiled: bool,
+}
+
+impl PointerGrab<State> for MoveGrab {
+    fn motion(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        _focus: Option<(WlSurface, Point<i32, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // No client gets pointer events while dragging
+        handle.motion(data, None, event);
+
+        if self.tiled {
+            let tiles: Vec<(Window, Rectangle<i32, Logical>)> = data.visible_tile_geometries();
+            data.tile_drag_motion(&tiles, event.location);
+            return;
+        }
+
+        let delta: Point<f64, Logical> = event.location - self.start_data.location;
+        let location: Point<i32, Logical> = (self.initial_location.to_f64() + delta).to_i32_round();
+        data.space.map_element(self.window.clone(), location, true);
+        data.update_snap_preview(&self.window, event.location);
+    }
+
+    fn relative_motion(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        focus: Option<(WlSurface, Point<i32, Logical>)>,
+        event: &RelativeMotionEvent,
+    ) {
+        handle.relative_motion(data, focus, event);
+    }
+
+    fn button(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+        if handle.current_pressed().contains(&self.start_data.button) {
+            return;
+        }
+
+        handle.unset_grab(data, event.serial, event.time);
+        if self.tiled {
+            if let Some(drop) = data.finish_tile_drag() {
+                data.apply_tile_drop(drop);
+            }
+        } else {
+            data.finish_snap_drag();
+        }
+    }
+
+    fn axis(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        details: AxisFrame,
+    ) {
+        handle.axis(data, details);
+    }
+
+    fn start_data(&self) -> &GrabStartData<State> {
+        &self.start_data
+    }
+}
+
+// Resizes a floating window from the edges in `edges`, the opposite ones
+// stay where they are
+pub struct ResizeGrab {
+    start_data: GrabStartData<State>,
+    window: Window,
+    edges: u32,
+    initial_size: Size<i32, Logical>,
+}
+
+// Lives a little longer than the grab, until the client committed the size
+// it was sent last
+pub struct ResizeState {
+    window: Window,
+    edges: u32,
+    // Window geometry in global coordinates when the resize started
+    initial: Rectangle<i32, Logical>,
+    released: bool,
+    // The size the pointer asks for, sent once per frame at most and only
+    // after the client caught up with the previous one
+    pending: Option<Size<i32, Logical>>,
+    configure: Option<Serial>,
+}
+
+// The client's min and max size, unlimited where it doesn't set one
+fn size_limits(window: &Window) -> (Size<i32, Logical>, Size<i32, Logical>) {
+    let (min, max): (Size<i32, Logical>, Size<i32, Logical>) =
+        with_states(window.toplevel().wl_surface(), |states: &SurfaceData| {
+            let cached = states.cached_state.current::<SurfaceCachedState>();
+            (cached.min_size, cached.max_size)
+        });
+
+    let min: Size<i32, Logical> = (min.w.max(1), min.h.max(1)).into();
+    let limit = |max: i32, min: i32| if max > 0 { max.max(min) } else { i32::MAX };
+    (min, (limit(max.w, min.w), limit(max.h, min.h)).into())
+}
+
+impl PointerGrab<State> for ResizeGrab {
+    fn motion(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        _focus: Option<(WlSurface, Point<i32, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        handle.motion(data, None, event);
+
+        let delta: Point<i32, Logical> = (event.location - self.start_data.location).to_i32_round();
+        let mut size: Size<i32, Logical> = self.initial_size;
+        if self.edges & LEFT != 0 {
+            size.w -= delta.x;
+        } else if self.edges & RIGHT != 0 {
+            size.w += delta.x;
+        }
+        if self.edges & TOP != 0 {
+            size.h -= delta.y;
+        } else if self.edges & BOTTOM != 0 {
+            size.h += delta.y;
+        }
+
+        let (min, max) = size_limits(&self.window);
+        let size: Size<i32, Logical> =
+            (size.w.clamp(min.w, max.w), size.h.clamp(min.h, max.h)).into();
+
+        if let Some(resize) = &mut data.resize {
+            resize.pending = Some(size);
+        }
+        data.request_redraw();
+    }
+
+    fn relative_motion(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        focus: Option<(WlSurface, Point<i32, Logical>)>,
+        event: &RelativeMotionEvent,
+    ) {
+        handle.relative_motion(data, focus, event);
+    }
+
+    fn button(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+        if handle.current_pressed().contains(&self.start_data.button) {
+            return;
+        }
+
+        handle.unset_grab(data, event.serial, event.time);
+        // The exact size the pointer ended at, whether the client caught
+        // up or not
+        let pending: Option<Size<i32, Logical>> =
+            data.resize.as_mut().and_then(|resize: &mut ResizeState| {
+                resize.released = true;
+                resize.pending.take()
+            });
+        let toplevel: &ToplevelSurface = self.window.toplevel();
+        toplevel.with_pending_state(|state| {
+            state.states.unset(xdg_toplevel::State::Resizing);
+            if let Some(size) = pending {
+                state.size = Some(size);
+            }
+        });
+        let serial: Option<Serial> = toplevel.send_pending_configure();
+        if let (Some(resize), Some(serial)) = (&mut data.resize, serial) {
+            resize.configure = Some(serial);
+        }
+    }
+
+    fn axis(
+        &mut self,
+        data: &mut State,
+        handle: &mut PointerInnerHandle<'_, State>,
+        details: AxisFrame,
+    ) {
+        handle.axis(data, details);
+    }
+
+    fn start_data(&self) -> &GrabStartData<State> {
+        &self.start_data
+    }
+}
+
+impl State {
+    // For move and resize requests, which are only honored while a button
+    // pressed on one of the client's surfaces is still held
+    pub fn client_grab_start(
+        &self,
+        surface: &ToplevelSurface,
+        serial: Serial,
+    ) -> Option<GrabStartData<State>> {
+        let pointer: PointerHandle<Self> = self.seat.get_pointer().unwrap();
+        if !pointer.has_grab(serial) {
+            return None;
+        }
+
+        let start_data: GrabStartData<State> = pointer.grab_start_data()?;
+        let same_client: bool = start_data.focus.as_ref().map_or(false, |(focus, _)| {
+            focus.id().same_client_as(&surface.wl_surface().id())
+        });
+        same_client.then_some(start_data)
+    }
+
+    pub fn start_move_grab(
+        &mut self,
+        window: &Window,
+        start_data: GrabStartData<State>,
+        serial: Serial,
+    ) {
+        let Some(initial_location) = self.space.element_location(window) else {
+            return;
+        };
+
+        let tiled: bool = self.tiling.is_tiled(window);
+        if tiled {
+            self.start_tile_drag(window);
+        }
+
+        let pointer: PointerHandle<Self> = self.seat.get_pointer().unwrap();
+        pointer.set_grab(
+            self,
+            MoveGrab {
+                start_data,
+                window: window.clone(),
+                initial_location,
+                tiled,
+            },
+            serial,
+            Focus::Clear,
+        );
+    }
+
+    // Tiled windows get their size from the layout, so only floating ones
+    // can be resized
+    pub fn start_resize_grab(
+        &mut self,
+        window: &Window,
+        start_data: GrabStartData<State>,
+        edges: u32,
+        serial: Serial,
+    ) {
+        if self.tiling.is_tiled(window) || edges & (TOP | BOTTOM | LEFT | RIGHT) == 0 {
+            return;
+        }
+        let Some(location) = self.space.element_location(window) else {
+            return;
+        };
+
+        let mut initial: Rectangle<i32, Logical> = window.geometry();
+        initial.loc += location;
+        self.resize = Some(ResizeState {
+            window: window.clone(),
+            edges,
+            initial,
+            released: false,
+            pending: None,
+            configure: None,
+        });
+
+        let pointer: PointerHandle<Self> = self.seat.get_pointer().unwrap();
+        pointer.set_grab(
+            self,
+            ResizeGrab {
+                start_data,
+                window: window.clone(),
+                edges,
+                initial_size: initial.size,
+            },
+            serial,
+            Focus::Clear,
+        );
+    }
+
+    // Modifier drags resize from whichever corner is closest to the pointer
+    pub fn start_corner_resize_grab(
+        &mut self,
+        window: &Window,
+        start_data: GrabStartData<State>,
+        serial: Serial,
+    ) {
+        let Some(location) = self.space.element_location(window) else {
+            return;
+        };
+        let geometry: Rectangle<i32, Logical> = window.geometry();
+        let center: Point<f64, Logical> = (location + geometry.loc).to_f64()
+            + Point::from((geometry.size.w as f64 / 2.0, geometry.size.h as f64 / 2.0));
+
+        let horizontal: u32 = if start_data.location.x < center.x {
+            LEFT
+        } else {
+            RIGHT
+        };
+        let vertical: u32 = if start_data.location.y < center.y {
+            TOP
+        } else {
+            BOTTOM
+        };
+        self.start_resize_grab(window, start_data, horizontal | vertical, serial);
+    }
+
+    // Called once per frame, before rendering
+    pub fn flush_resize(&mut self) {
+        let Some(resize) = &mut self.resize else {
+            return;
+        };
+        let toplevel: &ToplevelSurface = resize.window.toplevel();
+        let caught_up: bool = resize.configure.map_or(true, |serial: Serial| {
+            configure::has_caught_up(toplevel, serial)
+        });
+        if !caught_up {
+            return;
+        }
+        let Some(size) = resize.pending.take() else {
+            return;
+        };
+
+        toplevel.with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Resizing);
+            state.size = Some(size);
+        });
+        if let Some(serial) = toplevel.send_pending_configure() {
+            resize.configure = Some(serial);
+        }
+    }
+
+    // Keeps the edges opposite to the dragged ones in place as the client
+    // commits new sizes
+    pub fn resize_commit(&mut self, surface: &WlSurface) {
+        let Some(resize) = &self.resize else {
+            return;
+        };
+        if resize.window.toplevel().wl_surface() != surface {
+            return;
+        }
+
+        let geometry: Rectangle<i32, Logical> = resize.window.geometry();
+        let mut location: Point<i32, Logical> = resize.initial.loc;
+        if resize.edges & LEFT != 0 {
+            location.x += resize.initial.size.w - geometry.size.w;
+        }
+        if resize.edges & TOP != 0 {
+            location.y += resize.initial.size.h - geometry.size.h;
+        }
+
+        let window: Window = resize.window.clone();
+        let caught_up: bool = resize.configure.map_or(true, |serial: Serial| {
+            configure::has_caught_up(window.toplevel(), serial)
+        });
+        if resize.released && caught_up {
+            self.resize = None;
+        }
+        self.space
+            .map_element(window, location - geometry.loc, false);
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{lock::LockState, state::State};
+
+impl State {
+    // Once a frame comes out without damage the backends stop rendering
+    // until request_redraw wakes them up again, e.g. for a client commit.
+    // Only our own animations keep them going.
+    pub fn can_idle(&self) -> bool {
+        !self.animations.is_animating()
+            && !self.osd.is_visible()
+            && !matches!(self.session_lock.state, LockState::Locking { .. })
+    }
+
+    // For anything that may change what's on screen: input, clients
+    // connecting or sending requests, IPC
+    pub fn request_redraw(&self) {
+        self.redraw_ping.ping();
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    os::fd::{AsRawFd, RawFd},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use smithay::{
+    backend::allocator::dmabuf::Dmabuf,
+    reexports::{
+        calloop::{
+            channel::{self, Channel, Event},
+            LoopHandle,
+        },
+        nix::{
+            errno::Errno,
+            poll::{poll, PollFd, PollFlags},
+        },
+        wayland_server::{
+            protocol::{wl_buffer::WlBuffer, wl_surface::WlSurface},
+            DisplayHandle, Resource,
+        },
+    },
+    wayland::{
+        compositor::{
+            add_blocker, add_pre_commit_hook, with_states, Blocker, BlockerState, BufferAssignment,
+            CompositorHandler, SurfaceAttributes, SurfaceData,
+        },
+        dmabuf::get_dmabuf,
+        shm::{with_buffer_contents, BufferAccessError, BufferData},
+    },
+};
+
+use crate::{data::Data, state::State};
+
+// Smaller shm buffers are uploaded faster than they're handed to a worker
+const SHM_THRESHOLD: usize = 4 << 20;
+// A client whose GPU work never finishes only holds up its own surface,
+// and only for this long
+const FENCE_TIMEOUT: Duration = Duration::from_secs(1);
+const PAGE_SIZE: usize = 4096;
+
+enum Import {
+    // Faulted in from the client's pool, which may have to come from swap
+    // or a file on a slow disk
+    Shm(WlBuffer),
+    // Waited on until the client's rendering into it is done, the
+    // renderer would block on its implicit fence otherwise
+    Dmabuf(Dmabuf),
+}
+
+struct Job {
+    surface: WlSurface,
+    import: Import,
+    done: Arc<AtomicBool>,
+}
+
+// Holds a commit back until its buffer is ready to be imported
+struct ImportBlocker(Arc<AtomicBool>);
+
+impl Blocker for ImportBlocker {
+    fn state(&self) -> BlockerState {
+        if self.0.load(Ordering::Acquire) {
+            BlockerState::Released
+        } else {
+            BlockerState::Pending
+        }
+    }
+}
+
+// Where a surface's pre-commit hook sends its jobs, the hook doesn't get
+// the compositor state
+struct ImportQueue(mpsc::Sender<Job>);
+
+// Waits out what would stall the main thread when a buffer is imported,
+// faulting in a huge shm pool and a dmabuf's unfinished rendering, while
+// the commit is held back with a blocker. The event loop releases it once
+// a worker reports back. The upload itself still happens on the main
+// thread when the renderer draws the surface, its textures can't leave it.
+pub struct ImportPool {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl ImportPool {
+    pub fn new(handle: &LoopHandle<'_, Data>) -> Self {
+        let (sender, channel): (channel::Sender<WlSurface>, Channel<WlSurface>) =
+            channel::channel();
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let job_receiver: Arc<Mutex<mpsc::Receiver<Job>>> = Arc::new(Mutex::new(job_receiver));
+
+        handle
+            .insert_source(channel, |event: Event<WlSurface>, _, data: &mut Data| {
+                if let Event::Msg(surface) = event {
+                    data.state.on_imported(&surface);
+                }
+            })
+            .unwrap();
+
+        let workers: usize =
+            std::thread::available_parallelism().map_or(2, |count| count.get().min(4));
+        for index in 0..workers {
+            let sender: channel::Sender<WlSurface> = sender.clone();
+            let job_receiver: Arc<Mutex<mpsc::Receiver<Job>>> = job_receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("pulseWM-import-{index}"))
+                .spawn(move || loop {
+                    let job: Job = match job_receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    prepare(&job.import);
+                    job.done.store(true, Ordering::Release);
+                    if sender.send(job.surface).is_err() {
+                        break;
+                    }
+                })
+                .unwrap();
+        }
+
+        Self { jobs }
+    }
+}
+
+fn prepare(import: &Import) {
+    match import {
+        Import::Shm(buffer) => {
+            let result: Result<(), BufferAccessError> =
+                with_buffer_contents(buffer, |ptr: *const u8, len: usize, data: BufferData| {
+                    let end: usize = (data.offset + data.stride * data.height) as usize;
+                    for offset in (data.offset as usize..end.min(len)).step_by(PAGE_SIZE) {
+                        let _: u8 = unsafe { std::ptr::read_volatile(ptr.add(offset)) };
+                    }
+                });
+            if let Err(err) = result {
+                eprintln!("Failed to read shm buffer: {err:?}");
+            }
+        }
+        Import::Dmabuf(dmabuf) => {
+            let deadline: Instant = Instant::now() + FENCE_TIMEOUT;
+            for handle in dmabuf.handles() {
+                wait_for_fence(handle.as_raw_fd(), deadline);
+            }
+        }
+    }
+}
+
+// A dmabuf polls readable once the rendering into it is done. Gives up at
+// `deadline`, the renderer waits for the rest then.
+fn wait_for_fence(fd: RawFd, deadline: Instant) {
+    loop {
+        let timeout: Duration = deadline.saturating_duration_since(Instant::now());
+        let mut fds: [PollFd; 1] = [PollFd::new(fd, PollFlags::POLLIN)];
+        match poll(&mut fds, timeout.as_millis() as i32) {
+            Ok(_) => return,
+            Err(Errno::EINTR) => continue,
+            Err(err) => {
+                eprintln!("Failed to wait for dmabuf: {err}");
+                return;
+            }
+        }
+    }
+}
+
+// Runs before a commit is applied, while a blocker can still hold it back
+fn pre_commit(_display_handle: &DisplayHandle, surface: &WlSurface) {
+    let job: Option<(mpsc::Sender<Job>, WlBuffer)> =
+        with_states(surface, |states: &SurfaceData| {
+            let queue: &ImportQueue = states.data_map.get::<ImportQueue>()?;
+            match states
+                .cached_state
+                .pending::<SurfaceAttributes>()
+                .buffer
+                .as_ref()?
+            {
+                BufferAssignment::NewBuffer(buffer) => Some((queue.0.clone(), buffer.clone())),
+                BufferAssignment::Removed => None,
+            }
+        });
+    let Some((jobs, buffer)) = job else {
+        return;
+    };
+
+    let import: Import = if let Ok(dmabuf) = get_dmabuf(&buffer) {
+        Import::Dmabuf(dmabuf)
+    } else {
+        let size: usize = with_buffer_contents(&buffer, |_, _, data: BufferData| {
+            (data.stride * data.height) as usize
+        })
+        .unwrap_or(0);
+        if size < SHM_THRESHOLD {
+            return;
+        }
+        Import::Shm(buffer)
+    };
+
+    let done: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let job: Job = Job {
+        surface: surface.clone(),
+        import,
+        done: done.clone(),
+    };
+    if jobs.send(job).is_ok() {
+        add_blocker(surface, ImportBlocker(done));
+    }
+}
+
+impl State {
+    // Hooks only exist for surfaces that committed once, so their first
+    // buffer is still imported in place
+    pub fn import_commit(&mut self, surface: &WlSurface) {
+        let inserted: bool = with_states(surface, |states: &SurfaceData| {
+            states
+                .data_map
+                .insert_if_missing(|| ImportQueue(self.imports.jobs.clone()))
+        });
+        if inserted {
+            add_pre_commit_hook(surface, pre_commit);
+        }
+    }
+
+    fn on_imported(&mut self, surface: &WlSurface) {
+        let Some(client) = surface.client() else {
+            return;
+        };
+        let display_handle: DisplayHandle = self.display_handle.clone();
+        self.client_compositor_state(&client)
+            .blocker_cleared(self, &display_handle);
+    }
+}
@@ -0,0 +1,560 @@
+use std::{process::Child, time::Instant};
+
+use smithay::{
+    backend::input::{
+        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
+        KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionEvent,
+        Switch, SwitchState, SwitchToggleEvent,
+    },
+    desktop::{utils::under_from_surface_tree, LayerSurface, Window, WindowSurfaceType},
+    input::{
+        keyboard::{keysyms, FilterResult, KeyboardHandle, KeysymHandle, ModifiersState},
+        pointer::{AxisFrame, ButtonEvent, GrabStartData, MotionEvent, PointerHandle},
+    },
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point, Rectangle, Serial, SERIAL_COUNTER},
+    wayland::shell::wlr_layer::Layer,
+};
+
+use crate::{
+    config::{KeyChord, WindowDragConfig},
+    decorations::FramePart,
+    keybindings::Action,
+    outputs, root_scroll, spawn,
+    state::State,
+    switcher::SwitchScope,
+};
+
+// Ctrl+Alt+F1..F12, the keymap turns them into these
+fn vt_number(sym: u32) -> Option<i32> {
+    (keysyms::KEY_XF86Switch_VT_1..=keysyms::KEY_XF86Switch_VT_12)
+        .contains(&sym)
+        .then(|| (sym - keysyms::KEY_XF86Switch_VT_1 + 1) as i32)
+}
+
+// The number row by keycode, so it works the same with layouts that need
+// shift for digits
+fn workspace_index(keycode: u32) -> Option<usize> {
+    // xkb keycodes, evdev KEY_1..KEY_9 plus 8
+    (10..=18)
+        .contains(&keycode)
+        .then(|| (keycode - 10) as usize)
+}
+
+enum KeyAction {
+    Switch { scope: SwitchScope, backward: bool },
+    KioskAdmin,
+    SwitchVt(i32),
+    CloseMenu,
+    DismissWelcome,
+    // The next chord of a sequence binding, as written in the config
+    Prefix(KeyChord, String),
+    CancelSequence,
+    Run(Action),
+}
+
+// Pressed on their own while a sequence is pending, they're part of the
+// next chord
+fn is_modifier(sym: u32) -> bool {
+    (keysyms::KEY_Shift_L..=keysyms::KEY_Hyper_R).contains(&sym)
+        || sym == keysyms::KEY_ISO_Level3_Shift
+}
+
+impl State {
+    pub fn process_input_event<I: InputBackend>(&mut self, event: InputEvent<I>) {
+        self.latency.input_received(Instant::now());
+        self.request_redraw();
+        if self.dpms_input(&event) {
+            return;
+        }
+
+        match event {
+            InputEvent::Keyboard { event } => self.on_keyboard_key::<I>(event),
+            InputEvent::PointerMotion { event } => self.on_pointer_motion::<I>(event),
+            InputEvent::PointerMotionAbsolute { event } => {
+                self.on_pointer_motion_absolute::<I>(event)
+            }
+            InputEvent::PointerButton { event } => self.on_pointer_button::<I>(event),
+            InputEvent::PointerAxis { event } => self.on_pointer_axis::<I>(event),
+            InputEvent::SwitchToggle { event } => self.on_switch_toggle::<I>(event),
+            _ => {}
+        }
+    }
+
+    fn on_keyboard_key<I: InputBackend>(&mut self, event: I::KeyboardKeyEvent) {
+        let serial: Serial = SERIAL_COUNTER.next_serial();
+        let time: u32 = Event::time_msec(&event);
+        let press_state: KeyState = event.state();
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        // The release still goes to the client, it saw Alt being pressed
+        let mut alt_released: bool = false;
+        let action: Option<KeyAction> = keyboard.input::<KeyAction, _>(
+            self,
+            event.key_code(),
+            press_state,
+            serial,
+            time,
+            |state: &mut Self, modifiers: &ModifiersState, keysym: KeysymHandle<'_>| {
+                if !modifiers.alt {
+                    alt_released = true;
+                }
+
+                if press_state != KeyState::Pressed {
+                    return FilterResult::Forward;
+                }
+
+                // Works on the lock screen too, but not in kiosk mode
+                if let Some(vt) = vt_number(keysym.modified_sym()).filter(|_| !state.kiosk()) {
+                    return FilterResult::Intercept(KeyAction::SwitchVt(vt));
+                }
+
+                // The locker gets every key, no shortcut may get past it
+                if state.is_locked() {
+                    return FilterResult::Forward;
+                }
+
+                if state.cheatsheet.is_welcome() {
+                    return FilterResult::Intercept(KeyAction::DismissWelcome);
+                }
+
+                if state.kiosk() {
+                    let admin: bool = state.config.kiosk.admin_chord.map_or(false, |chord| {
+                        chord.matches(modifiers, keysym.modified_sym())
+                    });
+                    if admin {
+                        return FilterResult::Intercept(KeyAction::KioskAdmin);
+                    }
+                    return FilterResult::Forward;
+                }
+
+                let mut syms: Vec<u32> = vec![keysym.modified_sym()];
+                syms.extend_from_slice(keysym.raw_syms());
+                // Only the sequence's own bindings apply until it's done
+                if !state.key_sequence.pending().is_empty() {
+                    if is_modifier(keysym.modified_sym()) {
+                        return FilterResult::Forward;
+                    }
+                    let action: KeyAction = state
+                        .binding_key_action(modifiers, &syms)
+                        .unwrap_or(KeyAction::CancelSequence);
+                    return FilterResult::Intercept(action);
+                }
+
+                if modifiers.logo {
+                    if let Some(index) = workspace_index(keysym.raw_code()) {
+                        let action: Action = match (modifiers.ctrl, modifiers.shift) {
+                            (false, false) => Action::SwitchWorkspace(index + 1),
+                            (false, true) => Action::MoveToWorkspace(index + 1),
+                            (true, false) => Action::ToggleTagView(index + 1),
+                            (true, true) => Action::ToggleWindowTag(index + 1),
+                        };
+                        return FilterResult::Intercept(KeyAction::Run(action));
+                    }
+                }
+
+                match keysym.modified_sym() {
+                    keysyms::KEY_Escape if state.is_menu_open() => {
+                        return FilterResult::Intercept(KeyAction::CloseMenu);
+                    }
+                    keysyms::KEY_Escape if state.overview.is_open() => {
+                        return FilterResult::Intercept(KeyAction::Run(Action::ToggleOverview));
+                    }
+                    // The switcher stays open until Alt is released, so these
+                    // can't be rebound
+                    keysyms::KEY_Tab | keysyms::KEY_ISO_Left_Tab if modifiers.alt => {
+                        return FilterResult::Intercept(KeyAction::Switch {
+                            scope: SwitchScope::Applications,
+                            backward: modifiers.shift,
+                        });
+                    }
+                    keysyms::KEY_grave | keysyms::KEY_asciitilde if modifiers.alt => {
+                        return FilterResult::Intercept(KeyAction::Switch {
+                            scope: SwitchScope::Windows,
+                            backward: modifiers.shift,
+                        });
+                    }
+                    _ => {}
+                }
+
+                match state.binding_key_action(modifiers, &syms) {
+                    Some(action) => FilterResult::Intercept(action),
+                    None => FilterResult::Forward,
+                }
+            },
+        );
+
+        match action {
+            Some(KeyAction::Switch { scope, backward }) => self.switch(scope, backward),
+            Some(KeyAction::KioskAdmin) => {
+                if let Some(command) = &self.config.kiosk.admin_command {
+                    spawn::spawn_detached(command);
+                }
+            }
+            Some(KeyAction::SwitchVt(vt)) => self.switch_vt(vt),
+            Some(KeyAction::CloseMenu) => self.close_menu(),
+            Some(KeyAction::DismissWelcome) => self.dismiss_welcome(),
+            Some(KeyAction::Prefix(chord, name)) => self.continue_key_sequence(chord, name),
+            Some(KeyAction::CancelSequence) => self.end_key_sequence(),
+            Some(KeyAction::Run(action)) => {
+                self.end_key_sequence();
+                self.run_action(action);
+            }
+            None if press_state == KeyState::Pressed => self.key_typed(),
+            None => {}
+        }
+
+        if alt_released && self.switcher.is_active() {
+            self.finish_switch();
+        }
+    }
+
+    // The [keybindings] entry the key completes or continues. Inside a
+    // sequence any other key cancels it, and is swallowed too.
+    fn binding_key_action(&self, modifiers: &ModifiersState, syms: &[u32]) -> Option<KeyAction> {
+        let pending: &[KeyChord] = self.key_sequence.pending();
+        match self.config.keybindings.find(pending, modifiers, syms) {
+            Some((binding, false)) => {
+                let name: String = binding
+                    .name
+                    .split_whitespace()
+                    .nth(pending.len())
+                    .unwrap_or_default()
+                    .to_string();
+                Some(KeyAction::Prefix(binding.chords[pending.len()], name))
+            }
+            Some((binding, true)) if binding.action.is_available(self) => {
+                Some(KeyAction::Run(binding.action.clone()))
+            }
+            _ if !pending.is_empty() => Some(KeyAction::CancelSequence),
+            _ => None,
+        }
+    }
+
+    fn on_pointer_motion<I: InputBackend>(&mut self, event: I::PointerMotionEvent) {
+        let location: Point<f64, Logical> = self.pointer_location + event.delta();
+        self.pointer_moved(location, event.time_msec());
+    }
+
+    // Tablets and the nested window, mapped onto the first output
+    fn on_pointer_motion_absolute<I: InputBackend>(
+        &mut self,
+        event: I::PointerMotionAbsoluteEvent,
+    ) {
+        let Some(geometry) = self
+            .space
+            .outputs()
+            .next()
+            .and_then(|output: &Output| self.space.output_geometry(output))
+        else {
+            return;
+        };
+
+        let location: Point<f64, Logical> =
+            event.position_transformed(geometry.size) + geometry.loc.to_f64();
+        self.pointer_moved(location, event.time_msec());
+    }
+
+    fn pointer_moved(&mut self, location: Point<f64, Logical>, time: u32) {
+        self.pointer_location = self.clamp_to_outputs(location);
+        let pointer: PointerHandle<Self> = self.seat.get_pointer().unwrap();
+
+        // Not while a button is held, that would steal focus mid-drag. In
+        // tablet mode the pointer only moves to where a finger taps, the tap
+        // focuses on its own.
+        if self.config.focus.follows_mouse
+            && !self.tablet_mode
+            && !pointer.is_grabbed()
+            && !self.overview.is_open()
+        {
+            if let Some(window) = self.window_under(self.pointer_location) {
+                self.hover_focus(&window);
+            }
+        }
+
+        let under: Option<(WlSurface, Point<i32, Logical>)> =
+            self.surface_under(self.pointer_location);
+        pointer.motion(
+            self,
+            under,
+            &MotionEvent {
+                location: self.pointer_location,
+                serial: SERIAL_COUNTER.next_serial(),
+                time,
+            },
+        );
+    }
+
+    fn on_pointer_button<I: InputBackend>(&mut self, event: I::PointerButtonEvent) {
+        let serial: Serial = SERIAL_COUNTER.next_serial();
+        let button_state: ButtonState = event.state();
+        let pointer: PointerHandle<Self> = self.seat.get_pointer().unwrap();
+
+        if !self.is_locked() && self.menu_button(button_state == ButtonState::Pressed) {
+            return;
+        }
+
+        // The overview takes every click, none reach the thumbnails' clients
+        if self.overview.is_open() && !self.is_locked() {
+            if let Some((output, point)) = self.output_point(self.pointer_location) {
+                match button_state {
+                    ButtonState::Pressed => {
+                        self.overview_press(&output, point);
+                    }
+                    ButtonState::Released => self.overview_release(&output, point),
+                }
+            }
+            return;
+        }
+
+        // Clicking focuses and raises, whatever the focus policy
+        let button: u32 = event.button_code();
+        if button_state == ButtonState::Pressed && !pointer.is_grabbed() && !self.is_locked() {
+            if let Some(layer) = self.layer_under(self.pointer_location, true) {
+                self.focus_layer(&layer);
+            } else if let Some((window, part)) = self.decoration_under(self.pointer_location) {
+                self.focus_window(&window);
+                match part {
+                    FramePart::Close => window.toplevel().send_close(),
+                    FramePart::Title if !self.kiosk() => {
+                        let start_data: GrabStartData<Self> = GrabStartData {
+                            focus: None,
+                            button,
+                            location: self.pointer_location,
+                        };
+                        self.start_move_grab(&window, start_data, serial);
+                    }
+                    FramePart::Title => {}
+                }
+            } else if let Some(window) = self.window_under(self.pointer_location) {
+                self.focus_window(&window);
+
+                let modifiers: ModifiersState = self.seat.get_keyboard().unwrap().modifier_state();
+                let drag: &WindowDragConfig = &self.config.window_drag;
+                let moving: bool = button == drag.move_button.code();
+                let resizing: bool = button == drag.resize_button.code();
+                if !self.kiosk() && drag.modifier.is_held(&modifiers) && (moving || resizing) {
+                    let start_data: GrabStartData<Self> = GrabStartData {
+                        focus: None,
+                        button,
+                        location: self.pointer_location,
+                    };
+                    if moving {
+                        self.start_move_grab(&window, start_data, serial);
+                    } else {
+                        self.start_corner_resize_grab(&window, start_data, serial);
+                    }
+                }
+            } else if self.config.root_menu.enabled
+                && button == self.config.root_menu.button.code()
+                && !self.kiosk()
+            {
+                self.open_root_menu(self.pointer_location.to_i32_round());
+                return;
+            }
+        }
+
+        pointer.button(
+            self,
+            &ButtonEvent {
+                button,
+                state: button_state,
+                serial,
+                time: event.time_msec(),
+            },
+        );
+    }
+
+    fn on_pointer_axis<I: InputBackend>(&mut self, event: I::PointerAxisEvent) {
+        let source: AxisSource = event.source();
+        // Wheels without smooth scrolling only report clicks
+        let amount = |axis: Axis| -> f64 {
+            event
+                .amount(axis)
+                .unwrap_or_else(|| event.amount_discrete(axis).unwrap_or(0.0) * root_scroll::STEP)
+        };
+        let horizontal: f64 = amount(Axis::Horizontal);
+        let vertical: f64 = amount(Axis::Vertical);
+
+        // Over the bare desktop scrolling runs the [root_scroll] bindings
+        if !self.is_locked() && !self.overview.is_open() && self.is_over_root(self.pointer_location)
+        {
+            self.scroll_root(horizontal, vertical);
+            return;
+        }
+
+        let mut frame: AxisFrame = AxisFrame::new(event.time_msec()).source(source);
+        for (axis, value) in [(Axis::Horizontal, horizontal), (Axis::Vertical, vertical)] {
+            if value != 0.0 {
+                frame = frame.value(axis, value);
+                if let Some(discrete) = event.amount_discrete(axis) {
+                    frame = frame.discrete(axis, discrete as i32);
+                }
+            } else if source == AxisSource::Finger {
+                // Lets kinetic scrolling in the client know the fingers left
+                frame = frame.stop(axis);
+            }
+        }
+
+        let pointer: PointerHandle<Self> = self.seat.get_pointer().unwrap();
+        pointer.axis(self, frame);
+    }
+
+    // Nothing but the desktop at `location`. A wallpaper client's
+    // background surface counts as desktop, bars, docks and title bars
+    // don't.
+    fn is_over_root(&self, location: Point<f64, Logical>) -> bool {
+        if self.decoration_under(location).is_some() {
+            return false;
+        }
+        if self.surface_under(location).is_none() {
+            return true;
+        }
+        // surface_under only ends up in the lower layers with no window or
+        // upper layer surface there
+        self.window_under(location).is_none()
+            && self.layer_under(location, true).is_none()
+            && self
+                .layer_under(location, false)
+                .is_some_and(|layer: LayerSurface| layer.layer() == Layer::Background)
+    }
+
+    // Not when a bar or another layer surface covers the window there.
+    // Title bars count as part of their window.
+    fn window_under(&self, location: Point<f64, Logical>) -> Option<Window> {
+        if self.layer_under(location, true).is_some() {
+            return None;
+        }
+        if let Some((window, _)) = self.decoration_under(location) {
+            return Some(window);
+        }
+        self.space
+            .element_under(location)
+            .map(|(window, _)| window.clone())
+    }
+
+    // The surface the pointer is over and where that surface is, in global
+    // coordinates
+    fn surface_under(
+        &self,
+        location: Point<f64, Logical>,
+    ) -> Option<(WlSurface, Point<i32, Logical>)> {
+        if self.is_locked() {
+            let output: &Output = self.space.output_under(location).next()?;
+            let geometry: Rectangle<i32, Logical> = self.space.output_geometry(output)?;
+            // A locker that hasn't drawn the whole output yet gets no
+            // input past its buffer, or outside its input region
+            return self.lock_surface(output).and_then(|surface: WlSurface| {
+                under_from_surface_tree(&surface, location, geometry.loc, WindowSurfaceType::ALL)
+            });
+        }
+        if self.overview.is_open() {
+            return None;
+        }
+
+        if let Some(under) = self.layer_surface_under(location, true) {
+            return Some(under);
+        }
+        // Title bars are ours, no client gets the pointer over them
+        if self.decoration_under(location).is_some() {
+            return None;
+        }
+        let window_surface: Option<(WlSurface, Point<i32, Logical>)> = self
+            .space
+            .element_under(location)
+            .and_then(|(window, window_location)| {
+                window
+                    .surface_under(location - window_location.to_f64(), WindowSurfaceType::ALL)
+                    .map(|(surface, offset)| (surface, window_location + offset))
+            });
+        window_surface.or_else(|| self.layer_surface_under(location, false))
+    }
+
+    // The output under `location` and the point relative to it
+    fn output_point(&self, location: Point<f64, Logical>) -> Option<(Output, Point<f64, Logical>)> {
+        let output: Output = self.space.output_under(location).next()?.clone();
+        let geometry: Rectangle<i32, Logical> = self.space.output_geometry(&output)?;
+        Some((output, location - geometry.loc.to_f64()))
+    }
+
+    // Outputs without a neighbour on some side stop the pointer there
+    fn clamp_to_outputs(&self, location: Point<f64, Logical>) -> Point<f64, Logical> {
+        if self.space.output_under(location).next().is_some() {
+            return location;
+        }
+
+        let geometry: Option<Rectangle<i32, Logical>> = self
+            .space
+            .output_under(self.pointer_location)
+            .next()
+            .or_else(|| self.space.outputs().next())
+            .and_then(|output: &Output| self.space.output_geometry(output));
+        let Some(geometry) = geometry else {
+            return location;
+        };
+
+        let (x, y): (f64, f64) = (geometry.loc.x as f64, geometry.loc.y as f64);
+        (
+            location.x.clamp(x, x + geometry.size.w as f64 - 1.0),
+            location.y.clamp(y, y + geometry.size.h as f64 - 1.0),
+        )
+            .into()
+    }
+
+    fn on_switch_toggle<I: InputBackend>(&mut self, event: I::SwitchToggleEvent) {
+        let on: bool = event.state() == SwitchState::On;
+
+        match event.switch() {
+            Some(Switch::Lid) => self.on_lid(on),
+            Some(Switch::TabletMode) => self.set_tablet_mode(on),
+            None => {}
+        }
+    }
+
+    fn on_lid(&mut self, closed: bool) {
+        self.lid_closed = closed;
+
+        if self.lid_closed && !self.has_external_output() {
+            if let Some(command) = &self.config.lid.close_command {
+                spawn::spawn_detached(command);
+            }
+        }
+
+        self.apply_output_config();
+    }
+
+    // Decorations take taps further out and focus stops following the
+    // pointer, see decoration_under and pointer_moved. Here we only take
+    // care of the on-screen keyboard.
+    fn set_tablet_mode(&mut self, enabled: bool) {
+        if self.tablet_mode == enabled {
+            return;
+        }
+        self.tablet_mode = enabled;
+
+        if enabled {
+            if let Some(command) = &self.config.tablet_mode.osk_command {
+                self.osk = spawn::spawn_group(command);
+            }
+        } else if let Some(osk) = self.osk.take() {
+            spawn::kill_group(osk);
+        }
+    }
+
+    // One that exited on its own is forgotten, not killed later
+    pub fn reap_osk(&mut self) {
+        if self
+            .osk
+            .as_mut()
+            .is_some_and(|osk: &mut Child| !matches!(osk.try_wait(), Ok(None)))
+        {
+            self.osk = None;
+        }
+    }
+
+    fn has_external_output(&self) -> bool {
+        self.outputs.iter().any(|output: &Output| {
+            !outputs::is_internal(output) && self.space.output_geometry(output).is_some()
+        })
+    }
+}
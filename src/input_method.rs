@@ -0,0 +1,31 @@
+use smithay::{
+    delegate_input_method_manager, delegate_text_input_manager,
+    desktop::{PopupKind, Window},
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Rectangle},
+    wayland::input_method::{InputMethodHandler, PopupSurface},
+};
+
+use crate::state::State;
+
+// The keyboard grab an input method asks for is set up by smithay, all
+// that's left here are its popups
+impl InputMethodHandler for State {
+    // Candidate windows, smithay places them next to the text cursor
+    // rectangle the focused client reports through text-input
+    fn new_popup(&mut self, surface: PopupSurface) {
+        if let Err(err) = self.popups.track_popup(PopupKind::from(surface)) {
+            eprintln!("Failed to track input method popup: {err}");
+        }
+    }
+
+    // The cursor rectangle is relative to this
+    fn parent_geometry(&self, parent: &WlSurface) -> Rectangle<i32, Logical> {
+        self.windows
+            .get(parent)
+            .map(Window::geometry)
+            .unwrap_or_default()
+    }
+}
+delegate_input_method_manager!(State);
+delegate_text_input_manager!(State);
@@ -0,0 +1,660 @@
+use std::{
+    ffi::OsStr,
+    io::{ErrorKind, Read, Write},
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+use smithay::{
+    reexports::calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+    utils::{Point, Size},
+};
+
+use crate::{
+    data::Data, devices::AccelProfile, globals::OptionalGlobal, state::State, theme::Theme,
+    tree::WindowCriteria,
+};
+
+// Longer requests are refused instead of buffered
+const MAX_REQUEST: usize = 64 << 10;
+// Subscribers that fall this far behind on reading are dropped
+const MAX_QUEUED: usize = 1 << 20;
+
+// One JSON object per line, e.g. {"command": "services"}
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    Services,
+    Latency,
+    SetLatencyMode {
+        enabled: bool,
+    },
+    SetDamageDebug {
+        enabled: bool,
+    },
+    Globals,
+    SetGlobal {
+        name: OptionalGlobal,
+        enabled: bool,
+    },
+    Clients,
+    Theme,
+    SetTheme {
+        theme: Theme,
+    },
+    PointerDevices,
+    // Types into the focused window, e.g. {"command": "type", "text": "🙂"}
+    Type {
+        text: String,
+    },
+    Minimized,
+    // The index into what `minimized` lists, the last one without
+    Unminimize {
+        index: Option<usize>,
+    },
+    // Without a device it applies to every pointer
+    SetPointerSpeed {
+        device: Option<String>,
+        speed: f64,
+        profile: Option<AccelProfile>,
+    },
+    // Outputs, workspaces and their windows, with the ids the other
+    // commands take
+    Tree,
+    Focus {
+        id: u64,
+    },
+    // Without an id the focused window
+    MoveToWorkspace {
+        id: Option<u64>,
+        workspace: usize,
+    },
+    Gaps,
+    // Sets the gaps between tiled windows and around them, or changes them
+    // by that much with `relative`. Those left out stay as they are.
+    SetGaps {
+        inner: Option<i32>,
+        outer: Option<i32>,
+        #[serde(default)]
+        relative: bool,
+    },
+    // Counting from 1 like in the bar
+    SwitchWorkspace {
+        workspace: usize,
+    },
+    // Toggles without `enabled`
+    Fullscreen {
+        #[serde(flatten)]
+        criteria: WindowCriteria,
+        enabled: Option<bool>,
+    },
+    // Places the windows matching the criteria at `x`, `y` in global
+    // coordinates, or moves them by that much with `relative`, e.g.
+    // {"command": "move", "app_id": "foot", "x": 0, "y": 0}
+    Move {
+        #[serde(flatten)]
+        criteria: WindowCriteria,
+        x: i32,
+        y: i32,
+        #[serde(default)]
+        relative: bool,
+    },
+    // Like move, with the size of the window
+    Resize {
+        #[serde(flatten)]
+        criteria: WindowCriteria,
+        width: i32,
+        height: i32,
+        #[serde(default)]
+        relative: bool,
+    },
+    ReloadConfig,
+    Quit,
+    Presentation,
+    SetPresentation {
+        enabled: bool,
+    },
+    // Turns an output off or on by name, independent of the inactivity
+    // timeout
+    SetBlanked {
+        output: String,
+        blanked: bool,
+    },
+    // Answers with where the PNG is saved, without an output the one the
+    // pointer is on
+    Screenshot {
+        output: Option<String>,
+    },
+    // Keeps the connection open and sends an IpcEvent line for everything
+    // that happens of these kinds
+    Subscribe {
+        events: Vec<EventKind>,
+    },
+}
+
+// Requests written the way pulsectl takes them, separated by ";", for
+// bindings that do several things at once:
+//   "super+f" = { commands = "move-to-workspace workspace=3; switch-workspace workspace=3" }
+// Values can't contain spaces.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct CommandChain {
+    source: String,
+    requests: Vec<Request>,
+}
+
+impl CommandChain {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl TryFrom<String> for CommandChain {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let mut requests: Vec<Request> = Vec::new();
+        for command in source.split(';').map(str::trim) {
+            let words: Vec<String> = command.split_whitespace().map(String::from).collect();
+            if words.is_empty() {
+                continue;
+            }
+            let request: Request = serde_json::from_value(command_request(&words)?)
+                .map_err(|err| format!("invalid command \"{command}\": {err}"))?;
+            if matches!(request, Request::Subscribe { .. }) {
+                return Err("subscribe can't be part of a command chain".into());
+            }
+            requests.push(request);
+        }
+        if requests.is_empty() {
+            return Err(format!("no commands in \"{source}\""));
+        }
+        Ok(Self { source, requests })
+    }
+}
+
+// The request a command line like pulsectl's stands for. Fields are given
+// as field=value, values are parsed as JSON and taken as strings otherwise.
+pub fn command_request(words: &[String]) -> Result<serde_json::Value, String> {
+    let (command, fields) = words.split_first().ok_or("no command")?;
+
+    let mut request: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
+    request.insert(
+        "command".into(),
+        serde_json::Value::String(command.replace('-', "_")),
+    );
+    for field in fields {
+        let (name, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("Expected field=value, got \"{field}\""))?;
+        let value: serde_json::Value = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+        request.insert(name.replace('-', "_"), value);
+    }
+
+    Ok(serde_json::Value::Object(request))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Window,
+    Workspace,
+    Config,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum IpcEvent {
+    WindowOpened { id: u64 },
+    WindowClosed { id: u64 },
+    WindowFocused { id: u64 },
+    // Wanted focus and didn't get it, see focus.steal_prevention
+    WindowUrgent { id: u64 },
+    // Counting from 1 like in the bar
+    WorkspaceChanged { workspace: usize },
+    ConfigReloaded,
+}
+
+impl IpcEvent {
+    fn kind(&self) -> EventKind {
+        match self {
+            IpcEvent::WindowOpened { .. }
+            | IpcEvent::WindowClosed { .. }
+            | IpcEvent::WindowFocused { .. }
+            | IpcEvent::WindowUrgent { .. } => EventKind::Window,
+            IpcEvent::WorkspaceChanged { .. } => EventKind::Workspace,
+            IpcEvent::ConfigReloaded => EventKind::Config,
+        }
+    }
+}
+
+struct Subscriber {
+    id: u64,
+    stream: UnixStream,
+    events: Vec<EventKind>,
+    // Events the socket didn't take yet
+    queued: Vec<u8>,
+}
+
+// Connections that sent `subscribe`
+#[derive(Default)]
+pub struct IpcSubscribers {
+    subscribers: Vec<Subscriber>,
+    next_id: u64,
+}
+
+#[derive(Serialize)]
+pub struct Response {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    pub fn ok(data: impl Serialize) -> Self {
+        Self {
+            success: true,
+            data: serde_json::to_value(data).ok(),
+            error: None,
+        }
+    }
+
+    pub fn error(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+pub fn parse_request(line: &str) -> Result<Request, String> {
+    serde_json::from_str(line).map_err(|err| err.to_string())
+}
+
+fn queue_line(queued: &mut Vec<u8>, value: &impl Serialize) {
+    if serde_json::to_writer(&mut *queued, value).is_ok() {
+        queued.push(b'\n');
+    }
+}
+
+// Writes as much of `queued` as the socket takes without blocking, the
+// rest waits for it to become writable again. Fails once the client is
+// gone.
+fn flush(mut stream: &UnixStream, queued: &mut Vec<u8>) -> std::io::Result<()> {
+    while !queued.is_empty() {
+        match stream.write(queued) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(written) => {
+                queued.drain(..written);
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+// Removes the socket file again when dropped
+pub struct IpcServer {
+    path: PathBuf,
+}
+
+impl IpcServer {
+    pub fn new(handle: &LoopHandle<'_, Data>, wayland_display: &OsStr) -> Option<Self> {
+        let runtime_dir: PathBuf = std::env::var_os("XDG_RUNTIME_DIR")?.into();
+        let path: PathBuf = runtime_dir.join(format!(
+            "pulsewm-{}.sock",
+            wayland_display.to_string_lossy()
+        ));
+
+        // Left behind by a compositor that crashed
+        let _ = std::fs::remove_file(&path);
+
+        let listener: UnixListener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                eprintln!("Failed to bind IPC socket {}: {err}", path.display());
+                return None;
+            }
+        };
+        listener.set_nonblocking(true).unwrap();
+
+        let clients: LoopHandle<'_, Data> = handle.clone();
+        handle
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                move |_, listener: &mut UnixListener, _: &mut Data| {
+                    while let Ok((stream, _)) = listener.accept() {
+                        accept_client(&clients, stream);
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .unwrap();
+
+        std::env::set_var("PULSEWM_SOCK", &path);
+
+        Some(Self { path })
+    }
+}
+
+enum ClientInput {
+    // No full line yet
+    Pending,
+    Line(String),
+    TooLong,
+    Closed,
+}
+
+// What a connection is doing, it is only ever read from, then written to
+enum Connection {
+    Reading(Vec<u8>),
+    // The answer, the connection is closed once it's written
+    Answering(Vec<u8>),
+    // The subscriber with this id, which keeps the events to write
+    Subscribed(u64),
+}
+
+// Reads whatever arrived into `buffer`. A client that closes its end
+// without a newline sent its last line anyway.
+fn read_request(stream: &mut UnixStream, buffer: &mut Vec<u8>) -> ClientInput {
+    let mut chunk: [u8; 4096] = [0; 4096];
+    loop {
+        match stream.read(&mut chunk) {
+            Ok(0) if buffer.is_empty() => return ClientInput::Closed,
+            Ok(0) => return ClientInput::Line(String::from_utf8_lossy(buffer).into_owned()),
+            Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return ClientInput::Pending,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return ClientInput::Closed,
+        }
+
+        if let Some(end) = buffer.iter().position(|byte: &u8| *byte == b'\n') {
+            return ClientInput::Line(String::from_utf8_lossy(&buffer[..end]).into_owned());
+        }
+        if buffer.len() > MAX_REQUEST {
+            return ClientInput::TooLong;
+        }
+    }
+}
+
+// Every client is a source of its own and never blocks the compositor. Its
+// request is buffered as it arrives and its answer or events are written
+// as the socket takes them, one that stops reading only holds up itself.
+// Edge triggered, so a writable socket with nothing to write doesn't wake
+// the loop.
+fn accept_client(handle: &LoopHandle<'_, Data>, stream: UnixStream) {
+    if let Err(err) = stream.set_nonblocking(true) {
+        eprintln!("Failed to set up IPC client: {err}");
+        return;
+    }
+
+    let mut connection: Connection = Connection::Reading(Vec::new());
+    let result = handle.insert_source(
+        Generic::new(stream, Interest::BOTH, Mode::Edge),
+        move |_, stream: &mut UnixStream, data: &mut Data| {
+            if let Connection::Reading(buffer) = &mut connection {
+                connection = match read_request(stream, buffer) {
+                    ClientInput::Pending => return Ok(PostAction::Continue),
+                    ClientInput::Line(line) => data.state.handle_ipc_client(stream, &line),
+                    ClientInput::TooLong => {
+                        let mut queued: Vec<u8> = Vec::new();
+                        queue_line(&mut queued, &Response::error("request too long"));
+                        Connection::Answering(queued)
+                    }
+                    ClientInput::Closed => return Ok(PostAction::Remove),
+                };
+            }
+
+            let open: bool = match &mut connection {
+                Connection::Reading(_) => true,
+                Connection::Answering(queued) => {
+                    flush(stream, queued).is_ok() && !queued.is_empty()
+                }
+                Connection::Subscribed(id) => data.state.flush_ipc_subscriber(*id),
+            };
+            if open {
+                Ok(PostAction::Continue)
+            } else {
+                Ok(PostAction::Remove)
+            }
+        },
+    );
+    if let Err(err) = result {
+        eprintln!("Failed to watch IPC client: {err}");
+    }
+}
+
+// Subscribers send nothing after `subscribe`, reading tells whether they
+// hung up
+fn hung_up(mut stream: &UnixStream) -> bool {
+    let mut chunk: [u8; 256] = [0; 256];
+    loop {
+        match stream.read(&mut chunk) {
+            Ok(0) => return true,
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return false,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(_) => return true,
+        }
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl State {
+    fn handle_ipc_client(&mut self, stream: &UnixStream, line: &str) -> Connection {
+        self.request_redraw();
+        let request: Result<Request, String> = parse_request(line);
+        if let Ok(Request::Subscribe { events }) = request {
+            return match stream.try_clone() {
+                Ok(stream) => self.subscribe(stream, events),
+                Err(err) => {
+                    eprintln!("Failed to set up IPC subscriber: {err}");
+                    Connection::Answering(Vec::new())
+                }
+            };
+        }
+        let response: Response = match request {
+            Ok(request) => self.handle_ipc_request(request),
+            Err(err) => Response::error(err),
+        };
+
+        let mut queued: Vec<u8> = Vec::new();
+        queue_line(&mut queued, &response);
+        Connection::Answering(queued)
+    }
+
+    // The client's source writes the acknowledgement, and the events from
+    // then on whenever they didn't fit into the socket right away
+    fn subscribe(&mut self, stream: UnixStream, events: Vec<EventKind>) -> Connection {
+        let mut queued: Vec<u8> = Vec::new();
+        queue_line(&mut queued, &Response::ok(&events));
+
+        let subscribers: &mut IpcSubscribers = &mut self.ipc_subscribers;
+        let id: u64 = subscribers.next_id;
+        subscribers.next_id += 1;
+        subscribers.subscribers.push(Subscriber {
+            id,
+            stream,
+            events,
+            queued,
+        });
+        Connection::Subscribed(id)
+    }
+
+    // Whether the subscriber is still there after writing what it has
+    // queued
+    fn flush_ipc_subscriber(&mut self, id: u64) -> bool {
+        let subscribers: &mut Vec<Subscriber> = &mut self.ipc_subscribers.subscribers;
+        let Some(index) = subscribers
+            .iter()
+            .position(|subscriber: &Subscriber| subscriber.id == id)
+        else {
+            return false;
+        };
+
+        let subscriber: &mut Subscriber = &mut subscribers[index];
+        let gone: bool = hung_up(&subscriber.stream)
+            || flush(&subscriber.stream, &mut subscriber.queued).is_err();
+        if gone {
+            subscribers.remove(index);
+        }
+        !gone
+    }
+
+    // Subscribers that don't keep up are dropped rather than buffered
+    // without end. Shutting their socket down wakes their source, which
+    // removes itself.
+    pub fn emit_ipc_event(&mut self, event: IpcEvent) {
+        let kind: EventKind = event.kind();
+        self.ipc_subscribers
+            .subscribers
+            .retain_mut(|subscriber: &mut Subscriber| {
+                if !subscriber.events.contains(&kind) {
+                    return true;
+                }
+                queue_line(&mut subscriber.queued, &event);
+                let keep: bool = subscriber.queued.len() <= MAX_QUEUED
+                    && flush(&subscriber.stream, &mut subscriber.queued).is_ok();
+                if !keep {
+                    let _ = subscriber.stream.shutdown(Shutdown::Both);
+                }
+                keep
+            });
+    }
+
+    // One after the other, stopping at the first that fails
+    pub fn run_commands(&mut self, chain: CommandChain) {
+        for request in chain.requests {
+            if let Some(err) = self.handle_ipc_request(request).error {
+                eprintln!("Failed to run \"{}\": {err}", chain.source);
+                return;
+            }
+        }
+    }
+
+    fn handle_ipc_request(&mut self, request: Request) -> Response {
+        match request {
+            Request::Services => Response::ok(self.services.status()),
+            Request::Latency => Response::ok(self.latency.report()),
+            Request::SetLatencyMode { enabled } => {
+                self.set_latency_mode(enabled);
+                Response::ok(self.latency.report())
+            }
+            Request::SetDamageDebug { enabled } => {
+                self.set_damage_debug(enabled);
+                Response::ok(self.damage_debug.is_enabled())
+            }
+            Request::Clients => Response::ok(self.audit.lock().unwrap().records()),
+            Request::Theme => Response::ok(self.theme),
+            Request::SetTheme { theme } => {
+                self.set_theme(theme);
+                Response::ok(self.theme)
+            }
+            Request::PointerDevices => Response::ok(self.pointer_devices()),
+            Request::SetPointerSpeed {
+                device,
+                speed,
+                profile,
+            } => match self.set_pointer_speed(device, speed, profile) {
+                Ok(()) => Response::ok(self.pointer_devices()),
+                Err(err) => Response::error(err),
+            },
+            Request::Type { text } => match self.type_text(&text) {
+                Ok(method) => Response::ok(method),
+                Err(err) => Response::error(err),
+            },
+            Request::Minimized => Response::ok(self.minimized_windows()),
+            Request::Unminimize { index } => match self.unminimize(index) {
+                Ok(()) => Response::ok(self.minimized_windows()),
+                Err(err) => Response::error(err),
+            },
+            Request::Tree => Response::ok(self.tree()),
+            Request::Focus { id } => match self.focus_window_by_id(id) {
+                Ok(()) => Response::ok(id),
+                Err(err) => Response::error(err),
+            },
+            Request::MoveToWorkspace { id, workspace } => {
+                match self.move_to_workspace(id, workspace) {
+                    Ok(()) => Response::ok(workspace),
+                    Err(err) => Response::error(err),
+                }
+            }
+            Request::Gaps => Response::ok(self.gap_status()),
+            Request::SetGaps {
+                inner,
+                outer,
+                relative,
+            } => Response::ok(self.set_gaps(inner, outer, relative)),
+            Request::SwitchWorkspace { workspace } => match self.switch_to_workspace(workspace) {
+                Ok(()) => Response::ok(workspace),
+                Err(err) => Response::error(err),
+            },
+            Request::Fullscreen { criteria, enabled } => {
+                match self.fullscreen_windows(&criteria, enabled) {
+                    Ok(ids) => Response::ok(ids),
+                    Err(err) => Response::error(err),
+                }
+            }
+            Request::Move {
+                criteria,
+                x,
+                y,
+                relative,
+            } => match self.move_windows(&criteria, Point::from((x, y)), relative) {
+                Ok(ids) => Response::ok(ids),
+                Err(err) => Response::error(err),
+            },
+            Request::Resize {
+                criteria,
+                width,
+                height,
+                relative,
+            } => match self.resize_windows(&criteria, Size::from((width, height)), relative) {
+                Ok(ids) => Response::ok(ids),
+                Err(err) => Response::error(err),
+            },
+            Request::ReloadConfig => match self.reload_config() {
+                Ok(()) => Response::ok(()),
+                Err(err) => Response::error(err),
+            },
+            Request::Quit => {
+                self.loop_signal.stop();
+                Response::ok(())
+            }
+            Request::Presentation => Response::ok(self.presentation_status()),
+            Request::SetPresentation { enabled } => {
+                self.set_presentation_mode(enabled);
+                Response::ok(self.presentation_status())
+            }
+            Request::SetBlanked { output, blanked } => {
+                match self.set_output_blanked(&output, blanked) {
+                    Ok(()) => Response::ok(self.presentation_status()),
+                    Err(err) => Response::error(err),
+                }
+            }
+            Request::Screenshot { output } => match self.take_screenshot(output.as_deref()) {
+                Ok(path) => Response::ok(path),
+                Err(err) => Response::error(err),
+            },
+            Request::Subscribe { .. } => Response::error("subscribe keeps its connection open"),
+            Request::Globals => Response::ok(self.global_status()),
+            Request::SetGlobal { name, enabled } => match self.set_global_enabled(name, enabled) {
+                Ok(()) => Response::ok(self.global_status()),
+                Err(err) => Response::error(err),
+            },
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use smithay::input::keyboard::ModifiersState;
+
+use crate::{config::KeyChord, ipc::CommandChain, snap::SnapTarget, state::State};
+
+// What a binding does. In the config unit actions are plain strings,
+// the others tables, e.g. "super+d" = { spawn = "fuzzel" }
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    // Removes a default binding
+    None,
+    Spawn(String),
+    SpawnTerminal,
+    CloseWindow,
+    Quit,
+    // Counting from 1, like the number keys
+    SwitchWorkspace(usize),
+    MoveToWorkspace(usize),
+    // Tag mode, see WorkspaceMode::Tags. Outside of it they switch and
+    // move like the two above.
+    ToggleTagView(usize),
+    ToggleWindowTag(usize),
+    NextWorkspace,
+    PrevWorkspace,
+    ToggleOverview,
+    Snap(SnapTarget),
+    BrightnessUp,
+    BrightnessDown,
+    GrowMaster,
+    ShrinkMaster,
+    SwapMaster,
+    SwapNext,
+    SwapPrev,
+    // Both the inner and the outer gaps
+    GrowGaps,
+    ShrinkGaps,
+    ToggleMaximize,
+    // Between tiled and floating
+    ToggleFloating,
+    Minimize,
+    // The most recently minimized window
+    Unminimize,
+    ReloadConfig,
+    // Turns the output under the pointer off or back on
+    ToggleBlank,
+    TogglePresentation,
+    // Of the output the pointer is on, saved to ~/Pictures
+    Screenshot,
+    // Shows or hides the list of all bindings
+    Cheatsheet,
+    // IPC requests one after the other, see CommandChain
+    Commands(CommandChain),
+}
+
+// The cheatsheet's sections, in this order. Bindings can name their own,
+// those come after these.
+pub const CATEGORIES: [&str; 6] = [
+    "Windows",
+    "Layout",
+    "Workspaces",
+    "Launch",
+    "Displays",
+    "Session",
+];
+
+impl Action {
+    // Actions that don't apply right now leave the key to the client
+    pub fn is_available(&self, state: &State) -> bool {
+        match self {
+            Action::BrightnessUp | Action::BrightnessDown => state.backlight.is_some(),
+            _ => true,
+        }
+    }
+
+    pub fn category(&self) -> &'static str {
+        match self {
+            Action::CloseWindow
+            | Action::Snap(_)
+            | Action::ToggleMaximize
+            | Action::ToggleFloating
+            | Action::Minimize
+            | Action::Unminimize => "Windows",
+            Action::GrowMaster
+            | Action::ShrinkMaster
+            | Action::SwapMaster
+            | Action::SwapNext
+            | Action::SwapPrev
+            | Action::GrowGaps
+            | Action::ShrinkGaps => "Layout",
+            Action::SwitchWorkspace(_)
+            | Action::MoveToWorkspace(_)
+            | Action::ToggleTagView(_)
+            | Action::ToggleWindowTag(_)
+            | Action::NextWorkspace
+            | Action::PrevWorkspace
+            | Action::ToggleOverview => "Workspaces",
+            Action::None | Action::Spawn(_) | Action::SpawnTerminal => "Launch",
+            Action::BrightnessUp
+            | Action::BrightnessDown
+            | Action::ToggleBlank
+            | Action::TogglePresentation => "Displays",
+            Action::Quit | Action::ReloadConfig | Action::Screenshot | Action::Cheatsheet => {
+                "Session"
+            }
+            Action::Commands(_) => "Commands",
+        }
+    }
+
+    // For the cheatsheet, when the binding has no description of its own
+    pub fn describe(&self) -> String {
+        match self {
+            Action::None => "Nothing".into(),
+            Action::Spawn(command) => format!("Run {command}"),
+            Action::SpawnTerminal => "Open a terminal".into(),
+            Action::CloseWindow => "Close the window".into(),
+            Action::Quit => "Quit pulseWM".into(),
+            Action::SwitchWorkspace(number) => format!("Go to workspace {number}"),
+            Action::MoveToWorkspace(number) => format!("Move the window to workspace {number}"),
+            Action::ToggleTagView(number) => format!("Show or hide tag {number}"),
+            Action::ToggleWindowTag(number) => format!("Add or remove tag {number}"),
+            Action::NextWorkspace => "Next workspace".into(),
+            Action::PrevWorkspace => "Previous workspace".into(),
+            Action::ToggleOverview => "Overview".into(),
+            Action::Snap(target) => format!("Snap to {target:?}"),
+            Action::BrightnessUp => "Brighter".into(),
+            Action::BrightnessDown => "Darker".into(),
+            Action::GrowMaster => "Grow the master area".into(),
+            Action::ShrinkMaster => "Shrink the master area".into(),
+            Action::SwapMaster => "Swap with the master window".into(),
+            Action::SwapNext => "Swap with the next window".into(),
+            Action::SwapPrev => "Swap with the previous window".into(),
+            Action::GrowGaps => "Wider gaps".into(),
+            Action::ShrinkGaps => "Narrower gaps".into(),
+            Action::ToggleMaximize => "Maximize or restore".into(),
+            Action::ToggleFloating => "Float or tile".into(),
+            Action::Minimize => "Minimize".into(),
+            Action::Unminimize => "Restore the last minimized window".into(),
+            Action::ReloadConfig => "Reload the config".into(),
+            Action::ToggleBlank => "Turn the output off or on".into(),
+            Action::TogglePresentation => "Presentation mode".into(),
+            Action::Screenshot => "Screenshot".into(),
+            Action::Cheatsheet => "This cheatsheet".into(),
+            Action::Commands(chain) => chain.source().to_string(),
+        }
+    }
+}
+
+// What a binding maps to in the config, the action alone or with what the
+// cheatsheet should say about it:
+//   "super+d" = { action = { spawn = "fuzzel" }, description = "Launcher" }
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum BindingEntry {
+    Described {
+        action: Action,
+        description: Option<String>,
+        category: Option<String>,
+    },
+    Plain(Action),
+}
+
+pub struct Binding {
+    // As written in the config, shown in the cheatsheet
+    pub name: String,
+    // More than one for sequences, pressed one after the other
+    pub chords: Vec<KeyChord>,
+    pub action: Action,
+    pub description: Option<String>,
+    pub category: Option<String>,
+}
+
+impl Binding {
+    pub fn description(&self) -> String {
+        self.description
+            .clone()
+            .unwrap_or_else(|| self.action.describe())
+    }
+
+    pub fn category(&self) -> &str {
+        self.category
+            .as_deref()
+            .unwrap_or_else(|| self.action.category())
+    }
+}
+
+// Chords separated by spaces, "super+x k" is super+x followed by k
+fn parse_sequence(name: &str) -> Result<Vec<KeyChord>, String> {
+    let chords: Vec<KeyChord> = name
+        .split_whitespace()
+        .map(|chord: &str| KeyChord::try_from(chord.to_string()))
+        .collect::<Result<_, _>>()?;
+    if chords.is_empty() {
+        return Err(format!("invalid key binding \"{name}\", no key"));
+    }
+    Ok(chords)
+}
+
+// [keybindings] in the config, on top of the defaults:
+//   "super+Return" = "spawn_terminal"
+//   "super+d" = { spawn = "fuzzel" }
+//   "super+shift+q" = "none"
+//   "super+x k" = "close_window"
+#[derive(Deserialize)]
+#[serde(try_from = "HashMap<String, BindingEntry>")]
+pub struct Keybindings {
+    bindings: Vec<Binding>,
+}
+
+const DEFAULTS: [(&str, Action); 31] = [
+    ("super+Return", Action::SpawnTerminal),
+    ("super+shift+q", Action::CloseWindow),
+    ("super+shift+e", Action::Quit),
+    ("super+shift+c", Action::ReloadConfig),
+    ("super+Tab", Action::ToggleOverview),
+    ("super+Page_Down", Action::NextWorkspace),
+    ("super+Page_Up", Action::PrevWorkspace),
+    ("super+Left", Action::Snap(SnapTarget::LeftHalf)),
+    ("super+Right", Action::Snap(SnapTarget::RightHalf)),
+    ("super+u", Action::Snap(SnapTarget::TopLeft)),
+    ("super+i", Action::Snap(SnapTarget::TopRight)),
+    ("super+j", Action::Snap(SnapTarget::BottomLeft)),
+    ("super+k", Action::Snap(SnapTarget::BottomRight)),
+    ("super+c", Action::Snap(SnapTarget::Center)),
+    ("super+l", Action::GrowMaster),
+    ("super+h", Action::ShrinkMaster),
+    ("super+shift+Return", Action::SwapMaster),
+    ("super+shift+Right", Action::SwapNext),
+    ("super+shift+Left", Action::SwapPrev),
+    ("super+equal", Action::GrowGaps),
+    ("super+minus", Action::ShrinkGaps),
+    ("super+m", Action::ToggleMaximize),
+    ("super+space", Action::ToggleFloating),
+    ("super+n", Action::Minimize),
+    ("super+shift+n", Action::Unminimize),
+    ("super+b", Action::ToggleBlank),
+    ("super+p", Action::TogglePresentation),
+    ("Print", Action::Screenshot),
+    ("super+slash", Action::Cheatsheet),
+    ("XF86MonBrightnessUp", Action::BrightnessUp),
+    ("XF86MonBrightnessDown", Action::BrightnessDown),
+];
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            bindings: DEFAULTS
+                .into_iter()
+                .map(|(name, action)| Binding {
+                    name: name.to_string(),
+                    chords: parse_sequence(name).unwrap(),
+                    action,
+                    description: None,
+                    category: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<HashMap<String, BindingEntry>> for Keybindings {
+    type Error = String;
+
+    fn try_from(configured: HashMap<String, BindingEntry>) -> Result<Self, Self::Error> {
+        let mut keybindings: Self = Self::default();
+
+        for (name, entry) in configured {
+            let chords: Vec<KeyChord> = parse_sequence(&name)?;
+            let (action, description, category) = match entry {
+                BindingEntry::Described {
+                    action,
+                    description,
+                    category,
+                } => (action, description, category),
+                BindingEntry::Plain(action) => (action, None, None),
+            };
+
+            keybindings
+                .bindings
+                .retain(|binding: &Binding| binding.chords != chords);
+            if action != Action::None {
+                keybindings.bindings.push(Binding {
+                    name,
+                    chords,
+                    action,
+                    description,
+                    category,
+                });
+            }
+        }
+
+        Ok(keybindings)
+    }
+}
+
+impl Keybindings {
+    // The binding the key continues after the chords in `pending`, and
+    // whether that completes it. A complete binding wins over longer
+    // sequences starting the same way.
+    // `keysyms` are the symbols the key produces, with and without the
+    // modifiers applied, so "super+shift+1" matches whatever shift+1 types
+    pub fn find(
+        &self,
+        pending: &[KeyChord],
+        modifiers: &ModifiersState,
+        keysyms: &[u32],
+    ) -> Option<(&Binding, bool)> {
+        let mut candidates: Vec<&Binding> = self
+            .bindings
+            .iter()
+            .filter(|binding: &&Binding| {
+                binding.chords.len() > pending.len()
+                    && binding.chords.starts_with(pending)
+                    && keysyms.iter().any(|keysym: &u32| {
+                        binding.chords[pending.len()].matches(modifiers, *keysym)
+                    })
+            })
+            .collect();
+        candidates.sort_by_key(|binding: &&Binding| binding.chords.len());
+        candidates
+            .first()
+            .map(|binding: &&Binding| (*binding, binding.chords.len() == pending.len() + 1))
+    }
+
+    // Grouped by category, the built-in ones first, bindings sorted by
+    // name within each
+    pub fn by_category(&self) -> Vec<(String, Vec<&Binding>)> {
+        let mut categories: Vec<(String, Vec<&Binding>)> = Vec::new();
+        for binding in &self.bindings {
+            let category: &str = binding.category();
+            match categories
+                .iter_mut()
+                .find(|(name, _)| name.as_str() == category)
+            {
+                Some((_, bindings)) => bindings.push(binding),
+                None => categories.push((category.to_string(), vec![binding])),
+            }
+        }
+
+        let rank = |name: &str| -> usize {
+            CATEGORIES
+                .iter()
+                .position(|category: &&str| *category == name)
+                .unwrap_or(CATEGORIES.len())
+        };
+        categories.sort_by(|(a, _), (b, _)| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+        for (_, bindings) in &mut categories {
+            bindings.sort_by(|a: &&Binding, b: &&Binding| a.name.cmp(&b.name));
+        }
+        categories
+    }
+}
+
+// The chords of a sequence typed so far. The next one has to follow
+// within SEQUENCE_TIMEOUT, the OSD shows what's pending until then.
+#[derive(Default)]
+pub struct KeySequence {
+    chords: Vec<KeyChord>,
+    names: Vec<String>,
+    last: Option<Instant>,
+}
+
+// About as long as the OSD stays up
+const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+impl KeySequence {
+    pub fn pending(&self) -> &[KeyChord] {
+        match self.last {
+            Some(last) if last.elapsed() < SEQUENCE_TIMEOUT => &self.chords,
+            _ => &[],
+        }
+    }
+}
+
+impl State {
+    // `chord` continued a sequence binding without completing it, `name`
+    // is how the config spells it
+    pub fn continue_key_sequence(&mut self, chord: KeyChord, name: String) {
+        if self.key_sequence.pending().is_empty() {
+            self.key_sequence.chords.clear();
+            self.key_sequence.names.clear();
+        }
+        self.key_sequence.chords.push(chord);
+        self.key_sequence.names.push(name);
+        self.key_sequence.last = Some(Instant::now());
+
+        let prefix: String = self.key_sequence.names.join(" ");
+        self.osd.show(format!("{prefix} …"), None);
+        self.request_redraw();
+    }
+
+    pub fn end_key_sequence(&mut self) {
+        if !self.key_sequence.chords.is_empty() {
+            self.key_sequence = KeySequence::default();
+            self.osd.hide();
+            self.request_redraw();
+        }
+    }
+
+    pub fn run_action(&mut self, action: Action) {
+        match action {
+            Action::None => {}
+            Action::Spawn(command) => {
+                self.launch(&command);
+            }
+            Action::SpawnTerminal => {
+                let terminal: String = self.config.programs.terminal.clone();
+                self.launch(&terminal);
+            }
+            Action::CloseWindow => {
+                if let Some(window) = self.focused_window() {
+                    window.toplevel().send_close();
+                }
+            }
+            Action::Quit => self.loop_signal.stop(),
+            Action::SwitchWorkspace(number) => self.switch_workspace(number.saturating_sub(1)),
+            Action::MoveToWorkspace(number) => {
+                self.move_focused_to_workspace(number.saturating_sub(1))
+            }
+            Action::ToggleTagView(number) => self.toggle_tag_view(number.saturating_sub(1)),
+            Action::ToggleWindowTag(number) => self.toggle_focused_tag(number.saturating_sub(1)),
+            Action::NextWorkspace => self.cycle_workspace(1),
+            Action::PrevWorkspace => self.cycle_workspace(-1),
+            Action::ToggleOverview => self.toggle_overview(),
+            Action::Snap(target) => self.snap_focused_window(target),
+            Action::BrightnessUp => self.adjust_brightness(self.config.brightness.step as i32),
+            Action::BrightnessDown => self.adjust_brightness(-(self.config.brightness.step as i32)),
+            Action::GrowMaster => self.grow_master(),
+            Action::ShrinkMaster => self.shrink_master(),
+            Action::SwapMaster => self.swap_focused_with_master(),
+            Action::SwapNext => self.swap_focused(1),
+            Action::SwapPrev => self.swap_focused(-1),
+            Action::GrowGaps => self.grow_gaps(),
+            Action::ShrinkGaps => self.shrink_gaps(),
+            Action::ToggleMaximize => self.toggle_maximize_focused(),
+            Action::ToggleFloating => {
+                if let Some(window) = self.focused_window() {
+                    self.toggle_floating(&window);
+                }
+            }
+            Action::Minimize => self.minimize_focused(),
+            Action::Unminimize => {
+                let _ = self.unminimize(None);
+            }
+            Action::ToggleBlank => self.toggle_blank_output(),
+            Action::TogglePresentation => self.toggle_presentation_mode(),
+            Action::Cheatsheet => self.toggle_cheatsheet(),
+            Action::Commands(chain) => self.run_commands(chain),
+            Action::Screenshot => {
+                if let Err(err) = self.take_screenshot(None) {
+                    eprintln!("Failed to take screenshot: {err}");
+                }
+            }
+            Action::ReloadConfig => {
+                if let Err(err) = self.reload_config() {
+                    eprintln!("Failed to reload config: {err}");
+                    self.osd.show("Config has errors, not reloaded", None);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use smithay::{
+    desktop::Window,
+    output::Output,
+    reexports::wayland_protocols::xdg::shell::server::xdg_toplevel,
+    utils::{Logical, Rectangle},
+};
+
+use crate::{config::KioskNewWindows, state::State};
+
+impl State {
+    pub fn kiosk(&self) -> bool {
+        self.config.kiosk.enabled
+    }
+
+    // Every toplevel covers the first output, there is nothing else to show
+    pub fn map_kiosk_window(&mut self, window: Window) {
+        let area: Rectangle<i32, Logical> = self
+            .space
+            .outputs()
+            .next()
+            .and_then(|output: &Output| self.space.output_geometry(output))
+            .unwrap_or_default();
+
+        window.toplevel().with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Fullscreen);
+            state.size = Some(area.size);
+        });
+
+        let current: Option<Window> = self.space.elements().last().cloned();
+        self.space.map_element(window.clone(), area.loc, false);
+
+        match (self.config.kiosk.new_windows, current) {
+            (KioskNewWindows::Replace, Some(current)) => {
+                current.toplevel().send_close();
+                self.focus_window(&window);
+            }
+            (KioskNewWindows::StackBehind, Some(current)) => {
+                self.space.raise_element(&current, false);
+            }
+            (_, None) => self.focus_window(&window),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::state::State;
+
+// Enough for a few seconds of busy typing or pointer motion
+const MAX_SAMPLES: usize = 1000;
+
+// Time from an input event arriving to the first frame submitted after it,
+// which is what the frame scheduler can actually influence
+#[derive(Default)]
+pub struct LatencyTracker {
+    enabled: bool,
+    // Oldest input event no submitted frame could have shown yet
+    pending: Option<Instant>,
+    samples: VecDeque<Duration>,
+}
+
+#[derive(Serialize)]
+pub struct LatencyReport {
+    pub enabled: bool,
+    pub samples: usize,
+    #[serde(flatten)]
+    pub summary: Option<LatencySummary>,
+}
+
+#[derive(Serialize)]
+pub struct LatencySummary {
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyTracker {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    // Starts over with fresh samples every time it gets enabled
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            self.samples.clear();
+        }
+        self.enabled = enabled;
+        self.pending = None;
+    }
+
+    pub fn input_received(&mut self, at: Instant) {
+        if self.enabled && self.pending.is_none() {
+            self.pending = Some(at);
+        }
+    }
+
+    pub fn frame_submitted(&mut self, at: Instant) {
+        let Some(received) = self.pending.take() else {
+            return;
+        };
+
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples
+            .push_back(at.saturating_duration_since(received));
+    }
+
+    pub fn report(&self) -> LatencyReport {
+        LatencyReport {
+            enabled: self.enabled,
+            samples: self.samples.len(),
+            summary: summarize(self.samples.iter().copied().collect()),
+        }
+    }
+}
+
+fn summarize(mut samples: Vec<Duration>) -> Option<LatencySummary> {
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort();
+
+    let ms = |duration: Duration| -> f64 { duration.as_secs_f64() * 1000.0 };
+    // Nearest rank
+    let percentile = |p: usize| -> f64 {
+        let rank: usize = (samples.len() * p + 99) / 100;
+        ms(samples[rank.clamp(1, samples.len()) - 1])
+    };
+    let total: Duration = samples.iter().sum();
+
+    Some(LatencySummary {
+        min_ms: ms(samples[0]),
+        mean_ms: ms(total) / samples.len() as f64,
+        p50_ms: percentile(50),
+        p95_ms: percentile(95),
+        p99_ms: percentile(99),
+        max_ms: ms(samples[samples.len() - 1]),
+    })
+}
+
+impl State {
+    pub fn set_latency_mode(&mut self, enabled: bool) {
+        self.latency.set_enabled(enabled);
+        eprintln!(
+            "Input latency measurement {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use std::time::Duration;
+
+use smithay::{
+    backend::renderer::{
+        element::{default_primary_scanout_output_compare, AsRenderElements, RenderElementStates},
+        gles::GlesRenderer,
+    },
+    delegate_layer_shell,
+    desktop::{
+        layer_map_for_output,
+        utils::{surface_primary_scanout_output, update_surface_primary_scanout_output},
+        LayerSurface, Window, WindowSurfaceType,
+    },
+    input::keyboard::KeyboardHandle,
+    output::Output,
+    reexports::wayland_server::protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    utils::{Logical, Physical, Point, Rectangle, Scale, SERIAL_COUNTER},
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        shell::wlr_layer::{
+            self, Layer, LayerSurfaceData, WlrLayerShellHandler, WlrLayerShellState,
+        },
+    },
+};
+
+use crate::{
+    fractional_scale,
+    render::CustomRenderElements,
+    stacking::{self, KeyboardInteractivity, StackEntry, StackLayer},
+    state::State,
+};
+
+fn stack_layer(layer: Layer) -> StackLayer {
+    match layer {
+        Layer::Background => StackLayer::Background,
+        Layer::Bottom => StackLayer::Bottom,
+        Layer::Top => StackLayer::Top,
+        Layer::Overlay => StackLayer::Overlay,
+    }
+}
+
+fn interactivity(layer: &LayerSurface) -> KeyboardInteractivity {
+    match layer.cached_state().keyboard_interactivity {
+        wlr_layer::KeyboardInteractivity::None => KeyboardInteractivity::None,
+        wlr_layer::KeyboardInteractivity::OnDemand => KeyboardInteractivity::OnDemand,
+        wlr_layer::KeyboardInteractivity::Exclusive => KeyboardInteractivity::Exclusive,
+    }
+}
+
+impl State {
+    // The output a layer surface is on
+    pub fn layer_output(&self, surface: &WlSurface) -> Option<Output> {
+        self.space
+            .outputs()
+            .find(|output: &&Output| {
+                layer_map_for_output(output)
+                    .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                    .is_some()
+            })
+            .cloned()
+    }
+
+    // Lays out the output's layer surfaces again and retiles if that moved
+    // an exclusive zone
+    fn arrange_layers(&mut self, output: &Output) {
+        let changed: bool = {
+            let mut map = layer_map_for_output(output);
+            let before: Rectangle<i32, Logical> = map.non_exclusive_zone();
+            map.arrange();
+            map.non_exclusive_zone() != before
+        };
+        if changed {
+            self.arrange();
+        }
+    }
+
+    pub fn layer_commit(&mut self, surface: &WlSurface) {
+        let Some(output) = self.layer_output(surface) else {
+            return;
+        };
+        self.arrange_layers(&output);
+
+        let initial_configure_sent: bool = with_states(surface, |states: &SurfaceData| {
+            states
+                .data_map
+                .get::<LayerSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .initial_configure_sent
+        });
+        if !initial_configure_sent {
+            // arrange() already picked the size, it just wasn't sent
+            if let Some(layer) = layer_map_for_output(&output)
+                .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+            {
+                layer.layer_surface().send_configure();
+            }
+        }
+
+        self.update_layer_focus();
+    }
+
+    // Exclusive surfaces on Top and Overlay, like launchers, take the
+    // keyboard for as long as they are mapped
+    fn update_layer_focus(&mut self) {
+        if self.is_locked() {
+            return;
+        }
+
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        let current: Option<WlSurface> = keyboard.current_focus();
+
+        let mut stack: Vec<StackEntry<WlSurface>> = self
+            .space
+            .elements()
+            .map(|window: &Window| {
+                let layer: StackLayer = if self.is_fullscreen(window) {
+                    StackLayer::Fullscreen
+                } else {
+                    StackLayer::Windows
+                };
+                StackEntry::new(
+                    window.toplevel().wl_surface().clone(),
+                    layer,
+                    KeyboardInteractivity::OnDemand,
+                )
+            })
+            .collect();
+        for output in self.space.outputs() {
+            for layer in layer_map_for_output(output).layers() {
+                stack.push(StackEntry::new(
+                    layer.wl_surface().clone(),
+                    stack_layer(layer.layer()),
+                    interactivity(layer),
+                ));
+            }
+        }
+        stacking::sort(&mut stack);
+
+        let focus: Option<WlSurface> = stacking::keyboard_focus(&stack, current.as_ref()).cloned();
+        if focus.is_some() && focus != current {
+            keyboard.set_focus(self, focus, SERIAL_COUNTER.next_serial());
+        }
+    }
+
+    // Top and Overlay surfaces cover windows, `upper` picks those, otherwise
+    // the Bottom and Background ones below them
+    pub fn layer_under(&self, location: Point<f64, Logical>, upper: bool) -> Option<LayerSurface> {
+        let output: &Output = self.space.output_under(location).next()?;
+        let output_location: Point<f64, Logical> = self.space.output_geometry(output)?.loc.to_f64();
+        let layers: [Layer; 2] = if upper {
+            [Layer::Overlay, Layer::Top]
+        } else {
+            [Layer::Bottom, Layer::Background]
+        };
+
+        let map = layer_map_for_output(output);
+        let layer: Option<LayerSurface> = layers
+            .into_iter()
+            .find_map(|layer: Layer| map.layer_under(layer, location - output_location))
+            .filter(|layer: &&LayerSurface| !self.is_layer_hidden(layer))
+            .cloned();
+        layer
+    }
+
+    // The surface of a layer surface (or one of its subsurfaces) at
+    // `location`, with its position in global coordinates
+    pub fn layer_surface_under(
+        &self,
+        location: Point<f64, Logical>,
+        upper: bool,
+    ) -> Option<(WlSurface, Point<i32, Logical>)> {
+        let layer: LayerSurface = self.layer_under(location, upper)?;
+        let output: Output = self.layer_output(layer.wl_surface())?;
+        let output_location: Point<i32, Logical> = self.space.output_geometry(&output)?.loc;
+        let layer_location: Point<i32, Logical> =
+            output_location + layer_map_for_output(&output).layer_geometry(&layer)?.loc;
+
+        layer
+            .surface_under(location - layer_location.to_f64(), WindowSurfaceType::ALL)
+            .map(|(surface, offset)| (surface, layer_location + offset))
+    }
+
+    // Clicking a bar or launcher that wants the keyboard focuses it
+    pub fn focus_layer(&mut self, layer: &LayerSurface) {
+        if !layer.can_receive_keyboard_focus() {
+            return;
+        }
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        keyboard.set_focus(
+            self,
+            Some(layer.wl_surface().clone()),
+            SERIAL_COUNTER.next_serial(),
+        );
+    }
+
+    // The surfaces on one layer, topmost first
+    pub fn layer_elements(
+        &self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        layer: Layer,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        let scale: f64 = output.current_scale().fractional_scale();
+
+        let map = layer_map_for_output(output);
+        for surface in map.layers_on(layer).rev() {
+            if self.is_layer_hidden(surface) {
+                continue;
+            }
+            let Some(geometry) = map.layer_geometry(surface) else {
+                continue;
+            };
+            let location: Point<i32, Physical> = geometry.loc.to_physical_precise_round(scale);
+            let surface_elements: Vec<CustomRenderElements> =
+                surface.render_elements(renderer, location, Scale::from(scale), 1.0);
+            elements.extend(surface_elements);
+        }
+    }
+
+    pub fn send_layer_frames(&self, output: &Output, states: &RenderElementStates, time: Duration) {
+        let map = layer_map_for_output(output);
+        for layer in map.layers() {
+            layer.with_surfaces(|surface: &WlSurface, data: &SurfaceData| {
+                let primary: Option<Output> = update_surface_primary_scanout_output(
+                    surface,
+                    output,
+                    data,
+                    states,
+                    default_primary_scanout_output_compare,
+                );
+                if let Some(primary) = primary {
+                    fractional_scale::send_preferred_scale(&primary, data);
+                }
+            });
+
+            layer.send_frame(
+                output,
+                time,
+                Some(Duration::ZERO),
+                surface_primary_scanout_output,
+            );
+        }
+    }
+}
+
+impl WlrLayerShellHandler for State {
+    fn shell_state(&mut self) -> &mut WlrLayerShellState {
+        &mut self.layer_shell_state
+    }
+
+    // Surfaces without an output go where the pointer is
+    fn new_layer_surface(
+        &mut self,
+        surface: wlr_layer::LayerSurface,
+        output: Option<WlOutput>,
+        _layer: Layer,
+        namespace: String,
+    ) {
+        let output: Option<Output> = output
+            .as_ref()
+            .and_then(Output::from_resource)
+            .or_else(|| {
+                self.space
+                    .output_under(self.pointer_location)
+                    .next()
+                    .cloned()
+            })
+            .or_else(|| self.space.outputs().next().cloned());
+        let Some(output) = output else {
+            surface.send_close();
+            return;
+        };
+
+        let result =
+            layer_map_for_output(&output).map_layer(&LayerSurface::new(surface, namespace));
+        if let Err(err) = result {
+            eprintln!("Failed to map layer surface: {err}");
+        }
+    }
+
+    fn layer_destroyed(&mut self, surface: wlr_layer::LayerSurface) {
+        let Some(output) = self.layer_output(surface.wl_surface()) else {
+            return;
+        };
+
+        let changed: bool = {
+            let mut map = layer_map_for_output(&output);
+            let before: Rectangle<i32, Logical> = map.non_exclusive_zone();
+            let layer: Option<LayerSurface> = map
+                .layer_for_surface(surface.wl_surface(), WindowSurfaceType::TOPLEVEL)
+                .cloned();
+            if let Some(layer) = layer {
+                map.unmap_layer(&layer);
+            }
+            map.arrange();
+            map.non_exclusive_zone() != before
+        };
+        if changed {
+            self.arrange();
+        }
+
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        if keyboard.current_focus().as_ref() == Some(surface.wl_surface()) {
+            self.focus_previous();
+        }
+    }
+}
+delegate_layer_shell!(State);
@@ -0,0 +1,609 @@
+// Tiling math, kept free of any smithay types so it can be tested without
+// a compositor. Everything is in logical pixels.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    // Never goes negative, an area smaller than the inset just ends up empty
+    pub fn shrink(self, by: i32) -> Self {
+        let by_x: i32 = by.min(self.w / 2).max(0);
+        let by_y: i32 = by.min(self.h / 2).max(0);
+        Self::new(
+            self.x + by_x,
+            self.y + by_y,
+            self.w - 2 * by_x,
+            self.h - 2 * by_y,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LayoutWindow {
+    // From the client's xdg_toplevel min size, 0 if it doesn't care
+    pub min_width: i32,
+    pub min_height: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayoutParams {
+    // Share of the width the master column gets
+    pub master_ratio: f64,
+    // 0 puts every window in the stack
+    pub master_count: usize,
+    // Between windows
+    pub inner_gap: i32,
+    // Between windows and the edges of the area
+    pub outer_gap: i32,
+}
+
+impl Default for LayoutParams {
+    fn default() -> Self {
+        Self {
+            master_ratio: 0.55,
+            master_count: 1,
+            inner_gap: 0,
+            outer_gap: 0,
+        }
+    }
+}
+
+// How far the ratio can go, so neither column disappears
+pub const MIN_RATIO: f64 = 0.05;
+pub const MAX_RATIO: f64 = 0.95;
+
+// Master windows in a column on the left, the rest stacked on the right.
+// Returns one rect per window, in the same order.
+pub fn master_stack(windows: &[LayoutWindow], area: Rect, params: &LayoutParams) -> Vec<Rect> {
+    if windows.is_empty() {
+        return Vec::new();
+    }
+
+    let area: Rect = area.shrink(params.outer_gap);
+    let gap: i32 = params.inner_gap.max(0);
+    let master_count: usize = params.master_count.min(windows.len());
+    let (masters, stack) = windows.split_at(master_count);
+
+    // A single column, nothing to split horizontally
+    if masters.is_empty() || stack.is_empty() {
+        return column(windows, area, gap);
+    }
+
+    let column_gap: i32 = fit_gap(area.w, gap, 2);
+    let available: i32 = area.w - column_gap;
+    let min_master: i32 = min_width(masters);
+    let min_stack: i32 = min_width(stack);
+
+    let mut master_w: i32 =
+        (available as f64 * params.master_ratio.clamp(MIN_RATIO, MAX_RATIO)).round() as i32;
+    // Give up on minimum widths that can't be satisfied together
+    if min_master + min_stack <= available {
+        master_w = master_w.clamp(min_master, available - min_stack);
+    }
+    let stack_w: i32 = available - master_w;
+
+    let mut geometries: Vec<Rect> =
+        column(masters, Rect::new(area.x, area.y, master_w, area.h), gap);
+    geometries.extend(column(
+        stack,
+        Rect::new(area.x + master_w + column_gap, area.y, stack_w, area.h),
+        gap,
+    ));
+    geometries
+}
+
+fn min_width(windows: &[LayoutWindow]) -> i32 {
+    windows
+        .iter()
+        .map(|window: &LayoutWindow| window.min_width.max(0))
+        .max()
+        .unwrap_or(0)
+}
+
+// The gap between `count` parts of `length`, shrunk so the gaps alone never
+// take up more than all of it
+fn fit_gap(length: i32, gap: i32, count: usize) -> i32 {
+    if count < 2 {
+        return gap;
+    }
+    gap.min(length.max(0) / (count as i32 - 1))
+}
+
+// Windows on top of each other, filling `area` exactly
+fn column(windows: &[LayoutWindow], area: Rect, gap: i32) -> Vec<Rect> {
+    let gap: i32 = fit_gap(area.h, gap, windows.len());
+    let mins: Vec<i32> = windows
+        .iter()
+        .map(|window: &LayoutWindow| window.min_height)
+        .collect();
+
+    let mut y: i32 = area.y;
+    split(area.h, gap, &mins)
+        .into_iter()
+        .map(|height: i32| {
+            let rect: Rect = Rect::new(area.x, y, area.w, height);
+            y += height + gap;
+            rect
+        })
+        .collect()
+}
+
+// Splits `length` into `mins.len()` parts with `gap` between them. Parts
+// are equal unless that would make one smaller than its minimum, leftover
+// pixels go to the last parts. Gaps too wide for `length` are shrunk.
+pub fn split(length: i32, gap: i32, mins: &[i32]) -> Vec<i32> {
+    let count: i32 = mins.len() as i32;
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let gap: i32 = fit_gap(length, gap, mins.len());
+    let available: i32 = (length - gap * (count - 1)).max(0);
+    let mins: Vec<i32> = mins.iter().map(|min: &i32| (*min).max(0)).collect();
+
+    // Parts pinned to their minimum, the rest share what's left equally
+    let mut pinned: Vec<bool> = vec![false; mins.len()];
+    if mins.iter().sum::<i32>() <= available {
+        loop {
+            let remaining: i32 = available
+                - mins
+                    .iter()
+                    .zip(&pinned)
+                    .filter(|(_, pinned)| **pinned)
+                    .map(|(min, _)| *min)
+                    .sum::<i32>();
+            let free: i32 = pinned.iter().filter(|pinned: &&bool| !**pinned).count() as i32;
+            if free == 0 {
+                break;
+            }
+
+            let share: i32 = remaining / free;
+            let mut changed: bool = false;
+            for (min, pinned) in mins.iter().zip(pinned.iter_mut()) {
+                if !*pinned && *min > share {
+                    *pinned = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    let remaining: i32 = available
+        - mins
+            .iter()
+            .zip(&pinned)
+            .filter(|(_, pinned)| **pinned)
+            .map(|(min, _)| *min)
+            .sum::<i32>();
+    let free: i32 = pinned.iter().filter(|pinned: &&bool| !**pinned).count() as i32;
+    let (share, extra) = if free > 0 {
+        (remaining / free, remaining % free)
+    } else {
+        (0, 0)
+    };
+
+    let mut free_index: i32 = 0;
+    mins.iter()
+        .zip(&pinned)
+        .map(|(min, pinned)| {
+            if *pinned {
+                return *min;
+            }
+            free_index += 1;
+            if free_index > free - extra {
+                share + 1
+            } else {
+                share
+            }
+        })
+        .collect()
+}
+
+// The four edges of a border `width` wide around `rect`: top, bottom, left
+// and right. The top and bottom ones span the corners.
+pub fn border_rects(rect: Rect, width: i32) -> [Rect; 4] {
+    [
+        Rect::new(rect.x - width, rect.y - width, rect.w + 2 * width, width),
+        Rect::new(rect.x - width, rect.y + rect.h, rect.w + 2 * width, width),
+        Rect::new(rect.x - width, rect.y, width, rect.h),
+        Rect::new(rect.x + rect.w, rect.y, width, rect.h),
+    ]
+}
+
+// Overlapping area of two rects, 0 if they don't overlap
+fn overlap(a: &Rect, b: &Rect) -> i64 {
+    let w: i32 = (a.x + a.w).min(b.x + b.w) - a.x.max(b.x);
+    let h: i32 = (a.y + a.h).min(b.y + b.h) - a.y.max(b.y);
+    if w <= 0 || h <= 0 {
+        return 0;
+    }
+    w as i64 * h as i64
+}
+
+// Where a new floating window of `w` x `h` goes inside `area`: covering as
+// little of `focused` as possible first, then as little of `others`, and
+// the top left most spot of the ones that are equally good. Windows larger
+// than the area are shrunk to it.
+pub fn smart_place(w: i32, h: i32, area: Rect, focused: Option<Rect>, others: &[Rect]) -> Rect {
+    let w: i32 = w.clamp(0, area.w.max(0));
+    let h: i32 = h.clamp(0, area.h.max(0));
+    let max_x: i32 = area.x + area.w - w;
+    let max_y: i32 = area.y + area.h - h;
+
+    // Only spots touching an edge of the area or of another window can be
+    // the best one
+    let mut xs: Vec<i32> = vec![area.x, max_x];
+    let mut ys: Vec<i32> = vec![area.y, max_y];
+    for rect in focused.iter().chain(others) {
+        xs.extend([rect.x + rect.w, rect.x - w]);
+        ys.extend([rect.y + rect.h, rect.y - h]);
+    }
+    for positions in [&mut xs, &mut ys] {
+        positions.sort_unstable();
+        positions.dedup();
+    }
+    xs.retain(|x: &i32| (area.x..=max_x).contains(x));
+    ys.retain(|y: &i32| (area.y..=max_y).contains(y));
+
+    let mut best: Rect = Rect::new(area.x, area.y, w, h);
+    let mut best_score: (i64, i64) = (i64::MAX, i64::MAX);
+    for y in &ys {
+        for x in &xs {
+            let candidate: Rect = Rect::new(*x, *y, w, h);
+            let score: (i64, i64) = (
+                focused.map_or(0, |focused: Rect| overlap(&candidate, &focused)),
+                others
+                    .iter()
+                    .map(|other: &Rect| overlap(&candidate, other))
+                    .sum(),
+            );
+            // Rows first, so ties keep the top left most spot
+            if score < best_score {
+                best = candidate;
+                best_score = score;
+            }
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        w: 1000,
+        h: 600,
+    };
+
+    fn windows(count: usize) -> Vec<LayoutWindow> {
+        vec![LayoutWindow::default(); count]
+    }
+
+    fn params(master_ratio: f64, inner_gap: i32, outer_gap: i32) -> LayoutParams {
+        LayoutParams {
+            master_ratio,
+            master_count: 1,
+            inner_gap,
+            outer_gap,
+        }
+    }
+
+    fn overlaps(a: &Rect, b: &Rect) -> bool {
+        a.x < b.x + b.w && b.x < a.x + a.w && a.y < b.y + b.h && b.y < a.y + a.h
+    }
+
+    #[test]
+    fn no_windows_no_geometries() {
+        assert!(master_stack(&[], AREA, &LayoutParams::default()).is_empty());
+    }
+
+    #[test]
+    fn single_window_fills_area() {
+        let layout = master_stack(&windows(1), AREA, &LayoutParams::default());
+        assert_eq!(layout, [AREA]);
+    }
+
+    #[test]
+    fn single_window_keeps_outer_gap() {
+        let layout = master_stack(&windows(1), AREA, &params(0.5, 10, 20));
+        assert_eq!(layout, [Rect::new(20, 20, 960, 560)]);
+    }
+
+    #[test]
+    fn two_windows_split_by_ratio() {
+        let layout = master_stack(&windows(2), AREA, &params(0.6, 0, 0));
+        assert_eq!(
+            layout,
+            [Rect::new(0, 0, 600, 600), Rect::new(600, 0, 400, 600)]
+        );
+    }
+
+    #[test]
+    fn inner_gap_separates_columns() {
+        let layout = master_stack(&windows(2), AREA, &params(0.5, 10, 0));
+        assert_eq!(
+            layout,
+            [Rect::new(0, 0, 495, 600), Rect::new(505, 0, 495, 600)]
+        );
+    }
+
+    #[test]
+    fn gaps_between_stacked_windows() {
+        let layout = master_stack(&windows(3), AREA, &params(0.5, 10, 5));
+        assert_eq!(
+            layout,
+            [
+                Rect::new(5, 5, 490, 590),
+                Rect::new(505, 5, 490, 290),
+                Rect::new(505, 305, 490, 290),
+            ]
+        );
+    }
+
+    #[test]
+    fn odd_counts_fill_the_column_exactly() {
+        for count in 2..=8 {
+            let layout = master_stack(&windows(count), AREA, &params(0.5, 7, 0));
+            let stack = &layout[1..];
+
+            assert_eq!(stack.first().unwrap().y, 0);
+            let last = stack.last().unwrap();
+            assert_eq!(last.y + last.h, AREA.h, "{count} windows");
+
+            for pair in stack.windows(2) {
+                assert_eq!(pair[0].y + pair[0].h + 7, pair[1].y);
+                // Leftover pixels spread one each, never more
+                assert!((pair[0].h - pair[1].h).abs() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn leftover_pixels_go_to_the_last_windows() {
+        assert_eq!(split(10, 0, &[0, 0, 0]), [3, 3, 4]);
+        assert_eq!(split(11, 0, &[0, 0, 0]), [3, 4, 4]);
+        assert_eq!(split(12, 0, &[0, 0, 0]), [4, 4, 4]);
+    }
+
+    #[test]
+    fn nothing_overlaps() {
+        for count in 1..=9 {
+            for master_count in 0..=3 {
+                let layout = master_stack(
+                    &windows(count),
+                    AREA,
+                    &LayoutParams {
+                        master_ratio: 0.55,
+                        master_count,
+                        inner_gap: 4,
+                        outer_gap: 8,
+                    },
+                );
+                assert_eq!(layout.len(), count);
+                for (i, a) in layout.iter().enumerate() {
+                    for b in &layout[i + 1..] {
+                        assert!(!overlaps(a, b), "{a:?} overlaps {b:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn several_masters_share_the_master_column() {
+        let layout = master_stack(
+            &windows(3),
+            AREA,
+            &LayoutParams {
+                master_ratio: 0.5,
+                master_count: 2,
+                inner_gap: 0,
+                outer_gap: 0,
+            },
+        );
+        assert_eq!(
+            layout,
+            [
+                Rect::new(0, 0, 500, 300),
+                Rect::new(0, 300, 500, 300),
+                Rect::new(500, 0, 500, 600),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_masters_stacks_everything() {
+        let layout = master_stack(
+            &windows(2),
+            AREA,
+            &LayoutParams {
+                master_count: 0,
+                ..LayoutParams::default()
+            },
+        );
+        assert_eq!(
+            layout,
+            [Rect::new(0, 0, 1000, 300), Rect::new(0, 300, 1000, 300)]
+        );
+    }
+
+    #[test]
+    fn more_masters_than_windows_is_one_column() {
+        let layout = master_stack(
+            &windows(2),
+            AREA,
+            &LayoutParams {
+                master_count: 5,
+                ..LayoutParams::default()
+            },
+        );
+        assert_eq!(
+            layout,
+            [Rect::new(0, 0, 1000, 300), Rect::new(0, 300, 1000, 300)]
+        );
+    }
+
+    #[test]
+    fn ratio_is_clamped() {
+        let layout = master_stack(&windows(2), AREA, &params(1.5, 0, 0));
+        assert_eq!(layout[0].w, 950);
+        assert_eq!(layout[1].w, 50);
+
+        let layout = master_stack(&windows(2), AREA, &params(-1.0, 0, 0));
+        assert_eq!(layout[0].w, 50);
+    }
+
+    #[test]
+    fn min_height_is_honored_in_the_stack() {
+        let mut stack = windows(4);
+        stack[2].min_height = 400;
+
+        let layout = master_stack(&stack, AREA, &params(0.5, 0, 0));
+        assert_eq!(layout[1].h, 100);
+        assert_eq!(layout[2].h, 400);
+        assert_eq!(layout[3].h, 100);
+        assert_eq!(layout[3].y + layout[3].h, AREA.h);
+    }
+
+    #[test]
+    fn min_heights_pin_in_rounds() {
+        // Pinning the first raises nobody else above their share, pinning
+        // the second needs a second pass once the first is taken out
+        assert_eq!(split(100, 0, &[60, 25, 0]), [60, 25, 15]);
+    }
+
+    #[test]
+    fn impossible_min_heights_are_ignored() {
+        assert_eq!(split(100, 0, &[80, 80]), [50, 50]);
+    }
+
+    #[test]
+    fn min_width_pushes_the_ratio() {
+        let mut two = windows(2);
+        two[1].min_width = 700;
+
+        let layout = master_stack(&two, AREA, &params(0.5, 0, 0));
+        assert_eq!(layout[0].w, 300);
+        assert_eq!(layout[1].w, 700);
+
+        two[1].min_width = 0;
+        two[0].min_width = 800;
+        let layout = master_stack(&two, AREA, &params(0.5, 0, 0));
+        assert_eq!(layout[0].w, 800);
+    }
+
+    #[test]
+    fn impossible_min_widths_keep_the_ratio() {
+        let mut two = windows(2);
+        two[0].min_width = 600;
+        two[1].min_width = 600;
+
+        let layout = master_stack(&two, AREA, &params(0.5, 0, 0));
+        assert_eq!(layout[0].w, 500);
+        assert_eq!(layout[1].w, 500);
+    }
+
+    #[test]
+    fn tiny_areas_never_go_negative() {
+        let area = Rect::new(0, 0, 10, 10);
+        for count in 1..=5 {
+            for (inner_gap, outer_gap) in [(8, 20), (8, 2), (30, 0)] {
+                let layout =
+                    master_stack(&windows(count), area, &params(0.5, inner_gap, outer_gap));
+                for rect in layout {
+                    assert!(rect.w >= 0 && rect.h >= 0, "{rect:?}");
+                    assert!(
+                        rect.x >= area.x && rect.x + rect.w <= area.x + area.w,
+                        "{rect:?}"
+                    );
+                    assert!(
+                        rect.y >= area.y && rect.y + rect.h <= area.y + area.h,
+                        "{rect:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gaps_shrink_to_fit() {
+        assert_eq!(split(10, 8, &[0, 0, 0]), [0, 0, 0]);
+        assert_eq!(split(12, 5, &[0, 0, 0]), [0, 1, 1]);
+
+        let layout = master_stack(&windows(3), Rect::new(0, 0, 10, 10), &params(0.5, 30, 0));
+        assert_eq!(
+            layout,
+            [
+                Rect::new(0, 0, 0, 10),
+                Rect::new(10, 0, 0, 0),
+                Rect::new(10, 10, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_area_places_top_left() {
+        let rect = smart_place(300, 200, AREA, None, &[]);
+        assert_eq!(rect, Rect::new(0, 0, 300, 200));
+    }
+
+    #[test]
+    fn avoids_the_focused_window() {
+        let focused = Rect::new(0, 0, 500, 600);
+        let rect = smart_place(300, 200, AREA, Some(focused), &[]);
+        assert!(!overlaps(&rect, &focused), "{rect:?}");
+        assert_eq!(rect, Rect::new(500, 0, 300, 200));
+    }
+
+    #[test]
+    fn focused_window_matters_more_than_others() {
+        let focused = Rect::new(0, 0, 500, 600);
+        let other = Rect::new(500, 0, 500, 600);
+        let rect = smart_place(300, 200, AREA, Some(focused), &[other]);
+        assert!(!overlaps(&rect, &focused), "{rect:?}");
+    }
+
+    #[test]
+    fn fills_gaps_between_others() {
+        let others = [Rect::new(0, 0, 400, 600), Rect::new(700, 0, 300, 600)];
+        let rect = smart_place(300, 200, AREA, None, &others);
+        assert_eq!(rect, Rect::new(400, 0, 300, 200));
+    }
+
+    #[test]
+    fn stays_inside_the_area() {
+        let area = Rect::new(100, 50, 1000, 600);
+        let focused = Rect::new(100, 50, 990, 590);
+        let rect = smart_place(300, 200, area, Some(focused), &[]);
+        assert!(
+            rect.x >= area.x && rect.x + rect.w <= area.x + area.w,
+            "{rect:?}"
+        );
+        assert!(
+            rect.y >= area.y && rect.y + rect.h <= area.y + area.h,
+            "{rect:?}"
+        );
+    }
+
+    #[test]
+    fn larger_than_the_area_is_shrunk() {
+        let rect = smart_place(2000, 900, AREA, None, &[]);
+        assert_eq!(rect, AREA);
+    }
+}
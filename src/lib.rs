@@ -0,0 +1,86 @@
+// pulseWM is not a snake case name
+#![allow(non_snake_case)]
+
+// Private but for what other crates need: run for the pulseWM binary, the
+// config and IPC parsers for the fuzz targets and pulsectl
+
+mod acl;
+mod activation;
+mod animation;
+mod audit;
+mod backend;
+mod background;
+mod backlight;
+mod bar;
+mod borders;
+mod capture;
+mod cheatsheet;
+pub mod config;
+mod configure;
+mod cursor;
+mod damage_debug;
+mod data;
+mod data_control;
+mod decorations;
+mod devices;
+mod dmabuf;
+mod dnd;
+mod dpms;
+mod edid;
+mod encode;
+mod focus;
+mod fractional_scale;
+mod fullscreen;
+mod globals;
+#[cfg(test)]
+mod golden;
+mod grabs;
+mod idle;
+mod import;
+mod input;
+mod input_method;
+pub mod ipc;
+mod keybindings;
+mod kiosk;
+mod latency;
+mod layer_shell;
+mod layout;
+mod lock;
+mod logind;
+mod maximize;
+mod menu;
+mod minimize;
+mod osd;
+mod outputs;
+mod overview;
+mod placement;
+mod popups;
+mod presentation;
+mod profile;
+mod reload;
+mod render;
+mod root_scroll;
+mod rules;
+mod screencopy;
+mod screensaver;
+mod screenshot;
+mod services;
+mod snap;
+mod spawn;
+mod stacking;
+mod startup;
+mod state;
+mod subsurfaces;
+mod switcher;
+mod systemd;
+mod text;
+mod theme;
+mod tile_drag;
+mod tiling;
+mod title;
+mod transaction;
+mod tree;
+mod type_text;
+mod workspaces;
+
+pub use startup::run;
@@ -0,0 +1,399 @@
+use std::time::{Duration, Instant};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::OutputDamageTracker,
+            element::{
+                solid::{SolidColorBuffer, SolidColorRenderElement},
+                surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+                texture::{TextureBuffer, TextureRenderElement},
+                Kind,
+            },
+            gles::{GlesRenderer, GlesTexture},
+            Bind, Offscreen,
+        },
+    },
+    delegate_session_lock,
+    desktop::utils::{send_frames_surface_tree, surface_primary_scanout_output},
+    input::keyboard::KeyboardHandle,
+    output::Output,
+    reexports::wayland_server::protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    utils::{Buffer, Logical, Physical, Size, Transform, SERIAL_COUNTER},
+    wayland::session_lock::{
+        LockSurface, SessionLockHandler, SessionLockManagerState, SessionLocker,
+    },
+};
+
+use crate::{
+    animation::Animation, fractional_scale, outputs, render::CustomRenderElements, state::State,
+};
+
+const CURTAIN: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+// The frozen frame is halved this many times for its blurred copy. Each
+// pass averages 2x2 pixels through linear filtering, and scaling the result
+// back up smears it out again.
+const BLUR_PASSES: u32 = 4;
+
+pub enum LockState {
+    Unlocked,
+    // The desktop is frozen and blurs and fades out behind the curtain, with
+    // the locker's surfaces fading in on top. The locker only hears that the
+    // session is locked once every output showed the curtain fully opaque.
+    Locking {
+        confirmation: SessionLocker,
+        fade: Animation,
+    },
+    Locked,
+}
+
+// What an output showed when locking started, sharp and blurred, and how
+// far the curtain over it got
+struct FrozenOutput {
+    output: Output,
+    frame: Option<(TextureBuffer<GlesTexture>, TextureBuffer<GlesTexture>)>,
+    // The last frame rendered had the curtain fully opaque
+    opaque: bool,
+    // Such a frame is on screen
+    covered: bool,
+}
+
+pub struct SessionLock {
+    pub state: LockState,
+    surfaces: Vec<(Output, LockSurface)>,
+    frozen: Vec<FrozenOutput>,
+    curtain: SolidColorBuffer,
+}
+
+impl Default for SessionLock {
+    fn default() -> Self {
+        Self {
+            state: LockState::Unlocked,
+            surfaces: Vec::new(),
+            frozen: Vec::new(),
+            curtain: SolidColorBuffer::new((0, 0), CURTAIN),
+        }
+    }
+}
+
+impl SessionLock {
+    fn frozen(&mut self, output: &Output) -> &mut FrozenOutput {
+        let index: usize = match self
+            .frozen
+            .iter()
+            .position(|frozen: &FrozenOutput| frozen.output == *output)
+        {
+            Some(index) => index,
+            None => {
+                self.frozen.push(FrozenOutput {
+                    output: output.clone(),
+                    frame: None,
+                    opaque: false,
+                    covered: false,
+                });
+                self.frozen.len() - 1
+            }
+        };
+        &mut self.frozen[index]
+    }
+}
+
+// Draws `elements` into a new texture of `size`, the way a damage tracker
+// for an output with that scale and transform would
+fn render_to_texture(
+    renderer: &mut GlesRenderer,
+    size: Size<i32, Physical>,
+    scale: f64,
+    transform: Transform,
+    elements: &[CustomRenderElements],
+) -> Result<GlesTexture, String> {
+    let buffer_size: Size<i32, Buffer> = (size.w, size.h).into();
+    let texture: GlesTexture =
+        Offscreen::<GlesTexture>::create_buffer(renderer, Fourcc::Abgr8888, buffer_size)
+            .map_err(|err| err.to_string())?;
+    renderer
+        .bind(texture.clone())
+        .map_err(|err| err.to_string())?;
+    OutputDamageTracker::new(size, scale, transform)
+        .render_output(renderer, 0, elements, CURTAIN)
+        .map_err(|err| format!("{err:?}"))?;
+    Ok(texture)
+}
+
+// A copy of `texture` scaled down and back up, see BLUR_PASSES
+fn blur(
+    renderer: &mut GlesRenderer,
+    texture: GlesTexture,
+    size: Size<i32, Physical>,
+) -> Result<GlesTexture, String> {
+    let mut blurred: GlesTexture = texture;
+    for pass in 1..=BLUR_PASSES {
+        let pass_size: Size<i32, Physical> =
+            ((size.w >> pass).max(1), (size.h >> pass).max(1)).into();
+        let buffer: TextureBuffer<GlesTexture> =
+            TextureBuffer::from_texture(renderer, blurred, 1, Transform::Normal, None);
+        let element: CustomRenderElements = TextureRenderElement::from_texture_buffer(
+            (0.0, 0.0),
+            &buffer,
+            None,
+            None,
+            Some((pass_size.w, pass_size.h).into()),
+            Kind::Unspecified,
+        )
+        .into();
+        blurred = render_to_texture(renderer, pass_size, 1.0, Transform::Normal, &[element])?;
+    }
+    Ok(blurred)
+}
+
+impl State {
+    pub fn is_locked(&self) -> bool {
+        !matches!(self.session_lock.state, LockState::Unlocked)
+    }
+
+    // Where the pointer goes while locked
+    pub fn lock_surface(&self, output: &Output) -> Option<WlSurface> {
+        if !self.is_locked() {
+            return None;
+        }
+
+        self.session_lock
+            .surfaces
+            .iter()
+            .find(|(surface_output, _)| surface_output == output)
+            .map(|(_, surface)| surface.wl_surface().clone())
+    }
+
+    // Keeps what `output` showed when locking started, the first time it's
+    // rendered after. Called before the backend binds its own target.
+    pub fn freeze_output(&mut self, renderer: &mut GlesRenderer, output: &Output) {
+        if !matches!(self.session_lock.state, LockState::Locking { .. })
+            || self.session_lock.frozen(output).frame.is_some()
+        {
+            return;
+        }
+        let Some(mode) = output.current_mode() else {
+            return;
+        };
+
+        let mut elements: Vec<CustomRenderElements> = Vec::new();
+        self.desktop_elements(renderer, output, false, &mut elements);
+        let scale: f64 = output.current_scale().fractional_scale();
+        let transform: Transform = output.current_transform();
+        let frame: Result<(GlesTexture, GlesTexture), String> = render_to_texture(
+            renderer, mode.size, scale, transform, &elements,
+        )
+        .and_then(|sharp: GlesTexture| {
+            let blurred: GlesTexture = blur(renderer, sharp.clone(), mode.size)?;
+            Ok((sharp, blurred))
+        });
+
+        // Without it the curtain fades in over nothing, which hides the
+        // desktop just as well
+        let (sharp, blurred): (GlesTexture, GlesTexture) = match frame {
+            Ok(frame) => frame,
+            Err(err) => {
+                eprintln!("Failed to freeze {} for locking: {err}", output.name());
+                return;
+            }
+        };
+        self.session_lock.frozen(output).frame = Some((
+            TextureBuffer::from_texture(renderer, sharp, 1, transform, None),
+            TextureBuffer::from_texture(renderer, blurred, 1, transform, None),
+        ));
+    }
+
+    // Called by the backends once a frame of `output` is on screen, or when
+    // the one already there is still current
+    pub fn lock_frame_presented(&mut self, output: &Output) {
+        if !matches!(self.session_lock.state, LockState::Locking { .. }) {
+            return;
+        }
+        let frozen: &mut FrozenOutput = self.session_lock.frozen(output);
+        frozen.covered |= frozen.opaque;
+        self.confirm_lock();
+    }
+
+    // Once no output shows anything of the session anymore. Outputs that are
+    // off show nothing to begin with.
+    fn confirm_lock(&mut self) {
+        let covered: bool = self.outputs.iter().all(|output: &Output| {
+            self.dpms.is_output_off(output)
+                || self
+                    .session_lock
+                    .frozen
+                    .iter()
+                    .any(|frozen: &FrozenOutput| frozen.output == *output && frozen.covered)
+        });
+        if !covered {
+            return;
+        }
+
+        let LockState::Locking { confirmation, .. } =
+            std::mem::replace(&mut self.session_lock.state, LockState::Locked)
+        else {
+            return;
+        };
+        confirmation.lock();
+        self.session_lock.frozen.clear();
+        self.session_locked();
+    }
+
+    // While locking the locker's surfaces and the curtain fade in over the
+    // frozen desktop, which blurs as they do. Once locked nothing else is
+    // shown.
+    pub fn lock_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        let now: Instant = Instant::now();
+        let alpha: f32 = match &self.session_lock.state {
+            LockState::Unlocked => return,
+            LockState::Locking { fade, .. } if fade.is_done(now) => 1.0,
+            LockState::Locking { fade, .. } => fade.progress(now) as f32,
+            LockState::Locked => 1.0,
+        };
+        if matches!(self.session_lock.state, LockState::Locking { .. }) {
+            self.session_lock.frozen(output).opaque = alpha >= 1.0;
+        }
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let size: Size<i32, Logical> = outputs::logical_size(output);
+        self.session_lock.curtain.update(size, CURTAIN);
+
+        for (_, surface) in self
+            .session_lock
+            .surfaces
+            .iter()
+            .filter(|(surface_output, _)| surface_output == output)
+        {
+            let surface_elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> =
+                render_elements_from_surface_tree(
+                    renderer,
+                    surface.wl_surface(),
+                    (0, 0),
+                    scale,
+                    alpha,
+                    Kind::Unspecified,
+                );
+            elements.extend(surface_elements.into_iter().map(CustomRenderElements::from));
+        }
+
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                &self.session_lock.curtain,
+                (0, 0),
+                scale,
+                alpha,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+        if alpha >= 1.0 {
+            return;
+        }
+
+        // The blur sets in faster than the curtain, so it's seen before the
+        // curtain darkens everything
+        let Some(frozen) = self
+            .session_lock
+            .frozen
+            .iter()
+            .find(|frozen: &&FrozenOutput| frozen.output == *output)
+        else {
+            return;
+        };
+        if let Some((sharp, blurred)) = &frozen.frame {
+            for (buffer, buffer_alpha) in [(blurred, (alpha * 2.0).min(1.0)), (sharp, 1.0)] {
+                elements.push(
+                    TextureRenderElement::from_texture_buffer(
+                        (0.0, 0.0),
+                        buffer,
+                        Some(buffer_alpha),
+                        None,
+                        Some(size),
+                        Kind::Unspecified,
+                    )
+                    .into(),
+                );
+            }
+        }
+    }
+
+    pub fn send_lock_frames(&self, output: &Output, time: Duration) {
+        for (surface_output, surface) in &self.session_lock.surfaces {
+            if surface_output == output {
+                fractional_scale::send_preferred_scale_tree(surface.wl_surface(), output);
+                send_frames_surface_tree(
+                    surface.wl_surface(),
+                    output,
+                    time,
+                    Some(Duration::ZERO),
+                    surface_primary_scanout_output,
+                );
+            }
+        }
+    }
+}
+
+impl SessionLockHandler for State {
+    fn lock_state(&mut self) -> &mut SessionLockManagerState {
+        &mut self.session_lock_state
+    }
+
+    // Input stops reaching the desktop right away, the locker waits until
+    // the desktop is out of sight, see confirm_lock
+    fn lock(&mut self, confirmation: SessionLocker) {
+        let duration: Duration = Duration::from_millis(self.config.session.lock_fade_ms);
+        self.session_lock.state = LockState::Locking {
+            confirmation,
+            fade: Animation::new(duration),
+        };
+        self.session_lock.frozen.clear();
+
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+        // With every output off or none at all there's nothing to wait for
+        self.confirm_lock();
+        self.request_redraw();
+    }
+
+    fn unlock(&mut self) {
+        self.session_lock.state = LockState::Unlocked;
+        self.session_lock.surfaces.clear();
+        self.session_lock.frozen.clear();
+        self.focus_topmost();
+    }
+
+    fn new_surface(&mut self, surface: LockSurface, output: WlOutput) {
+        let Some(output) = Output::from_resource(&output) else {
+            return;
+        };
+
+        let size: Size<i32, Logical> = outputs::logical_size(&output);
+        surface.with_pending_state(|state| {
+            state.size = Some((size.w as u32, size.h as u32).into());
+        });
+        surface.send_configure();
+        // Lockers pick their buffer scale and transform from the outputs
+        // their surface entered. Windows and layer surfaces get this from
+        // the space and the layer map, lock surfaces are in neither.
+        output.enter(surface.wl_surface());
+
+        // The first lock surface gets the keyboard, that's where the
+        // password goes
+        if self.session_lock.surfaces.is_empty() {
+            let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+            keyboard.set_focus(
+                self,
+                Some(surface.wl_surface().clone()),
+                SERIAL_COUNTER.next_serial(),
+            );
+        }
+        self.session_lock.surfaces.push((output, surface));
+    }
+}
+delegate_session_lock!(State);
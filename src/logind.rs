@@ -0,0 +1,167 @@
+use std::{sync::mpsc, time::Duration};
+
+use smithay::reexports::calloop::{
+    channel::{self, Channel, Event},
+    LoopHandle,
+};
+use zbus::{blocking::Connection, dbus_proxy, zvariant::OwnedFd};
+
+use crate::{data::Data, lock::LockState, spawn, state::State};
+
+// logind only waits this long for delay inhibitors anyway
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    #[dbus_proxy(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+pub enum LogindEvent {
+    PrepareForSleep,
+    Resumed,
+}
+
+pub struct Logind {
+    // Tells the D-Bus thread the session is locked and suspend may go ahead
+    ready: mpsc::Sender<()>,
+    // Suspend is held back until the locker confirmed the lock, or the
+    // D-Bus thread gives up waiting
+    waiting: bool,
+}
+
+impl Logind {
+    pub fn new(handle: &LoopHandle<'_, Data>) -> Option<Self> {
+        let connection: Connection = match Connection::system() {
+            Ok(connection) => connection,
+            Err(err) => {
+                eprintln!(
+                    "Failed to connect to the system bus, logind integration disabled: {err}"
+                );
+                return None;
+            }
+        };
+
+        let (sender, channel): (channel::Sender<LogindEvent>, Channel<LogindEvent>) =
+            channel::channel();
+        let (ready, ready_receiver) = mpsc::channel::<()>();
+
+        handle
+            .insert_source(channel, |event: Event<LogindEvent>, _, data: &mut Data| {
+                if let Event::Msg(event) = event {
+                    data.state.on_logind_event(event);
+                }
+            })
+            .unwrap();
+
+        std::thread::Builder::new()
+            .name("pulseWM-logind".into())
+            .spawn(move || {
+                if let Err(err) = watch_sleep(connection, sender, ready_receiver) {
+                    eprintln!("Lost connection to logind: {err}");
+                }
+            })
+            .unwrap();
+
+        Some(Self {
+            ready,
+            waiting: false,
+        })
+    }
+
+    fn ready_for_sleep(&mut self) {
+        if std::mem::take(&mut self.waiting) {
+            let _ = self.ready.send(());
+        }
+    }
+}
+
+fn watch_sleep(
+    connection: Connection,
+    sender: channel::Sender<LogindEvent>,
+    ready: mpsc::Receiver<()>,
+) -> zbus::Result<()> {
+    let manager: ManagerProxyBlocking = ManagerProxyBlocking::new(&connection)?;
+    let take_lock = || {
+        manager.inhibit(
+            "sleep",
+            "pulseWM",
+            "Lock the session before suspending",
+            "delay",
+        )
+    };
+
+    let mut inhibitor: Option<OwnedFd> = Some(take_lock()?);
+
+    for signal in manager.receive_prepare_for_sleep()? {
+        if signal.args()?.start {
+            // Drop acks left over from a sleep that timed out
+            while ready.try_recv().is_ok() {}
+
+            if sender.send(LogindEvent::PrepareForSleep).is_err() {
+                break;
+            }
+
+            let _ = ready.recv_timeout(LOCK_TIMEOUT);
+            // Closing the fd is what lets the suspend continue
+            inhibitor = None;
+        } else {
+            if sender.send(LogindEvent::Resumed).is_err() {
+                break;
+            }
+
+            if inhibitor.is_none() {
+                inhibitor = Some(take_lock()?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl State {
+    fn on_logind_event(&mut self, event: LogindEvent) {
+        match event {
+            LogindEvent::PrepareForSleep => {
+                let Some(logind) = &mut self.logind else {
+                    return;
+                };
+                logind.waiting = true;
+
+                match &self.config.session.lock_command {
+                    Some(command) if !self.is_locked() => {
+                        spawn::spawn_detached(command);
+                    }
+                    // Already locking, confirm_lock lets suspend go ahead
+                    _ if matches!(self.session_lock.state, LockState::Locking { .. }) => {}
+                    // Nothing to wait for
+                    _ => self.session_locked(),
+                }
+            }
+            // Outputs may come back from suspend with a different state than
+            // the one we left them in, and without the power state and gamma
+            // we set
+            LogindEvent::Resumed => {
+                if let Some(logind) = &mut self.logind {
+                    logind.waiting = false;
+                }
+                self.apply_output_config();
+                self.reapply_output_power();
+            }
+        }
+    }
+
+    // Once the lock is confirmed to the locker, or right away with no
+    // locker to wait for
+    pub fn session_locked(&mut self) {
+        if let Some(logind) = &mut self.logind {
+            logind.ready_for_sleep();
+        }
+    }
+}
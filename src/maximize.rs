@@ -0,0 +1,95 @@
+use smithay::{
+    desktop::Window,
+    output::Output,
+    reexports::wayland_protocols::xdg::shell::server::xdg_toplevel,
+    utils::{Logical, Rectangle},
+};
+
+use crate::state::State;
+
+pub struct MaximizedWindow {
+    window: Window,
+    // Where the window goes back to, in global coordinates
+    restore: Rectangle<i32, Logical>,
+    tiled: bool,
+}
+
+impl State {
+    pub fn is_maximized(&self, window: &Window) -> bool {
+        self.maximized
+            .iter()
+            .any(|maximized: &MaximizedWindow| maximized.window == *window)
+    }
+
+    // Fills the usable area of the window's output, so bars and other
+    // exclusive zones stay visible
+    pub fn maximize_window(&mut self, window: &Window) {
+        let area: Option<Rectangle<i32, Logical>> = self
+            .space
+            .outputs_for_element(window)
+            .into_iter()
+            .next()
+            .or_else(|| self.space.outputs().next().cloned())
+            .and_then(|output: Output| self.usable_area(&output));
+        let Some(area) = area.filter(|_| !self.is_maximized(window)) else {
+            window.toplevel().send_pending_configure();
+            return;
+        };
+
+        let mut restore: Rectangle<i32, Logical> = window.geometry();
+        restore.loc += self.space.element_location(window).unwrap_or_default();
+        let tiled: bool = self.tiling.is_tiled(window);
+
+        self.maximized.push(MaximizedWindow {
+            window: window.clone(),
+            restore,
+            tiled,
+        });
+        if tiled {
+            self.untile_window(window);
+        }
+
+        window.toplevel().with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Maximized);
+        });
+        let geometry: Rectangle<i32, Logical> = self.inside_decoration(window, area);
+        self.place_window(window, geometry);
+    }
+
+    pub fn unmaximize_window(&mut self, window: &Window) {
+        let Some(index) = self
+            .maximized
+            .iter()
+            .position(|maximized: &MaximizedWindow| maximized.window == *window)
+        else {
+            window.toplevel().send_pending_configure();
+            return;
+        };
+        let maximized: MaximizedWindow = self.maximized.remove(index);
+
+        window.toplevel().with_pending_state(|state| {
+            state.states.unset(xdg_toplevel::State::Maximized);
+        });
+        if maximized.tiled {
+            self.tile_window(window);
+        } else {
+            self.place_window(window, maximized.restore);
+        }
+    }
+
+    pub fn toggle_maximize_focused(&mut self) {
+        let Some(window) = self.focused_window() else {
+            return;
+        };
+        if self.is_maximized(&window) {
+            self.unmaximize_window(&window);
+        } else {
+            self.maximize_window(&window);
+        }
+    }
+
+    pub fn forget_maximized(&mut self, window: &Window) {
+        self.maximized
+            .retain(|maximized: &MaximizedWindow| maximized.window != *window);
+    }
+}
@@ -0,0 +1,331 @@
+use smithay::{
+    backend::renderer::{
+        element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            texture::{TextureBuffer, TextureRenderElement},
+            Kind,
+        },
+        gles::{GlesRenderer, GlesTexture},
+    },
+    desktop::Window,
+    input::pointer::GrabStartData,
+    output::Output,
+    utils::{IsAlive, Logical, Physical, Point, Rectangle, Serial, SERIAL_COUNTER},
+};
+
+use crate::{
+    config::RootMenuEntry, keybindings::Action, render::CustomRenderElements, state::State,
+};
+
+const ITEM_HEIGHT: i32 = 28;
+const PADDING: i32 = 6;
+const TEXT_PADDING: i32 = 16;
+const MIN_WIDTH: i32 = 160;
+const FONT_SIZE: f32 = 14.0;
+
+const BACKGROUND: [f32; 4] = [0.12, 0.12, 0.12, 0.95];
+const HIGHLIGHT: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+
+// What the grabs started from the menu take as the held button, they end
+// with the next click
+const BTN_LEFT: u32 = 0x110;
+
+#[derive(Clone)]
+pub enum MenuCommand {
+    Move(Window),
+    Resize(Window),
+    ToggleFloating(Window),
+    ToggleFullscreen(Window),
+    // Counting from 0
+    MoveToWorkspace(Window, usize),
+    Close(Window),
+    // From the root menu
+    Run(Action),
+}
+
+pub struct MenuItem {
+    pub label: String,
+    pub command: MenuCommand,
+}
+
+// A compositor-drawn list of commands, open until one is clicked or a click
+// lands outside of it. That click still goes wherever it would have.
+pub struct Menu {
+    items: Vec<MenuItem>,
+    // Global, the top left corner
+    location: Point<i32, Logical>,
+    width: i32,
+    // The button that opened the menu is still held, its release must not
+    // pick whatever item ended up under the pointer
+    pressed: bool,
+    background: SolidColorBuffer,
+    highlight: SolidColorBuffer,
+}
+
+impl Menu {
+    fn geometry(&self) -> Rectangle<i32, Logical> {
+        let height: i32 = self.items.len() as i32 * ITEM_HEIGHT + 2 * PADDING;
+        Rectangle::from_loc_and_size(self.location, (self.width, height))
+    }
+
+    fn item_at(&self, point: Point<f64, Logical>) -> Option<usize> {
+        let geometry: Rectangle<i32, Logical> = self.geometry();
+        if !geometry.to_f64().contains(point) {
+            return None;
+        }
+        let y: i32 = point.y as i32 - geometry.loc.y - PADDING;
+        let index: usize = (y.max(0) / ITEM_HEIGHT) as usize;
+        (index < self.items.len()).then_some(index)
+    }
+}
+
+impl State {
+    pub fn is_menu_open(&self) -> bool {
+        self.menu.is_some()
+    }
+
+    // At `location` in global coordinates, moved to fit on its output
+    pub fn open_menu(&mut self, items: Vec<MenuItem>, location: Point<i32, Logical>) {
+        if items.is_empty() {
+            return;
+        }
+        let width: i32 = match self.text.as_mut() {
+            Some(text) => items
+                .iter()
+                .map(|item: &MenuItem| text.measure(&item.label, FONT_SIZE).0)
+                .max()
+                .unwrap_or(0),
+            None => 0,
+        };
+        let width: i32 = (width + 2 * TEXT_PADDING).max(MIN_WIDTH);
+        let height: i32 = items.len() as i32 * ITEM_HEIGHT + 2 * PADDING;
+
+        let mut location: Point<i32, Logical> = location;
+        let area: Option<Rectangle<i32, Logical>> = self
+            .space
+            .output_under(location.to_f64())
+            .next()
+            .and_then(|output: &Output| self.space.output_geometry(output));
+        if let Some(area) = area {
+            location.x = location
+                .x
+                .min(area.loc.x + area.size.w - width)
+                .max(area.loc.x);
+            location.y = location
+                .y
+                .min(area.loc.y + area.size.h - height)
+                .max(area.loc.y);
+        }
+
+        let pointer_held: bool = self
+            .seat
+            .get_pointer()
+            .is_some_and(|pointer| pointer.is_grabbed());
+        self.menu = Some(Menu {
+            items,
+            location,
+            width,
+            pressed: !pointer_held,
+            background: SolidColorBuffer::new((width, height), BACKGROUND),
+            highlight: SolidColorBuffer::new((width - 2 * PADDING, ITEM_HEIGHT), HIGHLIGHT),
+        });
+        self.request_redraw();
+    }
+
+    pub fn close_menu(&mut self) {
+        if self.menu.take().is_some() {
+            self.request_redraw();
+        }
+    }
+
+    // xdg_toplevel.show_window_menu, `location` is relative to the window
+    // geometry
+    pub fn open_window_menu(&mut self, window: &Window, location: Point<i32, Logical>) {
+        let Some(window_location) = self.space.element_location(window) else {
+            return;
+        };
+        let tiled: bool = self.tiling.is_tiled(window);
+        let fullscreen: bool = self.is_fullscreen(window);
+
+        let item = |label: &str, command: MenuCommand| MenuItem {
+            label: label.to_string(),
+            command,
+        };
+        let mut items: Vec<MenuItem> = vec![item("Move", MenuCommand::Move(window.clone()))];
+        if !tiled && !fullscreen {
+            items.push(item("Resize", MenuCommand::Resize(window.clone())));
+        }
+        items.push(item(
+            if tiled { "Float" } else { "Tile" },
+            MenuCommand::ToggleFloating(window.clone()),
+        ));
+        items.push(item(
+            if fullscreen {
+                "Leave fullscreen"
+            } else {
+                "Fullscreen"
+            },
+            MenuCommand::ToggleFullscreen(window.clone()),
+        ));
+        let current: Option<usize> = self.workspace_of(window);
+        for index in (0..self.workspaces.count()).filter(|index: &usize| Some(*index) != current) {
+            items.push(MenuItem {
+                label: format!("Move to workspace {}", index + 1),
+                command: MenuCommand::MoveToWorkspace(window.clone(), index),
+            });
+        }
+        items.push(item("Close", MenuCommand::Close(window.clone())));
+
+        let origin: Point<i32, Logical> = window_location + window.geometry().loc + location;
+        self.open_menu(items, origin);
+    }
+
+    // Opened by the press of `[root_menu] button` over the bare desktop,
+    // that press and its release are the menu's
+    pub fn open_root_menu(&mut self, location: Point<i32, Logical>) {
+        let items: Vec<MenuItem> = self
+            .config
+            .root_menu
+            .entries
+            .iter()
+            .map(|entry: &RootMenuEntry| MenuItem {
+                label: entry.label.clone(),
+                command: MenuCommand::Run(entry.action.clone()),
+            })
+            .collect();
+        self.open_menu(items, location);
+        if let Some(menu) = &mut self.menu {
+            menu.pressed = false;
+        }
+    }
+
+    // Returns whether the menu took the button event. The release of the
+    // button that opened it goes on to the client that saw it pressed.
+    pub fn menu_button(&mut self, pressed: bool) -> bool {
+        let Some(menu) = &mut self.menu else {
+            return false;
+        };
+        if pressed {
+            if menu.item_at(self.pointer_location).is_none() {
+                self.close_menu();
+                return false;
+            }
+            menu.pressed = true;
+            return true;
+        }
+        if !menu.pressed {
+            return false;
+        }
+
+        let Some(index) = menu.item_at(self.pointer_location) else {
+            return true;
+        };
+        let command: MenuCommand = menu.items[index].command.clone();
+        self.close_menu();
+        self.run_menu_command(command);
+        true
+    }
+
+    fn run_menu_command(&mut self, command: MenuCommand) {
+        let location: Point<f64, Logical> = self.pointer_location;
+        let start_data = || GrabStartData {
+            focus: None,
+            button: BTN_LEFT,
+            location,
+        };
+        let serial: Serial = SERIAL_COUNTER.next_serial();
+
+        match command {
+            MenuCommand::Move(window) if window.alive() => {
+                self.start_move_grab(&window, start_data(), serial)
+            }
+            MenuCommand::Resize(window) if window.alive() => {
+                self.start_corner_resize_grab(&window, start_data(), serial)
+            }
+            MenuCommand::ToggleFloating(window) if window.alive() => self.toggle_floating(&window),
+            MenuCommand::ToggleFullscreen(window) if window.alive() => {
+                if self.is_fullscreen(&window) {
+                    self.unfullscreen_window(&window);
+                } else {
+                    self.fullscreen_window(&window, None);
+                }
+            }
+            MenuCommand::MoveToWorkspace(window, index) if window.alive() => {
+                self.move_window_to_workspace(&window, index)
+            }
+            MenuCommand::Close(window) => window.toplevel().send_close(),
+            MenuCommand::Run(action) => self.run_action(action),
+            // The window closed while the menu was open
+            _ => {}
+        }
+    }
+
+    pub fn menu_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        let (Some(menu), Some(output_geometry)) = (&self.menu, self.space.output_geometry(output))
+        else {
+            return;
+        };
+        let geometry: Rectangle<i32, Logical> = menu.geometry();
+        if !output_geometry.overlaps(geometry) {
+            return;
+        }
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let origin: Point<i32, Logical> = geometry.loc - output_geometry.loc;
+        let at = |offset: (i32, i32)| -> Point<i32, Physical> {
+            (origin + Point::from(offset)).to_physical_precise_round(scale)
+        };
+
+        if let Some(text) = self.text.as_mut() {
+            for (index, item) in menu.items.iter().enumerate() {
+                let buffer: Option<TextureBuffer<GlesTexture>> =
+                    text.render(renderer, &item.label, FONT_SIZE, TEXT, scale.ceil() as i32);
+                let Some(buffer) = buffer else {
+                    continue;
+                };
+                let text_height: i32 = text.measure(&item.label, FONT_SIZE).1;
+                let y: i32 = PADDING + index as i32 * ITEM_HEIGHT + (ITEM_HEIGHT - text_height) / 2;
+                elements.push(
+                    TextureRenderElement::from_texture_buffer(
+                        at((TEXT_PADDING, y)).to_f64(),
+                        &buffer,
+                        None,
+                        None,
+                        None,
+                        Kind::Unspecified,
+                    )
+                    .into(),
+                );
+            }
+        }
+
+        if let Some(index) = menu.item_at(self.pointer_location) {
+            elements.push(
+                SolidColorRenderElement::from_buffer(
+                    &menu.highlight,
+                    at((PADDING, PADDING + index as i32 * ITEM_HEIGHT)),
+                    scale,
+                    1.0,
+                    Kind::Unspecified,
+                )
+                .into(),
+            );
+        }
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                &menu.background,
+                at((0, 0)),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+use smithay::{
+    desktop::Window,
+    utils::{IsAlive, Logical, Point},
+};
+
+use crate::{state::State, switcher, tree};
+
+// Minimized windows are out of the space, but keep their place in the
+// tiling so they come back where they were
+pub struct MinimizedWindow {
+    window: Window,
+    location: Point<i32, Logical>,
+}
+
+#[derive(Serialize)]
+pub struct MinimizedStatus {
+    pub id: u64,
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    pub name: Option<String>,
+}
+
+impl State {
+    pub fn minimize_window(&mut self, window: &Window) {
+        let Some(location) = self.space.element_location(window) else {
+            return;
+        };
+        let was_focused: bool = self.focused_window().as_ref() == Some(window);
+
+        self.space.unmap_elem(window);
+        self.minimized.push(MinimizedWindow {
+            window: window.clone(),
+            location,
+        });
+        self.arrange();
+        if was_focused {
+            self.focus_previous();
+        }
+    }
+
+    pub fn minimize_focused(&mut self) {
+        if let Some(window) = self.focused_window() {
+            self.minimize_window(&window);
+        }
+    }
+
+    // Oldest first, the indices are what unminimize takes
+    pub fn minimized_windows(&self) -> Vec<MinimizedStatus> {
+        self.minimized
+            .iter()
+            .map(|minimized: &MinimizedWindow| MinimizedStatus {
+                id: tree::window_id(&minimized.window),
+                app_id: switcher::app_id(&minimized.window),
+                title: switcher::title(&minimized.window),
+                name: self.window_name(&minimized.window),
+            })
+            .collect()
+    }
+
+    // Brings the window back onto the active workspace, the most recently
+    // minimized one without an index
+    pub fn unminimize(&mut self, index: Option<usize>) -> Result<(), String> {
+        self.minimized
+            .retain(|minimized: &MinimizedWindow| minimized.window.alive());
+        let index: usize = match index {
+            Some(index) if index < self.minimized.len() => index,
+            Some(index) => return Err(format!("no minimized window {index}")),
+            None => self
+                .minimized
+                .len()
+                .checked_sub(1)
+                .ok_or("no minimized windows")?,
+        };
+
+        let minimized: MinimizedWindow = self.minimized.remove(index);
+        self.space
+            .map_element(minimized.window.clone(), minimized.location, false);
+        self.arrange();
+        self.focus_window(&minimized.window);
+        Ok(())
+    }
+
+    pub fn forget_minimized(&mut self, window: &Window) {
+        self.minimized
+            .retain(|minimized: &MinimizedWindow| minimized.window != *window);
+    }
+}
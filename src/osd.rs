@@ -0,0 +1,144 @@
+use std::time::{Duration, Instant};
+
+use smithay::{
+    backend::renderer::{
+        element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            texture::{TextureBuffer, TextureRenderElement},
+            Kind,
+        },
+        gles::{GlesRenderer, GlesTexture},
+    },
+    output::Output,
+    utils::{Logical, Physical, Point, Rectangle, Size},
+};
+
+use crate::{render::CustomRenderElements, text::TextRenderer};
+
+const TIMEOUT: Duration = Duration::from_millis(1500);
+
+const WIDTH: i32 = 300;
+const HEIGHT: i32 = 64;
+const PADDING: i32 = 12;
+const BAR_HEIGHT: i32 = 8;
+const FONT_SIZE: f32 = 16.0;
+// Distance from the bottom edge of the output
+const MARGIN: i32 = 96;
+
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+const TRACK: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+const FILL: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+
+// Short lived popup for things like brightness changes
+pub struct Osd {
+    label: String,
+    // 0.0..=1.0, draws a progress bar under the label
+    level: Option<f64>,
+    shown_at: Instant,
+    background: SolidColorBuffer,
+    track: SolidColorBuffer,
+    fill: SolidColorBuffer,
+}
+
+impl Default for Osd {
+    fn default() -> Self {
+        Self {
+            label: String::new(),
+            level: None,
+            shown_at: Instant::now(),
+            background: SolidColorBuffer::new((WIDTH, HEIGHT), BACKGROUND),
+            track: SolidColorBuffer::new((WIDTH - 2 * PADDING, BAR_HEIGHT), TRACK),
+            fill: SolidColorBuffer::new((0, BAR_HEIGHT), FILL),
+        }
+    }
+}
+
+impl Osd {
+    pub fn show(&mut self, label: impl Into<String>, level: Option<f64>) {
+        self.label = label.into();
+        self.level = level.map(|level: f64| level.clamp(0.0, 1.0));
+        self.shown_at = Instant::now();
+
+        let fill_width: i32 = (self.level.unwrap_or(0.0) * (WIDTH - 2 * PADDING) as f64) as i32;
+        self.fill.update((fill_width, BAR_HEIGHT), FILL);
+    }
+
+    pub fn hide(&mut self) {
+        self.label.clear();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        !self.label.is_empty() && self.shown_at.elapsed() < TIMEOUT
+    }
+
+    pub fn render_elements(
+        &self,
+        renderer: &mut GlesRenderer,
+        text: Option<&mut TextRenderer>,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        if !self.is_visible() {
+            return;
+        }
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let output_size: Size<i32, Logical> = output
+            .current_mode()
+            .map(|mode| mode.size.to_f64().to_logical(scale).to_i32_round())
+            .unwrap_or_default();
+        let area: Rectangle<i32, Logical> = Rectangle::from_loc_and_size(
+            ((output_size.w - WIDTH) / 2, output_size.h - MARGIN - HEIGHT),
+            (WIDTH, HEIGHT),
+        );
+        let at = |offset: (i32, i32)| -> Point<i32, Physical> {
+            (area.loc + Point::from(offset)).to_physical_precise_round(scale)
+        };
+
+        if let Some(text) = text {
+            let buffer: Option<TextureBuffer<GlesTexture>> =
+                text.render(renderer, &self.label, FONT_SIZE, TEXT, scale.ceil() as i32);
+            if let Some(buffer) = buffer {
+                elements.push(
+                    TextureRenderElement::from_texture_buffer(
+                        at((PADDING, PADDING)).to_f64(),
+                        &buffer,
+                        None,
+                        None,
+                        None,
+                        Kind::Unspecified,
+                    )
+                    .into(),
+                );
+            }
+        }
+
+        if self.level.is_some() {
+            let bar_y: i32 = HEIGHT - PADDING - BAR_HEIGHT;
+            for buffer in [&self.fill, &self.track] {
+                elements.push(
+                    SolidColorRenderElement::from_buffer(
+                        buffer,
+                        at((PADDING, bar_y)),
+                        scale,
+                        1.0,
+                        Kind::Unspecified,
+                    )
+                    .into(),
+                );
+            }
+        }
+
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                &self.background,
+                at((0, 0)),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use smithay::{
+    desktop::Window,
+    output::{Mode, Output, Scale},
+    reexports::wayland_server::backend::GlobalId,
+    utils::{Logical, Point, Rectangle, Size},
+};
+
+use crate::{
+    config::{OutputConfig, OutputLayoutConfig},
+    fractional_scale, profile,
+    state::State,
+};
+
+// Laptop panels, the ones a closed lid turns off
+pub fn is_internal(output: &Output) -> bool {
+    let name: String = output.name();
+    ["eDP", "LVDS", "DSI"]
+        .iter()
+        .any(|prefix: &&str| name.starts_with(prefix))
+}
+
+// Size in the global compositor space, after transform and scale
+pub fn logical_size(output: &Output) -> Size<i32, Logical> {
+    let Some(mode) = output.current_mode() else {
+        return Size::default();
+    };
+
+    output
+        .current_transform()
+        .transform_size(mode.size)
+        .to_f64()
+        .to_logical(output.current_scale().fractional_scale())
+        .to_i32_round()
+}
+
+pub fn validate(
+    layout: &[(String, Rectangle<i32, Logical>)],
+    rules: &OutputLayoutConfig,
+) -> Result<(), String> {
+    if rules.reject_overlaps {
+        for (i, (name, rect)) in layout.iter().enumerate() {
+            for (other_name, other) in &layout[i + 1..] {
+                if rect.overlaps(*other) {
+                    return Err(format!("{name} overlaps {other_name}"));
+                }
+            }
+        }
+    }
+
+    // Every output has to be reachable from the first one through shared
+    // edges, otherwise the pointer can't move between them
+    if rules.reject_gaps && layout.len() > 1 {
+        let mut reached: Vec<bool> = vec![false; layout.len()];
+        let mut pending: Vec<usize> = vec![0];
+        reached[0] = true;
+
+        while let Some(current) = pending.pop() {
+            for (index, (_, rect)) in layout.iter().enumerate() {
+                if !reached[index] && touches(&layout[current].1, rect) {
+                    reached[index] = true;
+                    pending.push(index);
+                }
+            }
+        }
+
+        if let Some(index) = reached.iter().position(|reached: &bool| !reached) {
+            return Err(format!(
+                "{} is not adjacent to any other output",
+                layout[index].0
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Sharing an edge segment, corners alone don't count
+fn touches(a: &Rectangle<i32, Logical>, b: &Rectangle<i32, Logical>) -> bool {
+    let (a_right, a_bottom) = (a.loc.x + a.size.w, a.loc.y + a.size.h);
+    let (b_right, b_bottom) = (b.loc.x + b.size.w, b.loc.y + b.size.h);
+
+    let x_overlap: i32 = a_right.min(b_right) - a.loc.x.max(b.loc.x);
+    let y_overlap: i32 = a_bottom.min(b_bottom) - a.loc.y.max(b.loc.y);
+
+    (x_overlap > 0 && y_overlap >= 0) || (y_overlap > 0 && x_overlap >= 0)
+}
+
+// Puts outputs without a configured position in a row to the right of
+// everything else, in the order they were connected
+fn arrange(
+    outputs: &[(Output, Option<Point<i32, Logical>>)],
+) -> Vec<(Output, Point<i32, Logical>)> {
+    let mut next_x: i32 = outputs
+        .iter()
+        .filter_map(|(output, position)| Some(position.as_ref()?.x + logical_size(output).w))
+        .max()
+        .unwrap_or(0);
+
+    outputs
+        .iter()
+        .map(|(output, position)| {
+            let position: Point<i32, Logical> = position.unwrap_or_else(|| {
+                let position: Point<i32, Logical> = (next_x, 0).into();
+                next_x += logical_size(output).w;
+                position
+            });
+            (output.clone(), position)
+        })
+        .collect()
+}
+
+fn apply_settings(output: &Output, config: &OutputConfig) {
+    let mode: Option<Mode> = config.mode.and_then(|wanted| {
+        output.modes().into_iter().find(|mode: &Mode| {
+            mode.size.w == wanted.width
+                && mode.size.h == wanted.height
+                && wanted
+                    .refresh
+                    .map_or(true, |refresh: i32| (mode.refresh - refresh).abs() < 500)
+        })
+    });
+
+    let scale: Option<Scale> = config.scale.and_then(|scale: f64| {
+        let output_scale: Option<Scale> = fractional_scale::output_scale(scale);
+        if output_scale.is_none() {
+            eprintln!(
+                "Ignoring scale {scale} for {}, expected 0.25 to 8",
+                output.name()
+            );
+        }
+        output_scale
+    });
+
+    output.change_current_state(mode, config.transform.map(Into::into), scale, None);
+}
+
+impl State {
+    // Where new windows open, the output under the pointer
+    pub fn active_output(&self) -> Option<Output> {
+        self.space
+            .output_under(self.pointer_location)
+            .next()
+            .or_else(|| self.space.outputs().next())
+            .cloned()
+    }
+
+    // The output showing most of the window. Windows without a buffer yet
+    // belong to the output their location is on.
+    pub fn window_output(&self, window: &Window) -> Option<Output> {
+        if let Some(output) = self.space.outputs_for_element(window).into_iter().next() {
+            return Some(output);
+        }
+        let location: Point<i32, Logical> = self.space.element_location(window)?;
+        self.space.output_under(location.to_f64()).next().cloned()
+    }
+
+    // Windows left outside every output, e.g. after their output was
+    // unplugged, are moved onto the active one at the same relative spot
+    pub fn rescue_windows(&mut self) {
+        let Some(target) = self
+            .active_output()
+            .and_then(|output: Output| self.space.output_geometry(&output))
+        else {
+            return;
+        };
+
+        let lost: Vec<(Window, Point<i32, Logical>)> = self
+            .space
+            .elements()
+            .filter(|window: &&Window| self.window_output(window).is_none())
+            .filter_map(|window: &Window| {
+                Some((window.clone(), self.space.element_location(window)?))
+            })
+            .collect();
+
+        for (window, location) in lost {
+            let size: Size<i32, Logical> = window.geometry().size;
+            let x: i32 = location.x.rem_euclid(target.size.w.max(1));
+            let y: i32 = location.y.rem_euclid(target.size.h.max(1));
+            let location: Point<i32, Logical> = Point::from((
+                target.loc.x + x.min((target.size.w - size.w).max(0)),
+                target.loc.y + y.min((target.size.h - size.h).max(0)),
+            ));
+            self.space.map_element(window, location, false);
+        }
+    }
+
+    // The backend lost the output, e.g. its GPU went away
+    pub fn remove_output(&mut self, output: &Output, global: GlobalId) {
+        self.space.unmap_output(output);
+        self.outputs.retain(|other: &Output| other != output);
+        self.display_handle.remove_global::<State>(global);
+    }
+
+    // Applies the matching profile, or the [[output]] entries when no profile
+    // matches, then lays out whatever ended up enabled
+    pub fn apply_output_config(&mut self) {
+        let configs: Vec<(Output, Option<OutputConfig>)> =
+            match profile::find_profile(&self.config.profiles, &self.outputs) {
+                Some((profile, assignment)) => {
+                    eprintln!("Applying output profile \"{}\"", profile.name);
+                    assignment
+                        .into_iter()
+                        .map(|(output, config)| (output, Some(config.clone())))
+                        .collect()
+                }
+                None => self
+                    .outputs
+                    .iter()
+                    .map(|output: &Output| {
+                        let config: Option<OutputConfig> = self
+                            .config
+                            .outputs
+                            .iter()
+                            .find(|config: &&OutputConfig| {
+                                profile::matches(&config.criteria, output)
+                            })
+                            .cloned();
+                        (output.clone(), config)
+                    })
+                    .collect(),
+            };
+
+        let mut enabled: Vec<(Output, Option<Point<i32, Logical>>)> = Vec::new();
+        for (output, config) in &configs {
+            match config {
+                Some(config) if !config.enable => {}
+                Some(config) => {
+                    apply_settings(output, config);
+                    enabled.push((output.clone(), config.position.map(Point::from)));
+                }
+                None => enabled.push((output.clone(), None)),
+            }
+        }
+
+        // A closed lid turns the panel off, as long as that doesn't leave us
+        // without any output
+        if self.lid_closed && enabled.iter().any(|(output, _)| !is_internal(output)) {
+            enabled.retain(|(output, _)| !is_internal(output));
+        }
+
+        let mut layout: Vec<(Output, Point<i32, Logical>)> = arrange(&enabled);
+        let rects: Vec<(String, Rectangle<i32, Logical>)> = layout
+            .iter()
+            .map(|(output, position)| {
+                let rect: Rectangle<i32, Logical> =
+                    Rectangle::from_loc_and_size(*position, logical_size(output));
+                (output.name(), rect)
+            })
+            .collect();
+
+        if let Err(err) = validate(&rects, &self.config.output_layout) {
+            eprintln!("Invalid output layout, arranging outputs automatically: {err}");
+            let unpositioned: Vec<(Output, Option<Point<i32, Logical>>)> = enabled
+                .into_iter()
+                .map(|(output, _)| (output, None))
+                .collect();
+            layout = arrange(&unpositioned);
+        }
+
+        for output in &self.outputs {
+            if !layout.iter().any(|(enabled, _)| enabled == output) {
+                self.space.unmap_output(output);
+            }
+        }
+
+        let mut newly_enabled: Vec<Output> = Vec::new();
+        for (output, position) in layout {
+            if self.space.output_geometry(&output).is_none() {
+                newly_enabled.push(output.clone());
+            }
+            output.change_current_state(None, None, None, Some(position));
+            self.space.map_output(&output, position);
+        }
+
+        // Workspaces are shared by all outputs, so this only picks which one
+        // is shown when the output shows up
+        for output in newly_enabled {
+            if let Some(workspace) = self.output_rules(&output).workspace {
+                self.switch_workspace(workspace.saturating_sub(1));
+            }
+        }
+
+        self.rescue_windows();
+        self.arrange();
+    }
+}
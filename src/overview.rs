@@ -0,0 +1,231 @@
+use smithay::{
+    backend::renderer::{
+        element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            surface::WaylandSurfaceRenderElement,
+            utils::RescaleRenderElement,
+            AsRenderElements, Kind,
+        },
+        gles::GlesRenderer,
+    },
+    desktop::Window,
+    output::Output,
+    utils::{Logical, Physical, Point, Rectangle, Scale, Size},
+};
+
+use crate::{outputs, render::CustomRenderElements, state::State, theme::Palette};
+
+const GAP: i32 = 16;
+const MAX_THUMBNAIL_WIDTH: i32 = 240;
+
+// Premultiplied
+const BACKDROP: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+// Dims the desktop and shows a strip with a thumbnail of every workspace
+// along the top edge. Windows can be dragged from one thumbnail to another.
+pub struct Overview {
+    open: bool,
+    // Picked up from a thumbnail, dropped on another one
+    dragging: Option<Window>,
+    backdrop: SolidColorBuffer,
+    thumbnails: Vec<SolidColorBuffer>,
+}
+
+impl Default for Overview {
+    fn default() -> Self {
+        Self {
+            open: false,
+            dragging: None,
+            backdrop: SolidColorBuffer::new((0, 0), BACKDROP),
+            thumbnails: Vec::new(),
+        }
+    }
+}
+
+impl Overview {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+// Thumbnail rectangles inside an output of `output_size`, centered in a row
+pub fn strip_layout(output_size: Size<i32, Logical>, count: usize) -> Vec<Rectangle<i32, Logical>> {
+    if count == 0 || output_size.w <= 0 || output_size.h <= 0 {
+        return Vec::new();
+    }
+
+    let count_i32: i32 = count as i32;
+    let width: i32 = ((output_size.w - (count_i32 + 1) * GAP) / count_i32)
+        .min(MAX_THUMBNAIL_WIDTH)
+        .max(1);
+    let height: i32 = (width * output_size.h / output_size.w).max(1);
+    let total: i32 = count_i32 * width + (count_i32 - 1) * GAP;
+    let start: i32 = (output_size.w - total) / 2;
+
+    (0..count_i32)
+        .map(|index: i32| {
+            Rectangle::from_loc_and_size((start + index * (width + GAP), GAP), (width, height))
+        })
+        .collect()
+}
+
+impl State {
+    pub fn toggle_overview(&mut self) {
+        self.overview.open = !self.overview.open;
+        self.overview.dragging = None;
+    }
+
+    fn thumbnail_at(
+        &self,
+        output: &Output,
+        point: Point<f64, Logical>,
+    ) -> Option<(usize, Rectangle<i32, Logical>)> {
+        let size: Size<i32, Logical> = outputs::logical_size(output);
+        strip_layout(size, self.workspaces.count())
+            .into_iter()
+            .enumerate()
+            .find(|(_, rect)| rect.to_f64().contains(point))
+    }
+
+    // `point` is relative to the output. Returns the window shown at that
+    // spot of a thumbnail, topmost first.
+    fn thumbnail_window_at(
+        &self,
+        output: &Output,
+        point: Point<f64, Logical>,
+    ) -> Option<(usize, Option<Window>)> {
+        let (index, rect) = self.thumbnail_at(output, point)?;
+        let output_geometry: Rectangle<i32, Logical> = self.space.output_geometry(output)?;
+        let factor: f64 = rect.size.w as f64 / output_geometry.size.w as f64;
+
+        // Back into global coordinates of that workspace
+        let global: Point<f64, Logical> =
+            (point - rect.loc.to_f64()).upscale(1.0 / factor) + output_geometry.loc.to_f64();
+
+        let window: Option<Window> = self
+            .workspace_windows(index)
+            .into_iter()
+            .rev()
+            .find(|(window, location)| {
+                let mut geometry: Rectangle<i32, Logical> = window.geometry();
+                geometry.loc += *location;
+                geometry.to_f64().contains(global)
+            })
+            .map(|(window, _)| window);
+
+        Some((index, window))
+    }
+
+    // Pointer button pressed inside the overview, `point` relative to the
+    // output. Returns false when the press wasn't on the strip.
+    pub fn overview_press(&mut self, output: &Output, point: Point<f64, Logical>) -> bool {
+        let Some((index, window)) = self.thumbnail_window_at(output, point) else {
+            return false;
+        };
+
+        match window {
+            Some(window) => self.overview.dragging = Some(window),
+            None => self.switch_workspace(index),
+        }
+        true
+    }
+
+    pub fn overview_release(&mut self, output: &Output, point: Point<f64, Logical>) {
+        let Some(window) = self.overview.dragging.take() else {
+            return;
+        };
+
+        match self.thumbnail_at(output, point) {
+            Some((index, _)) if Some(index) != self.workspace_of(&window) => {
+                self.move_window_to_workspace(&window, index);
+            }
+            // Dropped back where it came from, treat it as a click
+            Some((index, _)) => self.switch_workspace(index),
+            None => {}
+        }
+    }
+
+    pub fn overview_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        if !self.overview.open {
+            return;
+        }
+        let Some(output_geometry) = self.space.output_geometry(output) else {
+            return;
+        };
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let layout: Vec<Rectangle<i32, Logical>> =
+            strip_layout(output_geometry.size, self.workspaces.count());
+
+        let palette: &Palette = self.theme.palette();
+        self.overview.thumbnails.resize_with(layout.len(), || {
+            SolidColorBuffer::new((0, 0), palette.thumbnail)
+        });
+        for (index, (buffer, rect)) in self.overview.thumbnails.iter_mut().zip(&layout).enumerate()
+        {
+            let color: [f32; 4] = if self.workspaces.is_shown(index) {
+                palette.thumbnail_active
+            } else {
+                palette.thumbnail
+            };
+            buffer.update(rect.size, color);
+        }
+        self.overview
+            .backdrop
+            .update(output_geometry.size, BACKDROP);
+
+        for (index, rect) in layout.iter().enumerate() {
+            let factor: f64 = rect.size.w as f64 / output_geometry.size.w as f64;
+            let origin: Point<i32, Physical> = rect.loc.to_physical_precise_round(scale);
+
+            for (window, location) in self.workspace_windows(index).into_iter().rev() {
+                let mut geometry: Rectangle<i32, Logical> = window.geometry();
+                geometry.loc += location;
+                if !geometry.overlaps(output_geometry) {
+                    continue;
+                }
+
+                let relative: Point<i32, Logical> = location - output_geometry.loc;
+                let window_elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> = window
+                    .render_elements(
+                        renderer,
+                        origin + relative.to_physical_precise_round(scale),
+                        Scale::from(scale),
+                        1.0,
+                    );
+                elements.extend(window_elements.into_iter().map(
+                    |element: WaylandSurfaceRenderElement<GlesRenderer>| {
+                        RescaleRenderElement::from_element(element, origin, factor).into()
+                    },
+                ));
+            }
+
+            elements.push(
+                SolidColorRenderElement::from_buffer(
+                    &self.overview.thumbnails[index],
+                    origin,
+                    scale,
+                    1.0,
+                    Kind::Unspecified,
+                )
+                .into(),
+            );
+        }
+
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                &self.overview.backdrop,
+                (0, 0),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+}
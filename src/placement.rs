@@ -0,0 +1,233 @@
+use smithay::{
+    desktop::Window,
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point, Rectangle, Serial, Size},
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        shell::xdg::SurfaceCachedState,
+    },
+};
+
+use crate::{
+    config::DecorationConfig,
+    configure, decorations,
+    layout::{self, Rect},
+    rules::{self, WindowRule},
+    state::State,
+    tiling,
+};
+
+// A floating window that hasn't committed a buffer yet, so its size isn't
+// known. `focused` is what had focus when it opened.
+pub struct UnplacedWindow {
+    window: Window,
+    focused: Option<Window>,
+    // Taken out of tiling, it's centered once it committed the size it
+    // picked in reply to this configure
+    untiled: Option<Serial>,
+}
+
+fn to_rect(rectangle: Rectangle<i32, Logical>) -> Rect {
+    Rect::new(
+        rectangle.loc.x,
+        rectangle.loc.y,
+        rectangle.size.w,
+        rectangle.size.h,
+    )
+}
+
+impl State {
+    // Dialogs and windows that can't be resized float instead of being
+    // tiled
+    pub fn wants_floating(&self, window: &Window) -> bool {
+        if window.toplevel().parent().is_some() {
+            return true;
+        }
+        with_states(window.toplevel().wl_surface(), |states: &SurfaceData| {
+            let min_size: Size<i32, Logical> =
+                states.cached_state.current::<SurfaceCachedState>().min_size;
+            let max_size: Size<i32, Logical> =
+                states.cached_state.current::<SurfaceCachedState>().max_size;
+            min_size.w > 0 && min_size == max_size
+        })
+    }
+
+    // Called before the new window takes focus, and before its initial
+    // configure
+    pub fn float_new_window(&mut self, window: &Window) {
+        if let Some([width, height]) =
+            rules::window_rule(window).and_then(|rule: &WindowRule| rule.size)
+        {
+            window.toplevel().with_pending_state(|state| {
+                state.size = Some((width, height).into());
+            });
+        }
+        self.unplaced.push(UnplacedWindow {
+            window: window.clone(),
+            focused: self.focused_window(),
+            untiled: None,
+        });
+    }
+
+    // Floating windows get to pick their own size again
+    pub fn toggle_floating(&mut self, window: &Window) {
+        if !self.tiling.is_tiled(window) {
+            self.forget_unplaced(window);
+            self.tile_window(window);
+            return;
+        }
+
+        self.untile_window(window);
+        window.toplevel().with_pending_state(|state| {
+            state.size = None;
+        });
+        match window.toplevel().send_pending_configure() {
+            Some(serial) => self.unplaced.push(UnplacedWindow {
+                window: window.clone(),
+                focused: None,
+                untiled: Some(serial),
+            }),
+            None => self.place_centered(window),
+        }
+    }
+
+    pub fn placement_commit(&mut self, surface: &WlSurface) {
+        let Some(index) = self.unplaced.iter().position(|unplaced: &UnplacedWindow| {
+            unplaced.window.toplevel().wl_surface() == surface
+        }) else {
+            return;
+        };
+        let size: Size<i32, Logical> = tiling::window_size(&self.unplaced[index].window);
+        if size.w <= 0 || size.h <= 0 {
+            return;
+        }
+        if let Some(serial) = self.unplaced[index].untiled {
+            if !configure::has_caught_up(self.unplaced[index].window.toplevel(), serial) {
+                return;
+            }
+        }
+
+        let unplaced: UnplacedWindow = self.unplaced.remove(index);
+        if unplaced.untiled.is_some() {
+            self.place_centered(&unplaced.window);
+        } else {
+            self.place_floating(&unplaced.window, unplaced.focused.as_ref());
+        }
+    }
+
+    pub fn forget_unplaced(&mut self, window: &Window) {
+        self.unplaced
+            .retain(|unplaced: &UnplacedWindow| unplaced.window != *window);
+    }
+
+    // Global geometry including the title bar and border, if it has them
+    fn frame_geometry(&self, window: &Window) -> Option<Rectangle<i32, Logical>> {
+        let mut geometry: Rectangle<i32, Logical> = window.geometry();
+        geometry.loc += self.space.element_location(window)?;
+        if !self.is_server_side(window) {
+            let border: i32 = self.border_width(window);
+            return Some(Rectangle::from_loc_and_size(
+                (geometry.loc.x - border, geometry.loc.y - border),
+                (geometry.size.w + 2 * border, geometry.size.h + 2 * border),
+            ));
+        }
+        let config: &DecorationConfig = &self.config.decorations;
+        Some(decorations::frame_rects(geometry, config.title_height, config.border_width).outer)
+    }
+
+    // The whole frame is centered, as far as it fits
+    fn place_centered(&mut self, window: &Window) {
+        let area: Option<Rectangle<i32, Logical>> = self
+            .window_output(window)
+            .or_else(|| self.active_output())
+            .and_then(|output: Output| self.usable_area(&output));
+        let (Some(area), Some(frame), Some(location)) = (
+            area,
+            self.frame_geometry(window),
+            self.space.element_location(window),
+        ) else {
+            return;
+        };
+
+        let centered: Point<i32, Logical> = Point::from((
+            area.loc.x + ((area.size.w - frame.size.w) / 2).max(0),
+            area.loc.y + ((area.size.h - frame.size.h) / 2).max(0),
+        ));
+        self.space
+            .map_element(window.clone(), location + (centered - frame.loc), false);
+    }
+
+    // Floating windows stay above tiled ones, fullscreen ones above both.
+    // Focus and map_element raise windows to the very top, this puts them
+    // back in their layer. Called once per frame.
+    pub fn restack_floating(&mut self) {
+        // Kiosk mode keeps its own order
+        if self.kiosk() {
+            return;
+        }
+        let layers: Vec<(Window, u8)> = self
+            .space
+            .elements()
+            .map(|window: &Window| {
+                let layer: u8 = if self.is_fullscreen(window) {
+                    2
+                } else if self.tiling.is_tiled(window) {
+                    0
+                } else {
+                    1
+                };
+                (window.clone(), layer)
+            })
+            .collect();
+        if layers.windows(2).all(|pair| pair[0].1 <= pair[1].1) {
+            return;
+        }
+
+        let mut sorted: Vec<(Window, u8)> = layers;
+        sorted.sort_by_key(|(_, layer)| *layer);
+        for (window, _) in sorted.iter().filter(|(_, layer)| *layer > 0) {
+            self.space.raise_element(window, false);
+        }
+        self.request_redraw();
+    }
+
+    fn place_floating(&mut self, window: &Window, focused: Option<&Window>) {
+        let area: Option<Rectangle<i32, Logical>> = self
+            .window_output(window)
+            .or_else(|| self.active_output())
+            .and_then(|output: Output| self.usable_area(&output));
+        let (Some(area), Some(frame), Some(location)) = (
+            area,
+            self.frame_geometry(window),
+            self.space.element_location(window),
+        ) else {
+            return;
+        };
+
+        let focused: Option<&Window> = focused.filter(|focused: &&Window| *focused != window);
+        let others: Vec<Rect> = self
+            .space
+            .elements()
+            .filter(|other: &&Window| *other != window && Some(*other) != focused)
+            .filter_map(|other: &Window| self.frame_geometry(other))
+            .map(to_rect)
+            .collect();
+        let focused: Option<Rect> = focused
+            .and_then(|focused: &Window| self.frame_geometry(focused))
+            .map(to_rect);
+
+        let placed: Rect = match rules::window_rule(window)
+            .and_then(|rule: &WindowRule| rule.position)
+        {
+            Some([x, y]) => Rect::new(area.loc.x + x, area.loc.y + y, frame.size.w, frame.size.h),
+            None => {
+                layout::smart_place(frame.size.w, frame.size.h, to_rect(area), focused, &others)
+            }
+        };
+        // The whole frame moves, the window keeps its place inside it
+        let moved: Point<i32, Logical> = Point::from((placed.x, placed.y)) - frame.loc;
+        self.space
+            .map_element(window.clone(), location + moved, false);
+    }
+}
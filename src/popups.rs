@@ -0,0 +1,145 @@
+use smithay::{
+    desktop::{
+        find_popup_root_surface, get_popup_toplevel_coords, layer_map_for_output, LayerSurface,
+        PopupKeyboardGrab, PopupKind, PopupPointerGrab, PopupUngrabStrategy, WindowSurfaceType,
+    },
+    input::{
+        pointer::{Focus, PointerHandle},
+        Seat,
+    },
+    output::Output,
+    reexports::wayland_server::protocol::{wl_seat::WlSeat, wl_surface::WlSurface},
+    utils::{Logical, Point, Rectangle, Serial},
+    wayland::shell::xdg::{PopupSurface, PositionerState},
+};
+
+use crate::state::State;
+
+impl State {
+    pub fn track_popup(&mut self, surface: PopupSurface, positioner: PositionerState) {
+        surface.with_pending_state(|state| {
+            state.geometry = positioner.get_geometry();
+            state.positioner = positioner;
+        });
+        self.unconstrain_popup(&surface);
+        if let Err(err) = self.popups.track_popup(PopupKind::Xdg(surface)) {
+            eprintln!("Failed to track popup: {err}");
+        }
+    }
+
+    // Popups are configured once their first commit comes in, like toplevels
+    pub fn popup_commit(&mut self, surface: &WlSurface) {
+        self.popups.commit(surface);
+
+        let Some(PopupKind::Xdg(popup)) = self.popups.find_popup(surface) else {
+            return;
+        };
+        if !popup.is_initial_configure_sent() {
+            if let Err(err) = popup.send_configure() {
+                eprintln!("Failed to configure popup: {err}");
+            }
+        }
+    }
+
+    // The parent moved or changed size and the client wants the popup placed
+    // again
+    pub fn reposition_popup(
+        &mut self,
+        surface: PopupSurface,
+        positioner: PositionerState,
+        token: u32,
+    ) {
+        surface.with_pending_state(|state| {
+            state.geometry = positioner.get_geometry();
+            state.positioner = positioner;
+        });
+        self.unconstrain_popup(&surface);
+        surface.send_repositioned(token);
+    }
+
+    // The output the root window or layer surface is on, relative to the
+    // popup's parent, which is where the positioner's coordinates start
+    fn popup_target(&self, popup: &PopupSurface) -> Option<Rectangle<i32, Logical>> {
+        let kind: PopupKind = PopupKind::Xdg(popup.clone());
+        let root: WlSurface = find_popup_root_surface(&kind).ok()?;
+
+        let (output, root_location): (Output, Point<i32, Logical>) = match self.windows.get(&root) {
+            Some(window) => {
+                let location: Point<i32, Logical> =
+                    self.space.element_location(window)? + window.geometry().loc;
+                (self.window_output(window)?, location)
+            }
+            None => {
+                let output: Output = self.layer_output(&root)?;
+                let map = layer_map_for_output(&output);
+                let layer: &LayerSurface =
+                    map.layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)?;
+                let location: Point<i32, Logical> =
+                    self.space.output_geometry(&output)?.loc + map.layer_geometry(layer)?.loc;
+                drop(map);
+                (output, location)
+            }
+        };
+
+        let mut target: Rectangle<i32, Logical> = self.space.output_geometry(&output)?;
+        target.loc -= root_location + get_popup_toplevel_coords(&kind);
+        Some(target)
+    }
+
+    // Flips, slides or resizes the popup as its positioner allows, so menus
+    // near the edge of the screen stay on it
+    fn unconstrain_popup(&self, popup: &PopupSurface) {
+        let Some(target) = self.popup_target(popup) else {
+            return;
+        };
+        popup.with_pending_state(|state| {
+            state.geometry = state.positioner.get_unconstrained_geometry(target);
+        });
+    }
+
+    // Explicit grabs, e.g. for menus. Clicking outside the client's popups
+    // or pressing escape dismisses them, PopupPointerGrab and
+    // PopupKeyboardGrab take care of that.
+    pub fn grab_popup(&mut self, surface: PopupSurface, seat: WlSeat, serial: Serial) {
+        let Some(seat) = Seat::<State>::from_resource(&seat) else {
+            return;
+        };
+        let kind: PopupKind = PopupKind::Xdg(surface);
+        let Ok(root) = find_popup_root_surface(&kind) else {
+            return;
+        };
+        let mut grab = match self.popups.grab_popup(root, kind, &seat, serial) {
+            Ok(grab) => grab,
+            Err(err) => {
+                eprintln!("Failed to grab popup: {err}");
+                return;
+            }
+        };
+
+        // The grab has to come from an input event the client actually got,
+        // which is the one that started the grab that's currently active
+        if let Some(keyboard) = seat.get_keyboard() {
+            if keyboard.is_grabbed()
+                && !(keyboard.has_grab(serial)
+                    || keyboard.has_grab(grab.previous_serial().unwrap_or(serial)))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            keyboard.set_focus(self, grab.current_grab(), serial);
+            keyboard.set_grab(PopupKeyboardGrab::new(&grab), serial);
+        }
+
+        let pointer: Option<PointerHandle<State>> = seat.get_pointer();
+        if let Some(pointer) = pointer {
+            if pointer.is_grabbed()
+                && !(pointer.has_grab(serial)
+                    || pointer.has_grab(grab.previous_serial().unwrap_or_else(|| grab.serial())))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            pointer.set_grab(self, PopupPointerGrab::new(&grab), serial, Focus::Keep);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use serde::Serialize;
+use smithay::desktop::LayerSurface;
+
+use crate::state::State;
+
+#[derive(Serialize)]
+pub struct PresentationStatus {
+    pub enabled: bool,
+    // Outputs turned off by hand
+    pub blanked: Vec<String>,
+}
+
+impl State {
+    // While presenting the displays never turn off on their own and
+    // notifications are kept off screen
+    pub fn set_presentation_mode(&mut self, enabled: bool) {
+        if self.presentation_mode == enabled {
+            return;
+        }
+        self.presentation_mode = enabled;
+
+        let label: &str = if enabled {
+            "Presentation mode on"
+        } else {
+            "Presentation mode off"
+        };
+        self.osd.show(label, None);
+        self.request_redraw();
+    }
+
+    pub fn toggle_presentation_mode(&mut self) {
+        self.set_presentation_mode(!self.presentation_mode);
+    }
+
+    pub fn presentation_status(&self) -> PresentationStatus {
+        PresentationStatus {
+            enabled: self.presentation_mode,
+            blanked: self.dpms.blanked().to_vec(),
+        }
+    }
+
+    // Notification daemons are layer surfaces, told apart by namespace
+    pub fn is_layer_hidden(&self, layer: &LayerSurface) -> bool {
+        self.presentation_mode
+            && self
+                .config
+                .presentation
+                .hidden_namespaces
+                .iter()
+                .any(|namespace: &String| namespace == layer.namespace())
+    }
+}
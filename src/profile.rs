@@ -0,0 +1,153 @@
+use smithay::output::Output;
+
+use crate::{
+    config::{OutputConfig, OutputProfile},
+    edid,
+};
+
+// Same rules as kanshi: a profile applies when each connected output is
+// claimed by exactly one of its entries, and no entry is left over
+pub fn find_profile<'a>(
+    profiles: &'a [OutputProfile],
+    outputs: &[Output],
+) -> Option<(&'a OutputProfile, Vec<(Output, &'a OutputConfig)>)> {
+    profiles.iter().find_map(|profile: &OutputProfile| {
+        if profile.outputs.len() != outputs.len() {
+            return None;
+        }
+
+        let matching: Vec<Vec<bool>> = outputs
+            .iter()
+            .map(|output: &Output| {
+                profile
+                    .outputs
+                    .iter()
+                    .map(|entry: &OutputConfig| matches(&entry.criteria, output))
+                    .collect()
+            })
+            .collect();
+        let assignment: Vec<usize> = assign(&matching, profile.outputs.len())?;
+
+        Some((
+            profile,
+            outputs
+                .iter()
+                .cloned()
+                .zip(
+                    assignment
+                        .into_iter()
+                        .map(|entry: usize| &profile.outputs[entry]),
+                )
+                .collect(),
+        ))
+    })
+}
+
+// For each output the entry claiming it, `matching[output][entry]` tells
+// which may. Taking the first entry that matches isn't enough: a "*" taken
+// by one output can be the only entry left that matches another, so every
+// choice is undone again when the outputs after it can't be claimed.
+fn assign(matching: &[Vec<bool>], entries: usize) -> Option<Vec<usize>> {
+    fn claim(matching: &[Vec<bool>], taken: &mut [bool], assignment: &mut Vec<usize>) -> bool {
+        let Some(row) = matching.get(assignment.len()) else {
+            return true;
+        };
+        for (entry, matches) in row.iter().enumerate() {
+            if !*matches || taken[entry] {
+                continue;
+            }
+            taken[entry] = true;
+            assignment.push(entry);
+            if claim(matching, taken, assignment) {
+                return true;
+            }
+            taken[entry] = false;
+            assignment.pop();
+        }
+        false
+    }
+
+    let mut taken: Vec<bool> = vec![false; entries];
+    let mut assignment: Vec<usize> = Vec::with_capacity(matching.len());
+    claim(matching, &mut taken, &mut assignment).then_some(assignment)
+}
+
+pub fn matches(criteria: &str, output: &Output) -> bool {
+    matches_description(criteria, &output.name(), &edid::output_description(output))
+}
+
+fn matches_description(criteria: &str, name: &str, description: &str) -> bool {
+    criteria == "*"
+        || criteria == name
+        || criteria == description
+        // Lets "Dell Inc. DELL U2720Q" match any serial
+        || description.starts_with(&format!("{criteria} "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Outputs as (connector, description)
+    fn assign_names(criteria: &[&str], outputs: &[(&str, &str)]) -> Option<Vec<usize>> {
+        let matching: Vec<Vec<bool>> = outputs
+            .iter()
+            .map(|(name, description): &(&str, &str)| {
+                criteria
+                    .iter()
+                    .map(|criteria: &&str| matches_description(criteria, name, description))
+                    .collect()
+            })
+            .collect();
+        assign(&matching, criteria.len())
+    }
+
+    #[test]
+    fn wildcard_left_for_the_output_that_needs_it() {
+        // First fit would give eDP-1's "*" to DP-1 and find nothing for eDP-1
+        let assignment: Option<Vec<usize>> = assign_names(
+            &["*", "DP-1"],
+            &[
+                ("DP-1", "Dell Inc. DELL U2720Q 1234"),
+                ("eDP-1", "BOE 0x0BCA"),
+            ],
+        );
+        assert_eq!(assignment, Some(vec![1, 0]));
+    }
+
+    #[test]
+    fn entries_are_claimed_once() {
+        let assignment: Option<Vec<usize>> = assign_names(
+            &["DP-1", "DP-1"],
+            &[
+                ("DP-1", "Dell Inc. DELL U2720Q 1234"),
+                ("DP-2", "Dell Inc. DELL U2720Q 5678"),
+            ],
+        );
+        assert_eq!(assignment, None);
+    }
+
+    #[test]
+    fn description_without_serial_matches_every_unit() {
+        let assignment: Option<Vec<usize>> = assign_names(
+            &["Dell Inc. DELL U2720Q 5678", "Dell Inc. DELL U2720Q"],
+            &[
+                ("DP-1", "Dell Inc. DELL U2720Q 1234"),
+                ("DP-2", "Dell Inc. DELL U2720Q 5678"),
+            ],
+        );
+        assert_eq!(assignment, Some(vec![1, 0]));
+    }
+
+    #[test]
+    fn unmatched_output_fails() {
+        let assignment: Option<Vec<usize>> =
+            assign_names(&["eDP-1"], &[("HDMI-A-1", "Goldstar Company Ltd LG TV")]);
+        assert_eq!(assignment, None);
+    }
+
+    #[test]
+    fn no_outputs_need_no_entries() {
+        assert_eq!(assign_names(&[], &[]), Some(Vec::new()));
+    }
+}
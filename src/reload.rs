@@ -0,0 +1,76 @@
+use std::{io::Read, os::unix::net::UnixStream};
+
+use signal_hook::{consts::SIGHUP, low_level::pipe};
+use smithay::reexports::calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction};
+
+use crate::{
+    config::Config,
+    data::Data,
+    ipc::IpcEvent,
+    spawn,
+    state::State,
+    text::{FontError, TextRenderer},
+};
+
+// `kill -HUP` reloads the config like the keybinding does. The handler only
+// writes to a pipe, the reload itself happens in the event loop.
+pub fn reload_on_sighup(handle: &LoopHandle<'static, Data>) {
+    let (reader, writer) = match UnixStream::pair() {
+        Ok(pair) => pair,
+        Err(err) => {
+            eprintln!("Failed to create the SIGHUP pipe: {err}");
+            return;
+        }
+    };
+    if let Err(err) = reader
+        .set_nonblocking(true)
+        .and_then(|()| pipe::register(SIGHUP, writer).map(|_| ()))
+    {
+        eprintln!("Failed to handle SIGHUP: {err}");
+        return;
+    }
+
+    handle
+        .insert_source(
+            Generic::new(reader, Interest::READ, Mode::Level),
+            |_, reader: &mut UnixStream, data: &mut Data| {
+                let mut buffer: [u8; 16] = [0; 16];
+                while matches!(reader.read(&mut buffer), Ok(read) if read > 0) {}
+                if let Err(err) = data.state.reload_config() {
+                    eprintln!("Failed to reload config: {err}");
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .unwrap();
+}
+
+impl State {
+    // Most settings are read from self.config whenever they're used, like
+    // keybindings and output rules, this applies the rest. Running clients
+    // are left alone. The number of workspaces only changes on restart.
+    pub fn reload_config(&mut self) -> Result<(), String> {
+        let config: Config = Config::try_load()?;
+
+        spawn::set_child_env(config.child_env());
+        self.tiling.params = config.layout.params();
+        if config.appearance.font != self.config.appearance.font {
+            self.text = TextRenderer::new(&config.appearance.font)
+                .map_err(|err: FontError| eprintln!("Failed to load font: {err}"))
+                .ok();
+        }
+        let theme_changed: bool = config.appearance.theme != self.config.appearance.theme;
+        self.config = config;
+
+        if theme_changed {
+            self.set_theme(self.config.appearance.theme);
+        }
+        self.reload_cursor_theme();
+        self.apply_output_config();
+        self.arrange();
+
+        self.osd.show("Config reloaded", None);
+        self.emit_ipc_event(IpcEvent::ConfigReloaded);
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+use smithay::{
+    backend::renderer::{
+        element::{
+            default_primary_scanout_output_compare, solid::SolidColorRenderElement,
+            surface::WaylandSurfaceRenderElement, texture::TextureRenderElement,
+            utils::RescaleRenderElement, AsRenderElements, RenderElementStates,
+        },
+        gles::{GlesRenderer, GlesTexture},
+    },
+    desktop::utils::{surface_primary_scanout_output, update_surface_primary_scanout_output},
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    render_elements,
+    utils::{Logical, Physical, Point, Scale},
+    wayland::{compositor::SurfaceData, shell::wlr_layer::Layer},
+};
+
+use crate::{
+    borders::Borders, decorations::Decorations, fractional_scale, rules, state::State,
+    text::TextRenderer,
+};
+
+// Everything drawn on an output, client windows and the compositor's own UI
+render_elements! {
+    pub CustomRenderElements<=GlesRenderer>;
+    Surface=WaylandSurfaceRenderElement<GlesRenderer>,
+    Solid=SolidColorRenderElement,
+    Texture=TextureRenderElement<GlesTexture>,
+    Rescaled=RescaleRenderElement<WaylandSurfaceRenderElement<GlesRenderer>>,
+}
+
+impl State {
+    // Called before rendering an output
+    pub fn prepare_frame(&mut self) {
+        self.advance_animations();
+        self.flush_resize();
+        self.process_pending_raise();
+        self.restack_floating();
+    }
+
+    // Called after an output's frame was submitted
+    pub fn frame_finished(&mut self) {
+        self.space.refresh();
+        self.popups.cleanup();
+    }
+
+    // Fills `elements` topmost first, like the damage tracker expects them.
+    // Backends keep `elements` between frames, so this only allocates when
+    // the scene grows.
+    pub fn output_render_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        self.freeze_output(renderer, output);
+        self.scene_elements(renderer, output, false, elements);
+        self.damage_debug_elements(output, elements);
+    }
+
+    // Everything on the output, with `capture` set the way screencopy sees
+    // it, see State::capture_elements. Each part is pushed straight into
+    // `elements`, walking the StackLayers from the top down.
+    pub(crate) fn scene_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        capture: bool,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        elements.clear();
+
+        // StackLayer::Cursor. Screencopy clients that want the cursor
+        // composite it themselves.
+        if !capture {
+            self.cursor_elements(renderer, output, elements);
+        }
+        // StackLayer::DragIcon
+        self.dnd_icon_elements(renderer, output, elements);
+        // StackLayer::Lock, with the desktop frozen below it while locking
+        self.lock_elements(renderer, output, elements);
+
+        if !self.is_locked() {
+            self.desktop_elements(renderer, output, capture, elements);
+        }
+    }
+
+    // What the session shows on the output, everything below
+    // StackLayer::Lock
+    pub(crate) fn desktop_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        capture: bool,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        // StackLayer::Osd, the menu on top
+        self.menu_elements(renderer, output, elements);
+        self.switcher_elements(renderer, output, elements);
+        self.cheatsheet_elements(renderer, output, elements);
+        self.osd
+            .render_elements(renderer, self.text.as_mut(), output, elements);
+        self.overview_elements(renderer, output, elements);
+
+        self.layer_elements(renderer, output, Layer::Overlay, elements);
+        // StackLayer::Fullscreen
+        self.window_elements(renderer, output, capture, true, elements);
+
+        // StackLayer::Top. The bar is hidden rather than covered, a
+        // fullscreen window may not be opaque.
+        self.layer_elements(renderer, output, Layer::Top, elements);
+        if self.fullscreen_on(output).is_none() {
+            self.bar_elements(renderer, output, elements);
+        }
+
+        // StackLayer::Windows
+        self.snap_preview_elements(output, elements);
+        self.tile_drag_elements(output, elements);
+        self.window_elements(renderer, output, capture, false, elements);
+
+        self.layer_elements(renderer, output, Layer::Bottom, elements);
+        // StackLayer::Background, the wallpaper below the layer surfaces
+        self.layer_elements(renderer, output, Layer::Background, elements);
+        self.background_elements(renderer, output, elements);
+    }
+
+    // Windows topmost first, each with its decoration around it. Either the
+    // fullscreen ones or all the others.
+    fn window_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        capture: bool,
+        fullscreen: bool,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        let Some(output_geometry) = self.space.output_geometry(output) else {
+            return;
+        };
+        let scale: f64 = output.current_scale().fractional_scale();
+
+        // Taken out while the loop below borrows the space, and put back to
+        // keep their buffers
+        let mut decorations: Decorations = std::mem::take(&mut self.decorations);
+        let mut borders: Borders = std::mem::take(&mut self.borders);
+        let mut text: Option<TextRenderer> = self.text.take();
+
+        for window in self.space.elements_for_output(output).rev() {
+            if self.is_fullscreen(window) != fullscreen {
+                continue;
+            }
+            let Some(location) = self.space.element_location(window) else {
+                continue;
+            };
+            let location: Point<i32, Logical> = location - output_geometry.loc;
+            let physical: Point<i32, Physical> = location.to_physical_precise_round(scale);
+
+            self.decoration_elements(
+                &mut decorations,
+                text.as_mut(),
+                renderer,
+                output,
+                window,
+                elements,
+            );
+            if capture && self.is_protected(window) {
+                elements.push(self.protected_element(window, location, scale));
+            } else {
+                elements.extend(window.render_elements::<CustomRenderElements>(
+                    renderer,
+                    physical,
+                    Scale::from(scale),
+                    rules::opacity(window),
+                ));
+            }
+            self.decoration_border_element(&mut decorations, output, window, elements);
+            self.border_elements(&mut borders, output, window, elements);
+        }
+
+        self.decorations = decorations;
+        self.borders = borders;
+        self.text = text;
+    }
+
+    // A window spanning several outputs is drawn on each of them, but should
+    // only get frame callbacks paced by one, the output showing most of it
+    pub fn send_frames(&self, output: &Output, states: &RenderElementStates, time: Duration) {
+        for window in self.space.elements_for_output(output) {
+            window.with_surfaces(|surface: &WlSurface, data: &SurfaceData| {
+                let primary: Option<Output> = update_surface_primary_scanout_output(
+                    surface,
+                    output,
+                    data,
+                    states,
+                    default_primary_scanout_output_compare,
+                );
+                if let Some(primary) = primary {
+                    fractional_scale::send_preferred_scale(&primary, data);
+                }
+            });
+
+            window.send_frame(
+                output,
+                time,
+                Some(Duration::ZERO),
+                surface_primary_scanout_output,
+            );
+        }
+
+        self.send_layer_frames(output, states, time);
+        self.send_lock_frames(output, time);
+        self.send_cursor_frames(output, time);
+        self.send_dnd_icon_frames(output, time);
+    }
+}
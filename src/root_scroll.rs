@@ -0,0 +1,66 @@
+use serde::Deserialize;
+
+use crate::{config::RootScrollConfig, state::State};
+
+// libinput reports one wheel click as 15 pixels of scrolling, touchpads
+// and smooth wheels add up to a step the same way
+pub const STEP: f64 = 15.0;
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RootAction {
+    WorkspaceNext,
+    WorkspacePrev,
+    // e.g. { spawn = "pactl set-sink-volume @DEFAULT_SINK@ +5%" }
+    Spawn(String),
+}
+
+#[derive(Default)]
+pub struct ScrollAccumulator {
+    horizontal: f64,
+    vertical: f64,
+}
+
+// Whole steps taken from `accumulated`, the remainder is kept for later
+fn take_steps(accumulated: &mut f64, delta: f64) -> i32 {
+    *accumulated += delta;
+    let steps: f64 = (*accumulated / STEP).trunc();
+    *accumulated -= steps * STEP;
+    steps as i32
+}
+
+impl State {
+    // Scrolling over the desktop background, in pixels like wl_pointer.axis.
+    // Positive is down and right.
+    pub fn scroll_root(&mut self, horizontal: f64, vertical: f64) {
+        let vertical_steps: i32 = take_steps(&mut self.root_scroll.vertical, vertical);
+        let horizontal_steps: i32 = take_steps(&mut self.root_scroll.horizontal, horizontal);
+
+        let bindings: &RootScrollConfig = &self.config.root_scroll;
+        let actions: [(i32, Option<RootAction>); 4] = [
+            (-vertical_steps, bindings.up.clone()),
+            (vertical_steps, bindings.down.clone()),
+            (-horizontal_steps, bindings.left.clone()),
+            (horizontal_steps, bindings.right.clone()),
+        ];
+
+        for (steps, action) in actions {
+            let Some(action) = action else {
+                continue;
+            };
+            for _ in 0..steps.max(0) {
+                self.run_root_action(&action);
+            }
+        }
+    }
+
+    fn run_root_action(&mut self, action: &RootAction) {
+        match action {
+            RootAction::WorkspaceNext => self.cycle_workspace(1),
+            RootAction::WorkspacePrev => self.cycle_workspace(-1),
+            RootAction::Spawn(command) => {
+                self.launch(command);
+            }
+        }
+    }
+}
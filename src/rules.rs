@@ -0,0 +1,123 @@
+use regex::Regex;
+use serde::Deserialize;
+use smithay::desktop::Window;
+
+use crate::{state::State, switcher};
+
+// "/firefox|chromium/" is a regex, anything else has to match exactly
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Pattern {
+    Exact(String),
+    Regex(Regex),
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        match pattern
+            .strip_prefix('/')
+            .and_then(|rest: &str| rest.strip_suffix('/'))
+        {
+            Some(regex) => Regex::new(regex)
+                .map(Pattern::Regex)
+                .map_err(|err: regex::Error| format!("invalid regex \"{regex}\": {err}")),
+            None => Ok(Pattern::Exact(pattern)),
+        }
+    }
+}
+
+impl Pattern {
+    // A window without the app_id or title never matches
+    fn matches(&self, value: Option<&str>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+        match self {
+            Pattern::Exact(exact) => exact == value,
+            Pattern::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+// What happens to a toplevel when it's first mapped, every field is
+// optional
+#[derive(Clone, Default, Deserialize)]
+pub struct WindowRule {
+    // Both have to match if both are set, a rule with neither matches
+    // every window
+    pub app_id: Option<Pattern>,
+    pub title: Option<Pattern>,
+    // Counting from 1, in tag mode the only tag it gets
+    pub workspace: Option<usize>,
+    // Tiled or not, instead of what the window asks for
+    pub floating: Option<bool>,
+    // Of floating windows, in logical pixels. The position is relative to
+    // the output's usable area.
+    pub size: Option<[i32; 2]>,
+    pub position: Option<[i32; 2]>,
+    // 0.0 to 1.0
+    pub opacity: Option<f32>,
+    // Off leaves out the [layout] border, server-side decorations keep
+    // theirs
+    pub border: Option<bool>,
+    // Shown black in screenshots and screencasts, like the apps in
+    // [capture] protected_app_ids
+    pub protected: Option<bool>,
+}
+
+impl WindowRule {
+    fn matches(&self, window: &Window) -> bool {
+        self.app_id.as_ref().map_or(true, |pattern: &Pattern| {
+            pattern.matches(switcher::app_id(window).as_deref())
+        }) && self.title.as_ref().map_or(true, |pattern: &Pattern| {
+            pattern.matches(switcher::title(window).as_deref())
+        })
+    }
+
+    pub fn merge(self, other: &WindowRule) -> Self {
+        Self {
+            app_id: other.app_id.clone(),
+            title: other.title.clone(),
+            workspace: other.workspace.or(self.workspace),
+            floating: other.floating.or(self.floating),
+            size: other.size.or(self.size),
+            position: other.position.or(self.position),
+            opacity: other.opacity.or(self.opacity),
+            border: other.border.or(self.border),
+            protected: other.protected.or(self.protected),
+        }
+    }
+}
+
+// The rule the window was mapped with, None before that
+pub fn window_rule(window: &Window) -> Option<&WindowRule> {
+    window.user_data().get::<WindowRule>()
+}
+
+pub fn opacity(window: &Window) -> f32 {
+    window_rule(window)
+        .and_then(|rule: &WindowRule| rule.opacity)
+        .map_or(1.0, |opacity: f32| opacity.clamp(0.0, 1.0))
+}
+
+impl State {
+    // Later rules override earlier ones field by field. Resolved once,
+    // on the initial commit: new_toplevel comes before the client had a
+    // chance to set its app_id and title. Changing them later changes
+    // nothing.
+    pub fn apply_window_rules(&mut self, window: &Window) -> WindowRule {
+        let rule: WindowRule = self
+            .config
+            .window_rules
+            .iter()
+            .filter(|rule: &&WindowRule| rule.matches(window))
+            .fold(
+                WindowRule::default(),
+                |merged: WindowRule, rule: &WindowRule| merged.merge(rule),
+            );
+        window.user_data().insert_if_missing(|| rule.clone());
+        rule
+    }
+}
@@ -0,0 +1,425 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::OutputDamageTracker,
+            element::RenderElementStates,
+            gles::{GlesMapping, GlesRenderer, GlesTexture},
+            Bind, ExportMem, Offscreen, TextureMapping,
+        },
+    },
+    output::Output,
+    reexports::{
+        nix::time::{clock_gettime, ClockId},
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            backend::GlobalId,
+            protocol::{wl_buffer::WlBuffer, wl_output::WlOutput, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Buffer, Logical, Physical, Rectangle, Size, Transform},
+    wayland::shm::{with_buffer_contents, with_buffer_contents_mut, BufferData},
+};
+
+use crate::{acl::PrivilegedProtocol, render::CustomRenderElements, state::State};
+
+const VERSION: u32 = 3;
+
+// Read back as RGBA, which every GLES implementation can do. The client
+// gets the same bytes, wl_shm calls that ABGR8888.
+const FORMAT: Fourcc = Fourcc::Abgr8888;
+const SHM_FORMAT: wl_shm::Format = wl_shm::Format::Abgr8888;
+
+pub fn create_global(state: &mut State) -> GlobalId {
+    state
+        .display_handle
+        .create_global::<State, ZwlrScreencopyManagerV1, _>(VERSION, ())
+}
+
+pub struct FrameData {
+    // The output and the region of its buffer, where the output's transform
+    // is already applied. None if there was nothing to capture.
+    target: Option<(Output, Rectangle<i32, Buffer>)>,
+    used: AtomicBool,
+}
+
+struct PendingCopy {
+    frame: ZwlrScreencopyFrameV1,
+    buffer: WlBuffer,
+    output: Output,
+    region: Rectangle<i32, Buffer>,
+    with_damage: bool,
+}
+
+// Each output is rendered into its own texture for copying, with a damage
+// tracker of its own that remembers what the previous copy saw
+struct Target {
+    output: Output,
+    size: Size<i32, Buffer>,
+    texture: GlesTexture,
+    damage_tracker: OutputDamageTracker,
+    rendered: bool,
+}
+
+#[derive(Default)]
+pub struct Screencopy {
+    pending: Vec<PendingCopy>,
+    targets: Vec<Target>,
+}
+
+impl Screencopy {
+    // Made again when the output's mode changed
+    fn target(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        size: Size<i32, Buffer>,
+    ) -> Result<&mut Target, String> {
+        self.targets
+            .retain(|target: &Target| target.output != *output || target.size == size);
+
+        let index: usize = match self
+            .targets
+            .iter()
+            .position(|target: &Target| target.output == *output)
+        {
+            Some(index) => index,
+            None => {
+                let texture: GlesTexture =
+                    Offscreen::<GlesTexture>::create_buffer(renderer, FORMAT, size)
+                        .map_err(|err| err.to_string())?;
+                self.targets.push(Target {
+                    output: output.clone(),
+                    size,
+                    texture,
+                    damage_tracker: OutputDamageTracker::from_output(output),
+                    rendered: false,
+                });
+                self.targets.len() - 1
+            }
+        };
+        Ok(&mut self.targets[index])
+    }
+}
+
+// The whole output, or `region` of it in output-local logical coordinates,
+// in the output's buffer
+fn buffer_region(
+    output: &Output,
+    region: Option<Rectangle<i32, Logical>>,
+) -> Option<Rectangle<i32, Buffer>> {
+    let mode_size: Size<i32, Physical> = output.current_mode()?.size;
+    let transform: Transform = output.current_transform();
+    let transformed: Size<i32, Physical> = transform.transform_size(mode_size);
+    let full: Rectangle<i32, Physical> = Rectangle::from_loc_and_size((0, 0), transformed);
+
+    let region: Rectangle<i32, Physical> = match region {
+        Some(region) => region
+            .to_physical_precise_round(output.current_scale().fractional_scale())
+            .intersection(full)?,
+        None => full,
+    };
+    let region: Rectangle<i32, Physical> =
+        transform.invert().transform_rect_in(region, &transformed);
+
+    Some(Rectangle::from_loc_and_size(
+        (region.loc.x, region.loc.y),
+        (region.size.w, region.size.h),
+    ))
+}
+
+fn send_ready(frame: &ZwlrScreencopyFrameV1) {
+    let Ok(now) = clock_gettime(ClockId::CLOCK_MONOTONIC) else {
+        frame.failed();
+        return;
+    };
+    let seconds: u64 = now.tv_sec() as u64;
+    frame.ready((seconds >> 32) as u32, seconds as u32, now.tv_nsec() as u32);
+}
+
+impl State {
+    fn capture_output(
+        &mut self,
+        frame: New<ZwlrScreencopyFrameV1>,
+        output: &WlOutput,
+        region: Option<Rectangle<i32, Logical>>,
+        data_init: &mut DataInit<'_, State>,
+    ) {
+        let target: Option<(Output, Rectangle<i32, Buffer>)> = Output::from_resource(output)
+            .and_then(|output: Output| {
+                let region: Rectangle<i32, Buffer> = buffer_region(&output, region)?;
+                Some((output, region))
+            });
+        let frame: ZwlrScreencopyFrameV1 = data_init.init(
+            frame,
+            FrameData {
+                target: target.clone(),
+                used: AtomicBool::new(false),
+            },
+        );
+
+        // The output is gone or the region is outside of it
+        let Some((_, region)) = target else {
+            frame.failed();
+            return;
+        };
+        frame.buffer(
+            SHM_FORMAT,
+            region.size.w as u32,
+            region.size.h as u32,
+            region.size.w as u32 * 4,
+        );
+        if frame.version() >= 3 {
+            frame.buffer_done();
+        }
+    }
+
+    fn queue_copy(&mut self, frame: &ZwlrScreencopyFrameV1, buffer: WlBuffer, with_damage: bool) {
+        let data: &FrameData = frame.data::<FrameData>().unwrap();
+        if data.used.swap(true, Ordering::Relaxed) {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+                "frame was already copied",
+            );
+            return;
+        }
+
+        let Some((output, region)) = data.target.clone() else {
+            frame.failed();
+            return;
+        };
+        let fits: bool = with_buffer_contents(&buffer, |_, _, buffer_data: BufferData| {
+            buffer_data.format == SHM_FORMAT
+                && buffer_data.width == region.size.w
+                && buffer_data.height == region.size.h
+                && buffer_data.stride == region.size.w * 4
+        })
+        .unwrap_or(false);
+        if !fits {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+                "buffer doesn't match the advertised one",
+            );
+            return;
+        }
+
+        self.screencopy.pending.push(PendingCopy {
+            frame: frame.clone(),
+            buffer,
+            output,
+            region,
+            with_damage,
+        });
+        self.request_redraw();
+    }
+
+    // Called by the backends right after rendering `output`. Copies with
+    // damage wait until something on the output changed.
+    pub fn process_screencopies(&mut self, renderer: &mut GlesRenderer, output: &Output) {
+        self.screencopy
+            .pending
+            .retain(|copy: &PendingCopy| copy.frame.is_alive());
+        self.screencopy
+            .targets
+            .retain(|target: &Target| self.outputs.contains(&target.output));
+        if !self
+            .screencopy
+            .pending
+            .iter()
+            .any(|copy: &PendingCopy| copy.output == *output)
+        {
+            return;
+        }
+        let Some(mode) = output.current_mode() else {
+            return;
+        };
+        let size: Size<i32, Buffer> = (mode.size.w, mode.size.h).into();
+
+        let elements: Vec<CustomRenderElements> = self.capture_elements(renderer, output);
+        let clear_color: [f32; 4] = self.background_color(output);
+        let target: &mut Target = match self.screencopy.target(renderer, output, size) {
+            Ok(target) => target,
+            Err(err) => {
+                eprintln!("Failed to create the screencopy buffer: {err}");
+                return;
+            }
+        };
+
+        let age: usize = usize::from(target.rendered);
+        let result: Result<(Option<Vec<Rectangle<i32, Physical>>>, RenderElementStates), String> =
+            renderer
+                .bind(target.texture.clone())
+                .map_err(|err| err.to_string())
+                .and_then(|_| {
+                    target
+                        .damage_tracker
+                        .render_output(renderer, age, &elements, clear_color)
+                        .map_err(|err| format!("{err:?}"))
+                });
+        let damage: Option<Vec<Rectangle<i32, Physical>>> = match result {
+            Ok((damage, _)) => damage,
+            Err(err) => {
+                eprintln!("Failed to render for screencopy: {err}");
+                target.rendered = false;
+                return;
+            }
+        };
+        target.rendered = true;
+
+        let (ready, waiting): (Vec<PendingCopy>, Vec<PendingCopy>) =
+            std::mem::take(&mut self.screencopy.pending)
+                .into_iter()
+                .partition(|copy: &PendingCopy| {
+                    copy.output == *output && (!copy.with_damage || damage.is_some())
+                });
+        self.screencopy.pending = waiting;
+
+        for copy in ready {
+            match copy_pixels(renderer, &copy) {
+                Ok(flipped) => {
+                    let flags: zwlr_screencopy_frame_v1::Flags = if flipped {
+                        zwlr_screencopy_frame_v1::Flags::YInvert
+                    } else {
+                        zwlr_screencopy_frame_v1::Flags::empty()
+                    };
+                    copy.frame.flags(flags);
+                    if copy.with_damage {
+                        send_damage(&copy, damage.as_deref().unwrap_or_default());
+                    }
+                    send_ready(&copy.frame);
+                }
+                Err(err) => {
+                    eprintln!("Failed to copy the screen: {err}");
+                    copy.frame.failed();
+                }
+            }
+        }
+    }
+}
+
+// Returns whether the rows ended up bottom to top
+fn copy_pixels(renderer: &mut GlesRenderer, copy: &PendingCopy) -> Result<bool, String> {
+    let mapping: GlesMapping = renderer
+        .copy_framebuffer(copy.region, FORMAT)
+        .map_err(|err| err.to_string())?;
+    let flipped: bool = mapping.flipped();
+    let pixels: &[u8] = renderer
+        .map_texture(&mapping)
+        .map_err(|err| err.to_string())?;
+
+    with_buffer_contents_mut(
+        &copy.buffer,
+        |ptr: *mut u8, len: usize, data: BufferData| {
+            let offset: usize = data.offset as usize;
+            let size: usize = (data.stride * data.height) as usize;
+            if offset + size > len || pixels.len() < size {
+                return Err("buffer is too small".to_string());
+            }
+            let contents: &mut [u8] =
+                unsafe { std::slice::from_raw_parts_mut(ptr.add(offset), size) };
+            contents.copy_from_slice(&pixels[..size]);
+            Ok(())
+        },
+    )
+    .map_err(|err| format!("{err:?}"))??;
+
+    Ok(flipped)
+}
+
+// Damage is in the output's buffer, moved to where the region starts
+fn send_damage(copy: &PendingCopy, damage: &[Rectangle<i32, Physical>]) {
+    let region: Rectangle<i32, Physical> = Rectangle::from_loc_and_size(
+        (copy.region.loc.x, copy.region.loc.y),
+        (copy.region.size.w, copy.region.size.h),
+    );
+    for rect in damage {
+        let Some(rect) = rect.intersection(region) else {
+            continue;
+        };
+        copy.frame.damage(
+            (rect.loc.x - region.loc.x) as u32,
+            (rect.loc.y - region.loc.y) as u32,
+            rect.size.w as u32,
+            rect.size.h as u32,
+        );
+    }
+}
+
+impl GlobalDispatch<ZwlrScreencopyManagerV1, ()> for State {
+    fn bind(
+        state: &mut Self,
+        _handle: &DisplayHandle,
+        client: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if !state.may_bind(client, PrivilegedProtocol::Screencopy) {
+            data_init.post_error(resource, 0u32, "screencopy is not allowed for this client");
+            return;
+        }
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for State {
+    // The cursor is a hardware plane and never part of the frame, so
+    // overlay_cursor makes no difference
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _data: &(),
+        _handle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput { frame, output, .. } => {
+                state.capture_output(frame, &output, None, data_init);
+            }
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                frame,
+                output,
+                x,
+                y,
+                width,
+                height,
+                ..
+            } => {
+                let region: Rectangle<i32, Logical> =
+                    Rectangle::from_loc_and_size((x, y), (width, height));
+                state.capture_output(frame, &output, Some(region), data_init);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, FrameData> for State {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        _data: &FrameData,
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => {
+                state.queue_copy(resource, buffer, false);
+            }
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => {
+                state.queue_copy(resource, buffer, true);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use smithay::reexports::calloop::{
+    channel::{self, Channel, Event},
+    LoopHandle,
+};
+use zbus::{
+    blocking::{fdo::DBusProxy, Connection, ConnectionBuilder},
+    dbus_interface,
+    fdo::NameOwnerChangedArgs,
+    MessageHeader,
+};
+
+use crate::{data::Data, state::State};
+
+const BUS_NAME: &str = "org.freedesktop.ScreenSaver";
+// Apps disagree on which one to call
+const OBJECT_PATHS: [&str; 2] = ["/org/freedesktop/ScreenSaver", "/ScreenSaver"];
+
+struct Inhibitor {
+    cookie: u32,
+    // The unique bus name of the app, so its inhibitors go away when it
+    // quits or crashes without calling UnInhibit
+    owner: String,
+}
+
+struct Inhibitors {
+    next_cookie: u32,
+    active: Vec<Inhibitor>,
+    // Whether anything inhibits idling now, sent on every change
+    changed: channel::Sender<bool>,
+}
+
+impl Inhibitors {
+    fn retain(&mut self, keep: impl Fn(&Inhibitor) -> bool) {
+        let before: usize = self.active.len();
+        self.active.retain(keep);
+        if self.active.len() != before {
+            let _ = self.changed.send(!self.active.is_empty());
+        }
+    }
+}
+
+struct ScreenSaverInterface {
+    inhibitors: Arc<Mutex<Inhibitors>>,
+}
+
+#[dbus_interface(name = "org.freedesktop.ScreenSaver")]
+impl ScreenSaverInterface {
+    fn inhibit(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        application_name: &str,
+        reason_for_inhibit: &str,
+    ) -> u32 {
+        let owner: String = header
+            .sender()
+            .ok()
+            .flatten()
+            .map_or_else(String::new, |sender| sender.to_string());
+        eprintln!("{application_name} inhibits idling: {reason_for_inhibit}");
+
+        let mut inhibitors: MutexGuard<Inhibitors> = self.inhibitors.lock().unwrap();
+        inhibitors.next_cookie = inhibitors.next_cookie.wrapping_add(1).max(1);
+        let cookie: u32 = inhibitors.next_cookie;
+        inhibitors.active.push(Inhibitor { cookie, owner });
+        let _ = inhibitors.changed.send(true);
+        cookie
+    }
+
+    fn un_inhibit(&self, cookie: u32) {
+        self.inhibitors
+            .lock()
+            .unwrap()
+            .retain(|inhibitor: &Inhibitor| inhibitor.cookie != cookie);
+    }
+}
+
+// org.freedesktop.ScreenSaver on the session bus, for apps like Firefox and
+// Steam that keep the screen on through it rather than the Wayland
+// protocol. While anything inhibits, the displays don't go off on their own.
+pub struct ScreenSaver {
+    _connection: Connection,
+    inhibited: bool,
+}
+
+impl ScreenSaver {
+    pub fn new(handle: &LoopHandle<'_, Data>) -> Option<Self> {
+        let (sender, channel): (channel::Sender<bool>, Channel<bool>) = channel::channel();
+        let inhibitors: Arc<Mutex<Inhibitors>> = Arc::new(Mutex::new(Inhibitors {
+            next_cookie: 0,
+            active: Vec::new(),
+            changed: sender,
+        }));
+
+        let interface = || ScreenSaverInterface {
+            inhibitors: inhibitors.clone(),
+        };
+        let connection: zbus::Result<Connection> = ConnectionBuilder::session()
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| builder.serve_at(OBJECT_PATHS[0], interface()))
+            .and_then(|builder| builder.serve_at(OBJECT_PATHS[1], interface()))
+            .and_then(|builder| builder.build());
+        let connection: Connection = match connection {
+            Ok(connection) => connection,
+            Err(err) => {
+                eprintln!("Failed to register {BUS_NAME}: {err}");
+                return None;
+            }
+        };
+
+        handle
+            .insert_source(channel, |event: Event<bool>, _, data: &mut Data| {
+                if let Event::Msg(inhibited) = event {
+                    data.state.set_dbus_idle_inhibited(inhibited);
+                }
+            })
+            .unwrap();
+
+        let watched: Connection = connection.clone();
+        std::thread::Builder::new()
+            .name("pulseWM-screensaver".into())
+            .spawn(move || {
+                if let Err(err) = drop_vanished(watched, inhibitors) {
+                    eprintln!("Stopped watching idle inhibitors: {err}");
+                }
+            })
+            .unwrap();
+
+        Some(Self {
+            _connection: connection,
+            inhibited: false,
+        })
+    }
+}
+
+fn drop_vanished(connection: Connection, inhibitors: Arc<Mutex<Inhibitors>>) -> zbus::Result<()> {
+    let dbus: DBusProxy = DBusProxy::new(&connection)?;
+    for signal in dbus.receive_name_owner_changed()? {
+        let args: NameOwnerChangedArgs<'_> = signal.args()?;
+        if args.new_owner().is_some() {
+            continue;
+        }
+        let name: String = args.name().to_string();
+        inhibitors
+            .lock()
+            .unwrap()
+            .retain(|inhibitor: &Inhibitor| inhibitor.owner != name);
+    }
+    Ok(())
+}
+
+impl State {
+    pub fn is_idle_inhibited(&self) -> bool {
+        self.screensaver
+            .as_ref()
+            .is_some_and(|screensaver: &ScreenSaver| screensaver.inhibited)
+    }
+
+    fn set_dbus_idle_inhibited(&mut self, inhibited: bool) {
+        if let Some(screensaver) = self.screensaver.as_mut() {
+            screensaver.inhibited = inhibited;
+        }
+    }
+}
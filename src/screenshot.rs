@@ -0,0 +1,210 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::OutputDamageTracker,
+            gles::{GlesMapping, GlesRenderer, GlesTexture},
+            Bind, ExportMem, Offscreen, TextureMapping,
+        },
+    },
+    output::Output,
+    utils::{Physical, Rectangle, Size, Transform},
+};
+
+use crate::{config, encode::Frame, render::CustomRenderElements, state::State};
+
+const DIRECTORY: &str = "~/Pictures";
+
+struct PendingScreenshot {
+    output: Output,
+    path: PathBuf,
+}
+
+// Screenshots wait for the backend to render their output, that's when a
+// renderer is around to read it back with
+#[derive(Default)]
+pub struct Screenshots {
+    pending: Vec<PendingScreenshot>,
+    // The name of the last screenshot and how many were taken under it
+    last: Option<(String, u32)>,
+}
+
+// UTC, e.g. "2023-07-14_09-41-05", from Howard Hinnant's days_from_civil
+// inverse
+fn timestamp(seconds: u64) -> String {
+    let days: i64 = (seconds / 86400) as i64;
+    let time: u64 = seconds % 86400;
+
+    let z: i64 = days + 719468;
+    let era: i64 = z.div_euclid(146097);
+    let day_of_era: i64 = z.rem_euclid(146097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month: i64 = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year: i64 = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+// Down to the millisecond, with a counter for screenshots taken within the
+// same one
+fn screenshot_path(last: &mut Option<(String, u32)>) -> Result<PathBuf, String> {
+    let directory: PathBuf = config::expand_home(Path::new(DIRECTORY));
+    std::fs::create_dir_all(&directory)
+        .map_err(|err| format!("Failed to create {}: {err}", directory.display()))?;
+
+    let now: Duration = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let name: String = format!(
+        "pulseWM-{}.{:03}",
+        timestamp(now.as_secs()),
+        now.subsec_millis()
+    );
+    let count: u32 = match last {
+        Some((last_name, count)) if *last_name == name => {
+            *count += 1;
+            *count
+        }
+        _ => {
+            *last = Some((name.clone(), 1));
+            1
+        }
+    };
+
+    Ok(if count == 1 {
+        directory.join(format!("{name}.png"))
+    } else {
+        directory.join(format!("{name}-{count}.png"))
+    })
+}
+
+impl State {
+    // Without a name the output the pointer is on. Returns where the PNG
+    // will be once it's encoded.
+    pub fn take_screenshot(&mut self, output: Option<&str>) -> Result<PathBuf, String> {
+        let output: Output = match output {
+            Some(name) => self
+                .outputs
+                .iter()
+                .find(|output: &&Output| output.name() == name)
+                .cloned()
+                .ok_or_else(|| format!("no output named \"{name}\""))?,
+            None => self.active_output().ok_or("no output")?,
+        };
+
+        let path: PathBuf = screenshot_path(&mut self.screenshots.last)?;
+        self.screenshots.pending.push(PendingScreenshot {
+            output,
+            path: path.clone(),
+        });
+        self.request_redraw();
+        Ok(path)
+    }
+
+    // Called by the backends right after rendering `output`
+    pub fn process_screenshots(&mut self, renderer: &mut GlesRenderer, output: &Output) {
+        while let Some(index) = self
+            .screenshots
+            .pending
+            .iter()
+            .position(|pending: &PendingScreenshot| pending.output == *output)
+        {
+            let pending: PendingScreenshot = self.screenshots.pending.remove(index);
+            match self.render_screenshot(renderer, output) {
+                Ok(frame) => self.encoder.encode(frame, pending.path),
+                Err(err) => eprintln!("Failed to take screenshot: {err}"),
+            }
+        }
+    }
+
+    // Rendered upright, whatever the output's transform, and with
+    // protected windows blacked out like for screencopy
+    fn render_screenshot(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+    ) -> Result<Frame, String> {
+        let mode_size: Size<i32, Physical> =
+            output.current_mode().ok_or("output has no mode")?.size;
+        let size: Size<i32, Physical> = output.current_transform().transform_size(mode_size);
+
+        let elements: Vec<CustomRenderElements> = self.capture_elements(renderer, output);
+        let pixels: Vec<u8> = render_pixels(
+            renderer,
+            size,
+            output.current_scale().fractional_scale(),
+            &elements,
+            self.background_color(output),
+        )?;
+
+        Ok(Frame {
+            pixels,
+            width: size.w as u32,
+            height: size.h as u32,
+        })
+    }
+}
+
+// Renders `elements` offscreen and reads them back as RGBA, first row at
+// the top. The golden image tests go through here too.
+pub(crate) fn render_pixels(
+    renderer: &mut GlesRenderer,
+    size: Size<i32, Physical>,
+    scale: f64,
+    elements: &[CustomRenderElements],
+    clear_color: [f32; 4],
+) -> Result<Vec<u8>, String> {
+    let texture: GlesTexture = Offscreen::<GlesTexture>::create_buffer(
+        renderer,
+        Fourcc::Abgr8888,
+        (size.w, size.h).into(),
+    )
+    .map_err(|err| err.to_string())?;
+    renderer.bind(texture).map_err(|err| err.to_string())?;
+
+    let mut damage_tracker: OutputDamageTracker =
+        OutputDamageTracker::new(size, scale, Transform::Normal);
+    damage_tracker
+        .render_output(renderer, 0, elements, clear_color)
+        .map_err(|err| format!("{err:?}"))?;
+
+    let mapping: GlesMapping = renderer
+        .copy_framebuffer(
+            Rectangle::from_loc_and_size((0, 0), (size.w, size.h)),
+            Fourcc::Abgr8888,
+        )
+        .map_err(|err| err.to_string())?;
+    let pixels: Vec<u8> = renderer
+        .map_texture(&mapping)
+        .map_err(|err| err.to_string())?
+        .to_vec();
+
+    if !mapping.flipped() {
+        return Ok(pixels);
+    }
+    let stride: usize = size.w as usize * 4;
+    Ok(pixels
+        .chunks_exact(stride)
+        .rev()
+        .flatten()
+        .copied()
+        .collect())
+}
@@ -0,0 +1,173 @@
+use std::{
+    process::{Child, ExitStatus},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use smithay::reexports::calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
+
+use crate::{config::ServiceConfig, data::Data, spawn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// A service that stayed up this long is considered healthy again
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ServiceState {
+    Running {
+        pid: u32,
+    },
+    // Crashed, waiting for the backoff to run out
+    Restarting {
+        exit_code: Option<i32>,
+        restarts: u32,
+    },
+    // Exited cleanly, or crashed with restarting disabled
+    Stopped {
+        exit_code: Option<i32>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Serialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub command: String,
+    #[serde(flatten)]
+    pub state: ServiceState,
+}
+
+struct Service {
+    config: ServiceConfig,
+    child: Option<Child>,
+    state: ServiceState,
+    restarts: u32,
+    started_at: Instant,
+    restart_at: Option<Instant>,
+}
+
+impl Service {
+    fn start(&mut self) {
+        self.restart_at = None;
+        self.started_at = Instant::now();
+
+        match spawn::spawn_shell(&self.config.command) {
+            Some(child) => {
+                self.state = ServiceState::Running { pid: child.id() };
+                self.child = Some(child);
+            }
+            None => {
+                self.state = ServiceState::Failed {
+                    error: "failed to spawn".into(),
+                };
+            }
+        }
+    }
+
+    fn poll(&mut self) {
+        if let Some(restart_at) = self.restart_at {
+            if Instant::now() >= restart_at {
+                self.start();
+            }
+            return;
+        }
+
+        let Some(child) = &mut self.child else {
+            return;
+        };
+        let status: ExitStatus = match child.try_wait() {
+            Ok(Some(status)) => status,
+            Ok(None) => return,
+            Err(err) => {
+                self.state = ServiceState::Failed {
+                    error: err.to_string(),
+                };
+                self.child = None;
+                return;
+            }
+        };
+        self.child = None;
+
+        if status.success() || !self.config.restart {
+            self.state = ServiceState::Stopped {
+                exit_code: status.code(),
+            };
+            return;
+        }
+
+        if self.started_at.elapsed() >= STABLE_AFTER {
+            self.restarts = 0;
+        }
+        let backoff: Duration = Duration::from_secs(1 << self.restarts.min(6)).min(MAX_BACKOFF);
+        self.restarts += 1;
+
+        eprintln!(
+            "Service \"{}\" exited with {status}, restarting in {}s",
+            self.config.name,
+            backoff.as_secs()
+        );
+
+        self.state = ServiceState::Restarting {
+            exit_code: status.code(),
+            restarts: self.restarts,
+        };
+        self.restart_at = Some(Instant::now() + backoff);
+    }
+}
+
+// Long running helpers the session needs, like a polkit agent or portals
+#[derive(Default)]
+pub struct Services {
+    services: Vec<Service>,
+}
+
+impl Services {
+    pub fn start(configs: &[ServiceConfig], handle: &LoopHandle<'_, Data>) -> Self {
+        let mut services: Vec<Service> = configs
+            .iter()
+            .map(|config: &ServiceConfig| Service {
+                config: config.clone(),
+                child: None,
+                state: ServiceState::Stopped { exit_code: None },
+                restarts: 0,
+                started_at: Instant::now(),
+                restart_at: None,
+            })
+            .collect();
+        services.iter_mut().for_each(Service::start);
+
+        handle
+            .insert_source(
+                Timer::from_duration(POLL_INTERVAL),
+                |_, _, data: &mut Data| {
+                    data.state.services.poll();
+                    TimeoutAction::ToDuration(POLL_INTERVAL)
+                },
+            )
+            .unwrap();
+
+        Self { services }
+    }
+
+    pub fn poll(&mut self) {
+        self.services.iter_mut().for_each(Service::poll);
+    }
+
+    pub fn status(&self) -> Vec<ServiceStatus> {
+        self.services
+            .iter()
+            .map(|service: &Service| ServiceStatus {
+                name: service.config.name.clone(),
+                command: service.config.command.clone(),
+                state: service.state.clone(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,241 @@
+use serde::Deserialize;
+use smithay::{
+    backend::renderer::element::{
+        solid::{SolidColorBuffer, SolidColorRenderElement},
+        Kind,
+    },
+    desktop::Window,
+    output::Output,
+    utils::{Logical, Point, Rectangle, Serial, Size},
+};
+
+use crate::{config::SnapConfig, render::CustomRenderElements, state::State};
+
+// Premultiplied
+const PREVIEW: [f32; 4] = [0.1, 0.2, 0.35, 0.4];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapTarget {
+    LeftHalf,
+    RightHalf,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    // Keeps the size, only moves the window
+    Center,
+}
+
+impl SnapTarget {
+    // Where a window of `size` ends up inside `area`
+    pub fn geometry(
+        self,
+        area: Rectangle<i32, Logical>,
+        size: Size<i32, Logical>,
+    ) -> Rectangle<i32, Logical> {
+        let half_w: i32 = area.size.w / 2;
+        let half_h: i32 = area.size.h / 2;
+        // Odd sizes give the extra pixel to the right and bottom halves
+        let right: i32 = area.loc.x + half_w;
+        let bottom: i32 = area.loc.y + half_h;
+
+        let (loc, size): (Point<i32, Logical>, Size<i32, Logical>) = match self {
+            SnapTarget::LeftHalf => (area.loc, (half_w, area.size.h).into()),
+            SnapTarget::RightHalf => (
+                (right, area.loc.y).into(),
+                (area.size.w - half_w, area.size.h).into(),
+            ),
+            SnapTarget::TopLeft => (area.loc, (half_w, half_h).into()),
+            SnapTarget::TopRight => (
+                (right, area.loc.y).into(),
+                (area.size.w - half_w, half_h).into(),
+            ),
+            SnapTarget::BottomLeft => (
+                (area.loc.x, bottom).into(),
+                (half_w, area.size.h - half_h).into(),
+            ),
+            SnapTarget::BottomRight => (
+                (right, bottom).into(),
+                (area.size.w - half_w, area.size.h - half_h).into(),
+            ),
+            SnapTarget::Center => {
+                let size: Size<i32, Logical> =
+                    (size.w.min(area.size.w), size.h.min(area.size.h)).into();
+                let loc: Point<i32, Logical> = (
+                    area.loc.x + (area.size.w - size.w) / 2,
+                    area.loc.y + (area.size.h - size.h) / 2,
+                )
+                    .into();
+                (loc, size)
+            }
+        };
+
+        Rectangle::from_loc_and_size(loc, size)
+    }
+}
+
+// The snap target for a window dragged with the pointer at `point`, near an
+// edge or corner of `area`
+pub fn target_at(
+    area: Rectangle<i32, Logical>,
+    point: Point<f64, Logical>,
+    config: &SnapConfig,
+) -> Option<SnapTarget> {
+    let area: Rectangle<f64, Logical> = area.to_f64();
+    let edge: f64 = config.edge_margin as f64;
+    let corner: f64 = config.corner_margin as f64;
+
+    let left: bool = point.x < area.loc.x + edge;
+    let right: bool = point.x >= area.loc.x + area.size.w - edge;
+    let top: bool = point.y < area.loc.y + corner;
+    let bottom: bool = point.y >= area.loc.y + area.size.h - corner;
+
+    match (left, right, top, bottom) {
+        (true, _, true, _) => Some(SnapTarget::TopLeft),
+        (true, _, _, true) => Some(SnapTarget::BottomLeft),
+        (true, _, _, _) => Some(SnapTarget::LeftHalf),
+        (_, true, true, _) => Some(SnapTarget::TopRight),
+        (_, true, _, true) => Some(SnapTarget::BottomRight),
+        (_, true, _, _) => Some(SnapTarget::RightHalf),
+        _ => None,
+    }
+}
+
+// Shown while a window is dragged onto a snap target, applied on release
+pub struct SnapPreview {
+    window: Window,
+    target: SnapTarget,
+    output: Output,
+    // Global coordinates
+    geometry: Rectangle<i32, Logical>,
+    buffer: SolidColorBuffer,
+}
+
+impl State {
+    // Called by the move grab on every motion event
+    pub fn update_snap_preview(&mut self, window: &Window, pointer: Point<f64, Logical>) {
+        let hit: Option<(Output, Rectangle<i32, Logical>, SnapTarget)> =
+            if self.config.snap.edge_drag {
+                self.space
+                    .output_under(pointer)
+                    .next()
+                    .cloned()
+                    .and_then(|output: Output| {
+                        let area: Rectangle<i32, Logical> = self.usable_area(&output)?;
+                        let target: SnapTarget = target_at(area, pointer, &self.config.snap)?;
+                        Some((output, area, target))
+                    })
+            } else {
+                None
+            };
+
+        let Some((output, area, target)) = hit else {
+            self.snap_preview = None;
+            return;
+        };
+
+        let unchanged: bool = self
+            .snap_preview
+            .as_ref()
+            .map_or(false, |preview: &SnapPreview| {
+                preview.window == *window && preview.target == target && preview.output == output
+            });
+        if unchanged {
+            return;
+        }
+
+        let geometry: Rectangle<i32, Logical> = target.geometry(area, window.geometry().size);
+        self.snap_preview = Some(SnapPreview {
+            window: window.clone(),
+            target,
+            output,
+            geometry,
+            buffer: SolidColorBuffer::new(geometry.size, PREVIEW),
+        });
+    }
+
+    // Returns whether the dragged window got snapped
+    pub fn finish_snap_drag(&mut self) -> bool {
+        let Some(preview) = self.snap_preview.take() else {
+            return false;
+        };
+
+        self.place_window(&preview.window, preview.geometry);
+        true
+    }
+
+    pub fn snap_preview_elements(&self, output: &Output, elements: &mut Vec<CustomRenderElements>) {
+        let Some(preview) = self
+            .snap_preview
+            .as_ref()
+            .filter(|preview: &&SnapPreview| preview.output == *output)
+        else {
+            return;
+        };
+        let Some(output_geometry) = self.space.output_geometry(output) else {
+            return;
+        };
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let loc: Point<i32, Logical> = preview.geometry.loc - output_geometry.loc;
+
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                &preview.buffer,
+                loc.to_physical_precise_round(scale),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+
+    pub fn snap_focused_window(&mut self, target: SnapTarget) {
+        if let Some(window) = self.focused_window() {
+            self.snap_window(&window, target);
+        }
+    }
+
+    // Snapped windows leave the tiling, they're placed by hand from then on
+    pub fn snap_window(&mut self, window: &Window, target: SnapTarget) {
+        self.untile_window(window);
+        let Some(output) = self.space.outputs_for_element(window).into_iter().next() else {
+            return;
+        };
+        let Some(area) = self.usable_area(&output) else {
+            return;
+        };
+
+        let geometry: Rectangle<i32, Logical> = target.geometry(area, window.geometry().size);
+        self.place_window(window, geometry);
+    }
+
+    // `geometry` is the window geometry, without client side shadows. See
+    // animate_window for what it returns.
+    pub fn place_window(
+        &mut self,
+        window: &Window,
+        geometry: Rectangle<i32, Logical>,
+    ) -> Option<Serial> {
+        self.animate_window(window, geometry)
+    }
+
+    // Like place_window, but without animating. Returns the serial of the
+    // configure, if the size changed.
+    pub fn set_window_geometry(
+        &mut self,
+        window: &Window,
+        geometry: Rectangle<i32, Logical>,
+    ) -> Option<Serial> {
+        window.toplevel().with_pending_state(|state| {
+            state.size = Some(geometry.size);
+        });
+        let serial: Option<Serial> = window.toplevel().send_pending_configure();
+
+        self.space
+            .map_element(window.clone(), geometry.loc - window.geometry().loc, true);
+        serial
+    }
+}
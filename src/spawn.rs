@@ -0,0 +1,117 @@
+use std::{
+    io::Read,
+    os::unix::{net::UnixStream, process::CommandExt},
+    process::{Child, Command},
+    sync::Mutex,
+};
+
+use signal_hook::{consts::SIGCHLD, low_level::pipe};
+use smithay::reexports::{
+    calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+    nix::libc,
+};
+
+use crate::{data::Data, systemd};
+
+// [env] from the config, exported to children but not to pulseWM itself
+static CHILD_ENV: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+// Children nobody else waits for, reaped once they exit so they don't stay
+// around as zombies. Services and the on-screen keyboard keep their Child
+// and wait for it themselves.
+static DETACHED: Mutex<Vec<Child>> = Mutex::new(Vec::new());
+
+pub fn set_child_env(env: impl IntoIterator<Item = (String, String)>) {
+    *CHILD_ENV.lock().unwrap() = env.into_iter().collect();
+}
+
+// Runs a user supplied command line through the shell, so config entries
+// can use pipes, `&&` and environment variables
+pub fn spawn_shell(command: &str) -> Option<Child> {
+    spawn(command, false)
+}
+
+// In a process group of its own, for children stopped later with
+// kill_group. Killing only the shell would leave whatever it started.
+pub fn spawn_group(command: &str) -> Option<Child> {
+    spawn(command, true)
+}
+
+fn spawn(command: &str, group: bool) -> Option<Child> {
+    let env: Vec<(String, String)> = CHILD_ENV.lock().unwrap().clone();
+    let mut shell: Command = Command::new("sh");
+    shell.args(["-c", command]).envs(env);
+    if group {
+        shell.process_group(0);
+    }
+    match shell.spawn() {
+        Ok(child) => {
+            systemd::move_to_scope(command, child.id());
+            Some(child)
+        }
+        Err(err) => {
+            eprintln!("Failed to spawn \"{command}\": {err}");
+            None
+        }
+    }
+}
+
+// The group is the shell's pid, see spawn_group
+pub fn kill_group(mut child: Child) {
+    let group: libc::pid_t = child.id() as libc::pid_t;
+    if unsafe { libc::killpg(group, libc::SIGTERM) } != 0 {
+        eprintln!(
+            "Failed to stop process group {group}: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    let _ = child.wait();
+}
+
+// For commands started and forgotten, like keybindings. Returns the pid of
+// the shell running the command.
+pub fn spawn_detached(command: &str) -> Option<u32> {
+    let child: Child = spawn_shell(command)?;
+    let pid: u32 = child.id();
+    DETACHED.lock().unwrap().push(child);
+    Some(pid)
+}
+
+fn reap_detached() {
+    DETACHED
+        .lock()
+        .unwrap()
+        .retain_mut(|child: &mut Child| matches!(child.try_wait(), Ok(None)));
+}
+
+// Like SIGHUP in reload.rs, the handler only writes to a pipe and the
+// children are reaped in the event loop
+pub fn reap_on_sigchld(handle: &LoopHandle<'static, Data>) {
+    let (reader, writer) = match UnixStream::pair() {
+        Ok(pair) => pair,
+        Err(err) => {
+            eprintln!("Failed to create the SIGCHLD pipe: {err}");
+            return;
+        }
+    };
+    if let Err(err) = reader
+        .set_nonblocking(true)
+        .and_then(|()| pipe::register(SIGCHLD, writer).map(|_| ()))
+    {
+        eprintln!("Failed to handle SIGCHLD: {err}");
+        return;
+    }
+
+    handle
+        .insert_source(
+            Generic::new(reader, Interest::READ, Mode::Level),
+            |_, reader: &mut UnixStream, data: &mut Data| {
+                let mut buffer: [u8; 16] = [0; 16];
+                while matches!(reader.read(&mut buffer), Ok(read) if read > 0) {}
+                reap_detached();
+                data.state.reap_osk();
+                Ok(PostAction::Continue)
+            },
+        )
+        .unwrap();
+}
@@ -0,0 +1,263 @@
+// Z-order and focus rules for everything pulseWM shows, kept free of any
+// smithay types so the policy can be reasoned about (and tested) on its own
+
+// Bottom to top, the derived Ord is the stacking order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StackLayer {
+    Background,
+    Bottom,
+    Windows,
+    Top,
+    // Fullscreen windows cover bars, but not notifications or launchers
+    Fullscreen,
+    Overlay,
+    // Compositor drawn UI like the OSD
+    Osd,
+    Lock,
+    DragIcon,
+    Cursor,
+}
+
+impl StackLayer {
+    // Drag icons and the cursor are only decoration, they never get input
+    pub fn accepts_input(self) -> bool {
+        !matches!(self, StackLayer::DragIcon | StackLayer::Cursor)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyboardInteractivity {
+    None,
+    OnDemand,
+    Exclusive,
+}
+
+#[derive(Clone, Debug)]
+pub struct StackEntry<T> {
+    pub target: T,
+    pub layer: StackLayer,
+    pub interactivity: KeyboardInteractivity,
+}
+
+impl<T> StackEntry<T> {
+    pub fn new(target: T, layer: StackLayer, interactivity: KeyboardInteractivity) -> Self {
+        Self {
+            target,
+            layer,
+            interactivity,
+        }
+    }
+}
+
+// Stable, so entries within a layer keep their relative order (most
+// recently raised last)
+pub fn sort<T>(stack: &mut [StackEntry<T>]) {
+    stack.sort_by_key(|entry: &StackEntry<T>| entry.layer);
+}
+
+pub fn is_locked<T>(stack: &[StackEntry<T>]) -> bool {
+    stack
+        .iter()
+        .any(|entry: &StackEntry<T>| entry.layer == StackLayer::Lock)
+}
+
+// Who gets the keyboard, given the surface the user or a client asked to
+// focus. `stack` has to be sorted bottom to top.
+pub fn keyboard_focus<'a, T: PartialEq>(
+    stack: &'a [StackEntry<T>],
+    requested: Option<&T>,
+) -> Option<&'a T> {
+    let topmost = |layers: &[StackLayer]| -> Option<&'a T> {
+        stack
+            .iter()
+            .rev()
+            .find(|entry: &&StackEntry<T>| {
+                layers.contains(&entry.layer) && entry.interactivity != KeyboardInteractivity::None
+            })
+            .map(|entry: &StackEntry<T>| &entry.target)
+    };
+
+    // A locked session only ever talks to the locker
+    if is_locked(stack) {
+        return topmost(&[StackLayer::Lock]);
+    }
+
+    // Exclusive layer surfaces (launchers, lock-like overlays) grab the
+    // keyboard from anything below them
+    let exclusive: Option<&StackEntry<T>> = stack.iter().rev().find(|entry: &&StackEntry<T>| {
+        entry.interactivity == KeyboardInteractivity::Exclusive
+            && matches!(entry.layer, StackLayer::Top | StackLayer::Overlay)
+    });
+    if let Some(exclusive) = exclusive {
+        // A fullscreen window hides the Top layer, so only an exclusive
+        // Overlay surface can take focus away from it
+        let hidden: bool = exclusive.layer == StackLayer::Top
+            && stack
+                .iter()
+                .any(|entry: &StackEntry<T>| entry.layer == StackLayer::Fullscreen);
+        if !hidden {
+            return Some(&exclusive.target);
+        }
+    }
+
+    let requested: &StackEntry<T> = stack
+        .iter()
+        .find(|entry: &&StackEntry<T>| Some(&entry.target) == requested)?;
+
+    if !requested.layer.accepts_input() || requested.interactivity == KeyboardInteractivity::None {
+        return None;
+    }
+
+    // Windows under a fullscreen one can't be typed into
+    if requested.layer == StackLayer::Windows
+        && stack
+            .iter()
+            .any(|entry: &StackEntry<T>| entry.layer == StackLayer::Fullscreen)
+    {
+        return topmost(&[StackLayer::Fullscreen]);
+    }
+
+    Some(&requested.target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        target: &'static str,
+        layer: StackLayer,
+        interactivity: KeyboardInteractivity,
+    ) -> StackEntry<&'static str> {
+        StackEntry::new(target, layer, interactivity)
+    }
+
+    fn window(target: &'static str) -> StackEntry<&'static str> {
+        entry(target, StackLayer::Windows, KeyboardInteractivity::OnDemand)
+    }
+
+    #[test]
+    fn sort_orders_layers_and_keeps_raise_order() {
+        let mut stack = vec![
+            entry("cursor", StackLayer::Cursor, KeyboardInteractivity::None),
+            window("a"),
+            entry("bar", StackLayer::Top, KeyboardInteractivity::None),
+            entry(
+                "fullscreen",
+                StackLayer::Fullscreen,
+                KeyboardInteractivity::OnDemand,
+            ),
+            entry(
+                "wallpaper",
+                StackLayer::Background,
+                KeyboardInteractivity::None,
+            ),
+            window("b"),
+            entry("lock", StackLayer::Lock, KeyboardInteractivity::Exclusive),
+            entry("drag", StackLayer::DragIcon, KeyboardInteractivity::None),
+        ];
+        sort(&mut stack);
+
+        let order: Vec<&str> = stack.iter().map(|entry| entry.target).collect();
+        assert_eq!(
+            order,
+            [
+                "wallpaper",
+                "a",
+                "b",
+                "bar",
+                "fullscreen",
+                "lock",
+                "drag",
+                "cursor"
+            ]
+        );
+    }
+
+    #[test]
+    fn fullscreen_covers_top_but_not_overlay() {
+        assert!(StackLayer::Fullscreen > StackLayer::Top);
+        assert!(StackLayer::Overlay > StackLayer::Fullscreen);
+        assert!(StackLayer::Lock > StackLayer::Overlay);
+        assert!(StackLayer::Cursor > StackLayer::DragIcon);
+    }
+
+    #[test]
+    fn requested_window_gets_keyboard() {
+        let stack = vec![window("a"), window("b")];
+        assert_eq!(keyboard_focus(&stack, Some(&"a")), Some(&"a"));
+        assert_eq!(keyboard_focus(&stack, None), None);
+    }
+
+    #[test]
+    fn lock_takes_keyboard_from_everything() {
+        let stack = vec![
+            window("a"),
+            entry(
+                "launcher",
+                StackLayer::Overlay,
+                KeyboardInteractivity::Exclusive,
+            ),
+            entry("lock", StackLayer::Lock, KeyboardInteractivity::Exclusive),
+        ];
+        assert_eq!(keyboard_focus(&stack, Some(&"a")), Some(&"lock"));
+    }
+
+    #[test]
+    fn exclusive_overlay_beats_fullscreen() {
+        let stack = vec![
+            entry(
+                "video",
+                StackLayer::Fullscreen,
+                KeyboardInteractivity::OnDemand,
+            ),
+            entry(
+                "launcher",
+                StackLayer::Overlay,
+                KeyboardInteractivity::Exclusive,
+            ),
+        ];
+        assert_eq!(keyboard_focus(&stack, Some(&"video")), Some(&"launcher"));
+    }
+
+    #[test]
+    fn exclusive_top_is_hidden_by_fullscreen() {
+        let stack = vec![
+            entry("menu", StackLayer::Top, KeyboardInteractivity::Exclusive),
+            entry(
+                "video",
+                StackLayer::Fullscreen,
+                KeyboardInteractivity::OnDemand,
+            ),
+        ];
+        assert_eq!(keyboard_focus(&stack, Some(&"video")), Some(&"video"));
+    }
+
+    #[test]
+    fn windows_below_fullscreen_cannot_take_focus() {
+        let stack = vec![
+            window("a"),
+            entry(
+                "video",
+                StackLayer::Fullscreen,
+                KeyboardInteractivity::OnDemand,
+            ),
+        ];
+        assert_eq!(keyboard_focus(&stack, Some(&"a")), Some(&"video"));
+    }
+
+    #[test]
+    fn non_interactive_surfaces_never_get_keyboard() {
+        let stack = vec![
+            window("a"),
+            entry("bar", StackLayer::Top, KeyboardInteractivity::None),
+            entry(
+                "drag",
+                StackLayer::DragIcon,
+                KeyboardInteractivity::OnDemand,
+            ),
+        ];
+        assert_eq!(keyboard_focus(&stack, Some(&"bar")), None);
+        assert_eq!(keyboard_focus(&stack, Some(&"drag")), None);
+    }
+}
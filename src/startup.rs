@@ -0,0 +1,291 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    os::{fd::AsRawFd, unix::net::UnixStream},
+    sync::Arc,
+    time::Duration,
+};
+
+use smithay::{
+    desktop::{PopupManager, Space, Window},
+    input::{
+        keyboard::{xkb, XkbConfig},
+        pointer::CursorImageStatus,
+        Seat, SeatState,
+    },
+    reexports::{
+        calloop::{
+            generic::Generic,
+            ping::{self, Ping, PingSource},
+            EventLoop, Interest, Mode, PostAction,
+        },
+        wayland_server::{Client, Display, DisplayHandle},
+    },
+    utils::Point,
+    wayland::{
+        compositor::CompositorState,
+        data_device::DataDeviceState,
+        dmabuf::DmabufState,
+        fractional_scale::FractionalScaleManagerState,
+        input_method::InputMethodManagerState,
+        output::OutputManagerState,
+        primary_selection::PrimarySelectionState,
+        session_lock::SessionLockManagerState,
+        shell::{
+            kde::decoration::KdeDecorationState,
+            wlr_layer::WlrLayerShellState,
+            xdg::{decoration::XdgDecorationState, XdgShellState},
+        },
+        shm::ShmState,
+        socket::ListeningSocketSource,
+        text_input::TextInputManagerState,
+        viewporter::ViewporterState,
+        xdg_activation::XdgActivationState,
+    },
+};
+
+use crate::{
+    activation, animation, audit, backend, background, backlight, bar, borders, cheatsheet, config,
+    cursor, damage_debug, data, data_control, decorations, devices, dpms, encode, focus, globals,
+    import, ipc, keybindings, latency, layout, lock, logind, osd, overview, reload, root_scroll,
+    screencopy, screensaver, screenshot, services, spawn, state, switcher, text, theme, tiling,
+    transaction, type_text, workspaces,
+};
+
+// Everything main does, in the library so the modules can stay private
+pub fn run() {
+    let mut event_loop: EventLoop<'static, data::Data> =
+        EventLoop::try_new().expect("Failed to create EventLoop");
+
+    let mut display: Display<state::State> = Display::new().unwrap();
+
+    let socket: ListeningSocketSource = ListeningSocketSource::new_auto().unwrap();
+    let socket_name: OsString = socket.socket_name().to_os_string();
+
+    event_loop
+        .handle()
+        .insert_source(socket, |stream: UnixStream, _, data: &mut data::Data| {
+            let client_data: data::ClientData = data::ClientData::new(data.state.audit.clone());
+            let client: Client = data
+                .display
+                .handle()
+                .insert_client(stream, Arc::new(client_data))
+                .unwrap();
+            data.state.audit_connected(&client);
+            data.state.request_redraw();
+        })
+        .unwrap();
+
+    event_loop
+        .handle()
+        .insert_source(
+            Generic::new(
+                display.backend().poll_fd().as_raw_fd(),
+                Interest::READ,
+                Mode::Level,
+            ),
+            |_, _, data: &mut data::Data| {
+                data.display.dispatch_clients(&mut data.state).unwrap();
+                data.state.request_redraw();
+                Ok(PostAction::Continue)
+            },
+        )
+        .unwrap();
+
+    let display_handle: DisplayHandle = display.handle();
+
+    let compositor_state: CompositorState = CompositorState::new::<state::State>(&display_handle);
+    // Only ARGB8888 and XRGB8888 until the backend knows what its renderer
+    // can import
+    let shm_state: ShmState = ShmState::new::<state::State>(&display_handle, Vec::new());
+    let output_manager_state: OutputManagerState =
+        OutputManagerState::new_with_xdg_output::<state::State>(&display_handle);
+    let xdg_shell_state: XdgShellState = XdgShellState::new::<state::State>(&display_handle);
+    let mut seat_state: SeatState<state::State> = SeatState::<state::State>::new();
+    let space: Space<Window> = Space::<Window>::default();
+    let data_device_state: DataDeviceState = DataDeviceState::new::<state::State>(&display_handle);
+    let primary_selection_state: PrimarySelectionState =
+        PrimarySelectionState::new::<state::State>(&display_handle);
+    let session_lock_state: SessionLockManagerState =
+        SessionLockManagerState::new::<state::State, _>(&display_handle, |_| true);
+    let layer_shell_state: WlrLayerShellState =
+        WlrLayerShellState::new::<state::State>(&display_handle);
+    // Only the globals, they keep no state we need to reach
+    InputMethodManagerState::new::<state::State>(&display_handle);
+    TextInputManagerState::new::<state::State>(&display_handle);
+    ViewporterState::new::<state::State>(&display_handle);
+    FractionalScaleManagerState::new::<state::State>(&display_handle);
+
+    let mut seat: Seat<state::State> = seat_state.new_wl_seat(&display_handle, "pulseWM_seat");
+    let xkb_config: XkbConfig = XkbConfig::default();
+    let keymap: xkb::Keymap = type_text::compile_keymap(&xkb_config).unwrap();
+    seat.add_keyboard(xkb_config, 500, 500).unwrap();
+    seat.add_pointer();
+
+    let first_run: bool = !config::config_path().exists();
+    let config: config::Config = config::Config::load();
+    spawn::set_child_env(config.child_env());
+    let backlight: Option<backlight::Backlight> = if config.brightness.enabled {
+        backlight::Backlight::open(config.brightness.device.as_deref())
+    } else {
+        None
+    };
+    let text: Option<text::TextRenderer> = text::TextRenderer::new(&config.appearance.font)
+        .map_err(|err: text::FontError| eprintln!("Failed to load font: {err}"))
+        .ok();
+    let cursor: cursor::Cursor = cursor::Cursor::load(&config.cursor.theme);
+
+    let xdg_decoration_state: XdgDecorationState =
+        XdgDecorationState::new::<state::State>(&display_handle);
+    let xdg_activation_state: XdgActivationState =
+        XdgActivationState::new::<state::State>(&display_handle);
+    let kde_decoration_state: KdeDecorationState = KdeDecorationState::new::<state::State>(
+        &display_handle,
+        decorations::kde_default_mode(&config.decorations),
+    );
+
+    let workspace_count: usize = config.workspaces.count;
+    let workspace_mode: config::WorkspaceMode = config.workspaces.mode;
+    let theme: theme::Theme = config.appearance.theme;
+    let layout_params: layout::LayoutParams = config.layout.params();
+    let latency: latency::LatencyTracker = latency::LatencyTracker::new(config.debug.latency);
+    let damage_debug: damage_debug::DamageDebug =
+        damage_debug::DamageDebug::new(config.debug.damage);
+
+    let (redraw_ping, redraw_source): (Ping, PingSource) = ping::make_ping().unwrap();
+    event_loop
+        .handle()
+        .insert_source(redraw_source, |_, _, data: &mut data::Data| {
+            data.backend.wake(&mut data.state);
+            data.display.flush_clients().unwrap();
+        })
+        .unwrap();
+
+    let mut state: state::State = state::State {
+        config,
+        display_handle,
+        compositor_state,
+        data_device_state,
+        primary_selection_state,
+        seat_state,
+        seat,
+        keymap,
+        shm_state,
+        space,
+        windows: HashMap::new(),
+        popups: PopupManager::default(),
+        output_manager_state,
+        cursor_status: CursorImageStatus::Default,
+        cursor,
+        dnd_icon: None,
+        menu: None,
+        pointer_location: Point::default(),
+        outputs: Vec::new(),
+        lid_closed: false,
+        tablet_mode: false,
+        presentation_mode: false,
+        osk: None,
+        input_devices: devices::InputDevices::default(),
+        logind: None,
+        screensaver: None,
+        backlight,
+        osd: osd::Osd::default(),
+        key_sequence: keybindings::KeySequence::default(),
+        cheatsheet: if first_run {
+            cheatsheet::Cheatsheet::welcome()
+        } else {
+            cheatsheet::Cheatsheet::default()
+        },
+        text,
+        services: services::Services::default(),
+        latency,
+        switcher: switcher::Switcher::default(),
+        snap_preview: None,
+        animations: animation::Animations::default(),
+        globals: globals::OptionalGlobals::new(event_loop.handle()),
+        audit: audit::SharedAuditLog::default(),
+        workspaces: workspaces::Workspaces::new(workspace_count, workspace_mode),
+        overview: overview::Overview::default(),
+        tile_drag: None,
+        tiling: tiling::Tiling::new(layout_params),
+        transactions: transaction::Transactions::new(event_loop.handle()),
+        resize: None,
+        fullscreen: Vec::new(),
+        maximized: Vec::new(),
+        minimized: Vec::new(),
+        unplaced: Vec::new(),
+        pending_raise: None,
+        focus_stack: focus::FocusStack::default(),
+        activation: activation::Activation::default(),
+        root_scroll: root_scroll::ScrollAccumulator::default(),
+        wallpapers: background::Wallpapers::default(),
+        bar: bar::Bar::default(),
+        session_lock: lock::SessionLock::default(),
+        damage_debug,
+        encoder: encode::Encoder::new(&event_loop.handle()),
+        imports: import::ImportPool::new(&event_loop.handle()),
+        screencopy: screencopy::Screencopy::default(),
+        data_control: data_control::DataControl::default(),
+        screenshots: screenshot::Screenshots::default(),
+        theme,
+        portal: theme::Portal::new(theme),
+        decorations: decorations::Decorations::default(),
+        borders: borders::Borders::default(),
+        dpms: dpms::Dpms::default(),
+        ipc_subscribers: ipc::IpcSubscribers::default(),
+        session: None,
+        loop_signal: event_loop.get_signal(),
+        redraw_ping,
+        xdg_shell_state,
+        session_lock_state,
+        layer_shell_state,
+        xdg_decoration_state,
+        kde_decoration_state,
+        xdg_activation_state,
+        dmabuf_state: DmabufState::new(),
+        dmabuf_formats: Vec::new(),
+    };
+
+    state.register_optional_global(
+        globals::OptionalGlobal::Screencopy,
+        screencopy::create_global,
+    );
+    state.register_optional_global(
+        globals::OptionalGlobal::DataControl,
+        data_control::create_global,
+    );
+    state.logind = logind::Logind::new(&event_loop.handle());
+    state.screensaver = screensaver::ScreenSaver::new(&event_loop.handle());
+    state.dpms = dpms::Dpms::start(
+        &event_loop.handle(),
+        Duration::from_secs(state.config.dpms.timeout),
+    );
+
+    let backend: backend::Backend = match backend::init(&event_loop.handle(), &mut state) {
+        Ok(backend) => backend,
+        Err(err) => {
+            eprintln!("Failed to start the backend: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut data: data::Data = data::Data {
+        state,
+        display,
+        backend,
+    };
+
+    data.state.apply_output_config();
+
+    std::env::set_var("WAYLAND_DISPLAY", &socket_name);
+
+    let _ipc_server: Option<ipc::IpcServer> =
+        ipc::IpcServer::new(&event_loop.handle(), &socket_name);
+    reload::reload_on_sighup(&event_loop.handle());
+    spawn::reap_on_sigchld(&event_loop.handle());
+    // Started last so they inherit WAYLAND_DISPLAY and PULSEWM_SOCK
+    data.state.services =
+        services::Services::start(&data.state.config.services, &event_loop.handle());
+
+    event_loop.run(None, &mut data, |_| {}).unwrap();
+}
@@ -1,50 +1,201 @@
-use crate::data;
+use std::{collections::HashMap, os::unix::io::RawFd, process::Child};
+
+use crate::{
+    activation::Activation,
+    animation::Animations,
+    audit::SharedAuditLog,
+    background::Wallpapers,
+    backlight::Backlight,
+    bar::Bar,
+    borders::Borders,
+    cheatsheet::Cheatsheet,
+    config::Config,
+    cursor::Cursor,
+    damage_debug::DamageDebug,
+    data,
+    data_control::DataControl,
+    decorations::Decorations,
+    devices::InputDevices,
+    dnd::DndIcon,
+    dpms::Dpms,
+    encode::Encoder,
+    focus::{FocusStack, PendingRaise},
+    fullscreen::FullscreenWindow,
+    globals::OptionalGlobals,
+    grabs::ResizeState,
+    import::ImportPool,
+    ipc::{IpcEvent, IpcSubscribers},
+    keybindings::KeySequence,
+    latency::LatencyTracker,
+    lock::SessionLock,
+    logind::Logind,
+    maximize::MaximizedWindow,
+    menu::Menu,
+    minimize::MinimizedWindow,
+    osd::Osd,
+    overview::Overview,
+    placement::UnplacedWindow,
+    root_scroll::ScrollAccumulator,
+    rules::WindowRule,
+    screencopy::Screencopy,
+    screensaver::ScreenSaver,
+    screenshot::Screenshots,
+    services::Services,
+    snap::SnapPreview,
+    switcher::Switcher,
+    text::TextRenderer,
+    theme::{Portal, Theme},
+    tile_drag::TileDrag,
+    tiling::Tiling,
+    transaction::Transactions,
+    tree,
+    workspaces::Workspaces,
+};
 use smithay::{
-    backend::renderer::utils::on_commit_buffer_handler,
-    delegate_compositor, delegate_data_device, delegate_output, delegate_seat, delegate_shm,
-    delegate_xdg_shell,
-    desktop::{Space, Window},
-    input::{SeatHandler, SeatState},
+    backend::{
+        allocator::Format, renderer::utils::on_commit_buffer_handler,
+        session::libseat::LibSeatSession,
+    },
+    delegate_compositor, delegate_data_device, delegate_output, delegate_primary_selection,
+    delegate_seat, delegate_shm, delegate_viewporter, delegate_xdg_shell,
+    desktop::{PopupManager, Space, Window},
+    input::{
+        keyboard::{xkb, KeyboardHandle},
+        pointer::CursorImageStatus,
+        Seat, SeatHandler, SeatState,
+    },
+    output::Output,
     reexports::{
-        wayland_protocols::xdg::shell::server::xdg_toplevel,
+        calloop::{ping::Ping, LoopSignal},
+        wayland_protocols::{
+            wp::primary_selection::zv1::server::zwp_primary_selection_source_v1,
+            xdg::shell::server::xdg_toplevel,
+        },
+        wayland_protocols_wlr::data_control::v1::server::zwlr_data_control_source_v1,
         wayland_server::{
             protocol::{
                 wl_buffer,
+                wl_data_source::WlDataSource,
+                wl_output::WlOutput,
                 wl_seat::{self, WlSeat},
                 wl_surface::WlSurface,
             },
-            Client,
+            Client, DisplayHandle, Resource,
         },
     },
-    utils::Serial,
+    utils::{Logical, Point, Rectangle, Serial},
     wayland::{
         buffer::BufferHandler,
         compositor::{
             with_states, CompositorClientState, CompositorHandler, CompositorState, SurfaceData,
         },
-        data_device::{
-            ClientDndGrabHandler, DataDeviceHandler, DataDeviceState, ServerDndGrabHandler,
-        },
+        data_device::{set_data_device_focus, DataDeviceHandler, DataDeviceState},
+        dmabuf::DmabufState,
         output::OutputManagerState,
+        primary_selection::{set_primary_focus, PrimarySelectionHandler, PrimarySelectionState},
+        session_lock::SessionLockManagerState,
+        shell::kde::decoration::KdeDecorationState,
+        shell::wlr_layer::WlrLayerShellState,
+        shell::xdg::decoration::XdgDecorationState,
         shell::xdg::{
             PopupSurface, PositionerState, ToplevelSurface, XdgShellHandler, XdgShellState,
             XdgToplevelSurfaceData,
         },
         shm::{ShmHandler, ShmState},
+        xdg_activation::XdgActivationState,
     },
 };
 
 pub struct State {
+    pub config: Config,
+    pub display_handle: DisplayHandle,
     // pub clock: Clock<Monotonic>,
     pub compositor_state: CompositorState,
     pub data_device_state: DataDeviceState,
+    pub primary_selection_state: PrimarySelectionState,
     pub seat_state: SeatState<Self>,
+    pub seat: Seat<Self>,
+    // What the seat's keyboard was created with, see type_text
+    pub keymap: xkb::Keymap,
     pub shm_state: ShmState,
     pub space: Space<Window>,
-    // pub cursor_status: CursorImageStatus,
-    // pub pointer_location: Point<f64, Logical>,
+    // Every toplevel by its surface, including ones stashed on hidden
+    // workspaces, so commits don't have to search the space
+    pub windows: HashMap<WlSurface, Window>,
+    pub popups: PopupManager,
+    pub cursor_status: CursorImageStatus,
+    pub cursor: Cursor,
+    pub dnd_icon: Option<DndIcon>,
+    pub menu: Option<Menu>,
+    // In global compositor coordinates
+    pub pointer_location: Point<f64, Logical>,
     pub output_manager_state: OutputManagerState,
+    // Every connected output, including ones a profile disabled
+    pub outputs: Vec<Output>,
+    pub lid_closed: bool,
+    pub tablet_mode: bool,
+    pub presentation_mode: bool,
+    pub osk: Option<Child>,
+    pub input_devices: InputDevices,
+    pub logind: Option<Logind>,
+    pub screensaver: Option<ScreenSaver>,
+    pub backlight: Option<Backlight>,
+    pub osd: Osd,
+    pub key_sequence: KeySequence,
+    pub cheatsheet: Cheatsheet,
+    // None if no usable font was found
+    pub text: Option<TextRenderer>,
+    pub services: Services,
+    pub latency: LatencyTracker,
+    pub switcher: Switcher,
+    pub snap_preview: Option<SnapPreview>,
+    pub animations: Animations,
+    pub globals: OptionalGlobals,
+    pub audit: SharedAuditLog,
+    pub workspaces: Workspaces,
+    pub overview: Overview,
+    pub tile_drag: Option<TileDrag>,
+    pub tiling: Tiling,
+    pub transactions: Transactions,
+    pub resize: Option<ResizeState>,
+    pub fullscreen: Vec<FullscreenWindow>,
+    pub maximized: Vec<MaximizedWindow>,
+    pub minimized: Vec<MinimizedWindow>,
+    pub unplaced: Vec<UnplacedWindow>,
+    pub pending_raise: Option<PendingRaise>,
+    pub focus_stack: FocusStack,
+    pub activation: Activation,
+    pub root_scroll: ScrollAccumulator,
+    pub wallpapers: Wallpapers,
+    pub bar: Bar,
+    pub session_lock: SessionLock,
+    pub damage_debug: DamageDebug,
+    pub encoder: Encoder,
+    pub imports: ImportPool,
+    pub screencopy: Screencopy,
+    pub data_control: DataControl,
+    pub screenshots: Screenshots,
+    pub theme: Theme,
+    pub portal: Option<Portal>,
+    pub decorations: Decorations,
+    pub borders: Borders,
+    pub dpms: Dpms,
+    pub ipc_subscribers: IpcSubscribers,
+    // Only on a TTY, used to switch VTs
+    pub session: Option<LibSeatSession>,
+    // Stops the event loop, which quits pulseWM
+    pub loop_signal: LoopSignal,
+    // Wakes the backend up after it stopped rendering while idle
+    pub redraw_ping: Ping,
     pub xdg_shell_state: XdgShellState,
+    pub session_lock_state: SessionLockManagerState,
+    pub layer_shell_state: WlrLayerShellState,
+    pub xdg_decoration_state: XdgDecorationState,
+    pub kde_decoration_state: KdeDecorationState,
+    pub xdg_activation_state: XdgActivationState,
+    pub dmabuf_state: DmabufState,
+    // What the backend's renderer can import, see create_dmabuf_global
+    pub dmabuf_formats: Vec<Format>,
 }
 
 impl BufferHandler for State {
@@ -65,13 +216,15 @@ impl CompositorHandler for State {
 
     fn commit(&mut self, surface: &WlSurface) {
         on_commit_buffer_handler::<Self>(surface);
+        self.import_commit(surface);
+        if !self.subsurface_commit(surface) {
+            return;
+        }
+        self.popup_commit(surface);
+        self.layer_commit(surface);
+        self.dnd_icon_commit(surface);
 
-        if let Some(window) = self
-            .space
-            .elements()
-            .find(|w| w.toplevel().wl_surface() == surface)
-            .cloned()
-        {
+        if let Some(window) = self.windows.get(surface) {
             window.on_commit();
 
             let initial_configure_sent: bool = with_states(surface, |states: &SurfaceData| {
@@ -85,25 +238,94 @@ impl CompositorHandler for State {
             });
 
             if !initial_configure_sent {
+                // Tiled and focused right away, so the initial configure
+                // already has its size and is activated. Floating windows
+                // pick their size and are placed once it's known. Kiosk
+                // mode decides on its own.
+                let window: Window = window.clone();
+                if !self.kiosk() && self.space.element_location(&window).is_some() {
+                    let rule: WindowRule = self.apply_window_rules(&window);
+                    if self.is_fullscreen(&window) {
+                        // Covers the output already
+                    } else if rule
+                        .floating
+                        .unwrap_or_else(|| self.wants_floating(&window))
+                    {
+                        self.float_new_window(&window);
+                    } else {
+                        self.tile_window(&window);
+                    }
+                    if let Some(workspace) = rule.workspace {
+                        self.move_window_to_workspace(&window, workspace.saturating_sub(1));
+                    }
+                    // Not if a rule put it on a hidden workspace
+                    if self.space.element_location(&window).is_some() {
+                        self.focus_new_window(&window);
+                    }
+                }
                 window.toplevel().send_pending_configure();
             }
         }
+
+        self.placement_commit(surface);
+        self.resize_commit(surface);
     }
 }
 delegate_compositor!(State);
 
-impl ClientDndGrabHandler for State {}
-impl ServerDndGrabHandler for State {}
-
+// Selections set through data-control are served by the clipboard
+// manager's source
 impl DataDeviceHandler for State {
-    type SelectionUserData = ();
+    type SelectionUserData = zwlr_data_control_source_v1::ZwlrDataControlSourceV1;
 
     fn data_device_state(&self) -> &DataDeviceState {
         &self.data_device_state
     }
+
+    fn new_selection(&mut self, source: Option<WlDataSource>, _seat: Seat<Self>) {
+        self.client_selection(source);
+    }
+
+    fn send_selection(
+        &mut self,
+        mime_type: String,
+        fd: RawFd,
+        _seat: Seat<Self>,
+        user_data: &Self::SelectionUserData,
+    ) {
+        self.send_control_selection(mime_type, fd, user_data);
+    }
 }
 delegate_data_device!(State);
 
+// Middle-click paste, offered to the focused client like the clipboard
+impl PrimarySelectionHandler for State {
+    type SelectionUserData = zwlr_data_control_source_v1::ZwlrDataControlSourceV1;
+
+    fn primary_selection_state(&self) -> &PrimarySelectionState {
+        &self.primary_selection_state
+    }
+
+    fn new_selection(
+        &mut self,
+        source: Option<zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1>,
+        _seat: Seat<Self>,
+    ) {
+        self.client_primary_selection(source);
+    }
+
+    fn send_selection(
+        &mut self,
+        mime_type: String,
+        fd: RawFd,
+        _seat: Seat<Self>,
+        user_data: &Self::SelectionUserData,
+    ) {
+        self.send_control_selection(mime_type, fd, user_data);
+    }
+}
+delegate_primary_selection!(State);
+
 impl SeatHandler for State {
     type KeyboardFocus = WlSurface;
     type PointerFocus = WlSurface;
@@ -112,14 +334,30 @@ impl SeatHandler for State {
         &mut self.seat_state
     }
 
-    fn cursor_image(
-        &mut self,
-        _: &smithay::input::Seat<Self>,
-        _image: smithay::input::pointer::CursorImageStatus,
-    ) {
+    fn cursor_image(&mut self, _: &smithay::input::Seat<Self>, image: CursorImageStatus) {
+        self.cursor_status = image;
+        self.request_redraw();
     }
 
-    fn focus_changed(&mut self, _: &smithay::input::Seat<Self>, _: Option<&WlSurface>) {}
+    fn focus_changed(&mut self, seat: &smithay::input::Seat<Self>, focused: Option<&WlSurface>) {
+        if let Some(window) = focused
+            .and_then(|surface: &WlSurface| self.windows.get(surface))
+            .cloned()
+        {
+            self.focus_stack.push(&window);
+            // Borders and title bars show which window has it
+            self.request_redraw();
+            self.emit_ipc_event(IpcEvent::WindowFocused {
+                id: tree::window_id(&window),
+            });
+        }
+
+        // Clipboard offers go to whichever client has the keyboard
+        let client: Option<Client> = focused
+            .and_then(|surface: &WlSurface| self.display_handle.get_client(surface.id()).ok());
+        set_data_device_focus(&self.display_handle, seat, client.clone());
+        set_primary_focus(&self.display_handle, seat, client);
+    }
 }
 delegate_seat!(State);
 
@@ -130,6 +368,10 @@ impl ShmHandler for State {
 }
 delegate_shm!(State);
 
+// Cropping and scaling is applied by smithay when it builds the surface's
+// render elements and its bounding box, there's nothing to handle here
+delegate_viewporter!(State);
+
 impl XdgShellHandler for State {
     fn xdg_shell_state(&mut self) -> &mut XdgShellState {
         &mut self.xdg_shell_state
@@ -137,25 +379,147 @@ impl XdgShellHandler for State {
 
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
         let window: Window = Window::new(surface);
-        self.space.map_element(window, (0, 0), false);
+        self.windows
+            .insert(window.toplevel().wl_surface().clone(), window.clone());
+        self.emit_ipc_event(IpcEvent::WindowOpened {
+            id: tree::window_id(&window),
+        });
+        self.tag_new_window(&window);
+        if self.kiosk() {
+            self.map_kiosk_window(window);
+            return;
+        }
+
+        // Placed for real once it's tiled, this only picks the output
+        let location: Point<i32, Logical> = self
+            .active_output()
+            .and_then(|output: Output| self.space.output_geometry(&output))
+            .map(|geometry: Rectangle<i32, Logical>| geometry.loc)
+            .unwrap_or_default();
+        self.space.map_element(window, location, false);
+    }
+
+    fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        let Some(window) = self.windows.remove(surface.wl_surface()) else {
+            return;
+        };
+        self.emit_ipc_event(IpcEvent::WindowClosed {
+            id: tree::window_id(&window),
+        });
+        self.focus_stack.remove(&window);
+        self.forget_fullscreen(&window);
+        self.forget_maximized(&window);
+        self.forget_minimized(&window);
+        self.forget_unplaced(&window);
+        self.forget_workspace(&window);
+
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        let was_focused: bool = keyboard
+            .current_focus()
+            .map_or(true, |focus: WlSurface| focus == *surface.wl_surface());
+
+        self.space.unmap_elem(&window);
+        self.untile_window(&window);
+        if was_focused {
+            self.focus_previous();
+        }
     }
 
-    fn toplevel_destroyed(&mut self, _surface: ToplevelSurface) {}
+    fn new_popup(&mut self, surface: PopupSurface, positioner: PositionerState) {
+        self.track_popup(surface, positioner);
+    }
 
-    fn new_popup(&mut self, _surface: PopupSurface, _positioner: PositionerState) {}
+    fn reposition_request(
+        &mut self,
+        surface: PopupSurface,
+        positioner: PositionerState,
+        token: u32,
+    ) {
+        self.reposition_popup(surface, positioner, token);
+    }
 
-    fn move_request(&mut self, _surface: ToplevelSurface, _seat: WlSeat, _serial: Serial) {}
+    fn move_request(&mut self, surface: ToplevelSurface, _seat: WlSeat, serial: Serial) {
+        let Some(start_data) = self.client_grab_start(&surface, serial) else {
+            return;
+        };
+
+        if let Some(window) = self.windows.get(surface.wl_surface()).cloned() {
+            self.start_move_grab(&window, start_data, serial);
+        }
+    }
 
     fn resize_request(
         &mut self,
-        _surface: ToplevelSurface,
+        surface: ToplevelSurface,
         _seat: wl_seat::WlSeat,
+        serial: Serial,
+        edges: xdg_toplevel::ResizeEdge,
+    ) {
+        let Some(start_data) = self.client_grab_start(&surface, serial) else {
+            return;
+        };
+
+        if let Some(window) = self.windows.get(surface.wl_surface()).cloned() {
+            self.start_resize_grab(&window, start_data, edges.into(), serial);
+        }
+    }
+
+    // Kiosk windows are always fullscreen, the request only gets a
+    // configure back
+    fn fullscreen_request(&mut self, surface: ToplevelSurface, output: Option<WlOutput>) {
+        match self.windows.get(surface.wl_surface()).cloned() {
+            Some(window) if !self.kiosk() => self.fullscreen_window(&window, output),
+            _ => surface.send_pending_configure(),
+        }
+    }
+
+    fn unfullscreen_request(&mut self, surface: ToplevelSurface) {
+        match self.windows.get(surface.wl_surface()).cloned() {
+            Some(window) if !self.kiosk() => self.unfullscreen_window(&window),
+            _ => surface.send_pending_configure(),
+        }
+    }
+
+    fn maximize_request(&mut self, surface: ToplevelSurface) {
+        match self.windows.get(surface.wl_surface()).cloned() {
+            Some(window) if !self.kiosk() => self.maximize_window(&window),
+            _ => surface.send_pending_configure(),
+        }
+    }
+
+    fn unmaximize_request(&mut self, surface: ToplevelSurface) {
+        match self.windows.get(surface.wl_surface()).cloned() {
+            Some(window) if !self.kiosk() => self.unmaximize_window(&window),
+            _ => surface.send_pending_configure(),
+        }
+    }
+
+    // Nothing to configure, the client can't tell it's minimized
+    fn minimize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.windows.get(surface.wl_surface()).cloned() {
+            if !self.kiosk() {
+                self.minimize_window(&window);
+            }
+        }
+    }
+
+    fn show_window_menu(
+        &mut self,
+        surface: ToplevelSurface,
+        _seat: WlSeat,
         _serial: Serial,
-        _edges: xdg_toplevel::ResizeEdge,
+        location: Point<i32, Logical>,
     ) {
+        if let Some(window) = self.windows.get(surface.wl_surface()).cloned() {
+            if !self.kiosk() {
+                self.open_window_menu(&window, location);
+            }
+        }
     }
 
-    fn grab(&mut self, _surface: PopupSurface, _seat: wl_seat::WlSeat, _serial: Serial) {}
+    fn grab(&mut self, surface: PopupSurface, seat: wl_seat::WlSeat, serial: Serial) {
+        self.grab_popup(surface, seat, serial);
+    }
 }
 delegate_xdg_shell!(State);
 
@@ -0,0 +1,471 @@
+use smithay::{
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::{
+        compositor::{get_parent, get_role, is_sync_subsurface},
+        shell::{
+            wlr_layer::LAYER_SURFACE_ROLE,
+            xdg::{XDG_POPUP_ROLE, XDG_TOPLEVEL_ROLE},
+        },
+    },
+};
+
+use crate::state::State;
+
+// What a commit on a surface means for the rest of the commit handling
+#[derive(Debug, PartialEq)]
+enum SubsurfaceCommit {
+    // Not a subsurface, handled like any other surface
+    Surface,
+    // Synchronized, itself or through an ancestor. Its state stays cached
+    // until the parent commits, which applies it for the whole tree.
+    Cached,
+    // Desynchronized, its state is applied right away. The window, popup
+    // or layer surface at the root of the tree has to learn that its
+    // bounding box may have changed.
+    Applied(WlSurface),
+}
+
+// Which commit handler the root of a subsurface tree belongs to
+#[derive(Debug, PartialEq)]
+enum RootRole {
+    Toplevel,
+    Popup,
+    Layer,
+    // A cursor or drag icon, or a surface without a role yet
+    Other,
+}
+
+fn root_role(root: &WlSurface) -> RootRole {
+    match get_role(root) {
+        Some(XDG_TOPLEVEL_ROLE) => RootRole::Toplevel,
+        Some(XDG_POPUP_ROLE) => RootRole::Popup,
+        Some(LAYER_SURFACE_ROLE) => RootRole::Layer,
+        _ => RootRole::Other,
+    }
+}
+
+fn classify(surface: &WlSurface) -> SubsurfaceCommit {
+    if is_sync_subsurface(surface) {
+        return SubsurfaceCommit::Cached;
+    }
+    let root: WlSurface = root_surface(surface);
+    if root == *surface {
+        SubsurfaceCommit::Surface
+    } else {
+        SubsurfaceCommit::Applied(root)
+    }
+}
+
+// The surface at the top of `surface`'s subsurface tree, the surface itself
+// if it isn't a subsurface
+pub fn root_surface(surface: &WlSurface) -> WlSurface {
+    let mut root: WlSurface = surface.clone();
+    while let Some(parent) = get_parent(&root) {
+        root = parent;
+    }
+    root
+}
+
+impl State {
+    // Returns whether the rest of the commit handling should look at
+    // `surface`, see SubsurfaceCommit
+    pub fn subsurface_commit(&mut self, surface: &WlSurface) -> bool {
+        match classify(surface) {
+            SubsurfaceCommit::Surface => true,
+            SubsurfaceCommit::Cached => false,
+            SubsurfaceCommit::Applied(root) => {
+                match root_role(&root) {
+                    RootRole::Toplevel => {
+                        if let Some(window) = self.windows.get(&root).cloned() {
+                            window.on_commit();
+                        }
+                    }
+                    RootRole::Popup => self.popup_commit(&root),
+                    RootRole::Layer => self.layer_commit(&root),
+                    RootRole::Other => {}
+                }
+                false
+            }
+        }
+    }
+}
+
+// A client and a bare compositor talking over a socket pair, so the caching
+// is smithay's own and not a stand-in for it
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixStream, sync::Arc};
+
+    use smithay::{
+        delegate_compositor, delegate_layer_shell,
+        reexports::wayland_server::{
+            backend::{ClientData, ClientId, DisconnectReason},
+            protocol::wl_output::WlOutput,
+            Client, Display, Resource,
+        },
+        wayland::{
+            compositor::{
+                with_states, CompositorClientState, CompositorHandler, CompositorState,
+                SurfaceAttributes, SurfaceData,
+            },
+            shell::wlr_layer::{self, Layer, WlrLayerShellHandler, WlrLayerShellState},
+        },
+    };
+    use wayland_client::{
+        delegate_noop,
+        protocol::{
+            wl_callback::{self, WlCallback},
+            wl_compositor::WlCompositor,
+            wl_registry::{self, WlRegistry},
+            wl_subcompositor::WlSubcompositor,
+            wl_subsurface::WlSubsurface,
+            wl_surface::WlSurface as ClientSurface,
+        },
+        Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+    };
+    use wayland_protocols_wlr::layer_shell::v1::client::{
+        zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
+        zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+    };
+
+    use super::*;
+
+    struct Server {
+        compositor_state: CompositorState,
+        layer_shell_state: WlrLayerShellState,
+        commits: Vec<SubsurfaceCommit>,
+    }
+
+    struct ServerClient(CompositorClientState);
+
+    impl ClientData for ServerClient {
+        fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
+    }
+
+    impl CompositorHandler for Server {
+        fn compositor_state(&mut self) -> &mut CompositorState {
+            &mut self.compositor_state
+        }
+
+        fn client_compositor_state<'a>(&self, client: &'a Client) -> &'a CompositorClientState {
+            &client.get_data::<ServerClient>().unwrap().0
+        }
+
+        fn commit(&mut self, surface: &WlSurface) {
+            self.commits.push(classify(surface));
+        }
+    }
+    delegate_compositor!(Server);
+
+    impl WlrLayerShellHandler for Server {
+        fn shell_state(&mut self) -> &mut WlrLayerShellState {
+            &mut self.layer_shell_state
+        }
+
+        fn new_layer_surface(
+            &mut self,
+            _surface: wlr_layer::LayerSurface,
+            _output: Option<WlOutput>,
+            _layer: Layer,
+            _namespace: String,
+        ) {
+        }
+    }
+    delegate_layer_shell!(Server);
+
+    #[derive(Default)]
+    struct TestClient {
+        globals: Vec<(u32, String, u32)>,
+        synced: bool,
+    }
+
+    impl Dispatch<WlRegistry, ()> for TestClient {
+        fn event(
+            state: &mut Self,
+            _: &WlRegistry,
+            event: wl_registry::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } = event
+            {
+                state.globals.push((name, interface, version));
+            }
+        }
+    }
+
+    impl Dispatch<WlCallback, ()> for TestClient {
+        fn event(
+            state: &mut Self,
+            _: &WlCallback,
+            event: wl_callback::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let wl_callback::Event::Done { .. } = event {
+                state.synced = true;
+            }
+        }
+    }
+
+    delegate_noop!(TestClient: ignore WlCompositor);
+    delegate_noop!(TestClient: ignore WlSubcompositor);
+    delegate_noop!(TestClient: ignore WlSubsurface);
+    delegate_noop!(TestClient: ignore ClientSurface);
+    delegate_noop!(TestClient: ignore ZwlrLayerShellV1);
+    delegate_noop!(TestClient: ignore ZwlrLayerSurfaceV1);
+
+    // Lets the server handle everything the client sent so far, then the
+    // client everything the server sent back
+    fn roundtrip(
+        display: &mut Display<Server>,
+        server: &mut Server,
+        connection: &Connection,
+        queue: &mut EventQueue<TestClient>,
+        state: &mut TestClient,
+    ) {
+        state.synced = false;
+        connection.display().sync(&queue.handle(), ());
+        connection.flush().unwrap();
+        display.dispatch_clients(server).unwrap();
+        display.flush_clients().unwrap();
+        while !state.synced {
+            queue.blocking_dispatch(state).unwrap();
+        }
+    }
+
+    fn bind<I: Proxy + 'static>(
+        registry: &WlRegistry,
+        queue: &EventQueue<TestClient>,
+        state: &TestClient,
+    ) -> I
+    where
+        TestClient: Dispatch<I, ()>,
+    {
+        let (name, _, version) = state
+            .globals
+            .iter()
+            .find(|(_, interface, _): &&(u32, String, u32)| interface == I::interface().name)
+            .unwrap();
+        registry.bind::<I, (), TestClient>(*name, *version, &queue.handle(), ())
+    }
+
+    struct Harness {
+        display: Display<Server>,
+        server: Server,
+        client: Client,
+        connection: Connection,
+        queue: EventQueue<TestClient>,
+        state: TestClient,
+        compositor: WlCompositor,
+        subcompositor: WlSubcompositor,
+        layer_shell: ZwlrLayerShellV1,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let mut display: Display<Server> = Display::new().unwrap();
+            let mut server: Server = Server {
+                compositor_state: CompositorState::new::<Server>(&display.handle()),
+                layer_shell_state: WlrLayerShellState::new::<Server>(&display.handle()),
+                commits: Vec::new(),
+            };
+            let (server_end, client_end) = UnixStream::pair().unwrap();
+            let client: Client = display
+                .handle()
+                .insert_client(
+                    server_end,
+                    Arc::new(ServerClient(CompositorClientState::default())),
+                )
+                .unwrap();
+            let connection: Connection = Connection::from_socket(client_end).unwrap();
+            let mut queue: EventQueue<TestClient> = connection.new_event_queue();
+            let mut state: TestClient = TestClient::default();
+
+            let registry: WlRegistry = connection.display().get_registry(&queue.handle(), ());
+            roundtrip(
+                &mut display,
+                &mut server,
+                &connection,
+                &mut queue,
+                &mut state,
+            );
+            let compositor: WlCompositor = bind(&registry, &queue, &state);
+            let subcompositor: WlSubcompositor = bind(&registry, &queue, &state);
+            let layer_shell: ZwlrLayerShellV1 = bind(&registry, &queue, &state);
+
+            Self {
+                display,
+                server,
+                client,
+                connection,
+                queue,
+                state,
+                compositor,
+                subcompositor,
+                layer_shell,
+            }
+        }
+
+        fn roundtrip(&mut self) {
+            roundtrip(
+                &mut self.display,
+                &mut self.server,
+                &self.connection,
+                &mut self.queue,
+                &mut self.state,
+            );
+        }
+
+        fn surface(&mut self) -> ClientSurface {
+            let surface: ClientSurface = self.compositor.create_surface(&self.queue.handle(), ());
+            self.roundtrip();
+            surface
+        }
+
+        // Synchronized, like every new subsurface
+        fn subsurface(&mut self, parent: &ClientSurface) -> (ClientSurface, WlSubsurface) {
+            let surface: ClientSurface = self.compositor.create_surface(&self.queue.handle(), ());
+            let subsurface: WlSubsurface =
+                self.subcompositor
+                    .get_subsurface(&surface, parent, &self.queue.handle(), ());
+            self.roundtrip();
+            (surface, subsurface)
+        }
+
+        fn layer_surface(&mut self, surface: &ClientSurface) -> ZwlrLayerSurfaceV1 {
+            let layer_surface: ZwlrLayerSurfaceV1 = self.layer_shell.get_layer_surface(
+                surface,
+                None,
+                zwlr_layer_shell_v1::Layer::Top,
+                "test".into(),
+                &self.queue.handle(),
+                (),
+            );
+            self.roundtrip();
+            layer_surface
+        }
+
+        fn server_surface(&self, surface: &ClientSurface) -> WlSurface {
+            self.client
+                .object_from_protocol_id::<WlSurface>(
+                    &self.display.handle(),
+                    surface.id().protocol_id(),
+                )
+                .unwrap()
+        }
+
+        // The scale the compositor currently uses for the surface
+        fn buffer_scale(&self, surface: &ClientSurface) -> i32 {
+            with_states(&self.server_surface(surface), |states: &SurfaceData| {
+                states
+                    .cached_state
+                    .current::<SurfaceAttributes>()
+                    .buffer_scale
+            })
+        }
+
+        fn commit(&mut self, surface: &ClientSurface) {
+            surface.commit();
+            self.roundtrip();
+        }
+    }
+
+    #[test]
+    fn synchronized_state_waits_for_the_parent() {
+        let mut harness: Harness = Harness::new();
+        let parent: ClientSurface = harness.surface();
+        let (child, _subsurface) = harness.subsurface(&parent);
+
+        child.set_buffer_scale(2);
+        harness.commit(&child);
+        assert_eq!(harness.buffer_scale(&child), 1);
+        assert_eq!(
+            classify(&harness.server_surface(&child)),
+            SubsurfaceCommit::Cached
+        );
+
+        harness.commit(&parent);
+        assert_eq!(harness.buffer_scale(&child), 2);
+        assert!(harness.server.commits.contains(&SubsurfaceCommit::Surface));
+    }
+
+    #[test]
+    fn desynchronized_state_applies_right_away() {
+        let mut harness: Harness = Harness::new();
+        let parent: ClientSurface = harness.surface();
+        let (child, subsurface) = harness.subsurface(&parent);
+        subsurface.set_desync();
+
+        child.set_buffer_scale(3);
+        harness.commit(&child);
+        assert_eq!(harness.buffer_scale(&child), 3);
+        assert_eq!(
+            harness.server.commits.last(),
+            Some(&SubsurfaceCommit::Applied(harness.server_surface(&parent)))
+        );
+    }
+
+    #[test]
+    fn desynchronized_under_synchronized_parent_waits() {
+        let mut harness: Harness = Harness::new();
+        let root: ClientSurface = harness.surface();
+        let (child, _subsurface) = harness.subsurface(&root);
+        let (grandchild, subsurface) = harness.subsurface(&child);
+        subsurface.set_desync();
+
+        grandchild.set_buffer_scale(2);
+        harness.commit(&grandchild);
+        assert_eq!(harness.buffer_scale(&grandchild), 1);
+        assert_eq!(
+            classify(&harness.server_surface(&grandchild)),
+            SubsurfaceCommit::Cached
+        );
+
+        // Applied with the root's commit, the child's one only caches it
+        harness.commit(&child);
+        harness.commit(&root);
+        assert_eq!(harness.buffer_scale(&grandchild), 2);
+    }
+
+    #[test]
+    fn desynchronized_under_layer_surface_reaches_it() {
+        let mut harness: Harness = Harness::new();
+        let parent: ClientSurface = harness.surface();
+        let _layer_surface: ZwlrLayerSurfaceV1 = harness.layer_surface(&parent);
+        let (child, subsurface) = harness.subsurface(&parent);
+        subsurface.set_desync();
+
+        child.set_buffer_scale(2);
+        harness.commit(&child);
+        let root: WlSurface = harness.server_surface(&parent);
+        assert_eq!(
+            harness.server.commits.last(),
+            Some(&SubsurfaceCommit::Applied(root.clone()))
+        );
+        assert_eq!(root_role(&root), RootRole::Layer);
+        assert_eq!(root_role(&harness.server_surface(&child)), RootRole::Other);
+    }
+
+    #[test]
+    fn back_to_synchronized_caches_again() {
+        let mut harness: Harness = Harness::new();
+        let parent: ClientSurface = harness.surface();
+        let (child, subsurface) = harness.subsurface(&parent);
+        subsurface.set_desync();
+        subsurface.set_sync();
+
+        child.set_buffer_scale(2);
+        harness.commit(&child);
+        assert_eq!(harness.buffer_scale(&child), 1);
+
+        harness.commit(&parent);
+        assert_eq!(harness.buffer_scale(&child), 2);
+    }
+}
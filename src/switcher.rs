@@ -0,0 +1,282 @@
+use smithay::{
+    backend::renderer::{
+        element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            texture::{TextureBuffer, TextureRenderElement},
+            Kind,
+        },
+        gles::{GlesRenderer, GlesTexture},
+    },
+    desktop::Window,
+    output::Output,
+    utils::{Logical, Physical, Point, Size},
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        shell::xdg::XdgToplevelSurfaceData,
+    },
+};
+
+use crate::{render::CustomRenderElements, state::State};
+
+const FONT_SIZE: f32 = 16.0;
+const LINE_HEIGHT: i32 = 28;
+const PADDING: i32 = 16;
+// From the top of a row to its text
+const TEXT_OFFSET: i32 = 5;
+// Longer names are cut off
+const MAX_LABEL: usize = 60;
+
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.85];
+const SELECTION: [f32; 4] = [0.32, 0.58, 0.89, 1.0];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SwitchScope {
+    // Alt+Tab, one entry per application
+    Applications,
+    // Alt+`, the windows of the selected application
+    Windows,
+}
+
+// Only active while Alt is held, releasing it focuses the selection
+pub struct Switcher {
+    scope: Option<SwitchScope>,
+    candidates: Vec<Window>,
+    index: usize,
+    // Of the panel, its buffers are only updated when it changes so it
+    // isn't damaged every frame
+    size: (i32, i32),
+    background: SolidColorBuffer,
+    selection: SolidColorBuffer,
+}
+
+impl Default for Switcher {
+    fn default() -> Self {
+        Self {
+            scope: None,
+            candidates: Vec::new(),
+            index: 0,
+            size: (0, 0),
+            background: SolidColorBuffer::new((0, 0), BACKGROUND),
+            selection: SolidColorBuffer::new((0, 0), SELECTION),
+        }
+    }
+}
+
+impl Switcher {
+    pub fn is_active(&self) -> bool {
+        self.scope.is_some()
+    }
+
+    fn selected(&self) -> Option<&Window> {
+        self.candidates.get(self.index)
+    }
+
+    fn step(&mut self, backward: bool) {
+        let len: usize = self.candidates.len();
+        if len == 0 {
+            return;
+        }
+
+        self.index = if backward {
+            (self.index + len - 1) % len
+        } else {
+            (self.index + 1) % len
+        };
+    }
+}
+
+pub fn app_id(window: &Window) -> Option<String> {
+    with_states(window.toplevel().wl_surface(), |states: &SurfaceData| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()?
+            .lock()
+            .unwrap()
+            .app_id
+            .clone()
+    })
+}
+
+pub fn title(window: &Window) -> Option<String> {
+    with_states(window.toplevel().wl_surface(), |states: &SurfaceData| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()?
+            .lock()
+            .unwrap()
+            .title
+            .clone()
+    })
+}
+
+// The application when switching between them, the window title when
+// switching between its windows
+fn label(window: &Window, scope: SwitchScope) -> String {
+    let label: Option<String> = match scope {
+        SwitchScope::Applications => app_id(window).or_else(|| title(window)),
+        SwitchScope::Windows => title(window).or_else(|| app_id(window)),
+    };
+    let label: String = label.unwrap_or_else(|| "Untitled".into());
+    if label.chars().count() <= MAX_LABEL {
+        return label;
+    }
+    label.chars().take(MAX_LABEL - 1).chain(['…']).collect()
+}
+
+// Windows without an app_id can't be grouped, each one is its own group
+fn same_app(a: &Window, b: &Window) -> bool {
+    a == b || app_id(a).map_or(false, |id: String| app_id(b).as_ref() == Some(&id))
+}
+
+// `windows` is most recently used first, so is every group and the result
+fn candidates(windows: &[Window], scope: SwitchScope, anchor: &Window) -> Vec<Window> {
+    match scope {
+        SwitchScope::Applications => {
+            let mut leaders: Vec<Window> = Vec::new();
+            for window in windows {
+                if !leaders
+                    .iter()
+                    .any(|leader: &Window| same_app(leader, window))
+                {
+                    leaders.push(window.clone());
+                }
+            }
+            leaders
+        }
+        SwitchScope::Windows => std::iter::once(anchor.clone())
+            .chain(
+                windows
+                    .iter()
+                    .filter(|window: &&Window| *window != anchor && same_app(anchor, window))
+                    .cloned(),
+            )
+            .collect(),
+    }
+}
+
+impl State {
+    // The space keeps windows in stacking order and focusing raises, so the
+    // topmost window is the most recently used one
+    fn windows_by_recency(&self) -> Vec<Window> {
+        self.space.elements().rev().cloned().collect()
+    }
+
+    pub fn switch(&mut self, scope: SwitchScope, backward: bool) {
+        if self.switcher.scope != Some(scope) {
+            let windows: Vec<Window> = self.windows_by_recency();
+            // Switching from Alt+Tab to Alt+` cycles through the windows of
+            // the application selected so far
+            let Some(anchor) = self.switcher.selected().or(windows.first()).cloned() else {
+                return;
+            };
+
+            self.switcher.scope = Some(scope);
+            self.switcher.candidates = candidates(&windows, scope, &anchor);
+            self.switcher.index = 0;
+        }
+
+        self.switcher.step(backward);
+        self.request_redraw();
+    }
+
+    pub fn finish_switch(&mut self) {
+        let selected: Option<Window> = self.switcher.selected().cloned();
+        self.switcher.scope = None;
+        self.switcher.candidates.clear();
+        self.switcher.index = 0;
+        if let Some(window) = selected {
+            self.focus_window(&window);
+        }
+        self.request_redraw();
+    }
+
+    // A row per candidate with the selection highlighted, centered on the
+    // output the pointer is on
+    pub fn switcher_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output: &Output,
+        elements: &mut Vec<CustomRenderElements>,
+    ) {
+        let Some(scope) = self.switcher.scope else {
+            return;
+        };
+        if self.active_output().as_ref() != Some(output) {
+            return;
+        }
+        let Some(text) = self.text.as_mut() else {
+            return;
+        };
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let output_size: Size<i32, Logical> = output
+            .current_mode()
+            .map(|mode| mode.size.to_f64().to_logical(scale).to_i32_round())
+            .unwrap_or_default();
+
+        let labels: Vec<String> = self
+            .switcher
+            .candidates
+            .iter()
+            .map(|window: &Window| label(window, scope))
+            .collect();
+        let width: i32 = labels
+            .iter()
+            .map(|label: &String| text.measure(label, FONT_SIZE).0)
+            .max()
+            .unwrap_or(0)
+            + 2 * PADDING;
+        let height: i32 = labels.len() as i32 * LINE_HEIGHT + 2 * PADDING;
+        let origin: Point<i32, Logical> =
+            Point::from(((output_size.w - width) / 2, (output_size.h - height) / 2));
+        let at = |offset: (i32, i32)| -> Point<i32, Physical> {
+            (origin + Point::from(offset)).to_physical_precise_round(scale)
+        };
+
+        for (row, label) in labels.iter().enumerate() {
+            let buffer: Option<TextureBuffer<GlesTexture>> =
+                text.render(renderer, label, FONT_SIZE, TEXT, scale.ceil() as i32);
+            let Some(buffer) = buffer else {
+                continue;
+            };
+            let y: i32 = PADDING + row as i32 * LINE_HEIGHT + TEXT_OFFSET;
+            elements.push(
+                TextureRenderElement::from_texture_buffer(
+                    at((PADDING, y)).to_f64(),
+                    &buffer,
+                    None,
+                    None,
+                    None,
+                    Kind::Unspecified,
+                )
+                .into(),
+            );
+        }
+
+        let switcher: &mut Switcher = &mut self.switcher;
+        if switcher.size != (width, height) {
+            switcher.size = (width, height);
+            switcher.background.update((width, height), BACKGROUND);
+            switcher
+                .selection
+                .update((width - PADDING, LINE_HEIGHT), SELECTION);
+        }
+        let selection_y: i32 = PADDING + switcher.index as i32 * LINE_HEIGHT;
+        for (buffer, offset) in [
+            (&switcher.selection, (PADDING / 2, selection_y)),
+            (&switcher.background, (0, 0)),
+        ] {
+            elements.push(
+                SolidColorRenderElement::from_buffer(
+                    buffer,
+                    at(offset),
+                    scale,
+                    1.0,
+                    Kind::Unspecified,
+                )
+                .into(),
+            );
+        }
+    }
+}
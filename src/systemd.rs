@@ -0,0 +1,81 @@
+use std::{path::Path, sync::OnceLock};
+
+use zbus::{
+    blocking::Connection,
+    dbus_proxy,
+    zvariant::{OwnedObjectPath, Value},
+};
+
+// sd_booted() checks the same thing
+const SYSTEMD_RUNTIME_DIR: &str = "/run/systemd/system";
+
+static SESSION: OnceLock<Option<Connection>> = OnceLock::new();
+
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1"
+)]
+trait Manager {
+    fn start_transient_unit(
+        &self,
+        name: &str,
+        mode: &str,
+        properties: &[(&str, Value<'_>)],
+        aux: &[(&str, &[(&str, Value<'_>)])],
+    ) -> zbus::Result<OwnedObjectPath>;
+}
+
+// Gives a spawned client its own transient scope in the user manager, so
+// OOM kills and resource limits apply to that app alone. Without systemd
+// the process just stays in the compositor's cgroup.
+pub fn move_to_scope(command: &str, pid: u32) {
+    if !Path::new(SYSTEMD_RUNTIME_DIR).exists() {
+        return;
+    }
+
+    let unit: String = scope_name(command, pid);
+    // Talking to the bus can take a while, the compositor shouldn't wait
+    std::thread::spawn(move || {
+        let Some(connection) = SESSION.get_or_init(|| Connection::session().ok()) else {
+            return;
+        };
+
+        let aux: &[(&str, &[(&str, Value<'_>)])] = &[];
+        let result: zbus::Result<OwnedObjectPath> =
+            ManagerProxyBlocking::new(connection).and_then(|manager: ManagerProxyBlocking| {
+                manager.start_transient_unit(
+                    &unit,
+                    "fail",
+                    &[("PIDs", Value::from(vec![pid]))],
+                    aux,
+                )
+            });
+
+        if let Err(err) = result {
+            eprintln!("Failed to move process {pid} into {unit}: {err}");
+        }
+    });
+}
+
+// "FOO=1 /usr/bin/firefox --new-window" gives app-firefox-<pid>.scope
+fn scope_name(command: &str, pid: u32) -> String {
+    let program: &str = command
+        .split_whitespace()
+        .find(|word: &&str| !word.contains('='))
+        .and_then(|word: &str| word.rsplit('/').next())
+        .unwrap_or("unknown");
+
+    let name: String = program
+        .chars()
+        .map(|c: char| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    format!("app-{name}-{pid}.scope")
+}
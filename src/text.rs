@@ -0,0 +1,238 @@
+use std::{collections::HashMap, fmt, process::Command};
+
+use fontdue::{
+    layout::{CoordinateSystem, Layout, LayoutSettings, TextStyle},
+    Font, FontSettings,
+};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::texture::TextureBuffer,
+            gles::{GlesRenderer, GlesTexture},
+        },
+    },
+    utils::Transform,
+};
+
+// Rendered strings are kept around between frames, but titlebars and the bar
+// change often enough that the cache has to be bounded. The least recently
+// drawn one goes first.
+const CACHE_LIMIT: usize = 256;
+
+#[derive(Debug)]
+pub enum FontError {
+    NotFound(String),
+    Io(std::io::Error),
+    Parse(&'static str),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontError::NotFound(family) => write!(f, "no font found for \"{family}\""),
+            FontError::Io(err) => write!(f, "failed to read font: {err}"),
+            FontError::Parse(err) => write!(f, "failed to parse font: {err}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct TextKey {
+    // f32 isn't comparable, the size is stored in 1/64 px
+    size: u32,
+    color: [u8; 4],
+    scale: i32,
+}
+
+struct CachedText {
+    key: TextKey,
+    buffer: TextureBuffer<GlesTexture>,
+    // The value of `TextRenderer::uses` when it was last drawn
+    last_used: u64,
+}
+
+pub struct TextRenderer {
+    font: Font,
+    layout: Layout,
+    // By text first, so a lookup doesn't need an owned copy of the string
+    cache: HashMap<String, Vec<CachedText>>,
+    // Textures in the cache, a string can have one per size, color and scale
+    entries: usize,
+    // Counts lookups, orders the cache by when its textures were last drawn
+    uses: u64,
+}
+
+impl TextRenderer {
+    pub fn new(family: &str) -> Result<Self, FontError> {
+        let path: String = find_font(family)?;
+        let bytes: Vec<u8> = std::fs::read(path).map_err(FontError::Io)?;
+        let font: Font =
+            Font::from_bytes(bytes, FontSettings::default()).map_err(FontError::Parse)?;
+
+        Ok(Self {
+            font,
+            layout: Layout::new(CoordinateSystem::PositiveYDown),
+            cache: HashMap::new(),
+            entries: 0,
+            uses: 0,
+        })
+    }
+
+    // Returns a texture holding `text` drawn in `color` (straight RGBA),
+    // rasterized at `size * scale` pixels so it stays sharp on scaled outputs
+    pub fn render(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        text: &str,
+        size: f32,
+        color: [u8; 4],
+        scale: i32,
+    ) -> Option<TextureBuffer<GlesTexture>> {
+        let key: TextKey = TextKey {
+            size: (size * 64.0) as u32,
+            color,
+            scale,
+        };
+
+        self.uses += 1;
+        let cached: Option<&mut CachedText> = self.cache.get_mut(text).and_then(|entries| {
+            entries
+                .iter_mut()
+                .find(|cached: &&mut CachedText| cached.key == key)
+        });
+        if let Some(cached) = cached {
+            cached.last_used = self.uses;
+            return Some(cached.buffer.clone());
+        }
+
+        let (width, height, pixels) = self.rasterize(text, size * scale as f32, color)?;
+
+        let buffer: TextureBuffer<GlesTexture> = TextureBuffer::from_memory(
+            renderer,
+            &pixels,
+            Fourcc::Abgr8888,
+            (width, height),
+            false,
+            scale,
+            Transform::Normal,
+            None,
+        )
+        .ok()?;
+
+        if self.entries >= CACHE_LIMIT {
+            self.evict();
+        }
+        self.entries += 1;
+        self.cache
+            .entry(text.to_string())
+            .or_default()
+            .push(CachedText {
+                key,
+                buffer: buffer.clone(),
+                last_used: self.uses,
+            });
+
+        Some(buffer)
+    }
+
+    // Drops the texture drawn the longest time ago
+    fn evict(&mut self) {
+        let oldest: Option<(String, usize)> = self
+            .cache
+            .iter()
+            .flat_map(|(text, entries): (&String, &Vec<CachedText>)| {
+                entries
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, cached): (usize, &CachedText)| {
+                        (cached.last_used, text, index)
+                    })
+            })
+            .min_by_key(|(last_used, _, _)| *last_used)
+            .map(|(_, text, index)| (text.clone(), index));
+        let Some((text, index)) = oldest else {
+            return;
+        };
+
+        let entries: &mut Vec<CachedText> = self.cache.get_mut(&text).unwrap();
+        entries.swap_remove(index);
+        if entries.is_empty() {
+            self.cache.remove(&text);
+        }
+        self.entries -= 1;
+    }
+
+    // Size in logical pixels `text` would take up, without rendering it
+    pub fn measure(&mut self, text: &str, size: f32) -> (i32, i32) {
+        self.layout.reset(&LayoutSettings::default());
+        self.layout
+            .append(&[&self.font], &TextStyle::new(text, size, 0));
+
+        let width: f32 = self
+            .layout
+            .glyphs()
+            .iter()
+            .map(|glyph| glyph.x + glyph.width as f32)
+            .fold(0.0, f32::max);
+
+        (width.ceil() as i32, self.layout.height().ceil() as i32)
+    }
+
+    fn rasterize(&mut self, text: &str, size: f32, color: [u8; 4]) -> Option<(i32, i32, Vec<u8>)> {
+        let (width, height) = self.measure(text, size);
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        let mut pixels: Vec<u8> = vec![0; (width * height * 4) as usize];
+
+        for glyph in self.layout.glyphs() {
+            if glyph.width == 0 || glyph.height == 0 {
+                continue;
+            }
+
+            let (_, coverage) = self.font.rasterize_config(glyph.key);
+
+            for row in 0..glyph.height {
+                for column in 0..glyph.width {
+                    let x: i32 = glyph.x as i32 + column as i32;
+                    let y: i32 = glyph.y as i32 + row as i32;
+                    if x < 0 || y < 0 || x >= width || y >= height {
+                        continue;
+                    }
+
+                    let alpha: u32 =
+                        coverage[row * glyph.width + column] as u32 * color[3] as u32 / 255;
+                    let index: usize = ((y * width + x) * 4) as usize;
+
+                    // The renderer expects premultiplied alpha
+                    let pixel: &mut [u8] = &mut pixels[index..index + 4];
+                    if alpha as u8 > pixel[3] {
+                        pixel[0] = (color[0] as u32 * alpha / 255) as u8;
+                        pixel[1] = (color[1] as u32 * alpha / 255) as u8;
+                        pixel[2] = (color[2] as u32 * alpha / 255) as u8;
+                        pixel[3] = alpha as u8;
+                    }
+                }
+            }
+        }
+
+        Some((width, height, pixels))
+    }
+}
+
+// Resolves a family name (or any fontconfig pattern) to a font file
+fn find_font(family: &str) -> Result<String, FontError> {
+    let output = Command::new("fc-match")
+        .args(["--format=%{file}", family])
+        .output()
+        .map_err(FontError::Io)?;
+
+    let path: String = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || path.is_empty() {
+        return Err(FontError::NotFound(family.to_string()));
+    }
+
+    Ok(path)
+}
@@ -0,0 +1,195 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use zbus::{
+    blocking::{Connection, ConnectionBuilder},
+    dbus_interface, fdo,
+    zvariant::{OwnedValue, Value},
+};
+
+use crate::state::State;
+
+// xdg-desktop-portal finds this backend through a pulsewm.portal file that
+// names the bus name and the Settings interface, with UseIn=pulsewm
+const BUS_NAME: &str = "org.freedesktop.impl.portal.desktop.pulsewm";
+const OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+const SETTINGS_INTERFACE: &str = "org.freedesktop.impl.portal.Settings";
+
+const APPEARANCE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME: &str = "color-scheme";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    Light,
+    #[default]
+    Dark,
+}
+
+// Colors of everything pulseWM draws itself
+pub struct Palette {
+    pub bar_background: [f32; 4],
+    pub bar_text: [u8; 4],
+    pub thumbnail: [f32; 4],
+    pub thumbnail_active: [f32; 4],
+    pub title_focused: [f32; 4],
+    pub title_unfocused: [f32; 4],
+    pub title_text: [u8; 4],
+    pub border: [f32; 4],
+    pub close: [f32; 4],
+}
+
+const DARK: Palette = Palette {
+    bar_background: [0.08, 0.08, 0.08, 1.0],
+    bar_text: [220, 220, 220, 255],
+    thumbnail: [0.15, 0.15, 0.15, 1.0],
+    thumbnail_active: [0.25, 0.35, 0.5, 1.0],
+    title_focused: [0.2, 0.27, 0.38, 1.0],
+    title_unfocused: [0.15, 0.15, 0.15, 1.0],
+    title_text: [220, 220, 220, 255],
+    border: [0.1, 0.1, 0.1, 1.0],
+    close: [0.75, 0.25, 0.25, 1.0],
+};
+
+const LIGHT: Palette = Palette {
+    bar_background: [0.92, 0.92, 0.92, 1.0],
+    bar_text: [30, 30, 30, 255],
+    thumbnail: [0.8, 0.8, 0.8, 1.0],
+    thumbnail_active: [0.55, 0.7, 0.9, 1.0],
+    title_focused: [0.7, 0.8, 0.95, 1.0],
+    title_unfocused: [0.85, 0.85, 0.85, 1.0],
+    title_text: [30, 30, 30, 255],
+    border: [0.65, 0.65, 0.65, 1.0],
+    close: [0.9, 0.4, 0.4, 1.0],
+};
+
+impl Theme {
+    pub fn palette(self) -> &'static Palette {
+        match self {
+            Theme::Light => &LIGHT,
+            Theme::Dark => &DARK,
+        }
+    }
+
+    // As defined for org.freedesktop.appearance, 0 would be no preference
+    fn color_scheme(self) -> u32 {
+        match self {
+            Theme::Dark => 1,
+            Theme::Light => 2,
+        }
+    }
+}
+
+// "org.freedesktop.*" matches every namespace below org.freedesktop, the
+// portal only allows globbing trailing sections
+fn namespace_matches(pattern: &str, namespace: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => namespace.starts_with(prefix),
+        None => pattern == namespace,
+    }
+}
+
+struct Settings {
+    color_scheme: Arc<AtomicU32>,
+}
+
+#[dbus_interface(name = "org.freedesktop.impl.portal.Settings")]
+impl Settings {
+    // No namespaces means all of them
+    fn read_all(&self, namespaces: Vec<String>) -> HashMap<String, HashMap<String, OwnedValue>> {
+        let mut settings: HashMap<String, HashMap<String, OwnedValue>> = HashMap::new();
+        if namespaces.is_empty()
+            || namespaces
+                .iter()
+                .any(|pattern: &String| namespace_matches(pattern, APPEARANCE))
+        {
+            let color_scheme: u32 = self.color_scheme.load(Ordering::Relaxed);
+            settings.insert(
+                APPEARANCE.into(),
+                HashMap::from([(COLOR_SCHEME.into(), Value::U32(color_scheme).into())]),
+            );
+        }
+        settings
+    }
+
+    fn read(&self, namespace: &str, key: &str) -> fdo::Result<OwnedValue> {
+        if namespace == APPEARANCE && key == COLOR_SCHEME {
+            Ok(Value::U32(self.color_scheme.load(Ordering::Relaxed)).into())
+        } else {
+            Err(fdo::Error::Failed(format!(
+                "Requested setting {namespace}.{key} not found"
+            )))
+        }
+    }
+
+    #[dbus_interface(property, name = "version")]
+    fn version(&self) -> u32 {
+        1
+    }
+}
+
+// Serves the Settings portal on the session bus, so apps following the
+// system color scheme pick up theme switches
+pub struct Portal {
+    connection: Connection,
+    color_scheme: Arc<AtomicU32>,
+}
+
+impl Portal {
+    pub fn new(theme: Theme) -> Option<Self> {
+        let color_scheme: Arc<AtomicU32> = Arc::new(AtomicU32::new(theme.color_scheme()));
+        let settings: Settings = Settings {
+            color_scheme: color_scheme.clone(),
+        };
+
+        let connection: zbus::Result<Connection> = ConnectionBuilder::session()
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| builder.serve_at(OBJECT_PATH, settings))
+            .and_then(|builder| builder.build());
+
+        match connection {
+            Ok(connection) => Some(Self {
+                connection,
+                color_scheme,
+            }),
+            Err(err) => {
+                eprintln!("Failed to register the settings portal: {err}");
+                None
+            }
+        }
+    }
+
+    fn set_theme(&self, theme: Theme) {
+        let color_scheme: u32 = theme.color_scheme();
+        if self.color_scheme.swap(color_scheme, Ordering::Relaxed) == color_scheme {
+            return;
+        }
+
+        let result: zbus::Result<()> = self.connection.emit_signal(
+            None::<&str>,
+            OBJECT_PATH,
+            SETTINGS_INTERFACE,
+            "SettingChanged",
+            &(APPEARANCE, COLOR_SCHEME, Value::U32(color_scheme)),
+        );
+        if let Err(err) = result {
+            eprintln!("Failed to announce the color scheme change: {err}");
+        }
+    }
+}
+
+impl State {
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        if let Some(portal) = &self.portal {
+            portal.set_theme(theme);
+        }
+        self.request_redraw();
+    }
+}
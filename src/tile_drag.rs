@@ -0,0 +1,268 @@
+use smithay::{
+    backend::renderer::element::{
+        solid::{SolidColorBuffer, SolidColorRenderElement},
+        Kind,
+    },
+    desktop::Window,
+    output::Output,
+    utils::{Logical, Point, Rectangle},
+};
+
+use crate::{render::CustomRenderElements, state::State};
+
+// Premultiplied
+const INDICATOR: [f32; 4] = [0.1, 0.2, 0.35, 0.4];
+
+// How far into a tile, as a fraction of its size, an edge drop zone reaches
+const EDGE_FRACTION: f64 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DropZone {
+    // Center of a tile, the two windows trade places
+    Swap,
+    // Near an edge, the tile is split and the window goes on that side
+    Split(Edge),
+}
+
+// Which tile `point` is over and where in it
+pub fn drop_target(
+    tiles: &[Rectangle<i32, Logical>],
+    point: Point<f64, Logical>,
+) -> Option<(usize, DropZone)> {
+    let index: usize = tiles
+        .iter()
+        .position(|tile: &Rectangle<i32, Logical>| tile.to_f64().contains(point))?;
+    let tile: Rectangle<f64, Logical> = tiles[index].to_f64();
+
+    // 0.0..1.0 inside the tile
+    let x: f64 = (point.x - tile.loc.x) / tile.size.w;
+    let y: f64 = (point.y - tile.loc.y) / tile.size.h;
+
+    // The closest edge wins when the point is inside two edge zones
+    let zones: [(f64, Edge); 4] = [
+        (x, Edge::Left),
+        (1.0 - x, Edge::Right),
+        (y, Edge::Top),
+        (1.0 - y, Edge::Bottom),
+    ];
+    let zone: DropZone = zones
+        .into_iter()
+        .filter(|(distance, _)| *distance < EDGE_FRACTION)
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map_or(DropZone::Swap, |(_, edge)| DropZone::Split(edge));
+
+    Some((index, zone))
+}
+
+// Moves `dragged` in `order` the way dropping it onto `target` does. Master
+// and stack have no real splits, so an edge puts it right before or after
+// the target instead.
+pub fn reorder<T>(order: &mut Vec<T>, dragged: usize, target: usize, zone: DropZone) {
+    match zone {
+        DropZone::Swap => order.swap(dragged, target),
+        DropZone::Split(edge) => {
+            let item: T = order.remove(dragged);
+            // The target moved one up if the dragged one was before it
+            let target: usize = if dragged < target { target - 1 } else { target };
+            let index: usize = match edge {
+                Edge::Left | Edge::Top => target,
+                Edge::Right | Edge::Bottom => target + 1,
+            };
+            order.insert(index, item);
+        }
+    }
+}
+
+pub struct TileDrop {
+    pub dragged: Window,
+    pub target: Window,
+    pub zone: DropZone,
+}
+
+// A tiled window being dragged with Super held
+pub struct TileDrag {
+    dragged: Window,
+    target: Option<(Window, DropZone)>,
+    output: Option<Output>,
+    // Global coordinates
+    indicator: Rectangle<i32, Logical>,
+    buffer: SolidColorBuffer,
+}
+
+impl State {
+    pub fn start_tile_drag(&mut self, window: &Window) {
+        self.tile_drag = Some(TileDrag {
+            dragged: window.clone(),
+            target: None,
+            output: None,
+            indicator: Rectangle::default(),
+            buffer: SolidColorBuffer::new((0, 0), INDICATOR),
+        });
+    }
+
+    // `tiles` are the tiled windows of the workspace with their geometry
+    pub fn tile_drag_motion(
+        &mut self,
+        tiles: &[(Window, Rectangle<i32, Logical>)],
+        point: Point<f64, Logical>,
+    ) {
+        let output: Option<Output> = self.space.output_under(point).next().cloned();
+        let Some(dragged) = self
+            .tile_drag
+            .as_ref()
+            .map(|drag: &TileDrag| drag.dragged.clone())
+        else {
+            return;
+        };
+
+        let rects: Vec<Rectangle<i32, Logical>> = tiles.iter().map(|(_, rect)| *rect).collect();
+        let target: Option<(usize, DropZone)> =
+            drop_target(&rects, point).filter(|(index, _)| tiles[*index].0 != dragged);
+        // Where the layout would put the window, nothing if the drop
+        // wouldn't move it
+        let preview: Option<(Window, DropZone, Rectangle<i32, Logical>)> =
+            target.and_then(|(index, zone)| {
+                let target: &Window = &tiles[index].0;
+                self.tile_drop_preview(&dragged, target, zone)
+                    .map(|rect: Rectangle<i32, Logical>| (target.clone(), zone, rect))
+            });
+        let Some(drag) = &mut self.tile_drag else {
+            return;
+        };
+
+        match preview {
+            Some((target, zone, rect)) => {
+                drag.indicator = rect;
+                drag.buffer.update(drag.indicator.size, INDICATOR);
+                drag.target = Some((target, zone));
+                drag.output = output;
+            }
+            None => {
+                drag.target = None;
+                drag.output = None;
+            }
+        }
+    }
+
+    // What the layout has to do, None if the window was dropped nowhere
+    pub fn finish_tile_drag(&mut self) -> Option<TileDrop> {
+        let drag: TileDrag = self.tile_drag.take()?;
+        let (target, zone) = drag.target?;
+
+        Some(TileDrop {
+            dragged: drag.dragged,
+            target,
+            zone,
+        })
+    }
+
+    pub fn tile_drag_elements(&self, output: &Output, elements: &mut Vec<CustomRenderElements>) {
+        let Some(drag) = self
+            .tile_drag
+            .as_ref()
+            .filter(|drag: &&TileDrag| drag.output.as_ref() == Some(output))
+        else {
+            return;
+        };
+        let Some(output_geometry) = self.space.output_geometry(output) else {
+            return;
+        };
+
+        let scale: f64 = output.current_scale().fractional_scale();
+        let loc: Point<i32, Logical> = drag.indicator.loc - output_geometry.loc;
+
+        elements.push(
+            SolidColorRenderElement::from_buffer(
+                &drag.buffer,
+                loc.to_physical_precise_round(scale),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles() -> Vec<Rectangle<i32, Logical>> {
+        vec![
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Rectangle::from_loc_and_size((100, 0), (100, 100)),
+        ]
+    }
+
+    fn target(x: f64, y: f64) -> Option<(usize, DropZone)> {
+        drop_target(&tiles(), Point::from((x, y)))
+    }
+
+    #[test]
+    fn center_swaps() {
+        assert_eq!(target(50.0, 50.0), Some((0, DropZone::Swap)));
+        assert_eq!(target(150.0, 50.0), Some((1, DropZone::Swap)));
+    }
+
+    #[test]
+    fn edges_split() {
+        assert_eq!(target(10.0, 50.0), Some((0, DropZone::Split(Edge::Left))));
+        assert_eq!(target(90.0, 50.0), Some((0, DropZone::Split(Edge::Right))));
+        assert_eq!(target(50.0, 10.0), Some((0, DropZone::Split(Edge::Top))));
+        assert_eq!(target(50.0, 90.0), Some((0, DropZone::Split(Edge::Bottom))));
+    }
+
+    #[test]
+    fn edge_zones_end_where_the_center_starts() {
+        assert_eq!(target(24.0, 50.0), Some((0, DropZone::Split(Edge::Left))));
+        assert_eq!(target(25.0, 50.0), Some((0, DropZone::Swap)));
+    }
+
+    #[test]
+    fn closest_edge_wins_in_corners() {
+        assert_eq!(target(5.0, 20.0), Some((0, DropZone::Split(Edge::Left))));
+        assert_eq!(target(20.0, 5.0), Some((0, DropZone::Split(Edge::Top))));
+        assert_eq!(target(195.0, 80.0), Some((1, DropZone::Split(Edge::Right))));
+    }
+
+    #[test]
+    fn outside_every_tile_is_nothing() {
+        assert_eq!(target(250.0, 50.0), None);
+        assert_eq!(target(50.0, -1.0), None);
+    }
+
+    #[test]
+    fn swap_trades_places() {
+        let mut order = vec!['a', 'b', 'c'];
+        reorder(&mut order, 0, 2, DropZone::Swap);
+        assert_eq!(order, ['c', 'b', 'a']);
+    }
+
+    #[test]
+    fn split_goes_next_to_the_target() {
+        let mut order = vec!['a', 'b', 'c', 'd'];
+        reorder(&mut order, 0, 2, DropZone::Split(Edge::Left));
+        assert_eq!(order, ['b', 'a', 'c', 'd']);
+
+        let mut order = vec!['a', 'b', 'c', 'd'];
+        reorder(&mut order, 0, 2, DropZone::Split(Edge::Bottom));
+        assert_eq!(order, ['b', 'c', 'a', 'd']);
+
+        let mut order = vec!['a', 'b', 'c', 'd'];
+        reorder(&mut order, 3, 0, DropZone::Split(Edge::Top));
+        assert_eq!(order, ['d', 'a', 'b', 'c']);
+
+        let mut order = vec!['a', 'b', 'c', 'd'];
+        reorder(&mut order, 3, 0, DropZone::Split(Edge::Right));
+        assert_eq!(order, ['a', 'd', 'b', 'c']);
+    }
+}
@@ -0,0 +1,331 @@
+use serde::Serialize;
+use smithay::{
+    backend::renderer::utils::RendererSurfaceStateUserData,
+    desktop::Window,
+    output::Output,
+    utils::{IsAlive, Logical, Rectangle, Serial, Size},
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        shell::xdg::SurfaceCachedState,
+    },
+};
+
+use crate::{
+    layout::{self, LayoutParams, LayoutWindow, Rect},
+    state::State,
+    tile_drag::{self, DropZone, TileDrop},
+};
+
+// How much one keypress grows or shrinks the master column
+const RATIO_STEP: f64 = 0.05;
+// And the gaps, in logical pixels
+const GAP_STEP: i32 = 4;
+const MAX_GAP: i32 = 200;
+
+#[derive(Serialize)]
+pub struct GapStatus {
+    pub inner: i32,
+    pub outer: i32,
+}
+
+pub struct Tiling {
+    // Tiled windows of every workspace, masters first
+    order: Vec<Window>,
+    pub params: LayoutParams,
+}
+
+impl Tiling {
+    pub fn new(params: LayoutParams) -> Self {
+        Self {
+            order: Vec::new(),
+            params,
+        }
+    }
+
+    pub fn is_tiled(&self, window: &Window) -> bool {
+        self.order.contains(window)
+    }
+}
+
+fn to_rect(area: Rectangle<i32, Logical>) -> Rect {
+    Rect::new(area.loc.x, area.loc.y, area.size.w, area.size.h)
+}
+
+fn from_rect(rect: Rect) -> Rectangle<i32, Logical> {
+    Rectangle::from_loc_and_size((rect.x, rect.y), (rect.w, rect.h))
+}
+
+fn layout_window(window: &Window) -> LayoutWindow {
+    with_states(window.toplevel().wl_surface(), |states: &SurfaceData| {
+        let min_size: Size<i32, Logical> =
+            states.cached_state.current::<SurfaceCachedState>().min_size;
+        LayoutWindow {
+            min_width: min_size.w,
+            min_height: min_size.h,
+        }
+    })
+}
+
+// The window geometry the client set, otherwise the size of its main
+// surface, with buffer scale and transform applied. Window::geometry falls
+// back to the bounding box instead, which counts subsurfaces sticking out
+// of the window as well. Empty before the first commit, which is also why
+// this doesn't go through with_renderer_surface_state: it unwraps the
+// state that commit creates.
+pub fn window_size(window: &Window) -> Size<i32, Logical> {
+    with_states(window.toplevel().wl_surface(), |states: &SurfaceData| {
+        let geometry: Option<Rectangle<i32, Logical>> =
+            states.cached_state.current::<SurfaceCachedState>().geometry;
+        geometry
+            .map(|geometry: Rectangle<i32, Logical>| geometry.size)
+            .or_else(|| {
+                states
+                    .data_map
+                    .get::<RendererSurfaceStateUserData>()?
+                    .borrow()
+                    .surface_size()
+            })
+            .unwrap_or_default()
+    })
+}
+
+impl State {
+    // New windows go to the end of the stack
+    pub fn tile_window(&mut self, window: &Window) {
+        if !self.tiling.is_tiled(window) {
+            self.tiling.order.push(window.clone());
+        }
+        self.arrange();
+    }
+
+    pub fn untile_window(&mut self, window: &Window) {
+        self.tiling.order.retain(|other: &Window| other != window);
+        self.arrange();
+    }
+
+    // The visible tiled windows `output` is tiled with, in layout order
+    fn output_tiles(&self, output: &Output, outputs: &[Output]) -> Vec<Window> {
+        self.tiling
+            .order
+            .iter()
+            .filter(|window: &&Window| self.space.element_location(window).is_some())
+            // Windows that aren't on any output go to the first
+            .filter(|window: &&Window| {
+                let on: Option<Output> = self.window_output(window);
+                on.as_ref().or(outputs.first()) == Some(output)
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Recomputes the geometry of every tiled window on the active
+    // workspace, each output is tiled on its own
+    pub fn arrange(&mut self) {
+        if self.kiosk() {
+            return;
+        }
+        self.tiling.order.retain(|window: &Window| window.alive());
+
+        let outputs: Vec<Output> = self.space.outputs().cloned().collect();
+        let mut resized: Vec<(Window, Serial)> = Vec::new();
+        for output in &outputs {
+            let Some(area) = self.usable_area(output) else {
+                continue;
+            };
+
+            let windows: Vec<Window> = self.output_tiles(output, &outputs);
+
+            let layout_windows: Vec<LayoutWindow> = windows.iter().map(layout_window).collect();
+            let rects: Vec<Rect> =
+                layout::master_stack(&layout_windows, to_rect(area), &self.tiling.params);
+
+            for (window, rect) in windows.iter().zip(rects) {
+                let geometry: Rectangle<i32, Logical> =
+                    self.inside_decoration(window, from_rect(rect));
+                let size: Size<i32, Logical> = window_size(window);
+                // Nothing to animate from before the first buffer
+                let serial: Option<Serial> = if size.w <= 0 || size.h <= 0 {
+                    self.set_window_geometry(window, geometry)
+                } else {
+                    self.place_window(window, geometry)
+                };
+                if let Some(serial) = serial {
+                    resized.push((window.clone(), serial));
+                }
+            }
+        }
+        self.begin_transaction(resized);
+    }
+
+    pub fn adjust_master_ratio(&mut self, delta: f64) {
+        self.tiling.params.master_ratio =
+            (self.tiling.params.master_ratio + delta).clamp(layout::MIN_RATIO, layout::MAX_RATIO);
+        self.arrange();
+    }
+
+    pub fn grow_master(&mut self) {
+        self.adjust_master_ratio(RATIO_STEP);
+    }
+
+    pub fn shrink_master(&mut self) {
+        self.adjust_master_ratio(-RATIO_STEP);
+    }
+
+    // Sets the gaps given, or changes them by that much with `relative`.
+    // Until the config is reloaded.
+    pub fn set_gaps(
+        &mut self,
+        inner: Option<i32>,
+        outer: Option<i32>,
+        relative: bool,
+    ) -> GapStatus {
+        let apply = |current: i32, value: Option<i32>| -> i32 {
+            match value {
+                Some(value) if relative => (current + value).clamp(0, MAX_GAP),
+                Some(value) => value.clamp(0, MAX_GAP),
+                None => current,
+            }
+        };
+        let params: &mut LayoutParams = &mut self.tiling.params;
+        params.inner_gap = apply(params.inner_gap, inner);
+        params.outer_gap = apply(params.outer_gap, outer);
+        self.arrange();
+        self.gap_status()
+    }
+
+    pub fn gap_status(&self) -> GapStatus {
+        GapStatus {
+            inner: self.tiling.params.inner_gap,
+            outer: self.tiling.params.outer_gap,
+        }
+    }
+
+    pub fn grow_gaps(&mut self) {
+        self.set_gaps(Some(GAP_STEP), Some(GAP_STEP), true);
+    }
+
+    pub fn shrink_gaps(&mut self) {
+        self.set_gaps(Some(-GAP_STEP), Some(-GAP_STEP), true);
+    }
+
+    // Positions in `order` of the tiled windows on the active workspace
+    fn visible_tiles(&self) -> Vec<usize> {
+        (0..self.tiling.order.len())
+            .filter(|index: &usize| {
+                self.space
+                    .element_location(&self.tiling.order[*index])
+                    .is_some()
+            })
+            .collect()
+    }
+
+    // What a dragged tile can be dropped onto
+    pub fn visible_tile_geometries(&self) -> Vec<(Window, Rectangle<i32, Logical>)> {
+        self.visible_tiles()
+            .into_iter()
+            .filter_map(|index: usize| {
+                let window: &Window = &self.tiling.order[index];
+                let mut geometry: Rectangle<i32, Logical> = window.geometry();
+                geometry.loc += self.space.element_location(window)?;
+                Some((window.clone(), geometry))
+            })
+            .collect()
+    }
+
+    pub fn apply_tile_drop(&mut self, drop: TileDrop) {
+        let order: &mut Vec<Window> = &mut self.tiling.order;
+        let Some(dragged) = order
+            .iter()
+            .position(|window: &Window| *window == drop.dragged)
+        else {
+            return;
+        };
+        let Some(target) = order
+            .iter()
+            .position(|window: &Window| *window == drop.target)
+        else {
+            return;
+        };
+
+        tile_drag::reorder(order, dragged, target, drop.zone);
+        self.arrange();
+    }
+
+    // The geometry `dragged` gets from the layout once dropped onto `target`,
+    // None if it's tiled on another output and wouldn't move
+    pub fn tile_drop_preview(
+        &self,
+        dragged: &Window,
+        target: &Window,
+        zone: DropZone,
+    ) -> Option<Rectangle<i32, Logical>> {
+        let outputs: Vec<Output> = self.space.outputs().cloned().collect();
+        let output: Output = self
+            .window_output(target)
+            .or_else(|| outputs.first().cloned())?;
+        let area: Rectangle<i32, Logical> = self.usable_area(&output)?;
+
+        let mut windows: Vec<Window> = self.output_tiles(&output, &outputs);
+        let dragged_index: usize = windows
+            .iter()
+            .position(|window: &Window| window == dragged)?;
+        let target_index: usize = windows
+            .iter()
+            .position(|window: &Window| window == target)?;
+        tile_drag::reorder(&mut windows, dragged_index, target_index, zone);
+
+        let layout_windows: Vec<LayoutWindow> = windows.iter().map(layout_window).collect();
+        let rects: Vec<Rect> =
+            layout::master_stack(&layout_windows, to_rect(area), &self.tiling.params);
+        let index: usize = windows
+            .iter()
+            .position(|window: &Window| window == dragged)?;
+        Some(self.inside_decoration(dragged, from_rect(rects[index])))
+    }
+
+    // Trades places with the master, or the master with the next window
+    // when it already is the master
+    pub fn swap_focused_with_master(&mut self) {
+        let Some(window) = self.focused_window() else {
+            return;
+        };
+        let visible: Vec<usize> = self.visible_tiles();
+        let Some(position) = visible
+            .iter()
+            .position(|index: &usize| self.tiling.order[*index] == window)
+        else {
+            return;
+        };
+
+        let other: Option<usize> = if position == 0 {
+            visible.get(1).copied()
+        } else {
+            visible.first().copied()
+        };
+        if let Some(other) = other {
+            self.tiling.order.swap(visible[position], other);
+            self.arrange();
+        }
+    }
+
+    // Moves the focused window `offset` places through the tiles, wrapping
+    // around at both ends
+    pub fn swap_focused(&mut self, offset: i32) {
+        let Some(window) = self.focused_window() else {
+            return;
+        };
+        let visible: Vec<usize> = self.visible_tiles();
+        let Some(position) = visible
+            .iter()
+            .position(|index: &usize| self.tiling.order[*index] == window)
+        else {
+            return;
+        };
+
+        let other: usize = (position as i32 + offset).rem_euclid(visible.len() as i32) as usize;
+        if other != position {
+            self.tiling.order.swap(visible[position], visible[other]);
+            self.arrange();
+        }
+    }
+}
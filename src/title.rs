@@ -0,0 +1,48 @@
+use smithay::desktop::Window;
+
+use crate::{state::State, switcher};
+
+const ELLIPSIS: char = '…';
+
+// Fills in "{app_id}" and "{title}", missing ones as empty strings. Longer
+// than `max_length` characters it's cut and ends in an ellipsis, zero means
+// no limit.
+pub fn format_title(
+    format: &str,
+    app_id: Option<&str>,
+    title: Option<&str>,
+    max_length: usize,
+) -> String {
+    let name: String = format
+        .replace("{app_id}", app_id.unwrap_or_default())
+        .replace("{title}", title.unwrap_or_default());
+    let name: &str = name.trim();
+
+    if max_length == 0 || name.chars().count() <= max_length {
+        return name.to_string();
+    }
+    let mut cut: String = name.chars().take(max_length - 1).collect();
+    cut.truncate(cut.trim_end().len());
+    cut.push(ELLIPSIS);
+    cut
+}
+
+impl State {
+    // What the bar and IPC call the window, see [window_title]. None if
+    // the client set neither an app_id nor a title.
+    pub fn window_name(&self, window: &Window) -> Option<String> {
+        let app_id: Option<String> = switcher::app_id(window);
+        let title: Option<String> = switcher::title(window);
+        if app_id.is_none() && title.is_none() {
+            return None;
+        }
+
+        let name: String = format_title(
+            &self.config.window_title.format,
+            app_id.as_deref(),
+            title.as_deref(),
+            self.config.window_title.max_length,
+        );
+        Some(name).filter(|name: &String| !name.is_empty())
+    }
+}
@@ -0,0 +1,196 @@
+use std::time::{Duration, Instant};
+
+use smithay::{
+    desktop::Window,
+    output::Output,
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        LoopHandle,
+    },
+    utils::{IsAlive, Serial},
+};
+
+use crate::{configure, data::Data, state::State};
+
+// How long a new layout waits for slow clients before it's shown anyway
+const TIMEOUT: Duration = Duration::from_millis(150);
+
+// Tiled windows resized together, each with the configure it was sent.
+// Until all of them committed their new size the previous frame stays on
+// the outputs showing them, so there's never a frame where some windows
+// have their new size and others their old one, overlapping or leaving
+// gaps. Generic so the bookkeeping can be tested without clients.
+struct Transaction<W> {
+    windows: Vec<(W, Serial)>,
+    deadline: Instant,
+}
+
+impl<W: PartialEq> Transaction<W> {
+    // Windows still pending from an earlier layout wait for the new one as
+    // well, with a fresh deadline
+    fn merge(previous: Option<Self>, windows: Vec<(W, Serial)>, now: Instant) -> Self {
+        let mut pending: Vec<(W, Serial)> = previous
+            .map(|transaction: Self| transaction.windows)
+            .unwrap_or_default();
+        pending.retain(|(window, _): &(W, Serial)| {
+            !windows
+                .iter()
+                .any(|(other, _): &(W, Serial)| other == window)
+        });
+        pending.extend(windows);
+
+        Self {
+            windows: pending,
+            deadline: now + TIMEOUT,
+        }
+    }
+
+    // Forgets the windows that are `done` with their configure, whether the
+    // transaction is over
+    fn settle(&mut self, now: Instant, done: impl Fn(&W, Serial) -> bool) -> bool {
+        self.windows
+            .retain(|(window, serial): &(W, Serial)| !done(window, *serial));
+        self.windows.is_empty() || now >= self.deadline
+    }
+
+    // Whether an output is held back, `shown` tells which windows are on it
+    fn holds(&self, shown: impl Fn(&W) -> bool) -> bool {
+        self.windows
+            .iter()
+            .any(|(window, _): &(W, Serial)| shown(window))
+    }
+}
+
+pub struct Transactions {
+    current: Option<Transaction<Window>>,
+    handle: LoopHandle<'static, Data>,
+}
+
+impl Transactions {
+    pub fn new(handle: LoopHandle<'static, Data>) -> Self {
+        Self {
+            current: None,
+            handle,
+        }
+    }
+}
+
+impl State {
+    pub fn begin_transaction(&mut self, windows: Vec<(Window, Serial)>) {
+        // A single window can't be out of step with anything
+        if windows.len() < 2 {
+            return;
+        }
+
+        self.transactions.current = Some(Transaction::merge(
+            self.transactions.current.take(),
+            windows,
+            Instant::now(),
+        ));
+        // Commits wake the backend on their own, this is for when a client
+        // never answers
+        let timer = Timer::from_duration(TIMEOUT);
+        if let Err(err) = self
+            .transactions
+            .handle
+            .insert_source(timer, |_, _, data: &mut Data| {
+                data.state.request_redraw();
+                TimeoutAction::Drop
+            })
+        {
+            eprintln!("Failed to start the layout timeout: {err}");
+        }
+    }
+
+    // Whether the backend may present a new frame on `output`. Outputs
+    // without any of the windows go on as usual, and the lock screen never
+    // waits for a layout nobody is going to see.
+    pub fn transaction_ready(&mut self, output: &Output) -> bool {
+        if self.is_locked() {
+            return true;
+        }
+        let Some(transaction) = &mut self.transactions.current else {
+            return true;
+        };
+
+        let done: bool = transaction.settle(Instant::now(), |window: &Window, serial: Serial| {
+            !window.alive() || configure::has_caught_up(window.toplevel(), serial)
+        });
+        if done {
+            self.transactions.current = None;
+            return true;
+        }
+        !transaction.holds(|window: &Window| {
+            self.space
+                .outputs_for_element(window)
+                .iter()
+                .any(|other: &Output| other == output)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serial(value: u32) -> Serial {
+        Serial::from(value)
+    }
+
+    #[test]
+    fn merge_keeps_windows_still_pending() {
+        let now: Instant = Instant::now();
+        let first: Transaction<u32> =
+            Transaction::merge(None, vec![(1, serial(1)), (2, serial(2))], now);
+        let merged: Transaction<u32> =
+            Transaction::merge(Some(first), vec![(2, serial(3)), (3, serial(4))], now);
+
+        // The new configure replaces the old one of the same window
+        assert_eq!(
+            merged.windows,
+            vec![(1, serial(1)), (2, serial(3)), (3, serial(4))]
+        );
+    }
+
+    #[test]
+    fn merge_restarts_the_deadline() {
+        let now: Instant = Instant::now();
+        let first: Transaction<u32> = Transaction::merge(None, vec![(1, serial(1))], now);
+        let later: Instant = now + TIMEOUT / 2;
+        let merged: Transaction<u32> = Transaction::merge(Some(first), vec![(2, serial(2))], later);
+
+        assert_eq!(merged.deadline, later + TIMEOUT);
+    }
+
+    #[test]
+    fn settles_once_every_window_caught_up() {
+        let now: Instant = Instant::now();
+        let mut transaction: Transaction<u32> =
+            Transaction::merge(None, vec![(1, serial(1)), (2, serial(2))], now);
+
+        assert!(!transaction.settle(now, |window: &u32, _| *window == 1));
+        assert_eq!(transaction.windows, vec![(2, serial(2))]);
+        assert!(transaction.settle(now, |_, _| true));
+    }
+
+    #[test]
+    fn settles_at_the_deadline() {
+        let now: Instant = Instant::now();
+        let mut transaction: Transaction<u32> =
+            Transaction::merge(None, vec![(1, serial(1)), (2, serial(2))], now);
+
+        assert!(!transaction.settle(now + TIMEOUT / 2, |_, _| false));
+        assert!(transaction.settle(now + TIMEOUT, |_, _| false));
+    }
+
+    #[test]
+    fn holds_only_outputs_showing_a_window() {
+        let now: Instant = Instant::now();
+        // Windows 1 and 2 are on the first output, 3 on the second
+        let transaction: Transaction<u32> =
+            Transaction::merge(None, vec![(1, serial(1)), (2, serial(2))], now);
+
+        assert!(transaction.holds(|window: &u32| *window <= 2));
+        assert!(!transaction.holds(|window: &u32| *window == 3));
+    }
+}
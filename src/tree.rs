@@ -0,0 +1,319 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+use smithay::{
+    desktop::Window,
+    output::{Output, PhysicalProperties},
+    reexports::wayland_protocols::xdg::shell::server::xdg_toplevel,
+    utils::{Logical, Point, Rectangle, Size},
+};
+
+use crate::{
+    edid::{self, EdidInfo},
+    minimize::MinimizedStatus,
+    state::State,
+    switcher,
+};
+
+static NEXT_WINDOW_ID: AtomicU64 = AtomicU64::new(1);
+
+// Handed out on first use and never reused, so IPC clients can refer to a
+// window across requests
+struct WindowId(u64);
+
+pub fn window_id(window: &Window) -> u64 {
+    window
+        .user_data()
+        .insert_if_missing(|| WindowId(NEXT_WINDOW_ID.fetch_add(1, Ordering::Relaxed)));
+    window.user_data().get::<WindowId>().unwrap().0
+}
+
+#[derive(Serialize)]
+pub struct Tree {
+    pub outputs: Vec<OutputNode>,
+    pub workspaces: Vec<WorkspaceNode>,
+    pub minimized: Vec<MinimizedStatus>,
+}
+
+#[derive(Serialize)]
+pub struct OutputNode {
+    pub name: String,
+    // What output profiles match against, see [edid::output_description]
+    pub description: String,
+    pub make: String,
+    pub model: String,
+    pub serial: Option<String>,
+    // In millimeters, [0, 0] if the display doesn't tell
+    pub physical_size: [i32; 2],
+    pub geometry: Option<[i32; 4]>,
+    pub scale: f64,
+}
+
+#[derive(Serialize)]
+pub struct WorkspaceNode {
+    // As shown in the bar, starting at 1
+    pub number: usize,
+    pub active: bool,
+    // Bottom to top
+    pub windows: Vec<WindowNode>,
+}
+
+#[derive(Serialize)]
+pub struct WindowNode {
+    pub id: u64,
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    // Formatted like in the bar, see [window_title]
+    pub name: Option<String>,
+    // Global position and size, as the client sees it
+    pub geometry: [i32; 4],
+    pub focused: bool,
+    pub tiled: bool,
+    pub fullscreen: bool,
+    pub maximized: bool,
+}
+
+// Which windows an IPC command applies to, all set fields have to match.
+// With none set it's the focused window.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct WindowCriteria {
+    pub id: Option<u64>,
+    pub app_id: Option<String>,
+    // Part of the title is enough
+    pub title: Option<String>,
+}
+
+impl WindowCriteria {
+    fn is_empty(&self) -> bool {
+        self.id.is_none() && self.app_id.is_none() && self.title.is_none()
+    }
+
+    fn matches(&self, window: &Window) -> bool {
+        self.id.map_or(true, |id: u64| window_id(window) == id)
+            && self.app_id.as_ref().map_or(true, |app_id: &String| {
+                switcher::app_id(window).as_ref() == Some(app_id)
+            })
+            && self.title.as_ref().map_or(true, |title: &String| {
+                switcher::title(window).is_some_and(|other: String| other.contains(title.as_str()))
+            })
+    }
+}
+
+fn to_array(rectangle: Rectangle<i32, Logical>) -> [i32; 4] {
+    [
+        rectangle.loc.x,
+        rectangle.loc.y,
+        rectangle.size.w,
+        rectangle.size.h,
+    ]
+}
+
+impl State {
+    pub fn find_window(&self, id: u64) -> Option<Window> {
+        self.windows
+            .values()
+            .find(|window: &&Window| window_id(window) == id)
+            .cloned()
+    }
+
+    // Shows the window's workspace first if it's on another one
+    pub fn focus_window_by_id(&mut self, id: u64) -> Result<(), String> {
+        let window: Window = self
+            .find_window(id)
+            .ok_or_else(|| format!("no window {id}"))?;
+        let workspace: usize = self
+            .workspace_of(&window)
+            .ok_or_else(|| format!("window {id} is minimized"))?;
+
+        // In tag mode it may be shown already, with other tags
+        if !self.space.elements().any(|other: &Window| *other == window) {
+            self.switch_workspace(workspace);
+        }
+        self.focus_window(&window);
+        Ok(())
+    }
+
+    // `workspace` counts from 1 like in the bar, without an id the focused
+    // window is moved
+    pub fn move_to_workspace(&mut self, id: Option<u64>, workspace: usize) -> Result<(), String> {
+        if workspace == 0 || workspace > self.workspaces.count() {
+            return Err(format!("no workspace {workspace}"));
+        }
+        let window: Window = match id {
+            Some(id) => self
+                .find_window(id)
+                .ok_or_else(|| format!("no window {id}"))?,
+            None => self.focused_window().ok_or("no focused window")?,
+        };
+
+        self.move_window_to_workspace(&window, workspace - 1);
+        Ok(())
+    }
+
+    pub fn switch_to_workspace(&mut self, workspace: usize) -> Result<(), String> {
+        if workspace == 0 || workspace > self.workspaces.count() {
+            return Err(format!("no workspace {workspace}"));
+        }
+        self.switch_workspace(workspace - 1);
+        Ok(())
+    }
+
+    // Windows on the visible workspace
+    fn matching_windows(&self, criteria: &WindowCriteria) -> Result<Vec<Window>, String> {
+        if criteria.is_empty() {
+            return Ok(vec![self.focused_window().ok_or("no focused window")?]);
+        }
+        let windows: Vec<Window> = self
+            .space
+            .elements()
+            .filter(|window: &&Window| criteria.matches(window))
+            .cloned()
+            .collect();
+        if windows.is_empty() {
+            return Err("no window matches".into());
+        }
+        Ok(windows)
+    }
+
+    // A window placed by hand is no longer tiled or maximized, fullscreen
+    // ones are left alone
+    fn free_window(&mut self, window: &Window) -> Result<(), String> {
+        if self.is_fullscreen(window) {
+            return Err(format!("window {} is fullscreen", window_id(window)));
+        }
+        if self.is_maximized(window) {
+            self.forget_maximized(window);
+            window.toplevel().with_pending_state(|state| {
+                state.states.unset(xdg_toplevel::State::Maximized);
+            });
+        }
+        if self.tiling.is_tiled(window) {
+            self.untile_window(window);
+        }
+        Ok(())
+    }
+
+    pub fn fullscreen_windows(
+        &mut self,
+        criteria: &WindowCriteria,
+        enabled: Option<bool>,
+    ) -> Result<Vec<u64>, String> {
+        let windows: Vec<Window> = self.matching_windows(criteria)?;
+        for window in &windows {
+            let fullscreen: bool = self.is_fullscreen(window);
+            match enabled.unwrap_or(!fullscreen) {
+                true if !fullscreen => self.fullscreen_window(window, None),
+                false if fullscreen => self.unfullscreen_window(window),
+                _ => {}
+            }
+        }
+        Ok(windows.iter().map(window_id).collect())
+    }
+
+    // Global position of the window geometry, or an offset to it with
+    // `relative`. Answers with the ids of the windows moved.
+    pub fn move_windows(
+        &mut self,
+        criteria: &WindowCriteria,
+        position: Point<i32, Logical>,
+        relative: bool,
+    ) -> Result<Vec<u64>, String> {
+        let windows: Vec<Window> = self.matching_windows(criteria)?;
+        for window in &windows {
+            self.free_window(window)?;
+            let Some(location) = self.space.element_location(window) else {
+                continue;
+            };
+            let location: Point<i32, Logical> = if relative {
+                location + position
+            } else {
+                position - window.geometry().loc
+            };
+            self.space.map_element(window.clone(), location, false);
+        }
+        Ok(windows.iter().map(window_id).collect())
+    }
+
+    // Size of the window geometry, or a difference to it with `relative`
+    pub fn resize_windows(
+        &mut self,
+        criteria: &WindowCriteria,
+        size: Size<i32, Logical>,
+        relative: bool,
+    ) -> Result<Vec<u64>, String> {
+        let windows: Vec<Window> = self.matching_windows(criteria)?;
+        for window in &windows {
+            self.free_window(window)?;
+            let Some(location) = self.space.element_location(window) else {
+                continue;
+            };
+            let mut geometry: Rectangle<i32, Logical> = window.geometry();
+            geometry.loc += location;
+            let (width, height) = if relative {
+                (geometry.size.w + size.w, geometry.size.h + size.h)
+            } else {
+                (size.w, size.h)
+            };
+            geometry.size = Size::from((width.max(1), height.max(1)));
+            self.set_window_geometry(window, geometry);
+        }
+        Ok(windows.iter().map(window_id).collect())
+    }
+
+    fn window_node(&self, window: &Window, location: Point<i32, Logical>) -> WindowNode {
+        let mut geometry: Rectangle<i32, Logical> = window.geometry();
+        geometry.loc += location;
+
+        WindowNode {
+            id: window_id(window),
+            app_id: switcher::app_id(window),
+            title: switcher::title(window),
+            name: self.window_name(window),
+            geometry: to_array(geometry),
+            focused: self.focused_window().as_ref() == Some(window),
+            tiled: self.tiling.is_tiled(window),
+            fullscreen: self.is_fullscreen(window),
+            maximized: self.is_maximized(window),
+        }
+    }
+
+    pub fn tree(&self) -> Tree {
+        let outputs: Vec<OutputNode> = self
+            .outputs
+            .iter()
+            .map(|output: &Output| {
+                let properties: PhysicalProperties = output.physical_properties();
+                let edid: Option<&EdidInfo> = output.user_data().get::<EdidInfo>();
+
+                OutputNode {
+                    name: output.name(),
+                    description: edid::output_description(output),
+                    make: properties.make,
+                    model: properties.model,
+                    serial: edid.and_then(|edid: &EdidInfo| edid.serial.clone()),
+                    physical_size: [properties.size.w, properties.size.h],
+                    geometry: self.space.output_geometry(output).map(to_array),
+                    scale: output.current_scale().fractional_scale(),
+                }
+            })
+            .collect();
+
+        let workspaces: Vec<WorkspaceNode> = (0..self.workspaces.count())
+            .map(|index: usize| WorkspaceNode {
+                number: index + 1,
+                active: self.workspaces.is_shown(index),
+                windows: self
+                    .workspace_windows(index)
+                    .iter()
+                    .map(|(window, location)| self.window_node(window, *location))
+                    .collect(),
+            })
+            .collect();
+
+        Tree {
+            outputs,
+            workspaces,
+            minimized: self.minimized_windows(),
+        }
+    }
+}
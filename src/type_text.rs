@@ -0,0 +1,131 @@
+use std::{
+    cell::Cell,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use smithay::{
+    backend::input::KeyState,
+    input::keyboard::{xkb, FilterResult, KeyboardHandle, XkbConfig},
+    reexports::{
+        wayland_protocols::wp::text_input::zv3::server::zwp_text_input_v3::ZwpTextInputV3,
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
+    utils::SERIAL_COUNTER,
+    wayland::text_input::TextInputSeat,
+};
+
+use crate::state::State;
+
+// evdev KEY_LEFTSHIFT
+const SHIFT: u32 = 42;
+// xkb keycodes are the evdev ones plus 8
+const EVDEV_OFFSET: u32 = 8;
+
+struct KeyPress {
+    // evdev, like input events
+    keycode: u32,
+    shift: bool,
+}
+
+// The keymap a keyboard created from `config` ends up with. smithay keeps
+// the seat keyboard's own to itself, so this is compiled next to
+// add_keyboard from the same config and kept in State.
+pub fn compile_keymap(config: &XkbConfig<'_>) -> Option<xkb::Keymap> {
+    let context: xkb::Context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    xkb::Keymap::new_from_names(
+        &context,
+        config.rules,
+        config.model,
+        config.layout,
+        config.variant,
+        config.options.clone(),
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+}
+
+// The key that types `c` on the seat's layout, on the first two levels so
+// at most shift is needed
+fn find_key(keymap: &xkb::Keymap, c: char) -> Option<KeyPress> {
+    let keysym: u32 = match c {
+        '\n' => xkb::KEY_Return,
+        '\t' => xkb::KEY_Tab,
+        _ => xkb::utf32_to_keysym(c as u32),
+    };
+    if keysym == xkb::KEY_NoSymbol {
+        return None;
+    }
+
+    (keymap.min_keycode()..=keymap.max_keycode()).find_map(|keycode: u32| {
+        let level: u32 = (0..2).find(|level: &u32| {
+            keymap
+                .key_get_syms_by_level(keycode, 0, *level)
+                .contains(&keysym)
+        })?;
+        Some(KeyPress {
+            keycode: keycode.checked_sub(EVDEV_OFFSET)?,
+            shift: level == 1,
+        })
+    })
+}
+
+fn time_msec() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as u32)
+}
+
+impl State {
+    // For pickers like wofi-emoji. Text the layout can produce is typed
+    // key by key, which every client understands. Anything else, like
+    // emoji, is committed through text-input, which only works in clients
+    // supporting it. Returns which of the two was used.
+    pub fn type_text(&mut self, text: &str) -> Result<&'static str, String> {
+        let keyboard: KeyboardHandle<Self> = self.seat.get_keyboard().unwrap();
+        if keyboard.current_focus().is_none() {
+            return Err("nothing has keyboard focus".into());
+        }
+
+        let keys: Option<Vec<KeyPress>> = text
+            .chars()
+            .map(|c: char| find_key(&self.keymap, c))
+            .collect();
+
+        let Some(keys) = keys else {
+            let committed: Cell<bool> = Cell::new(false);
+            self.seat.text_input().with_focused_text_input(
+                |text_input: &ZwpTextInputV3, _: &WlSurface, serial: u32| {
+                    text_input.commit_string(Some(text.into()));
+                    text_input.done(serial);
+                    committed.set(true);
+                },
+            );
+            return if committed.get() {
+                Ok("text_input")
+            } else {
+                Err("the layout can't type this and the focused client has no text input".into())
+            };
+        };
+
+        let send = |state: &mut Self, keycode: u32, key_state: KeyState| {
+            keyboard.input::<(), _>(
+                state,
+                keycode,
+                key_state,
+                SERIAL_COUNTER.next_serial(),
+                time_msec(),
+                |_, _, _| FilterResult::Forward,
+            );
+        };
+        for key in keys {
+            if key.shift {
+                send(self, SHIFT, KeyState::Pressed);
+            }
+            send(self, key.keycode, KeyState::Pressed);
+            send(self, key.keycode, KeyState::Released);
+            if key.shift {
+                send(self, SHIFT, KeyState::Released);
+            }
+        }
+        Ok("keys")
+    }
+}
@@ -0,0 +1,373 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use smithay::{
+    desktop::Window,
+    utils::{IsAlive, Logical, Point},
+};
+
+use crate::{config::WorkspaceMode, ipc::IpcEvent, state::State};
+
+// Windows of the active workspace live in the space, the others are kept
+// here with their positions until their workspace is shown again
+#[derive(Default)]
+struct Workspace {
+    // Bottom to top
+    windows: Vec<(Window, Point<i32, Logical>)>,
+}
+
+// A window's tags in tag mode, one bit per workspace. A new window gets the
+// tags shown when it's created.
+struct Tags(AtomicU32);
+
+pub struct Workspaces {
+    active: usize,
+    workspaces: Vec<Workspace>,
+    mode: WorkspaceMode,
+    // Tag mode only, the tags shown and the windows without any of them
+    selected: u32,
+    hidden: Vec<(Window, Point<i32, Logical>)>,
+}
+
+impl Workspaces {
+    pub fn new(count: usize, mode: WorkspaceMode) -> Self {
+        let count: usize = match mode {
+            WorkspaceMode::Workspaces => count.max(1),
+            WorkspaceMode::Tags => count.clamp(1, 32),
+        };
+        Self {
+            active: 0,
+            workspaces: (0..count).map(|_| Workspace::default()).collect(),
+            mode,
+            selected: 1,
+            hidden: Vec::new(),
+        }
+    }
+
+    // In tag mode the lowest tag shown
+    pub fn active(&self) -> usize {
+        match self.mode {
+            WorkspaceMode::Workspaces => self.active,
+            WorkspaceMode::Tags => self.selected.trailing_zeros() as usize,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.workspaces.len()
+    }
+
+    // Several can be at once in tag mode
+    pub fn is_shown(&self, index: usize) -> bool {
+        match self.mode {
+            WorkspaceMode::Workspaces => index == self.active,
+            WorkspaceMode::Tags => index < 32 && self.selected & (1 << index) != 0,
+        }
+    }
+
+    fn all_tags(&self) -> u32 {
+        u32::MAX >> (32 - self.count())
+    }
+}
+
+// "1 3", counting from 1 like the bar
+fn tag_names(tags: u32) -> String {
+    (0..32)
+        .filter(|index: &u32| tags & (1 << index) != 0)
+        .map(|index: u32| (index + 1).to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl State {
+    pub fn switch_workspace(&mut self, index: usize) {
+        if self.workspaces.mode == WorkspaceMode::Tags {
+            if index < self.workspaces.count() {
+                self.view_tags(1 << index);
+            }
+            return;
+        }
+        let active: usize = self.workspaces.active;
+        if index == active || index >= self.workspaces.count() {
+            return;
+        }
+
+        let current: Vec<(Window, Point<i32, Logical>)> = self.workspace_windows(active);
+        for (window, _) in &current {
+            self.space.unmap_elem(window);
+        }
+        self.workspaces.workspaces[active].windows = current;
+
+        let next: Vec<(Window, Point<i32, Logical>)> =
+            std::mem::take(&mut self.workspaces.workspaces[index].windows);
+        for (window, location) in next {
+            if window.alive() {
+                self.space.map_element(window, location, false);
+            }
+        }
+        self.workspaces.active = index;
+
+        // Outputs may have changed while the workspace was hidden
+        self.rescue_windows();
+        self.arrange();
+        self.focus_topmost();
+        self.osd.show(format!("Workspace {}", index + 1), None);
+        self.emit_ipc_event(IpcEvent::WorkspaceChanged {
+            workspace: index + 1,
+        });
+    }
+
+    // Wraps around at both ends
+    pub fn cycle_workspace(&mut self, delta: i32) {
+        let count: i32 = self.workspaces.count() as i32;
+        let index: i32 = (self.workspaces.active() as i32 + delta).rem_euclid(count);
+        self.switch_workspace(index as usize);
+    }
+
+    // In tag mode the window gets only that tag
+    pub fn move_window_to_workspace(&mut self, window: &Window, index: usize) {
+        if self.workspaces.mode == WorkspaceMode::Tags {
+            if index < self.workspaces.count() {
+                self.set_window_tags(window, 1 << index);
+            }
+            return;
+        }
+        let Some(from) = self.workspace_of(window) else {
+            return;
+        };
+        if from == index || index >= self.workspaces.count() {
+            return;
+        }
+
+        let location: Point<i32, Logical> = if from == self.workspaces.active {
+            let location: Point<i32, Logical> =
+                self.space.element_location(window).unwrap_or_default();
+            self.space.unmap_elem(window);
+            self.focus_topmost();
+            location
+        } else {
+            let windows: &mut Vec<(Window, Point<i32, Logical>)> =
+                &mut self.workspaces.workspaces[from].windows;
+            let position: usize = windows
+                .iter()
+                .position(|(other, _)| other == window)
+                .unwrap();
+            windows.remove(position).1
+        };
+
+        if index == self.workspaces.active {
+            self.space.map_element(window.clone(), location, false);
+            self.focus_window(window);
+        } else {
+            self.workspaces.workspaces[index]
+                .windows
+                .push((window.clone(), location));
+        }
+        self.arrange();
+    }
+
+    pub fn move_focused_to_workspace(&mut self, index: usize) {
+        if let Some(window) = self.focused_window() {
+            self.move_window_to_workspace(&window, index);
+        }
+    }
+
+    // Shows or hides the windows with that tag, next to the ones shown
+    // already. Switches to the workspace outside of tag mode.
+    pub fn toggle_tag_view(&mut self, index: usize) {
+        match self.workspaces.mode {
+            WorkspaceMode::Workspaces => self.switch_workspace(index),
+            WorkspaceMode::Tags if index < self.workspaces.count() => {
+                self.view_tags(self.workspaces.selected ^ (1 << index))
+            }
+            WorkspaceMode::Tags => {}
+        }
+    }
+
+    // Adds the tag to the focused window or takes it away, it keeps at
+    // least one. Moves it to the workspace outside of tag mode.
+    pub fn toggle_focused_tag(&mut self, index: usize) {
+        let Some(window) = self.focused_window() else {
+            return;
+        };
+        match self.workspaces.mode {
+            WorkspaceMode::Workspaces => self.move_window_to_workspace(&window, index),
+            WorkspaceMode::Tags if index < self.workspaces.count() => {
+                let tags: u32 = self.window_tags(&window) ^ (1 << index);
+                self.set_window_tags(&window, tags);
+            }
+            WorkspaceMode::Tags => {}
+        }
+    }
+
+    // Before the window is mapped, in either mode so a later switch to tag
+    // mode finds it tagged
+    pub fn tag_new_window(&self, window: &Window) {
+        let selected: u32 = self.workspaces.selected;
+        window
+            .user_data()
+            .insert_if_missing(|| Tags(AtomicU32::new(selected)));
+    }
+
+    // Once the window is gone, so hidden workspaces don't keep it around
+    pub fn forget_workspace(&mut self, window: &Window) {
+        self.workspaces
+            .hidden
+            .retain(|(other, _): &(Window, Point<i32, Logical>)| other != window);
+        for workspace in &mut self.workspaces.workspaces {
+            workspace
+                .windows
+                .retain(|(other, _): &(Window, Point<i32, Logical>)| other != window);
+        }
+    }
+
+    // Every tag for a window that was never given any, so it can't get lost
+    fn window_tags(&self, window: &Window) -> u32 {
+        window
+            .user_data()
+            .get::<Tags>()
+            .map_or(self.workspaces.all_tags(), |tags: &Tags| {
+                tags.0.load(Ordering::Relaxed)
+            })
+    }
+
+    fn set_window_tags(&mut self, window: &Window, tags: u32) {
+        let tags: u32 = tags & self.workspaces.all_tags();
+        if tags == 0 {
+            return;
+        }
+        window
+            .user_data()
+            .insert_if_missing(|| Tags(AtomicU32::new(tags)));
+        window
+            .user_data()
+            .get::<Tags>()
+            .unwrap()
+            .0
+            .store(tags, Ordering::Relaxed);
+
+        // It may not be shown anymore, or be shown now
+        let selected: u32 = self.workspaces.selected;
+        self.view_tags_changed(selected);
+    }
+
+    fn view_tags(&mut self, selected: u32) {
+        let selected: u32 = selected & self.workspaces.all_tags();
+        if selected == 0 || selected == self.workspaces.selected {
+            return;
+        }
+        self.view_tags_changed(selected);
+
+        self.osd.show(format!("Tags {}", tag_names(selected)), None);
+        self.emit_ipc_event(IpcEvent::WorkspaceChanged {
+            workspace: self.workspaces.active() + 1,
+        });
+    }
+
+    // Maps the windows with one of the `selected` tags and hides the rest
+    fn view_tags_changed(&mut self, selected: u32) {
+        let shown: Vec<(Window, Point<i32, Logical>)> = self
+            .space
+            .elements()
+            .filter_map(|window: &Window| {
+                Some((window.clone(), self.space.element_location(window)?))
+            })
+            .collect();
+        for (window, location) in shown {
+            if self.window_tags(&window) & selected == 0 {
+                self.space.unmap_elem(&window);
+                self.workspaces.hidden.push((window, location));
+            }
+        }
+
+        let hidden: Vec<(Window, Point<i32, Logical>)> =
+            std::mem::take(&mut self.workspaces.hidden);
+        for (window, location) in hidden {
+            if !window.alive() {
+                continue;
+            }
+            if self.window_tags(&window) & selected != 0 {
+                self.space.map_element(window, location, false);
+            } else {
+                self.workspaces.hidden.push((window, location));
+            }
+        }
+        self.workspaces.selected = selected;
+
+        self.rescue_windows();
+        self.arrange();
+        let focused_shown: bool = self.focused_window().is_some_and(|window: Window| {
+            self.space.elements().any(|other: &Window| *other == window)
+        });
+        if !focused_shown {
+            self.focus_topmost();
+        }
+    }
+
+    // The lowest tag of the window in tag mode
+    pub fn workspace_of(&self, window: &Window) -> Option<usize> {
+        if self.workspaces.mode == WorkspaceMode::Tags {
+            let known: bool = self.space.elements().any(|other: &Window| other == window)
+                || self
+                    .workspaces
+                    .hidden
+                    .iter()
+                    .any(|(other, _)| other == window);
+            return known.then(|| self.window_tags(window).trailing_zeros() as usize);
+        }
+        if self.space.elements().any(|other: &Window| other == window) {
+            return Some(self.workspaces.active);
+        }
+
+        self.workspaces
+            .workspaces
+            .iter()
+            .position(|workspace: &Workspace| {
+                workspace.windows.iter().any(|(other, _)| other == window)
+            })
+    }
+
+    // Cheaper than workspace_windows, for when only that matters
+    pub fn workspace_is_empty(&self, index: usize) -> bool {
+        if self.workspaces.mode == WorkspaceMode::Tags {
+            return self.workspace_windows(index).is_empty();
+        }
+        if index == self.workspaces.active {
+            return self.space.elements().next().is_none();
+        }
+
+        !self.workspaces.workspaces[index]
+            .windows
+            .iter()
+            .any(|(window, _)| window.alive())
+    }
+
+    // Bottom to top, with their locations in the space. In tag mode the
+    // windows with that tag, shown ones above hidden ones.
+    pub fn workspace_windows(&self, index: usize) -> Vec<(Window, Point<i32, Logical>)> {
+        if self.workspaces.mode == WorkspaceMode::Tags || index == self.workspaces.active {
+            let shown = self.space.elements().filter_map(|window: &Window| {
+                Some((window.clone(), self.space.element_location(window)?))
+            });
+            if self.workspaces.mode == WorkspaceMode::Workspaces {
+                return shown.collect();
+            }
+
+            let tag: u32 = 1 << index.min(31);
+            return self
+                .workspaces
+                .hidden
+                .iter()
+                .filter(|(window, _)| window.alive())
+                .cloned()
+                .chain(shown)
+                .filter(|(window, _)| self.window_tags(window) & tag != 0)
+                .collect();
+        }
+
+        self.workspaces.workspaces[index]
+            .windows
+            .iter()
+            .filter(|(window, _)| window.alive())
+            .cloned()
+            .collect()
+    }
+}